@@ -0,0 +1,85 @@
+//! Compares payload parsing, which is dominated by the checksum, against the original bitwise crc
+//!
+//! Run with `cargo bench --bench crc`. The payloads add up to about three hours of voice.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use steam_audio_codec::SteamVoiceData;
+
+/// Number of payloads in three hours of voice, with a payload every 40ms
+const PAYLOADS: usize = 3 * 60 * 60 * 25;
+
+/// The bitwise crc the crate used before switching to lookup tables
+fn bitwise_crc(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xFFFFFFFF;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (-((crc & 1) as i32)) as u32;
+            crc = (crc >> 1) ^ (0xEDB88320 & mask);
+        }
+    }
+    !crc
+}
+
+/// Build a payload with a sample rate packet and an opus packet of two frames of pseudo random data
+fn payload(seed: u32) -> Vec<u8> {
+    let mut state = seed | 1;
+    let mut random = || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as u8
+    };
+
+    let mut data = 76561198000000000u64.to_le_bytes().to_vec();
+    data.push(11);
+    data.extend(24000u16.to_le_bytes());
+    let mut frames = Vec::new();
+    for seq in 0..2u16 {
+        let length = 60 + (random() as u16 % 80);
+        frames.extend(length.to_le_bytes());
+        frames.extend(seq.to_le_bytes());
+        frames.extend((0..length).map(|_| random()));
+    }
+    data.push(6);
+    data.extend((frames.len() as u16).to_le_bytes());
+    data.extend(frames);
+    let crc = bitwise_crc(&data);
+    data.extend(crc.to_le_bytes());
+    data
+}
+
+fn measure(name: &str, bytes: usize, mut run: impl FnMut()) -> Duration {
+    run();
+    let start = Instant::now();
+    run();
+    let elapsed = start.elapsed();
+    println!(
+        "{name:>10}: {elapsed:>10.2?} ({:.0} MB/s)",
+        bytes as f64 / elapsed.as_secs_f64() / 1_000_000.0
+    );
+    elapsed
+}
+
+fn main() {
+    let payloads: Vec<Vec<u8>> = (0..PAYLOADS as u32).map(payload).collect();
+    let bytes = payloads.iter().map(Vec::len).sum();
+    println!("{PAYLOADS} payloads, {:.1} MB", bytes as f64 / 1_000_000.0);
+
+    let bitwise = measure("bitwise", bytes, || {
+        for payload in &payloads {
+            let (data, crc) = payload.split_at(payload.len() - 4);
+            assert_eq!(black_box(bitwise_crc(data)).to_le_bytes(), crc);
+        }
+    });
+    let parse = measure("parse", bytes, || {
+        for payload in &payloads {
+            black_box(SteamVoiceData::new(payload).unwrap());
+        }
+    });
+    println!(
+        "speedup: {:.1}x",
+        bitwise.as_secs_f64() / parse.as_secs_f64()
+    );
+}
@@ -3,15 +3,13 @@ use std::fs;
 use std::fs::File;
 use std::io::BufWriter;
 use std::path::Path;
-use hound::{SampleFormat, WavSpec, WavWriter};
 use main_error::MainError;
-use steam_audio_codec::{SteamVoiceData, SteamVoiceDecoder};
+use steam_audio_codec::{EngineOpusData, PcmSink, SteamVoiceData, SteamVoiceDecoder, VoiceCodec, WavSink};
 use tf_demo_parser::demo::parser::MessageHandler;
 use tf_demo_parser::MessageType;
 pub use tf_demo_parser::{Demo, DemoParser, Parse, ParserState};
 use tf_demo_parser::demo::data::DemoTick;
 use tf_demo_parser::demo::message::Message;
-use tf_demo_parser::demo::message::voice::{VoiceInitMessage};
 
 fn main() -> Result<(), MainError> {
     let args: Vec<_> = env::args().collect();
@@ -30,30 +28,25 @@ fn main() -> Result<(), MainError> {
 
 struct Voice {
     out_buffer: Vec<i16>,
-    writer: WavWriter<BufWriter<File>>,
-    last_init: Option<VoiceInitMessage>,
+    writer: WavSink<BufWriter<File>>,
+    codec: Option<VoiceCodec>,
     decoder: SteamVoiceDecoder,
 }
 
 impl Voice {
     fn new<P: AsRef<Path>>(path: P) -> Result<Voice, MainError> {
-        let spec = WavSpec {
-            channels: 1,
-            sample_rate: 24000,
-            bits_per_sample: 16,
-            sample_format: SampleFormat::Int,
-        };
+        let decoder = SteamVoiceDecoder::builder().output_rate(24000).build()?;
         Ok(Voice {
             out_buffer: vec![0; 8192],
-            writer: WavWriter::create(path, spec)?,
-            last_init: None,
-            decoder: SteamVoiceDecoder::new(),
+            writer: WavSink::for_decoder(path, &decoder)?,
+            codec: None,
+            decoder,
         })
     }
 }
 
 impl MessageHandler for Voice {
-    type Output = WavWriter<BufWriter<File>>;
+    type Output = WavSink<BufWriter<File>>;
 
     fn does_handle(message_type: MessageType) -> bool {
         matches!(
@@ -65,22 +58,33 @@ impl MessageHandler for Voice {
     fn handle_message(&mut self, message: &Message, _tick: DemoTick, _parser_state: &ParserState) {
         match message {
             Message::VoiceInit(init) => {
-                self.last_init = Some(init.clone());
+                self.codec = match VoiceCodec::from_voice_init(&init.codec, init.quality, init.sampling_rate) {
+                    Ok(codec) => Some(codec),
+                    Err(e) => {
+                        eprintln!("{e}");
+                        None
+                    }
+                };
             }
             Message::VoiceData(data) => {
-                if let Some(init) = &self.last_init {
-                    match init.codec.as_str() {
-                        "steam" => {
-                            let data = data.data.clone().read_bytes(data.length as usize / 8).unwrap();
-                            let steam_data = SteamVoiceData::new(&data).unwrap();
-                            let count = self.decoder.decode(steam_data, &mut self.out_buffer).unwrap();
-                            for &sample in &self.out_buffer[0..count] {
-                                self.writer.write_sample(sample).unwrap();
-                            }
-                        },
-                        _ => panic!("this example only supports the steam voice codec")
-                    };
-                }
+                let bytes = data.data.clone().read_bytes(data.length as usize / 8).unwrap();
+                let count = match self.codec {
+                    Some(VoiceCodec::Steam) => {
+                        let steam_data = SteamVoiceData::new(&bytes).unwrap();
+                        self.decoder.decode(steam_data, &mut self.out_buffer).unwrap()
+                    }
+                    Some(VoiceCodec::EngineOpus { sample_rate }) => {
+                        let steam_data = EngineOpusData::new(&bytes).to_voice_data(0, sample_rate).unwrap();
+                        self.decoder.decode(steam_data.as_voice_data(), &mut self.out_buffer).unwrap()
+                    }
+                    Some(codec) => {
+                        eprintln!("this example doesn't include a decoder for {}", codec.name());
+                        self.codec = None;
+                        0
+                    }
+                    None => 0,
+                };
+                self.writer.write_samples(&self.out_buffer[0..count]).unwrap();
             }
             _ => {}
         }
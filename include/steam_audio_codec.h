@@ -0,0 +1,125 @@
+#ifndef STEAM_AUDIO_CODEC_H
+#define STEAM_AUDIO_CODEC_H
+
+/* Generated with cbindgen from src/ffi.rs, don't edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Result codes of the C functions, negative values are errors
+typedef enum SteamAudioErrorCode {
+  STEAM_AUDIO_ERROR_CODE_OK = 0,
+  // A required pointer argument was null
+  STEAM_AUDIO_ERROR_CODE_NULL_POINTER = -1,
+  // The checksum of the payload doesn't match
+  STEAM_AUDIO_ERROR_CODE_CRC_MISMATCH = -2,
+  // The payload is truncated
+  STEAM_AUDIO_ERROR_CODE_INSUFFICIENT_DATA = -3,
+  // The output buffer is too small for the decoded audio
+  STEAM_AUDIO_ERROR_CODE_INSUFFICIENT_OUTPUT_BUFFER = -4,
+  // The payload contains a packet of an unknown type
+  STEAM_AUDIO_ERROR_CODE_UNKNOWN_PACKET_TYPE = -5,
+  // The opus decoder failed
+  STEAM_AUDIO_ERROR_CODE_OPUS = -6,
+  // The library was built without an opus decoder
+  STEAM_AUDIO_ERROR_CODE_NO_BACKEND = -7,
+  // The payload has audio before its sample rate
+  STEAM_AUDIO_ERROR_CODE_NO_SAMPLE_RATE = -8,
+  STEAM_AUDIO_ERROR_CODE_INVALID_OPUS_PACKET = -9,
+  STEAM_AUDIO_ERROR_CODE_UNSUPPORTED_SAMPLE_RATE = -10,
+  STEAM_AUDIO_ERROR_CODE_UNSUPPORTED_CODEC = -11,
+  // The payload decodes to more samples than allowed
+  STEAM_AUDIO_ERROR_CODE_TOO_MANY_SAMPLES = -12,
+  // The opus data has trailing bytes
+  STEAM_AUDIO_ERROR_CODE_TRAILING_BYTES = -13,
+  // The decoder panicked, it shouldn't be used anymore
+  STEAM_AUDIO_ERROR_CODE_PANIC = -14,
+  // Any other error
+  STEAM_AUDIO_ERROR_CODE_OTHER = -15,
+} SteamAudioErrorCode;
+
+// Opaque decoder handle for C, created with [`steam_voice_decoder_new`] and freed with
+// [`steam_voice_decoder_free`]
+typedef struct SteamVoiceDecoder SteamVoiceDecoder;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Create a decoder with the default options, decoding to mono at the sample rate of the voice data
+//
+// Returns null if the library was built without an opus decoder. The decoder has to be freed with
+// [`steam_voice_decoder_free`].
+struct SteamVoiceDecoder *steam_voice_decoder_new(void);
+
+// Create a decoder decoding at `output_rate`, or the sample rate of the voice data if it is 0, to mono or
+// interleaved stereo
+//
+// Returns null if the sample rate isn't supported or the library was built without an opus decoder. The decoder
+// has to be freed with [`steam_voice_decoder_free`].
+struct SteamVoiceDecoder *steam_voice_decoder_new_with_options(uint32_t output_rate, bool stereo);
+
+// Free a decoder, passing null does nothing
+//
+// # Safety
+//
+// `decoder` has to be null or a pointer returned by one of the constructors that wasn't freed yet.
+void steam_voice_decoder_free(struct SteamVoiceDecoder *decoder);
+
+// Decode a voice payload into `output`, which has room for `output_len` samples
+//
+// Returns the number of samples written, or a negative [`SteamAudioErrorCode`]. 20ms of audio at 48kHz stereo
+// takes 1920 samples, a payload usually holds up to a few hundred milliseconds.
+//
+// # Safety
+//
+// `decoder` has to be a pointer returned by one of the constructors that wasn't freed, `payload` has to point
+// to `payload_len` readable bytes and `output` to `output_len` writable samples.
+int64_t steam_voice_decoder_decode(struct SteamVoiceDecoder *decoder,
+                                   const uint8_t *payload,
+                                   uintptr_t payload_len,
+                                   int16_t *output,
+                                   uintptr_t output_len);
+
+// Reset the decoder to decode a new, unrelated, stream of voice data
+//
+// Returns 0, or a negative [`SteamAudioErrorCode`].
+//
+// # Safety
+//
+// `decoder` has to be a pointer returned by one of the constructors that wasn't freed.
+int32_t steam_voice_decoder_reset(struct SteamVoiceDecoder *decoder);
+
+// The sample rate of the decoded audio, 0 until a payload set it
+//
+// # Safety
+//
+// `decoder` has to be null or a pointer returned by one of the constructors that wasn't freed.
+uint32_t steam_voice_decoder_sample_rate(const struct SteamVoiceDecoder *decoder);
+
+// The number of interleaved channels of the decoded audio
+//
+// # Safety
+//
+// `decoder` has to be null or a pointer returned by one of the constructors that wasn't freed.
+uint32_t steam_voice_decoder_channels(const struct SteamVoiceDecoder *decoder);
+
+// Read the steam id of the speaker of a payload into `steam_id`, without decoding it
+//
+// Returns 0, or a negative [`SteamAudioErrorCode`].
+//
+// # Safety
+//
+// `payload` has to point to `payload_len` readable bytes and `steam_id` to a writable `uint64_t`.
+int32_t steam_voice_payload_steam_id(const uint8_t *payload, uintptr_t payload_len, uint64_t *steam_id);
+
+// A static, nul terminated, description of a result code, results that aren't negative aren't errors
+const char *steam_audio_error_message(int64_t code);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* STEAM_AUDIO_CODEC_H */
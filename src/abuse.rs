@@ -0,0 +1,363 @@
+//! Heuristics for flagging abusive voice, like micspam or deliberately loud and distorted audio
+
+use crate::fingerprint::fft;
+use std::f32::consts::PI;
+use std::time::Duration;
+
+/// Samples at or above this magnitude count as clipped
+const CLIP_LEVEL: i16 = 32000;
+/// Length of the blocks the level is measured in
+const BLOCK_DURATION: Duration = Duration::from_millis(100);
+/// Number of samples of the frames the spectral flatness is measured in
+const FLATNESS_FRAME: usize = 512;
+/// Frames quieter than this, in dBFS, don't count towards the spectral flatness
+const FLATNESS_GATE_DB: f32 = -50.0;
+/// Default for [`VoiceAnalyzer::loud_db`]
+pub const DEFAULT_LOUD_DB: f32 = -12.0;
+
+/// Reasons why the voice of a speaker might be abusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum AbuseFlag {
+    /// A large part of the audio is clipped, as with a microphone that is overdriven on purpose
+    Clipping,
+    /// The audio stays loud for a long stretch without the pauses of normal speech
+    SustainedLoudness,
+    /// The speaker takes up most of the talk time of all speakers
+    Dominance,
+    /// The audio is noise-like rather than speech, as with static, distortion or played back noise
+    Micspam,
+}
+
+/// Limits above which a [`VoiceReport`] is flagged
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AbuseThresholds {
+    /// Fraction of clipped samples
+    pub clipping_ratio: f32,
+    /// Longest stretch of loud audio
+    pub sustained_loudness: Duration,
+    /// Fraction of the talk time of all speakers, only flagged when more than one speaker talked
+    pub talk_share: f32,
+    /// Mean spectral flatness of the audio
+    pub spectral_flatness: f32,
+}
+
+impl Default for AbuseThresholds {
+    fn default() -> Self {
+        AbuseThresholds {
+            clipping_ratio: 0.01,
+            sustained_loudness: Duration::from_secs(10),
+            talk_share: 0.6,
+            spectral_flatness: 0.4,
+        }
+    }
+}
+
+/// Measurements of the voice of a speaker, to find abusive voice without listening to all of it
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct VoiceReport {
+    pub steam_id: u64,
+    /// Duration of the analyzed audio
+    pub duration: Duration,
+    /// Fraction of the samples that are at or close to the limits of 16 bit samples
+    pub clipping_ratio: f32,
+    /// Fraction of the audio that is louder than the [`loud_db`](VoiceAnalyzer::loud_db) of the analyzer
+    pub loud_ratio: f32,
+    /// Longest stretch of audio that is louder than the [`loud_db`](VoiceAnalyzer::loud_db) of the analyzer
+    pub longest_loud: Duration,
+    /// Mean spectral flatness of the audio that isn't silent, from 0 for pure tones to 1 for white noise
+    ///
+    /// Speech, with its harmonics and formants, has a low flatness, while static and heavy distortion are
+    /// close to noise.
+    pub spectral_flatness: f32,
+    /// Fraction of the talk time of all speakers of the session taken up by the speaker
+    pub talk_share: f32,
+}
+
+impl VoiceReport {
+    /// The reasons for flagging the voice with the given thresholds, empty if nothing stands out
+    pub fn flags(&self, thresholds: &AbuseThresholds) -> Vec<AbuseFlag> {
+        let mut flags = Vec::new();
+        if self.clipping_ratio > thresholds.clipping_ratio {
+            flags.push(AbuseFlag::Clipping);
+        }
+        if self.longest_loud > thresholds.sustained_loudness {
+            flags.push(AbuseFlag::SustainedLoudness);
+        }
+        if self.talk_share > thresholds.talk_share && self.talk_share < 1.0 {
+            flags.push(AbuseFlag::Dominance);
+        }
+        if self.spectral_flatness > thresholds.spectral_flatness {
+            flags.push(AbuseFlag::Micspam);
+        }
+        flags
+    }
+}
+
+/// Analyzes the decoded audio of a speaker for signs of abusive voice, producing a [`VoiceReport`]
+///
+/// The audio should be analyzed before any gain control or limiting, which would hide clipping and loudness.
+/// Use a separate analyzer for every speaker, e.g. with
+/// [`VoiceSession::set_voice_analysis`](crate::VoiceSession::set_voice_analysis).
+#[derive(Debug, Clone)]
+pub struct VoiceAnalyzer {
+    steam_id: u64,
+    sample_rate: u32,
+    channels: usize,
+    loud_db: f32,
+    samples: u64,
+    clipped: u64,
+    /// Sum of the squares of the current block, and its number of samples
+    block_sum: f64,
+    block_samples: usize,
+    loud_blocks: u64,
+    current_loud: u64,
+    longest_loud: u64,
+    /// Mono samples of the current flatness frame
+    frame: Vec<f32>,
+    real: Vec<f32>,
+    imaginary: Vec<f32>,
+    flatness_sum: f64,
+    flatness_frames: u64,
+}
+
+impl VoiceAnalyzer {
+    /// Create an analyzer for the audio of `steam_id` at `sample_rate` with `channels` interleaved channels
+    pub fn new(steam_id: u64, sample_rate: u32, channels: usize) -> Self {
+        VoiceAnalyzer {
+            steam_id,
+            sample_rate,
+            channels: channels.max(1),
+            loud_db: DEFAULT_LOUD_DB,
+            samples: 0,
+            clipped: 0,
+            block_sum: 0.0,
+            block_samples: 0,
+            loud_blocks: 0,
+            current_loud: 0,
+            longest_loud: 0,
+            frame: Vec::with_capacity(FLATNESS_FRAME),
+            real: vec![0.0; FLATNESS_FRAME],
+            imaginary: vec![0.0; FLATNESS_FRAME],
+            flatness_sum: 0.0,
+            flatness_frames: 0,
+        }
+    }
+
+    /// Set the level in dBFS, measured as RMS over 100ms, above which audio counts as loud
+    pub fn with_loud_db(mut self, loud_db: f32) -> Self {
+        self.loud_db = loud_db;
+        self
+    }
+
+    pub fn steam_id(&self) -> u64 {
+        self.steam_id
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn loud_db(&self) -> f32 {
+        self.loud_db
+    }
+
+    /// Change the sample rate of the following audio, keeping the measurements so far
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Analyze the next chunk of interleaved audio
+    pub fn process(&mut self, samples: &[i16]) {
+        let block_length = self.block_length();
+        for frame in samples.chunks_exact(self.channels) {
+            let mut square = 0.0;
+            for sample in frame {
+                if sample.unsigned_abs() >= CLIP_LEVEL as u16 {
+                    self.clipped += 1;
+                }
+                let sample = *sample as f64 / 32768.0;
+                square += sample * sample;
+            }
+            self.samples += 1;
+            self.block_sum += square / self.channels as f64;
+            self.block_samples += 1;
+            if self.block_samples >= block_length {
+                self.end_block();
+            }
+
+            self.frame.push(frame[0] as f32 / 32768.0);
+            if self.frame.len() == FLATNESS_FRAME {
+                self.analyze_frame();
+            }
+        }
+    }
+
+    /// The measurements of the audio so far, without the [`talk_share`](VoiceReport::talk_share)
+    pub fn report(&self) -> VoiceReport {
+        let block = BLOCK_DURATION.as_secs_f64();
+        let blocks = self.samples as f64 / self.block_length() as f64;
+        VoiceReport {
+            steam_id: self.steam_id,
+            duration: Duration::from_secs_f64(self.samples as f64 / self.sample_rate.max(1) as f64),
+            clipping_ratio: ratio(self.clipped, self.samples * self.channels as u64),
+            loud_ratio: if blocks > 0.0 {
+                (self.loud_blocks as f64 / blocks).min(1.0) as f32
+            } else {
+                0.0
+            },
+            longest_loud: Duration::from_secs_f64(self.longest_loud as f64 * block),
+            spectral_flatness: if self.flatness_frames > 0 {
+                (self.flatness_sum / self.flatness_frames as f64) as f32
+            } else {
+                0.0
+            },
+            talk_share: 0.0,
+        }
+    }
+
+    /// Forget the audio analyzed so far
+    pub fn reset(&mut self) {
+        *self = VoiceAnalyzer::new(self.steam_id, self.sample_rate, self.channels)
+            .with_loud_db(self.loud_db);
+    }
+
+    fn block_length(&self) -> usize {
+        ((self.sample_rate as f64 * BLOCK_DURATION.as_secs_f64()) as usize).max(1)
+    }
+
+    fn end_block(&mut self) {
+        let mean = self.block_sum / self.block_samples as f64;
+        let level_db = 10.0 * mean.max(f64::MIN_POSITIVE).log10();
+        if level_db > self.loud_db as f64 {
+            self.loud_blocks += 1;
+            self.current_loud += 1;
+            self.longest_loud = self.longest_loud.max(self.current_loud);
+        } else {
+            self.current_loud = 0;
+        }
+        self.block_sum = 0.0;
+        self.block_samples = 0;
+    }
+
+    fn analyze_frame(&mut self) {
+        let power =
+            self.frame.iter().map(|sample| sample * sample).sum::<f32>() / FLATNESS_FRAME as f32;
+        if 10.0 * power.max(f32::MIN_POSITIVE).log10() > FLATNESS_GATE_DB {
+            for (i, ((real, imaginary), sample)) in self
+                .real
+                .iter_mut()
+                .zip(&mut self.imaginary)
+                .zip(&self.frame)
+                .enumerate()
+            {
+                let window = 0.5 - 0.5 * (2.0 * PI * i as f32 / FLATNESS_FRAME as f32).cos();
+                *real = sample * window;
+                *imaginary = 0.0;
+            }
+            fft(&mut self.real, &mut self.imaginary);
+
+            // geometric mean over arithmetic mean of the power spectrum, without the dc bin
+            let bins = 1..FLATNESS_FRAME / 2;
+            let count = bins.len() as f64;
+            let (log_sum, sum) = bins.fold((0.0, 0.0), |(log_sum, sum), bin| {
+                let power = (self.real[bin] * self.real[bin]
+                    + self.imaginary[bin] * self.imaginary[bin]) as f64
+                    + 1e-12;
+                (log_sum + power.ln(), sum + power)
+            });
+            self.flatness_sum += (log_sum / count).exp() / (sum / count);
+            self.flatness_frames += 1;
+        }
+        self.frame.clear();
+    }
+}
+
+fn ratio(part: u64, total: u64) -> f32 {
+    if total > 0 {
+        (part as f64 / total as f64) as f32
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analyze(samples: &[i16]) -> VoiceReport {
+        let mut analyzer = VoiceAnalyzer::new(1, 16000, 1);
+        for chunk in samples.chunks(320) {
+            analyzer.process(chunk);
+        }
+        analyzer.report()
+    }
+
+    #[test]
+    fn speech_is_not_flagged() {
+        // harmonics of a 150Hz voice, talking for half a second out of every second
+        let samples: Vec<i16> = (0..16000 * 12)
+            .map(|i| {
+                if i % 16000 >= 8000 {
+                    return 0;
+                }
+                let time = i as f32 / 16000.0;
+                let voice: f32 = (1..=5)
+                    .map(|harmonic| {
+                        (2.0 * PI * 150.0 * harmonic as f32 * time).sin() / harmonic as f32
+                    })
+                    .sum();
+                (voice * 4000.0) as i16
+            })
+            .collect();
+        let report = analyze(&samples);
+        assert_eq!(report.duration, Duration::from_secs(12));
+        assert_eq!(report.clipping_ratio, 0.0);
+        assert!(report.longest_loud < Duration::from_secs(1));
+        assert!(
+            report.spectral_flatness < 0.1,
+            "{}",
+            report.spectral_flatness
+        );
+        assert_eq!(report.flags(&AbuseThresholds::default()), vec![]);
+
+        let dominant = VoiceReport {
+            talk_share: 0.9,
+            ..report
+        };
+        assert_eq!(
+            dominant.flags(&AbuseThresholds::default()),
+            vec![AbuseFlag::Dominance]
+        );
+    }
+
+    #[test]
+    fn loud_noise_is_flagged() {
+        let mut state = 1u32;
+        let samples: Vec<i16> = (0..16000 * 12)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 16) as u16 as i16
+            })
+            .collect();
+        let report = analyze(&samples);
+        assert!(report.clipping_ratio > 0.01, "{}", report.clipping_ratio);
+        assert_eq!(report.loud_ratio, 1.0);
+        assert_eq!(report.longest_loud, Duration::from_secs(12));
+        assert!(
+            report.spectral_flatness > 0.4,
+            "{}",
+            report.spectral_flatness
+        );
+        assert_eq!(
+            report.flags(&AbuseThresholds::default()),
+            vec![
+                AbuseFlag::Clipping,
+                AbuseFlag::SustainedLoudness,
+                AbuseFlag::Micspam
+            ]
+        );
+    }
+}
@@ -0,0 +1,163 @@
+//! Evening out the level of speakers that talk too quietly or too loudly
+
+use std::time::Duration;
+
+/// Default for [`Agc::target_db`]
+pub const DEFAULT_AGC_TARGET_DB: f32 = -20.0;
+/// Default for [`Agc::max_gain_db`]
+pub const DEFAULT_AGC_MAX_GAIN_DB: f32 = 20.0;
+/// Default for [`Agc::attack`]
+pub const DEFAULT_AGC_ATTACK: Duration = Duration::from_millis(20);
+/// Default for [`Agc::release`]
+pub const DEFAULT_AGC_RELEASE: Duration = Duration::from_secs(1);
+/// Default for [`Agc::gate_db`]
+pub const DEFAULT_AGC_GATE_DB: f32 = -50.0;
+/// Time constant of the level measurement
+const LEVEL_WINDOW: Duration = Duration::from_millis(50);
+
+/// Automatic gain control, turning the audio of a speaker towards a target level
+///
+/// The level of the audio is measured over a short window, the gain is lowered within the [`attack`](Self::attack)
+/// time when the audio gets louder than the [`target_db`](Self::target_db) and raised within the
+/// [`release`](Self::release) time when it gets quieter, up to the [`max_gain_db`](Self::max_gain_db).
+/// While the level is below the [`gate_db`](Self::gate_db) the gain is held, so pauses and background noise
+/// aren't amplified.
+///
+/// Use a separate instance for every speaker, e.g. with [`VoiceSession::set_agc`](crate::VoiceSession::set_agc).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Agc {
+    sample_rate: u32,
+    target_db: f32,
+    max_gain_db: f32,
+    attack: Duration,
+    release: Duration,
+    gate_db: f32,
+    /// Smoothed mean square of the audio
+    level: f32,
+    gain_db: f32,
+}
+
+impl Agc {
+    /// Create an AGC for audio at `sample_rate` with the default parameters
+    pub fn new(sample_rate: u32) -> Self {
+        Agc {
+            sample_rate,
+            target_db: DEFAULT_AGC_TARGET_DB,
+            max_gain_db: DEFAULT_AGC_MAX_GAIN_DB,
+            attack: DEFAULT_AGC_ATTACK,
+            release: DEFAULT_AGC_RELEASE,
+            gate_db: DEFAULT_AGC_GATE_DB,
+            level: 0.0,
+            gain_db: 0.0,
+        }
+    }
+
+    /// Set the level in dBFS, measured as RMS, that the audio is turned towards
+    pub fn with_target_db(mut self, target_db: f32) -> Self {
+        self.target_db = target_db;
+        self
+    }
+
+    /// Set the largest amplification in dB, the audio is attenuated by at most the same amount
+    pub fn with_max_gain_db(mut self, max_gain_db: f32) -> Self {
+        self.max_gain_db = max_gain_db.max(0.0);
+        self
+    }
+
+    /// Set the time the gain takes to drop when the audio gets louder
+    pub fn with_attack(mut self, attack: Duration) -> Self {
+        self.attack = attack;
+        self
+    }
+
+    /// Set the time the gain takes to rise when the audio gets quieter
+    pub fn with_release(mut self, release: Duration) -> Self {
+        self.release = release;
+        self
+    }
+
+    /// Set the level in dBFS below which the gain is held
+    pub fn with_gate_db(mut self, gate_db: f32) -> Self {
+        self.gate_db = gate_db;
+        self
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn target_db(&self) -> f32 {
+        self.target_db
+    }
+
+    pub fn max_gain_db(&self) -> f32 {
+        self.max_gain_db
+    }
+
+    pub fn attack(&self) -> Duration {
+        self.attack
+    }
+
+    pub fn release(&self) -> Duration {
+        self.release
+    }
+
+    pub fn gate_db(&self) -> f32 {
+        self.gate_db
+    }
+
+    /// The gain in dB currently applied to the audio
+    pub fn gain_db(&self) -> f32 {
+        self.gain_db
+    }
+
+    /// Apply the gain control to interleaved samples with `channels` channels in place
+    pub fn process(&mut self, samples: &mut [i16], channels: usize) {
+        let channels = channels.max(1);
+        let level_coefficient = coefficient(LEVEL_WINDOW, self.sample_rate);
+        let attack_coefficient = coefficient(self.attack, self.sample_rate);
+        let release_coefficient = coefficient(self.release, self.sample_rate);
+        for frame in samples.chunks_mut(channels) {
+            let square = frame
+                .iter()
+                .map(|sample| {
+                    let sample = *sample as f32 / 32768.0;
+                    sample * sample
+                })
+                .fold(0.0, f32::max);
+            self.level = square + (self.level - square) * level_coefficient;
+
+            let level_db = 10.0 * self.level.max(f32::MIN_POSITIVE).log10();
+            if level_db > self.gate_db {
+                let target = (self.target_db - level_db).clamp(-self.max_gain_db, self.max_gain_db);
+                let coefficient = if target < self.gain_db {
+                    attack_coefficient
+                } else {
+                    release_coefficient
+                };
+                self.gain_db = target + (self.gain_db - target) * coefficient;
+            }
+
+            let gain = 10f32.powf(self.gain_db / 20.0);
+            for sample in frame {
+                *sample = (*sample as f32 * gain).clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+            }
+        }
+    }
+
+    /// Forget the level and gain of previous audio
+    pub fn reset(&mut self) {
+        self.level = 0.0;
+        self.gain_db = 0.0;
+    }
+}
+
+/// Factor the distance to a target is multiplied with every sample to approach it within `duration`
+fn coefficient(duration: Duration, sample_rate: u32) -> f32 {
+    let samples = duration.as_secs_f32() * sample_rate as f32;
+    if samples > 0.0 {
+        (-1.0 / samples).exp()
+    } else {
+        0.0
+    }
+}
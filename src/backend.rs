@@ -0,0 +1,396 @@
+//! Abstraction over the opus implementation used for decoding
+
+use crate::{OutputChannels, SteamAudioError};
+
+/// An opus decoder implementation
+///
+/// The decoder is created for a fixed output sample rate and channel count with a [`BackendConstructor`].
+/// Sample counts are per channel, output buffers contain interleaved samples.
+pub trait OpusBackend: Send {
+    /// Decode an opus packet into `output`, returns the number of samples per channel
+    ///
+    /// An empty packet requests packet loss concealment for the duration of `output`, when `fec` is set
+    /// the forward error correction data of the packet is decoded instead of the packet itself.
+    fn decode(
+        &mut self,
+        packet: &[u8],
+        output: &mut [i16],
+        fec: bool,
+    ) -> Result<usize, SteamAudioError>;
+
+    /// Decode an opus packet into floating point samples, see [`decode`](Self::decode)
+    ///
+    /// The default implementation decodes to 16 bit samples and converts them.
+    fn decode_float(
+        &mut self,
+        packet: &[u8],
+        output: &mut [f32],
+        fec: bool,
+    ) -> Result<usize, SteamAudioError> {
+        let mut samples = vec![0; output.len()];
+        let count = self.decode(packet, &mut samples, fec)?;
+        for (output, sample) in output.iter_mut().zip(&samples) {
+            *output = *sample as f32 / 32768.0;
+        }
+        Ok(count)
+    }
+
+    /// Reset the decoder state, e.g. when the encoder of the voice data was restarted
+    fn reset(&mut self) -> Result<(), SteamAudioError>;
+
+    /// Whether decoding with `fec` recovers the lost frame from the forward error correction data of a packet
+    ///
+    /// Backends without forward error correction are only asked to conceal lost frames.
+    /// The default implementation returns `true`.
+    fn supports_fec(&self) -> bool {
+        true
+    }
+
+    /// Set the gain applied to the decoded audio, in 1/256 dB like `OPUS_SET_GAIN`
+    ///
+    /// The gain has to apply to concealed audio too and is kept when the decoder is reset.
+    /// The default implementation fails with [`SteamAudioError::GainUnsupported`].
+    fn set_gain(&mut self, gain: i16) -> Result<(), SteamAudioError> {
+        let _ = gain;
+        Err(SteamAudioError::GainUnsupported)
+    }
+}
+
+/// Creates an [`OpusBackend`] decoding at the given sample rate and channels
+pub type BackendConstructor =
+    fn(rate: u32, channels: OutputChannels) -> Result<Box<dyn OpusBackend>, SteamAudioError>;
+
+/// Backend using the C libopus through the `opus` crate
+#[cfg(feature = "opus")]
+pub struct LibOpus {
+    decoder: opus::Decoder,
+}
+
+#[cfg(feature = "opus")]
+impl LibOpus {
+    pub fn new(rate: u32, channels: OutputChannels) -> Result<Self, SteamAudioError> {
+        let channels = match channels {
+            OutputChannels::Mono => opus::Channels::Mono,
+            OutputChannels::Stereo => opus::Channels::Stereo,
+        };
+        Ok(LibOpus {
+            decoder: opus::Decoder::new(rate, channels)?,
+        })
+    }
+
+    /// [`BackendConstructor`] for this backend
+    pub fn boxed(
+        rate: u32,
+        channels: OutputChannels,
+    ) -> Result<Box<dyn OpusBackend>, SteamAudioError> {
+        Ok(Box::new(Self::new(rate, channels)?))
+    }
+}
+
+#[cfg(feature = "opus")]
+impl OpusBackend for LibOpus {
+    fn decode(
+        &mut self,
+        packet: &[u8],
+        output: &mut [i16],
+        fec: bool,
+    ) -> Result<usize, SteamAudioError> {
+        Ok(self.decoder.decode(packet, output, fec)?)
+    }
+
+    fn decode_float(
+        &mut self,
+        packet: &[u8],
+        output: &mut [f32],
+        fec: bool,
+    ) -> Result<usize, SteamAudioError> {
+        Ok(self.decoder.decode_float(packet, output, fec)?)
+    }
+
+    fn reset(&mut self) -> Result<(), SteamAudioError> {
+        self.decoder.reset_state()?;
+        Ok(())
+    }
+
+    fn set_gain(&mut self, gain: i16) -> Result<(), SteamAudioError> {
+        self.decoder.set_gain(gain as i32)?;
+        Ok(())
+    }
+}
+
+/// Backend using the C libopus through the `audiopus` crate
+#[cfg(feature = "audiopus")]
+pub struct Audiopus {
+    decoder: audiopus::coder::Decoder,
+}
+
+#[cfg(feature = "audiopus")]
+impl Audiopus {
+    pub fn new(rate: u32, channels: OutputChannels) -> Result<Self, SteamAudioError> {
+        let rate = audiopus::SampleRate::try_from(rate as i32)?;
+        let channels = match channels {
+            OutputChannels::Mono => audiopus::Channels::Mono,
+            OutputChannels::Stereo => audiopus::Channels::Stereo,
+        };
+        Ok(Audiopus {
+            decoder: audiopus::coder::Decoder::new(rate, channels)?,
+        })
+    }
+
+    /// [`BackendConstructor`] for this backend
+    pub fn boxed(
+        rate: u32,
+        channels: OutputChannels,
+    ) -> Result<Box<dyn OpusBackend>, SteamAudioError> {
+        Ok(Box::new(Self::new(rate, channels)?))
+    }
+}
+
+#[cfg(feature = "audiopus")]
+impl OpusBackend for Audiopus {
+    fn decode(
+        &mut self,
+        packet: &[u8],
+        output: &mut [i16],
+        fec: bool,
+    ) -> Result<usize, SteamAudioError> {
+        // audiopus rejects empty packets, concealment is requested by passing no packet
+        let packet = (!packet.is_empty())
+            .then(|| packet.try_into())
+            .transpose()?;
+        Ok(self.decoder.decode(packet, output.try_into()?, fec)?)
+    }
+
+    fn decode_float(
+        &mut self,
+        packet: &[u8],
+        output: &mut [f32],
+        fec: bool,
+    ) -> Result<usize, SteamAudioError> {
+        let packet = (!packet.is_empty())
+            .then(|| packet.try_into())
+            .transpose()?;
+        Ok(self.decoder.decode_float(packet, output.try_into()?, fec)?)
+    }
+
+    fn reset(&mut self) -> Result<(), SteamAudioError> {
+        use audiopus::coder::GenericCtl;
+        self.decoder.reset_state()?;
+        Ok(())
+    }
+
+    fn set_gain(&mut self, gain: i16) -> Result<(), SteamAudioError> {
+        self.decoder.set_gain(gain as i32)?;
+        Ok(())
+    }
+}
+
+/// Backend using the pure Rust opus decoder from the `opus-decoder` crate
+///
+/// This doesn't require a C toolchain, which makes it usable on targets like `wasm32-unknown-unknown`.
+/// Forward error correction data is not supported, frames that would be recovered from it are concealed instead.
+///
+/// The decoder only produces correct output at 48kHz, other sample rates are decoded at 48kHz, low-pass filtered
+/// and decimated.
+#[cfg(feature = "opus-decoder")]
+pub struct RustOpus {
+    decoder: opus_decoder::OpusDecoder,
+    channels: usize,
+    /// Ratio between the 48kHz decoding rate and the output rate
+    factor: usize,
+    /// Low-pass filter applied before decimating, empty at 48kHz
+    taps: Vec<f32>,
+    /// The last `taps.len() - 1` frames of the previous packet followed by the 48kHz samples of the current packet
+    scratch: Vec<i16>,
+    /// Linear gain applied to the decoded samples, the decoder has no gain of its own
+    gain: f32,
+}
+
+/// Length of the decimation filter per unit of the decimation factor
+#[cfg(feature = "opus-decoder")]
+const TAPS_PER_FACTOR: usize = 16;
+
+/// Windowed sinc low-pass filter for decimating by `factor`, with the cutoff just below the output Nyquist frequency
+#[cfg(feature = "opus-decoder")]
+fn decimation_taps(factor: usize) -> Vec<f32> {
+    use std::f32::consts::PI;
+
+    let length = TAPS_PER_FACTOR * factor + 1;
+    // in cycles per 48kHz sample
+    let cutoff = 0.45 / factor as f32;
+    let middle = (length - 1) as f32 / 2.0;
+    let mut taps: Vec<f32> = (0..length)
+        .map(|i| {
+            let x = i as f32 - middle;
+            let sinc = if x == 0.0 {
+                2.0 * cutoff
+            } else {
+                (2.0 * PI * cutoff * x).sin() / (PI * x)
+            };
+            let phase = 2.0 * PI * i as f32 / (length - 1) as f32;
+            let window = 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+            sinc * window
+        })
+        .collect();
+    let sum: f32 = taps.iter().sum();
+    for tap in &mut taps {
+        *tap /= sum;
+    }
+    taps
+}
+
+#[cfg(feature = "opus-decoder")]
+impl RustOpus {
+    pub fn new(rate: u32, channels: OutputChannels) -> Result<Self, SteamAudioError> {
+        if rate == 0 || 48_000 % rate != 0 {
+            return Err(SteamAudioError::UnsupportedSampleRate { rate });
+        }
+        let factor = (48_000 / rate) as usize;
+        let taps = match factor {
+            1 => Vec::new(),
+            factor => decimation_taps(factor),
+        };
+        Ok(RustOpus {
+            decoder: opus_decoder::OpusDecoder::new(48_000, channels.count())?,
+            channels: channels.count(),
+            factor,
+            scratch: vec![0; taps.len().saturating_sub(1) * channels.count()],
+            taps,
+            gain: 1.0,
+        })
+    }
+
+    /// [`BackendConstructor`] for this backend
+    pub fn boxed(
+        rate: u32,
+        channels: OutputChannels,
+    ) -> Result<Box<dyn OpusBackend>, SteamAudioError> {
+        Ok(Box::new(Self::new(rate, channels)?))
+    }
+
+    /// Decode without applying the gain
+    fn decode_unscaled(
+        &mut self,
+        packet: &[u8],
+        output: &mut [i16],
+        fec: bool,
+    ) -> Result<usize, SteamAudioError> {
+        // without forward error correction the frame is concealed
+        let packet = if fec { &[][..] } else { packet };
+        if self.factor == 1 {
+            return Ok(self.decoder.decode(packet, output, false)?);
+        }
+
+        let (channels, factor) = (self.channels, self.factor);
+        let history = (self.taps.len() - 1) * channels;
+        self.scratch.resize(history + output.len() * factor, 0);
+        let decoded = self
+            .decoder
+            .decode(packet, &mut self.scratch[history..], false)?
+            / factor;
+        for (index, output) in output[..decoded * channels].iter_mut().enumerate() {
+            let (frame, channel) = (index / channels, index % channels);
+            let start = frame * factor;
+            let sum: f32 = self
+                .taps
+                .iter()
+                .enumerate()
+                .map(|(tap, weight)| {
+                    self.scratch[(start + tap) * channels + channel] as f32 * weight
+                })
+                .sum();
+            *output = sum.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        }
+        // keep the end of the packet for filtering the start of the next one
+        let end = history + decoded * factor * channels;
+        self.scratch.copy_within(end - history..end, 0);
+        Ok(decoded)
+    }
+}
+
+#[cfg(feature = "opus-decoder")]
+impl OpusBackend for RustOpus {
+    fn decode(
+        &mut self,
+        packet: &[u8],
+        output: &mut [i16],
+        fec: bool,
+    ) -> Result<usize, SteamAudioError> {
+        let decoded = self.decode_unscaled(packet, output, fec)?;
+        if self.gain != 1.0 {
+            for sample in &mut output[..decoded * self.channels] {
+                *sample =
+                    (*sample as f32 * self.gain).clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+            }
+        }
+        Ok(decoded)
+    }
+
+    fn reset(&mut self) -> Result<(), SteamAudioError> {
+        self.decoder.reset();
+        self.scratch.fill(0);
+        Ok(())
+    }
+
+    fn supports_fec(&self) -> bool {
+        false
+    }
+
+    fn set_gain(&mut self, gain: i16) -> Result<(), SteamAudioError> {
+        self.gain = 10f32.powf(gain as f32 / 256.0 / 20.0);
+        Ok(())
+    }
+}
+
+/// Constructor for builds without a built-in backend, a backend has to be provided when creating the decoder
+#[cfg(not(any(feature = "opus", feature = "audiopus", feature = "opus-decoder")))]
+fn no_backend(
+    _rate: u32,
+    _channels: OutputChannels,
+) -> Result<Box<dyn OpusBackend>, SteamAudioError> {
+    Err(SteamAudioError::NoBackend)
+}
+
+/// The backend used by decoders that don't specify one
+#[cfg(feature = "opus")]
+pub(crate) const DEFAULT_BACKEND: BackendConstructor = LibOpus::boxed;
+#[cfg(all(feature = "audiopus", not(feature = "opus")))]
+pub(crate) const DEFAULT_BACKEND: BackendConstructor = Audiopus::boxed;
+#[cfg(all(
+    feature = "opus-decoder",
+    not(any(feature = "opus", feature = "audiopus"))
+))]
+pub(crate) const DEFAULT_BACKEND: BackendConstructor = RustOpus::boxed;
+#[cfg(not(any(feature = "opus", feature = "audiopus", feature = "opus-decoder")))]
+pub(crate) const DEFAULT_BACKEND: BackendConstructor = no_backend;
+
+#[cfg(all(test, feature = "opus-decoder"))]
+mod tests {
+    use super::*;
+
+    /// Gain of the filter for a frequency in cycles per 48kHz sample
+    fn response(taps: &[f32], frequency: f32) -> f32 {
+        let (re, im) = taps
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(re, im), (i, tap)| {
+                let angle = 2.0 * std::f32::consts::PI * frequency * i as f32;
+                (re + tap * angle.cos(), im + tap * angle.sin())
+            });
+        (re * re + im * im).sqrt()
+    }
+
+    #[test]
+    fn decimation_filter_removes_aliases() {
+        for factor in [2, 3, 4, 6] {
+            let taps = decimation_taps(factor);
+            let nyquist = 0.5 / factor as f32;
+            assert!((response(&taps, 0.0) - 1.0).abs() < 1e-4);
+            assert!((response(&taps, nyquist * 0.5) - 1.0).abs() < 0.01);
+            // frequencies that would fold back into the output band
+            for above in [1.2, 1.5, 2.0] {
+                assert!(response(&taps, nyquist * above) < 0.01, "factor {factor}");
+            }
+        }
+    }
+}
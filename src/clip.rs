@@ -0,0 +1,233 @@
+//! Cutting the speech of every speaker into timestamped clips
+
+use crate::{
+    EnergyVad, SpeechSegment, VadSegmenter, VoiceActivityDetector, VoiceSession,
+    DEFAULT_MIN_SILENCE,
+};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// A section of speech of a speaker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpeechClip {
+    pub steam_id: u64,
+    /// Time of the first sample of the clip on the timeline
+    pub start: Duration,
+    /// Time directly after the last sample of the clip on the timeline
+    pub end: Duration,
+    /// Position of the first sample of the clip on the timeline
+    pub start_sample: u64,
+    pub samples: Vec<i16>,
+}
+
+struct SpeakerClipper<D> {
+    segmenter: VadSegmenter<D>,
+    /// Timeline position of position 0 of the segmenter
+    origin: u64,
+    /// Samples that can still become part of a clip, starting at segmenter position `buffer_start`
+    buffer: Vec<i16>,
+    buffer_start: u64,
+}
+
+/// Cuts the decoded audio of every speaker into clips of speech, e.g. to feed speech recognition
+///
+/// The audio of every speaker is split into speech segments by a [`VadSegmenter`], the samples of every segment are
+/// returned as a clip with its position on the timeline once the segment ended. Only the audio that can still
+/// become part of a clip is kept, so the clipper can run over a whole match without holding all of its audio.
+pub struct SpeechClipper<D = EnergyVad> {
+    detector: D,
+    sample_rate: u32,
+    min_silence: Duration,
+    speakers: HashMap<u64, SpeakerClipper<D>>,
+}
+
+impl<D> std::fmt::Debug for SpeechClipper<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpeechClipper")
+            .field("sample_rate", &self.sample_rate)
+            .field("min_silence", &self.min_silence)
+            .field("speakers", &self.speakers.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl SpeechClipper<EnergyVad> {
+    /// Create a clipper for mono audio at `sample_rate` using the [`EnergyVad`] with the default threshold
+    pub fn new(sample_rate: u32) -> Self {
+        SpeechClipper::with_detector(EnergyVad::default(), sample_rate)
+    }
+}
+
+impl<D: VoiceActivityDetector + Clone> SpeechClipper<D> {
+    /// Create a clipper using a copy of `detector` for every speaker
+    pub fn with_detector(detector: D, sample_rate: u32) -> Self {
+        SpeechClipper {
+            detector,
+            sample_rate,
+            min_silence: DEFAULT_MIN_SILENCE,
+            speakers: HashMap::new(),
+        }
+    }
+
+    /// Set the shortest pause that ends a clip
+    pub fn with_min_silence(mut self, min_silence: Duration) -> Self {
+        self.min_silence = min_silence;
+        self
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Process mono audio of a speaker starting at sample `position` of the timeline,
+    /// returns the clips that ended
+    ///
+    /// The audio of a speaker has to be passed in order, gaps between the audio are treated as silence.
+    pub fn process(&mut self, steam_id: u64, position: u64, samples: &[i16]) -> Vec<SpeechClip> {
+        let speaker = self
+            .speakers
+            .entry(steam_id)
+            .or_insert_with(|| SpeakerClipper {
+                segmenter: VadSegmenter::new(self.detector.clone(), self.sample_rate)
+                    .with_min_silence(self.min_silence),
+                origin: position,
+                buffer: Vec::new(),
+                buffer_start: 0,
+            });
+
+        let mut clips = Vec::new();
+        let local = position.saturating_sub(speaker.origin);
+        let end = speaker.segmenter.position();
+        if local > end {
+            if let Some(segment) = speaker.segmenter.skip(local - end) {
+                clips.push(self.clip(steam_id, segment));
+            }
+            self.trim(steam_id);
+        }
+
+        let speaker = self.speakers.get_mut(&steam_id).expect("speaker was added");
+        let skip = end.saturating_sub(local) as usize;
+        let samples = samples.get(skip..).unwrap_or_default();
+        if speaker.buffer.is_empty() {
+            speaker.buffer_start = speaker.segmenter.position();
+        }
+        speaker.buffer.extend_from_slice(samples);
+        let segments = speaker.segmenter.process(samples);
+        clips.extend(
+            segments
+                .into_iter()
+                .map(|segment| self.clip(steam_id, segment)),
+        );
+        self.trim(steam_id);
+        clips
+    }
+
+    /// Process the decoded audio of all speakers of a session, taking it out of the streams
+    ///
+    /// The session should decode mono audio at the sample rate of the clipper.
+    pub fn process_session(&mut self, session: &mut VoiceSession) -> Vec<SpeechClip> {
+        let mut clips = Vec::new();
+        for speaker in session.speakers_mut() {
+            let position = speaker.position();
+            let samples = speaker.take_samples();
+            clips.extend(self.process(speaker.steam_id(), position, &samples));
+        }
+        clips
+    }
+
+    /// End the audio of all speakers, returning the clips that were still open ordered by their start
+    pub fn finish(&mut self) -> Vec<SpeechClip> {
+        let segments: Vec<_> = self
+            .speakers
+            .iter_mut()
+            .filter_map(|(steam_id, speaker)| Some((*steam_id, speaker.segmenter.finish()?)))
+            .collect();
+        let mut clips: Vec<_> = segments
+            .into_iter()
+            .map(|(steam_id, segment)| self.clip(steam_id, segment))
+            .collect();
+        clips.sort_by_key(|clip| (clip.start_sample, clip.steam_id));
+        self.speakers.clear();
+        clips
+    }
+
+    /// Cut the samples of a segment out of the buffer of the speaker
+    fn clip(&self, steam_id: u64, segment: SpeechSegment) -> SpeechClip {
+        let speaker = &self.speakers[&steam_id];
+        let from = segment.start.saturating_sub(speaker.buffer_start) as usize;
+        let to = segment.end.saturating_sub(speaker.buffer_start) as usize;
+        let samples = speaker
+            .buffer
+            .get(from.min(speaker.buffer.len())..to.min(speaker.buffer.len()))
+            .unwrap_or_default()
+            .to_vec();
+        let start_sample = speaker.origin + segment.start;
+        let time =
+            |position: u64| Duration::from_secs_f64(position as f64 / self.sample_rate as f64);
+        SpeechClip {
+            steam_id,
+            start: time(start_sample),
+            end: time(speaker.origin + segment.end),
+            start_sample,
+            samples,
+        }
+    }
+
+    /// Drop the buffered samples that can't become part of a clip anymore
+    fn trim(&mut self, steam_id: u64) {
+        let Some(speaker) = self.speakers.get_mut(&steam_id) else {
+            return;
+        };
+        let keep_from = match speaker.segmenter.current() {
+            Some(current) => current.start,
+            None => speaker.segmenter.unclassified(),
+        };
+        let drop =
+            (keep_from.saturating_sub(speaker.buffer_start) as usize).min(speaker.buffer.len());
+        speaker.buffer.drain(..drop);
+        speaker.buffer_start += drop as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(count: usize) -> Vec<i16> {
+        (0..count)
+            .map(|i| ((i as f32 * 0.1).sin() * 8000.0) as i16)
+            .collect()
+    }
+
+    #[test]
+    fn clips_speech_of_every_speaker() {
+        let mut clipper = SpeechClipper::new(24000).with_min_silence(Duration::from_millis(100));
+
+        // the silence after the speech ends the clip
+        assert_eq!(clipper.process(1, 24000, &tone(4800)), vec![]);
+        assert_eq!(
+            clipper.process(1, 28800, &[0; 4800]),
+            vec![SpeechClip {
+                steam_id: 1,
+                start: Duration::from_secs(1),
+                end: Duration::from_millis(1200),
+                start_sample: 24000,
+                samples: tone(4800),
+            }]
+        );
+
+        // a gap between the audio of a speaker ends the clip
+        assert_eq!(clipper.process(2, 0, &tone(2400)), vec![]);
+        let clips = clipper.process(2, 12000, &tone(2400));
+        assert_eq!(clips.len(), 1);
+        assert_eq!(clips[0].steam_id, 2);
+        assert_eq!(clips[0].start_sample, 0);
+        assert_eq!(clips[0].samples, tone(2400));
+
+        let open = clipper.finish();
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].start_sample, 12000);
+        assert_eq!(open[0].end, Duration::from_millis(600));
+        assert_eq!(open[0].samples, tone(2400));
+    }
+}
@@ -0,0 +1,78 @@
+//! [`tokio_util::codec`] integration for reading voice payloads from a byte stream
+
+use crate::{payload_length, OwnedSteamVoiceData, SteamAudioError};
+use bytes::BytesMut;
+use std::fmt::Debug;
+use tokio_util::codec::Decoder;
+
+/// Splits a byte stream of consecutive voice payloads into the individual payloads
+///
+/// Each payload has its checksum verified and is yielded as [`OwnedSteamVoiceData`].
+#[derive(Debug, Default)]
+pub struct VoicePayloadCodec;
+
+impl VoicePayloadCodec {
+    pub fn new() -> Self {
+        VoicePayloadCodec
+    }
+}
+
+impl Decoder for VoicePayloadCodec {
+    type Item = OwnedSteamVoiceData;
+    type Error = SteamAudioError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        payload_length(src)?
+            .map(|length| OwnedSteamVoiceData::new(src.split_to(length).to_vec()))
+            .transpose()
+    }
+}
+
+#[cfg(feature = "decoder")]
+pub use pcm::VoicePcmCodec;
+
+#[cfg(feature = "decoder")]
+mod pcm {
+    use crate::{payload_length, SteamAudioError, SteamVoiceData, SteamVoiceDecoder};
+    use bytes::BytesMut;
+    use std::fmt::Debug;
+    use tokio_util::codec::Decoder;
+
+    /// Decodes a byte stream of consecutive voice payloads, yielding the decoded PCM for each payload
+    pub struct VoicePcmCodec {
+        decoder: SteamVoiceDecoder,
+    }
+
+    impl Debug for VoicePcmCodec {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("VoicePcmCodec").finish_non_exhaustive()
+        }
+    }
+
+    impl VoicePcmCodec {
+        pub fn new(decoder: SteamVoiceDecoder) -> Self {
+            VoicePcmCodec { decoder }
+        }
+
+        /// Get back the decoder
+        pub fn into_decoder(self) -> SteamVoiceDecoder {
+            self.decoder
+        }
+    }
+
+    impl Decoder for VoicePcmCodec {
+        type Item = Vec<i16>;
+        type Error = SteamAudioError;
+
+        fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+            let Some(length) = payload_length(src)? else {
+                return Ok(None);
+            };
+            let payload = src.split_to(length);
+            let voice_data = SteamVoiceData::new(&payload)?;
+            let mut samples = Vec::new();
+            self.decoder.decode_append(voice_data, &mut samples)?;
+            Ok(Some(samples))
+        }
+    }
+}
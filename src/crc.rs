@@ -0,0 +1,145 @@
+//! The CRC-32 variant used for the checksum of voice payloads
+
+use crate::SteamAudioError;
+
+/// Reversed polynomial of the IEEE CRC-32
+const POLYNOMIAL: u32 = 0xEDB88320;
+
+/// Lookup tables for processing 8 bytes at once, `TABLES[n][byte]` is the crc of `byte` followed by `n` zero bytes
+static TABLES: [[u32; 256]; 8] = tables();
+
+const fn tables() -> [[u32; 256]; 8] {
+    let mut tables = [[0; 256]; 8];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut crc = byte as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        tables[0][byte] = crc;
+        byte += 1;
+    }
+
+    let mut byte = 0;
+    while byte < 256 {
+        let mut table = 1;
+        while table < 8 {
+            let previous = tables[table - 1][byte];
+            tables[table][byte] = (previous >> 8) ^ tables[0][(previous & 0xFF) as usize];
+            table += 1;
+        }
+        byte += 1;
+    }
+    tables
+}
+
+/// Compute the CRC-32 checksum used for voice payloads, the same variant as zlib and PNG
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xFFFFFFFF;
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        let low = crc ^ u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        let high = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+        crc = TABLES[7][(low & 0xFF) as usize]
+            ^ TABLES[6][((low >> 8) & 0xFF) as usize]
+            ^ TABLES[5][((low >> 16) & 0xFF) as usize]
+            ^ TABLES[4][(low >> 24) as usize]
+            ^ TABLES[3][(high & 0xFF) as usize]
+            ^ TABLES[2][((high >> 8) & 0xFF) as usize]
+            ^ TABLES[1][((high >> 16) & 0xFF) as usize]
+            ^ TABLES[0][(high >> 24) as usize];
+    }
+    for &byte in chunks.remainder() {
+        crc = (crc >> 8) ^ TABLES[0][((crc ^ byte as u32) & 0xFF) as usize];
+    }
+    !crc
+}
+
+/// Verify the checksum at the end of a payload, returns the payload without the checksum
+pub fn verify_crc(data: &[u8]) -> Result<&[u8], SteamAudioError> {
+    if data.len() < 4 {
+        return Err(SteamAudioError::InsufficientData);
+    }
+    let (data, crc_data) = data.split_at(data.len() - 4);
+    let expected = u32::from_le_bytes(crc_data.try_into().unwrap());
+    let actual = crc32(data);
+    if expected != actual {
+        return Err(SteamAudioError::CrcMismatch { expected, actual });
+    }
+    Ok(data)
+}
+
+/// Append the checksum of the payload in `data` to it, e.g. after patching the packets of a payload
+pub fn append_crc(data: &mut Vec<u8>) {
+    let crc = crc32(data);
+    data.extend_from_slice(&crc.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bit by bit CRC-32 to check the table driven implementation against
+    fn reference(data: &[u8]) -> u32 {
+        let mut crc: u32 = 0xFFFFFFFF;
+        for &byte in data {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ POLYNOMIAL
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+        !crc
+    }
+
+    #[test]
+    fn check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(crc32(&[]), 0);
+    }
+
+    #[test]
+    fn matches_reference() {
+        let data: Vec<u8> = (0..64u32).map(|i| (i * 37 + 11) as u8).collect();
+        for start in 0..8 {
+            for len in 0..=17 {
+                let slice = &data[start..start + len];
+                assert_eq!(
+                    crc32(slice),
+                    reference(slice),
+                    "start {start}, length {len}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn verify_appended() {
+        let mut data = b"123456789".to_vec();
+        append_crc(&mut data);
+        assert_eq!(&data[9..], &0xCBF43926u32.to_le_bytes());
+        assert_eq!(verify_crc(&data).unwrap(), b"123456789");
+
+        data[0] ^= 1;
+        assert!(matches!(
+            verify_crc(&data),
+            Err(SteamAudioError::CrcMismatch {
+                expected: 0xCBF43926,
+                ..
+            })
+        ));
+        assert!(matches!(
+            verify_crc(&[0; 3]),
+            Err(SteamAudioError::InsufficientData)
+        ));
+    }
+}
@@ -0,0 +1,287 @@
+//! Voice messages of CS2 and other games using SteamNetworkingSockets
+//!
+//! The revised `CSVCMsg_VoiceData` message moves the voice data into a nested `CMsgVoiceAudio` message.
+//! Besides steam voice payloads, the voice data can contain bare opus packets, with the sample rate and
+//! the boundaries of the packets in separate fields instead of a steam payload framing them.
+
+use crate::protobuf::{read_varint, Fields, Value};
+#[cfg(feature = "decoder")]
+use crate::SteamVoiceDecoder;
+use crate::{CsgoVoiceData, SteamAudioError, SteamVoiceData};
+
+/// Field numbers of `CSVCMsg_VoiceData`
+const FIELD_AUDIO: u64 = 1;
+const FIELD_CLIENT: u64 = 2;
+const FIELD_PROXIMITY: u64 = 3;
+const FIELD_XUID: u64 = 4;
+const FIELD_AUDIBLE_MASK: u64 = 5;
+const FIELD_TICK: u64 = 6;
+const FIELD_PASSTHROUGH: u64 = 7;
+
+/// Field numbers of `CMsgVoiceAudio`
+const FIELD_FORMAT: u64 = 1;
+const FIELD_VOICE_DATA: u64 = 2;
+const FIELD_SEQUENCE_BYTES: u64 = 3;
+const FIELD_SECTION_NUMBER: u64 = 4;
+const FIELD_SAMPLE_RATE: u64 = 5;
+const FIELD_UNCOMPRESSED_SAMPLE_OFFSET: u64 = 6;
+const FIELD_NUM_PACKETS: u64 = 7;
+const FIELD_PACKET_OFFSETS: u64 = 8;
+const FIELD_VOICE_LEVEL: u64 = 9;
+
+/// Sample rate of opus voice data that doesn't specify its sample rate
+const DEFAULT_OPUS_RATE: u16 = 48000;
+
+/// The format of the voice data in a message, `VoiceDataFormat_t`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Cs2VoiceFormat {
+    /// A steam voice payload, the default when the field is missing
+    #[default]
+    Steam,
+    /// Frames of the engine voice codec
+    Engine,
+    /// Bare opus packets
+    Opus,
+}
+
+/// A decoded `CSVCMsg_VoiceData` message of CS2
+#[derive(Debug, Clone, Default)]
+pub struct Cs2VoiceData<'a> {
+    pub audio: Cs2VoiceAudio<'a>,
+    /// Index of the speaking client
+    pub client: i32,
+    pub proximity: bool,
+    /// Steam id of the speaking player
+    pub xuid: u64,
+    pub audible_mask: i32,
+    /// Server tick the voice data was sent at
+    pub tick: u32,
+    pub passthrough: i32,
+}
+
+/// A decoded `CMsgVoiceAudio` message, the voice data of a [`Cs2VoiceData`]
+#[derive(Debug, Clone, Default)]
+pub struct Cs2VoiceAudio<'a> {
+    pub format: Cs2VoiceFormat,
+    pub sequence_bytes: i32,
+    /// Number of the voice section, increased every time the client starts talking
+    pub section_number: u32,
+    /// Sample rate of opus voice data, 0 if not set
+    pub sample_rate: u32,
+    /// Offset of the voice data in samples since the start of the section
+    pub uncompressed_sample_offset: u32,
+    pub num_packets: u32,
+    /// Offsets marking the boundaries between the opus packets in the voice data
+    pub packet_offsets: Vec<u32>,
+    pub voice_level: f32,
+    voice_data: &'a [u8],
+}
+
+impl<'a> Cs2VoiceData<'a> {
+    /// Decode the protobuf encoded message, without the message type and size
+    ///
+    /// Unknown fields are skipped, fails with [`SteamAudioError::InvalidMessage`] if the message is malformed.
+    pub fn new(message: &'a [u8]) -> Result<Self, SteamAudioError> {
+        let mut voice = Cs2VoiceData {
+            client: -1,
+            ..Cs2VoiceData::default()
+        };
+        for field in Fields::new(message) {
+            match field? {
+                (FIELD_AUDIO, Value::Bytes(audio)) => voice.audio = Cs2VoiceAudio::new(audio)?,
+                (FIELD_CLIENT, Value::Varint(client)) => voice.client = client as i32,
+                (FIELD_PROXIMITY, Value::Varint(proximity)) => voice.proximity = proximity != 0,
+                (FIELD_XUID, Value::Fixed64(xuid)) => voice.xuid = xuid,
+                (FIELD_AUDIBLE_MASK, Value::Varint(mask)) => voice.audible_mask = mask as i32,
+                (FIELD_TICK, Value::Varint(tick)) => voice.tick = tick as u32,
+                (FIELD_PASSTHROUGH, Value::Varint(passthrough)) => {
+                    voice.passthrough = passthrough as i32
+                }
+                (1..=7, _) => return Err(SteamAudioError::InvalidMessage),
+                _ => {}
+            }
+        }
+        Ok(voice)
+    }
+}
+
+impl Cs2VoiceData<'_> {
+    /// Decode the voice data with `decoder`, appending the decoded samples to `output`
+    ///
+    /// Steam voice payloads are decoded as usual, bare opus packets are decoded as the frames of a payload
+    /// of the speaking player. Returns the number of samples appended.
+    #[cfg(feature = "decoder")]
+    pub fn decode_append(
+        &self,
+        decoder: &mut SteamVoiceDecoder,
+        output: &mut Vec<i16>,
+    ) -> Result<usize, SteamAudioError> {
+        match self.audio.format {
+            Cs2VoiceFormat::Opus => decoder.decode_opus_append(
+                self.xuid,
+                self.audio.opus_sample_rate()?,
+                self.audio.opus_packets()?,
+                output,
+            ),
+            _ => decoder.decode_append(self.audio.steam_voice_data()?, output),
+        }
+    }
+}
+
+impl<'a> Cs2VoiceAudio<'a> {
+    fn new(message: &'a [u8]) -> Result<Self, SteamAudioError> {
+        let mut audio = Cs2VoiceAudio::default();
+        for field in Fields::new(message) {
+            match field? {
+                (FIELD_FORMAT, Value::Varint(format)) => {
+                    audio.format = match format {
+                        0 => Cs2VoiceFormat::Steam,
+                        2 => Cs2VoiceFormat::Opus,
+                        _ => Cs2VoiceFormat::Engine,
+                    }
+                }
+                (FIELD_VOICE_DATA, Value::Bytes(data)) => audio.voice_data = data,
+                (FIELD_SEQUENCE_BYTES, Value::Varint(bytes)) => audio.sequence_bytes = bytes as i32,
+                (FIELD_SECTION_NUMBER, Value::Varint(section)) => {
+                    audio.section_number = section as u32
+                }
+                (FIELD_SAMPLE_RATE, Value::Varint(rate)) => audio.sample_rate = rate as u32,
+                (FIELD_UNCOMPRESSED_SAMPLE_OFFSET, Value::Varint(offset)) => {
+                    audio.uncompressed_sample_offset = offset as u32
+                }
+                (FIELD_NUM_PACKETS, Value::Varint(count)) => audio.num_packets = count as u32,
+                // repeated fields can be sent packed or as individual values
+                (FIELD_PACKET_OFFSETS, Value::Varint(offset)) => {
+                    audio.packet_offsets.push(offset as u32)
+                }
+                (FIELD_PACKET_OFFSETS, Value::Bytes(mut packed)) => {
+                    while !packed.is_empty() {
+                        audio.packet_offsets.push(read_varint(&mut packed)? as u32);
+                    }
+                }
+                (FIELD_VOICE_LEVEL, Value::Fixed32(level)) => {
+                    audio.voice_level = f32::from_bits(level)
+                }
+                (1..=9, _) => return Err(SteamAudioError::InvalidMessage),
+                _ => {}
+            }
+        }
+        Ok(audio)
+    }
+
+    /// The contents of the `voice_data` field
+    pub fn voice_data(&self) -> &'a [u8] {
+        self.voice_data
+    }
+
+    /// Parse the voice data as a steam voice payload, verifying its checksum
+    ///
+    /// Fails with [`SteamAudioError::UnsupportedCodec`] if the message doesn't use the steam format.
+    pub fn steam_voice_data(&self) -> Result<SteamVoiceData<'a>, SteamAudioError> {
+        match self.format {
+            Cs2VoiceFormat::Steam => SteamVoiceData::new(self.voice_data),
+            format => Err(unsupported_format(format)),
+        }
+    }
+
+    /// The sample rate of opus voice data, 48kHz if the message doesn't specify it
+    pub fn opus_sample_rate(&self) -> Result<u16, SteamAudioError> {
+        match self.sample_rate {
+            0 => Ok(DEFAULT_OPUS_RATE),
+            rate => {
+                u16::try_from(rate).map_err(|_| SteamAudioError::UnsupportedSampleRate { rate })
+            }
+        }
+    }
+
+    /// Split opus voice data into its packets
+    ///
+    /// The voice data is split at every packet offset that lies within it, so both the start and the end
+    /// offsets of the packets are understood. Voice data without offsets is a single packet.
+    /// Fails with [`SteamAudioError::UnsupportedCodec`] if the message doesn't use the opus format.
+    pub fn opus_packets(&self) -> Result<Vec<&'a [u8]>, SteamAudioError> {
+        if self.format != Cs2VoiceFormat::Opus {
+            return Err(unsupported_format(self.format));
+        }
+        let mut boundaries: Vec<usize> = self
+            .packet_offsets
+            .iter()
+            .map(|&offset| offset as usize)
+            .filter(|&offset| offset > 0 && offset < self.voice_data.len())
+            .collect();
+        boundaries.sort_unstable();
+        boundaries.dedup();
+        boundaries.push(self.voice_data.len());
+        let mut start = 0;
+        Ok(boundaries
+            .into_iter()
+            .map(|end| {
+                let packet = &self.voice_data[start..end];
+                start = end;
+                packet
+            })
+            .filter(|packet| !packet.is_empty())
+            .collect())
+    }
+}
+
+fn unsupported_format(format: Cs2VoiceFormat) -> SteamAudioError {
+    SteamAudioError::UnsupportedCodec {
+        codec: match format {
+            Cs2VoiceFormat::Steam => "steam voice format",
+            Cs2VoiceFormat::Engine => "engine voice format",
+            Cs2VoiceFormat::Opus => "opus voice format",
+        }
+        .into(),
+    }
+}
+
+/// A voice message of either CS:GO or CS2, see [`VoiceMessage::new`]
+#[derive(Debug, Clone)]
+pub enum VoiceMessage<'a> {
+    Csgo(CsgoVoiceData<'a>),
+    Cs2(Cs2VoiceData<'a>),
+}
+
+impl<'a> VoiceMessage<'a> {
+    /// Decode a `CSVCMsg_VoiceData` message, detecting whether it uses the CS:GO or the CS2 layout
+    ///
+    /// The layouts are told apart by the types of their fields: the CS2 layout starts with the nested audio
+    /// message and has a numeric field 5, while the CS:GO layout starts with the client index and has the
+    /// voice data in field 5.
+    pub fn new(message: &'a [u8]) -> Result<Self, SteamAudioError> {
+        let cs2 = Fields::new(message).find_map(|field| match field.ok()? {
+            (1, Value::Bytes(_)) | (5, Value::Varint(_)) => Some(true),
+            (1 | 5, _) => Some(false),
+            _ => None,
+        });
+        if cs2.unwrap_or(false) {
+            Cs2VoiceData::new(message).map(VoiceMessage::Cs2)
+        } else {
+            CsgoVoiceData::new(message).map(VoiceMessage::Csgo)
+        }
+    }
+
+    /// Steam id of the speaking player
+    pub fn steam_id(&self) -> u64 {
+        match self {
+            VoiceMessage::Csgo(voice) => voice.xuid,
+            VoiceMessage::Cs2(voice) => voice.xuid,
+        }
+    }
+
+    /// Decode the voice data with `decoder`, appending the decoded samples to `output`
+    ///
+    /// Returns the number of samples appended.
+    #[cfg(feature = "decoder")]
+    pub fn decode_append(
+        &self,
+        decoder: &mut SteamVoiceDecoder,
+        output: &mut Vec<i16>,
+    ) -> Result<usize, SteamAudioError> {
+        match self {
+            VoiceMessage::Csgo(voice) => decoder.decode_append(voice.steam_voice_data()?, output),
+            VoiceMessage::Cs2(voice) => voice.decode_append(decoder, output),
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! Voice messages of CS:GO demos
+//!
+//! CS:GO sends voice in `CSVCMsg_VoiceData` protobuf messages. When the message uses the steam format,
+//! the `voice_data` field holds a regular steam voice payload, including its steam id and checksum.
+//! The engine format holds the raw frames of the `vaudio_*` codec of the server instead.
+
+use crate::protobuf::{Fields, Value};
+use crate::{SteamAudioError, SteamVoiceData};
+
+/// Field numbers of `CSVCMsg_VoiceData`
+const FIELD_CLIENT: u64 = 1;
+const FIELD_PROXIMITY: u64 = 2;
+const FIELD_XUID: u64 = 3;
+const FIELD_AUDIBLE_MASK: u64 = 4;
+const FIELD_VOICE_DATA: u64 = 5;
+const FIELD_CASTER: u64 = 6;
+const FIELD_FORMAT: u64 = 7;
+const FIELD_SEQUENCE_BYTES: u64 = 8;
+const FIELD_SECTION_NUMBER: u64 = 9;
+const FIELD_UNCOMPRESSED_SAMPLE_OFFSET: u64 = 10;
+
+/// The format of the voice data in a message, `VoiceDataFormat_t`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CsgoVoiceFormat {
+    /// A steam voice payload
+    Steam,
+    /// Frames of the engine voice codec, the default when the field is missing
+    #[default]
+    Engine,
+}
+
+/// A decoded `CSVCMsg_VoiceData` message
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CsgoVoiceData<'a> {
+    /// Index of the speaking client
+    pub client: i32,
+    pub proximity: bool,
+    /// Steam id of the speaking player
+    pub xuid: u64,
+    pub audible_mask: i32,
+    pub caster: bool,
+    pub format: CsgoVoiceFormat,
+    /// Running number of bytes of voice data sent by the client, for the engine format
+    pub sequence_bytes: i32,
+    /// Number of the voice section, increased every time the client starts talking
+    pub section_number: u32,
+    /// Offset of the voice data in samples since the start of the section
+    pub uncompressed_sample_offset: u32,
+    voice_data: &'a [u8],
+}
+
+impl<'a> CsgoVoiceData<'a> {
+    /// Decode the protobuf encoded message, without the message type and size
+    ///
+    /// Unknown fields are skipped, fails with [`SteamAudioError::InvalidMessage`] if the message is malformed.
+    pub fn new(message: &'a [u8]) -> Result<Self, SteamAudioError> {
+        let mut voice = CsgoVoiceData::default();
+        for field in Fields::new(message) {
+            match field? {
+                (FIELD_CLIENT, Value::Varint(client)) => voice.client = client as i32,
+                (FIELD_PROXIMITY, Value::Varint(proximity)) => voice.proximity = proximity != 0,
+                (FIELD_XUID, Value::Fixed64(xuid)) => voice.xuid = xuid,
+                (FIELD_AUDIBLE_MASK, Value::Varint(mask)) => voice.audible_mask = mask as i32,
+                (FIELD_VOICE_DATA, Value::Bytes(data)) => voice.voice_data = data,
+                (FIELD_CASTER, Value::Varint(caster)) => voice.caster = caster != 0,
+                (FIELD_FORMAT, Value::Varint(format)) => {
+                    voice.format = match format {
+                        0 => CsgoVoiceFormat::Steam,
+                        _ => CsgoVoiceFormat::Engine,
+                    }
+                }
+                (FIELD_SEQUENCE_BYTES, Value::Varint(bytes)) => voice.sequence_bytes = bytes as i32,
+                (FIELD_SECTION_NUMBER, Value::Varint(section)) => {
+                    voice.section_number = section as u32
+                }
+                (FIELD_UNCOMPRESSED_SAMPLE_OFFSET, Value::Varint(offset)) => {
+                    voice.uncompressed_sample_offset = offset as u32
+                }
+                (
+                    FIELD_CLIENT
+                    | FIELD_PROXIMITY
+                    | FIELD_XUID
+                    | FIELD_AUDIBLE_MASK
+                    | FIELD_VOICE_DATA
+                    | FIELD_CASTER
+                    | FIELD_FORMAT
+                    | FIELD_SEQUENCE_BYTES
+                    | FIELD_SECTION_NUMBER
+                    | FIELD_UNCOMPRESSED_SAMPLE_OFFSET,
+                    _,
+                ) => return Err(SteamAudioError::InvalidMessage),
+                _ => {}
+            }
+        }
+        Ok(voice)
+    }
+
+    /// The contents of the `voice_data` field
+    pub fn voice_data(&self) -> &'a [u8] {
+        self.voice_data
+    }
+
+    /// Parse the voice data as a steam voice payload, verifying its checksum
+    ///
+    /// Fails with [`SteamAudioError::UnsupportedCodec`] if the message uses the engine format.
+    pub fn steam_voice_data(&self) -> Result<SteamVoiceData<'a>, SteamAudioError> {
+        match self.format {
+            CsgoVoiceFormat::Steam => SteamVoiceData::new(self.voice_data),
+            CsgoVoiceFormat::Engine => Err(SteamAudioError::UnsupportedCodec {
+                codec: "engine voice format".into(),
+            }),
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Removing a constant offset from decoded audio
+
+/// Default for [`DcBlocker::cutoff`], in Hz
+pub const DEFAULT_DC_CUTOFF: f32 = 20.0;
+
+/// High-pass filter removing a DC offset from audio
+///
+/// Some recordings are biased away from zero, which wastes headroom and causes thumps where the audio starts
+/// and stops. The filter removes the offset while leaving the frequencies of speech untouched.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DcBlocker {
+    sample_rate: u32,
+    cutoff: f32,
+    /// Pole of the filter, how much of the previous output is kept every sample
+    pole: f32,
+    /// Previous input and output of every channel
+    state: Vec<(f32, f32)>,
+}
+
+impl DcBlocker {
+    /// Create a filter for audio at `sample_rate` with the default cutoff
+    pub fn new(sample_rate: u32) -> Self {
+        DcBlocker::with_cutoff(sample_rate, DEFAULT_DC_CUTOFF)
+    }
+
+    /// Create a filter for audio at `sample_rate` removing frequencies below roughly `cutoff` Hz
+    pub fn with_cutoff(sample_rate: u32, cutoff: f32) -> Self {
+        let pole = (-2.0 * std::f32::consts::PI * cutoff / sample_rate.max(1) as f32).exp();
+        DcBlocker {
+            sample_rate,
+            cutoff,
+            pole,
+            state: Vec::new(),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn cutoff(&self) -> f32 {
+        self.cutoff
+    }
+
+    /// Filter interleaved samples with `channels` channels in place
+    pub fn process(&mut self, samples: &mut [i16], channels: usize) {
+        let channels = channels.max(1);
+        self.state.resize(channels, (0.0, 0.0));
+        for frame in samples.chunks_mut(channels) {
+            for (sample, (previous_in, previous_out)) in frame.iter_mut().zip(&mut self.state) {
+                let input = *sample as f32;
+                let output = input - *previous_in + self.pole * *previous_out;
+                *previous_in = input;
+                *previous_out = output;
+                *sample = output.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+            }
+        }
+    }
+
+    /// Forget the state of previous audio
+    pub fn reset(&mut self) {
+        self.state.clear();
+    }
+}
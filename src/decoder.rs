@@ -0,0 +1,1545 @@
+use crate::backend::{BackendConstructor, OpusBackend, DEFAULT_BACKEND};
+use crate::event::{DecodeEvent, ResetReason};
+use crate::fade::{EdgeFade, Source};
+use crate::options::{
+    gain_from_db, DecoderOptions, OutputChannels, ParseMode, SteamVoiceDecoderBuilder,
+};
+use crate::stats::DecodeStats;
+use crate::toc::packet_samples;
+use crate::{
+    decode_rate, frames_lost, push_opus_packet, LinearResampler, OpusFrame, OpusFrameIterator,
+    Packet, PacketType, SteamAudioError, SteamVoiceData, HEADER_SIZE,
+};
+use crate::{AudioFilter, DcBlocker};
+use std::collections::VecDeque;
+use std::fmt::Debug;
+
+/// Maximum number of samples in a single opus frame (120ms at 48kHz, stereo)
+const MAX_FRAME_SAMPLES: usize = 5760 * 2;
+/// Number of frames decoded to bring a fresh opus decoder back into the state of a snapshot
+const PRIMING_FRAMES: usize = 3;
+/// Number of opus decoders for previously used sample rates that are kept for when the sample rate switches back
+const CACHED_DECODERS: usize = 2;
+
+/// Destination for the audio produced while decoding a payload
+trait DecodeOutput {
+    /// Decode an opus frame, returns the number of samples produced
+    fn opus(
+        &mut self,
+        decoder: &mut OpusDecoder,
+        data: &[u8],
+        filters: &mut OutputFilters,
+    ) -> Result<usize, SteamAudioError>;
+
+    /// Generate `frame_size` samples of packet loss concealment, recovered from the
+    /// forward error correction data in `fec` if it isn't empty
+    fn conceal(
+        &mut self,
+        decoder: &mut OpusDecoder,
+        frame_size: usize,
+        fec: &[u8],
+        filters: &mut OutputFilters,
+    ) -> Result<usize, SteamAudioError>;
+
+    fn silence(&mut self, count: usize, filters: &mut OutputFilters)
+        -> Result<(), SteamAudioError>;
+
+    /// Write already decoded samples
+    fn pcm(&mut self, samples: &[i16]) -> Result<(), SteamAudioError>;
+
+    /// Number of samples that still fit in the output, if the output is limited
+    /// decoding stops before the first frame that doesn't fit
+    fn remaining(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// Writes the decoded audio into a caller provided buffer
+struct SliceOutput<'a> {
+    buffer: &'a mut [i16],
+    total: usize,
+    zero_fill: bool,
+}
+
+impl DecodeOutput for SliceOutput<'_> {
+    fn opus(
+        &mut self,
+        decoder: &mut OpusDecoder,
+        data: &[u8],
+        filters: &mut OutputFilters,
+    ) -> Result<usize, SteamAudioError> {
+        let output = self
+            .buffer
+            .get_mut(self.total..)
+            .ok_or(SteamAudioError::InsufficientOutputBuffer)?;
+        let count = decoder.decode(data, output)?;
+        filters.apply(&mut output[..count], Source::Audio);
+        self.total += count;
+        Ok(count)
+    }
+
+    fn conceal(
+        &mut self,
+        decoder: &mut OpusDecoder,
+        frame_size: usize,
+        fec: &[u8],
+        filters: &mut OutputFilters,
+    ) -> Result<usize, SteamAudioError> {
+        let output = self
+            .buffer
+            .get_mut(self.total..self.total + frame_size)
+            .ok_or(SteamAudioError::InsufficientOutputBuffer)?;
+        let count = decoder.conceal(fec, output)?;
+        filters.apply(&mut output[..count], conceal_source(fec));
+        self.total += count;
+        Ok(count)
+    }
+
+    fn silence(
+        &mut self,
+        count: usize,
+        filters: &mut OutputFilters,
+    ) -> Result<(), SteamAudioError> {
+        let output = &mut self.buffer[self.total..self.total + count];
+        if self.zero_fill {
+            output.fill(0);
+            filters.apply(output, Source::Silence);
+        } else {
+            filters.apply(&mut [], Source::Silence);
+        }
+        self.total += count;
+        Ok(())
+    }
+
+    fn pcm(&mut self, samples: &[i16]) -> Result<(), SteamAudioError> {
+        self.buffer
+            .get_mut(self.total..self.total + samples.len())
+            .ok_or(SteamAudioError::InsufficientOutputBuffer)?
+            .copy_from_slice(samples);
+        self.total += samples.len();
+        Ok(())
+    }
+
+    fn remaining(&self) -> Option<usize> {
+        Some(self.buffer.len() - self.total)
+    }
+}
+
+/// Appends the decoded audio to a vector
+struct VecOutput<'a> {
+    buffer: &'a mut Vec<i16>,
+}
+
+impl VecOutput<'_> {
+    /// Grow the vector by `size` samples for `write` to fill, keeping only the samples it wrote
+    fn append(
+        &mut self,
+        size: usize,
+        write: impl FnOnce(&mut [i16]) -> Result<usize, SteamAudioError>,
+    ) -> Result<usize, SteamAudioError> {
+        let start = self.buffer.len();
+        self.buffer.resize(start + size, 0);
+        let result = write(&mut self.buffer[start..]);
+        self.buffer.truncate(start + *result.as_ref().unwrap_or(&0));
+        result
+    }
+}
+
+impl DecodeOutput for VecOutput<'_> {
+    fn opus(
+        &mut self,
+        decoder: &mut OpusDecoder,
+        data: &[u8],
+        filters: &mut OutputFilters,
+    ) -> Result<usize, SteamAudioError> {
+        self.append(MAX_FRAME_SAMPLES, |output| {
+            let count = decoder.decode(data, output)?;
+            filters.apply(&mut output[..count], Source::Audio);
+            Ok(count)
+        })
+    }
+
+    fn conceal(
+        &mut self,
+        decoder: &mut OpusDecoder,
+        frame_size: usize,
+        fec: &[u8],
+        filters: &mut OutputFilters,
+    ) -> Result<usize, SteamAudioError> {
+        self.append(frame_size, |output| {
+            let count = decoder.conceal(fec, output)?;
+            filters.apply(&mut output[..count], conceal_source(fec));
+            Ok(count)
+        })
+    }
+
+    fn silence(
+        &mut self,
+        count: usize,
+        filters: &mut OutputFilters,
+    ) -> Result<(), SteamAudioError> {
+        let start = self.buffer.len();
+        self.buffer.resize(start + count, 0);
+        filters.apply(&mut self.buffer[start..], Source::Silence);
+        Ok(())
+    }
+
+    fn pcm(&mut self, samples: &[i16]) -> Result<(), SteamAudioError> {
+        self.buffer.extend_from_slice(samples);
+        Ok(())
+    }
+}
+
+/// Passes each decoded chunk to a callback
+struct CallbackOutput<F> {
+    buffer: [i16; MAX_FRAME_SAMPLES],
+    callback: F,
+}
+
+impl<F: FnMut(&[i16])> DecodeOutput for CallbackOutput<F> {
+    fn opus(
+        &mut self,
+        decoder: &mut OpusDecoder,
+        data: &[u8],
+        filters: &mut OutputFilters,
+    ) -> Result<usize, SteamAudioError> {
+        let count = decoder.decode(data, &mut self.buffer)?;
+        filters.apply(&mut self.buffer[..count], Source::Audio);
+        (self.callback)(&self.buffer[..count]);
+        Ok(count)
+    }
+
+    fn conceal(
+        &mut self,
+        decoder: &mut OpusDecoder,
+        frame_size: usize,
+        fec: &[u8],
+        filters: &mut OutputFilters,
+    ) -> Result<usize, SteamAudioError> {
+        let count = decoder.conceal(fec, &mut self.buffer[..frame_size])?;
+        filters.apply(&mut self.buffer[..count], conceal_source(fec));
+        (self.callback)(&self.buffer[..count]);
+        Ok(count)
+    }
+
+    fn silence(
+        &mut self,
+        mut count: usize,
+        filters: &mut OutputFilters,
+    ) -> Result<(), SteamAudioError> {
+        while count > 0 {
+            let chunk = count.min(self.buffer.len());
+            self.buffer[..chunk].fill(0);
+            filters.apply(&mut self.buffer[..chunk], Source::Silence);
+            (self.callback)(&self.buffer[..chunk]);
+            count -= chunk;
+        }
+        Ok(())
+    }
+
+    fn pcm(&mut self, samples: &[i16]) -> Result<(), SteamAudioError> {
+        (self.callback)(samples);
+        Ok(())
+    }
+}
+
+/// Limit on the number of decoded samples
+#[derive(Clone, Copy)]
+enum SampleLimit {
+    /// Maximum number of samples of a single payload
+    Payload(usize),
+    /// Maximum number of samples over all payloads
+    Total(u64),
+}
+
+impl SampleLimit {
+    fn error(self) -> SteamAudioError {
+        match self {
+            SampleLimit::Payload(limit) => SteamAudioError::TooManySamples { limit },
+            SampleLimit::Total(limit) => SteamAudioError::TotalSamplesExceeded { limit },
+        }
+    }
+}
+
+/// Enforces the maximum number of samples that can be decoded
+struct LimitedOutput<'a, O> {
+    inner: &'a mut O,
+    remaining: usize,
+    limit: SampleLimit,
+}
+
+impl<O: DecodeOutput> LimitedOutput<'_, O> {
+    fn take(&mut self, count: usize) -> Result<(), SteamAudioError> {
+        self.remaining = self
+            .remaining
+            .checked_sub(count)
+            .ok_or(self.limit.error())?;
+        Ok(())
+    }
+}
+
+impl<O: DecodeOutput> DecodeOutput for LimitedOutput<'_, O> {
+    fn opus(
+        &mut self,
+        decoder: &mut OpusDecoder,
+        data: &[u8],
+        filters: &mut OutputFilters,
+    ) -> Result<usize, SteamAudioError> {
+        let samples = decoder.samples(data)?;
+        self.take(samples)?;
+        self.inner.opus(decoder, data, filters)
+    }
+
+    fn conceal(
+        &mut self,
+        decoder: &mut OpusDecoder,
+        frame_size: usize,
+        fec: &[u8],
+        filters: &mut OutputFilters,
+    ) -> Result<usize, SteamAudioError> {
+        self.take(frame_size)?;
+        self.inner.conceal(decoder, frame_size, fec, filters)
+    }
+
+    fn silence(
+        &mut self,
+        count: usize,
+        filters: &mut OutputFilters,
+    ) -> Result<(), SteamAudioError> {
+        self.take(count)?;
+        self.inner.silence(count, filters)
+    }
+
+    fn pcm(&mut self, samples: &[i16]) -> Result<(), SteamAudioError> {
+        self.take(samples.len())?;
+        self.inner.pcm(samples)
+    }
+
+    fn remaining(&self) -> Option<usize> {
+        self.inner.remaining()
+    }
+}
+
+/// Processing applied to the decoded audio before it is written to the output
+#[derive(Default)]
+struct OutputFilters {
+    channels: usize,
+    dc: Option<DcBlocker>,
+    user: Option<Box<dyn AudioFilter>>,
+    fade: EdgeFade,
+}
+
+impl OutputFilters {
+    fn apply(&mut self, samples: &mut [i16], source: Source) {
+        if let Some(dc) = self.dc.as_mut() {
+            dc.process(samples, self.channels);
+        }
+        if let Some(user) = self.user.as_mut() {
+            user.process(samples, self.channels);
+        }
+        self.fade.apply(samples, source, self.channels);
+    }
+
+    fn reset(&mut self) {
+        if let Some(dc) = self.dc.as_mut() {
+            dc.reset();
+        }
+        if let Some(user) = self.user.as_mut() {
+            user.reset();
+        }
+        self.fade.reset();
+    }
+}
+
+/// Frames recovered from forward error correction data are decoded audio, other concealment is generated
+fn conceal_source(fec: &[u8]) -> Source {
+    if fec.is_empty() {
+        Source::Concealed
+    } else {
+        Source::Audio
+    }
+}
+
+/// Opus decoder that counts samples over all channels
+struct OpusDecoder {
+    backend: Box<dyn OpusBackend>,
+    rate: u32,
+    channels: usize,
+    /// Number of samples of the last decoded or concealed frame
+    last_frame_size: usize,
+}
+
+impl OpusDecoder {
+    fn new(
+        constructor: BackendConstructor,
+        rate: u32,
+        channels: OutputChannels,
+        gain: i16,
+    ) -> Result<Self, SteamAudioError> {
+        let mut backend = constructor(rate, channels)?;
+        if gain != 0 {
+            backend.set_gain(gain)?;
+        }
+        Ok(OpusDecoder {
+            backend,
+            rate,
+            channels: channels.count(),
+            last_frame_size: 0,
+        })
+    }
+
+    fn decode(&mut self, data: &[u8], output: &mut [i16]) -> Result<usize, SteamAudioError> {
+        let count = self.backend.decode(data, output, false)? * self.channels;
+        self.last_frame_size = count;
+        Ok(count)
+    }
+
+    /// Fill `output` with concealment, using the forward error correction data of the next frame if available
+    fn conceal(&mut self, fec: &[u8], output: &mut [i16]) -> Result<usize, SteamAudioError> {
+        let count = self.backend.decode(fec, output, !fec.is_empty())? * self.channels;
+        self.last_frame_size = count;
+        Ok(count)
+    }
+
+    /// Number of samples a frame decodes to, an empty frame is concealed
+    fn samples(&self, data: &[u8]) -> Result<usize, SteamAudioError> {
+        if data.is_empty() {
+            return Ok(self.concealment_size());
+        }
+        Ok(packet_samples(data, self.rate)? * self.channels)
+    }
+
+    /// Number of samples concealing a lost frame, the duration of the last frame or 20ms if there is none
+    fn concealment_size(&self) -> usize {
+        match self.last_frame_size {
+            0 => self.rate as usize / 50 * self.channels,
+            size => size,
+        }
+        .min(MAX_FRAME_SAMPLES)
+    }
+
+    fn reset(&mut self) -> Result<(), SteamAudioError> {
+        self.backend.reset()?;
+        self.last_frame_size = 0;
+        Ok(())
+    }
+}
+
+/// Position within a payload at which decoding stopped
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodePosition {
+    /// Offset into the packet data of the packet being decoded
+    packet: usize,
+    /// Index of the packet being decoded
+    index: usize,
+    /// Offset into the opus frames of the packet, or the number of silence samples already written
+    within: usize,
+    /// Number of samples of the payload written before this position, counted against the sample limit
+    produced: usize,
+}
+
+impl DecodePosition {
+    /// Number of bytes of packet data that have been fully decoded
+    pub fn consumed(&self) -> usize {
+        self.packet
+    }
+}
+
+/// Result of [`SteamVoiceDecoder::decode_from`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeStatus {
+    /// The entire payload has been decoded
+    Complete { samples: usize },
+    /// The output buffer is full, decoding can be continued from `resume`
+    Incomplete {
+        samples: usize,
+        resume: DecodePosition,
+    },
+}
+
+impl DecodeStatus {
+    /// Number of samples written to the output buffer
+    pub fn samples(&self) -> usize {
+        match self {
+            DecodeStatus::Complete { samples } | DecodeStatus::Incomplete { samples, .. } => {
+                *samples
+            }
+        }
+    }
+}
+
+/// The most recently decoded frames, used to prime the opus decoder when restoring a snapshot
+#[derive(Default)]
+struct FrameHistory {
+    frames: VecDeque<Vec<u8>>,
+}
+
+impl FrameHistory {
+    fn push(&mut self, frame: &[u8]) {
+        let mut buffer = if self.frames.len() == PRIMING_FRAMES {
+            self.frames.pop_front().unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        buffer.clear();
+        buffer.extend_from_slice(frame);
+        self.frames.push_back(buffer);
+    }
+
+    fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+/// Saved state of a [`SteamVoiceDecoder`], see [`SteamVoiceDecoder::snapshot`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecoderSnapshot {
+    sample_rate: u16,
+    seq: u16,
+    silence_remainder: u64,
+    position: u64,
+    /// The last frames decoded before the snapshot was taken
+    frames: Vec<Vec<u8>>,
+}
+
+impl DecoderSnapshot {
+    /// The sample rate of the voice data at the time of the snapshot, if known
+    pub fn sample_rate(&self) -> Option<u16> {
+        (self.sample_rate > 0).then_some(self.sample_rate)
+    }
+}
+
+pub struct SteamVoiceDecoder {
+    decoder: Option<OpusDecoder>,
+    cached: Vec<OpusDecoder>,
+    backend: BackendConstructor,
+    sample_rate: u16,
+    seq: u16,
+    /// Fraction of a sample left over from converting silence to the output rate, in units of `1 / sample_rate`
+    silence_remainder: u64,
+    /// Converts uncompressed samples to the output rate, if it differs from the rate of the voice data
+    pcm_resampler: Option<LinearResampler>,
+    /// The uncompressed samples of the current packet at the output rate and channels, kept while it is decoded
+    /// in steps
+    pcm: Vec<i16>,
+    /// The uncompressed samples of the current packet at the rate of the voice data, before resampling
+    pcm_input: Vec<i16>,
+    history: FrameHistory,
+    stats: DecodeStats,
+    /// Number of samples produced since the decoder was created or reset
+    position: u64,
+    events: Option<Box<dyn FnMut(DecodeEvent) + Send>>,
+    filters: OutputFilters,
+    options: DecoderOptions,
+}
+
+impl Default for SteamVoiceDecoder {
+    fn default() -> Self {
+        SteamVoiceDecoder {
+            decoder: None,
+            cached: Vec::new(),
+            backend: DEFAULT_BACKEND,
+            sample_rate: 0,
+            seq: 0,
+            silence_remainder: 0,
+            pcm_resampler: None,
+            pcm: Vec::new(),
+            pcm_input: Vec::new(),
+            history: FrameHistory::default(),
+            stats: DecodeStats::default(),
+            position: 0,
+            events: None,
+            filters: OutputFilters::default(),
+            options: DecoderOptions::default(),
+        }
+    }
+}
+
+impl Debug for SteamVoiceDecoder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SteamVoiceDecoder")
+            .field("sample_rate", &self.sample_rate)
+            .field("seq", &self.seq)
+            .field("position", &self.position)
+            .field("options", &self.options)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SteamVoiceDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn builder() -> SteamVoiceDecoderBuilder {
+        SteamVoiceDecoderBuilder::new()
+    }
+
+    pub fn with_options(options: DecoderOptions) -> Result<Self, SteamAudioError> {
+        Self::with_backend(options, DEFAULT_BACKEND)
+    }
+
+    /// Create a decoder that uses a different opus implementation
+    pub fn with_backend(
+        options: DecoderOptions,
+        backend: BackendConstructor,
+    ) -> Result<Self, SteamAudioError> {
+        options.validate()?;
+        Ok(SteamVoiceDecoder {
+            filters: OutputFilters {
+                channels: options.channels.count(),
+                ..OutputFilters::default()
+            },
+            options,
+            backend,
+            ..Self::default()
+        })
+    }
+
+    pub fn options(&self) -> &DecoderOptions {
+        &self.options
+    }
+
+    /// Reset the decoder to start decoding a new, unrelated, stream of voice data
+    ///
+    /// The new stream has to set its sample rate again. Unlike creating a new decoder, the opus decoders are kept
+    /// for when the stream uses the same sample rate, only their state is reset. The statistics are cleared as well.
+    pub fn reset(&mut self) -> Result<(), SteamAudioError> {
+        for decoder in self.decoder.iter_mut().chain(self.cached.iter_mut()) {
+            decoder.reset()?;
+        }
+        self.cache_decoder();
+        self.sample_rate = 0;
+        self.seq = 0;
+        self.silence_remainder = 0;
+        self.pcm_resampler = None;
+        self.history.clear();
+        self.stats = DecodeStats::default();
+        self.position = 0;
+        self.filters.reset();
+        Ok(())
+    }
+
+    /// Change the gain applied to the decoded audio, in decibels
+    pub fn set_gain(&mut self, db: f32) -> Result<(), SteamAudioError> {
+        let gain = gain_from_db(db);
+        for decoder in self.decoder.iter_mut().chain(self.cached.iter_mut()) {
+            decoder.backend.set_gain(gain)?;
+        }
+        self.options.gain = gain;
+        Ok(())
+    }
+
+    /// Run the decoded audio through `filter` before it is written to the output, `None` removes the filter
+    ///
+    /// The filter sees all decoded audio including concealment and silence, use a
+    /// [`FilterChain`](crate::FilterChain) to run multiple filters.
+    pub fn set_filter(&mut self, filter: Option<Box<dyn AudioFilter>>) {
+        self.filters.user = filter;
+    }
+
+    /// Call `handler` for every concealed frame and every reset of the opus state while decoding
+    ///
+    /// This can be used to mark regions of the output that contain generated audio or discontinuities.
+    pub fn set_event_handler<F: FnMut(DecodeEvent) + Send + 'static>(&mut self, handler: F) {
+        self.events = Some(Box::new(handler));
+    }
+
+    pub fn clear_event_handler(&mut self) {
+        self.events = None;
+    }
+
+    /// Number of samples produced since the decoder was created or reset
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Statistics of the voice data decoded so far
+    pub fn stats(&self) -> &DecodeStats {
+        &self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = DecodeStats::default();
+    }
+
+    /// The sample rate of the decoded audio, if known
+    ///
+    /// This differs from the [`sample_rate`](Self::sample_rate) of the voice data when decoding at a fixed
+    /// output rate or when the voice data has a legacy sample rate, see [`decode_rate`].
+    pub fn output_rate(&self) -> Option<u32> {
+        self.options.output_rate.or_else(|| {
+            (self.sample_rate > 0)
+                .then(|| decode_rate(self.sample_rate as u32).ok())
+                .flatten()
+        })
+    }
+
+    /// The sample rate of the voice data, if known
+    pub fn sample_rate(&self) -> Option<u16> {
+        (self.sample_rate > 0).then_some(self.sample_rate)
+    }
+
+    fn channels(&self) -> usize {
+        self.options.channels.count()
+    }
+
+    /// Decode the voice data into the output buffer, returns the number of samples written
+    ///
+    /// Fails with [`SteamAudioError::InsufficientOutputBuffer`] if the decoded audio doesn't fit
+    /// into the output buffer, use [`decode_from`](Self::decode_from) to decode in multiple steps instead.
+    pub fn decode(
+        &mut self,
+        voice_data: SteamVoiceData,
+        output_buffer: &mut [i16],
+    ) -> Result<usize, SteamAudioError> {
+        let mut output = SliceOutput {
+            buffer: output_buffer,
+            total: 0,
+            zero_fill: self.options.zero_fill_silence,
+        };
+        match self.decode_limited(&voice_data, DecodePosition::default(), &mut output)? {
+            None => Ok(output.total),
+            Some(_) => Err(SteamAudioError::InsufficientOutputBuffer),
+        }
+    }
+
+    /// Parse and decode a voice payload, payloads with an invalid checksum are counted in the [`stats`](Self::stats)
+    pub fn decode_payload(
+        &mut self,
+        payload: &[u8],
+        output_buffer: &mut [i16],
+    ) -> Result<usize, SteamAudioError> {
+        let voice_data = match SteamVoiceData::new(payload) {
+            Err(e @ SteamAudioError::CrcMismatch { .. }) => {
+                self.stats.crc_failures += 1;
+                return Err(e);
+            }
+            voice_data => voice_data?,
+        };
+        self.decode(voice_data, output_buffer)
+    }
+
+    /// Decode the voice data starting from `position`, stopping when the output buffer is full
+    ///
+    /// Instead of failing when the output buffer is too small, decoding stops before the first
+    /// frame that doesn't fit, and the returned [`DecodeStatus::Incomplete`] contains the position
+    /// to continue from after the output buffer has been drained.
+    ///
+    /// Use [`DecodePosition::default()`] to start decoding a new payload.
+    pub fn decode_from(
+        &mut self,
+        voice_data: &SteamVoiceData,
+        position: DecodePosition,
+        output_buffer: &mut [i16],
+    ) -> Result<DecodeStatus, SteamAudioError> {
+        let mut output = SliceOutput {
+            buffer: output_buffer,
+            total: 0,
+            zero_fill: self.options.zero_fill_silence,
+        };
+        let stopped = self.decode_limited(voice_data, position, &mut output)?;
+        Ok(match stopped {
+            None => DecodeStatus::Complete {
+                samples: output.total,
+            },
+            Some(resume) => DecodeStatus::Incomplete {
+                samples: output.total,
+                resume,
+            },
+        })
+    }
+
+    /// Decode the voice data, passing each decoded chunk of samples to the callback
+    ///
+    /// Silence is passed to the callback as chunks of zeros.
+    pub fn decode_with<F: FnMut(&[i16])>(
+        &mut self,
+        voice_data: SteamVoiceData,
+        callback: F,
+    ) -> Result<(), SteamAudioError> {
+        let mut output = CallbackOutput {
+            buffer: [0; MAX_FRAME_SAMPLES],
+            callback,
+        };
+        self.decode_limited(&voice_data, DecodePosition::default(), &mut output)?;
+        Ok(())
+    }
+
+    /// Decode the voice data, appending the decoded samples to `output`, returns the number of samples appended
+    pub fn decode_append(
+        &mut self,
+        voice_data: SteamVoiceData,
+        output: &mut Vec<i16>,
+    ) -> Result<usize, SteamAudioError> {
+        let start = output.len();
+        let mut output = VecOutput { buffer: output };
+        self.decode_limited(&voice_data, DecodePosition::default(), &mut output)?;
+        Ok(output.buffer.len() - start)
+    }
+
+    /// Decode opus packets that aren't wrapped in a steam voice payload, appending the decoded samples to `output`
+    ///
+    /// The packets are decoded at `sample_rate` as consecutive frames following the previously decoded frames,
+    /// as if they were the frames of a payload of `steam_id`. Returns the number of samples appended.
+    pub fn decode_opus_append<'p>(
+        &mut self,
+        steam_id: u64,
+        sample_rate: u16,
+        packets: impl IntoIterator<Item = &'p [u8]>,
+        output: &mut Vec<i16>,
+    ) -> Result<usize, SteamAudioError> {
+        let mut packet_data = vec![PacketType::SampleRate.into()];
+        packet_data.extend_from_slice(&sample_rate.to_le_bytes());
+        let mut frames = Vec::new();
+        let mut seq = self.seq;
+        for packet in packets {
+            let length = u16::try_from(packet.len())
+                .ok()
+                .filter(|&length| length != u16::MAX)
+                .ok_or(SteamAudioError::InvalidOpusPacket)?;
+            // a single packet can only hold 64KiB of frames
+            if frames.len() + 4 + packet.len() > u16::MAX as usize {
+                push_opus_packet(&mut packet_data, &frames);
+                frames.clear();
+            }
+            frames.extend_from_slice(&length.to_le_bytes());
+            frames.extend_from_slice(&seq.to_le_bytes());
+            frames.extend_from_slice(packet);
+            seq = seq.wrapping_add(1);
+        }
+        push_opus_packet(&mut packet_data, &frames);
+        let voice_data = SteamVoiceData {
+            steam_id,
+            packet_data: &packet_data,
+        };
+        self.decode_append(voice_data, output)
+    }
+
+    /// Save the state of the decoder, so decoding can later continue from this point with [`restore`](Self::restore)
+    ///
+    /// The internal state of the opus decoder can't be saved directly, instead the last few decoded frames
+    /// are stored and decoded again when restoring, which brings a fresh opus decoder into nearly the same state.
+    pub fn snapshot(&self) -> DecoderSnapshot {
+        DecoderSnapshot {
+            sample_rate: self.sample_rate,
+            seq: self.seq,
+            silence_remainder: self.silence_remainder,
+            position: self.position,
+            frames: self.history.frames.iter().cloned().collect(),
+        }
+    }
+
+    /// Restore the decoder to the state of a snapshot, e.g. when seeking back in a recording
+    ///
+    /// The snapshot can be restored into any decoder, the options of the decoder are kept.
+    pub fn restore(&mut self, snapshot: &DecoderSnapshot) -> Result<(), SteamAudioError> {
+        self.cache_decoder();
+        self.sample_rate = 0;
+        self.history.clear();
+        if let Some(rate) = snapshot.sample_rate() {
+            self.set_sample_rate(rate)?;
+        }
+        if let Some(decoder) = self.decoder.as_mut() {
+            decoder.reset()?;
+            let mut scratch = vec![0; MAX_FRAME_SAMPLES];
+            for frame in &snapshot.frames {
+                decoder.decode(frame, &mut scratch)?;
+                self.history.push(frame);
+            }
+        }
+        self.seq = snapshot.seq;
+        self.silence_remainder = snapshot.silence_remainder;
+        self.position = snapshot.position;
+        self.filters.reset();
+        Ok(())
+    }
+
+    fn decode_limited<O: DecodeOutput>(
+        &mut self,
+        voice_data: &SteamVoiceData,
+        start: DecodePosition,
+        output: &mut O,
+    ) -> Result<Option<DecodePosition>, SteamAudioError> {
+        if start == DecodePosition::default() {
+            self.stats.payloads += 1;
+        }
+        // the samples written before resuming count against the limit of the payload
+        let payload_limit = self.options.max_output_samples.map(|limit| {
+            (
+                limit.saturating_sub(start.produced),
+                SampleLimit::Payload(limit),
+            )
+        });
+        let total_limit = self.options.max_total_samples.map(|limit| {
+            let remaining = limit.saturating_sub(self.position);
+            (
+                usize::try_from(remaining).unwrap_or(usize::MAX),
+                SampleLimit::Total(limit),
+            )
+        });
+        let (remaining, limit) = payload_limit
+            .into_iter()
+            .chain(total_limit)
+            .min_by_key(|(remaining, _)| *remaining)
+            .unwrap_or((usize::MAX, SampleLimit::Payload(usize::MAX)));
+        let mut output = LimitedOutput {
+            inner: output,
+            remaining,
+            limit,
+        };
+        let stopped = self.decode_into(voice_data, start, &mut output)?;
+        let produced = start.produced + (remaining - output.remaining);
+        Ok(stopped.map(|position| DecodePosition {
+            produced,
+            ..position
+        }))
+    }
+
+    /// Decode the voice data into the output, returns the position to resume from if the output is full
+    fn decode_into<O: DecodeOutput>(
+        &mut self,
+        voice_data: &SteamVoiceData,
+        start: DecodePosition,
+        output: &mut O,
+    ) -> Result<Option<DecodePosition>, SteamAudioError> {
+        let lenient = self.options.parse_mode == ParseMode::Lenient;
+        let data = voice_data.packet_data;
+        let mut offset = start.packet;
+        let mut index = start.index;
+        let mut within = start.within;
+        while offset < data.len() {
+            let read = if lenient {
+                Packet::read_lenient(&data[offset..])
+            } else {
+                Packet::read(&data[offset..])
+            };
+            let (packet, rest) = match read {
+                Ok(packet) => packet,
+                Err(_) if lenient => {
+                    let skip = Packet::resync(&data[offset..]);
+                    self.stats.skipped_bytes += skip as u64;
+                    offset += skip;
+                    within = 0;
+                    continue;
+                }
+                Err(e) => return Err(e.in_packet(index, HEADER_SIZE + offset)),
+            };
+            match packet {
+                Packet::SampleRate(rate) => {
+                    if self.sample_rate != rate {
+                        self.set_sample_rate(rate)?;
+                    }
+                }
+                Packet::OpusPlc(opus) => {
+                    match self.decode_opus(voice_data.steam_id, opus.data, within, output) {
+                        Ok(None) => {}
+                        Ok(Some(frame)) => {
+                            return Ok(Some(DecodePosition {
+                                packet: offset,
+                                index,
+                                within: frame,
+                                ..DecodePosition::default()
+                            }))
+                        }
+                        Err(
+                            SteamAudioError::InsufficientData | SteamAudioError::InvalidOpusPacket,
+                        ) if lenient => {}
+                        Err(e) => return Err(e.in_packet(index, HEADER_SIZE + offset)),
+                    }
+                }
+                Packet::RawPcm(pcm) => {
+                    let written = self
+                        .decode_pcm(pcm, within, output)
+                        .map_err(|e| e.in_packet(index, HEADER_SIZE + offset))?;
+                    if let Some(written) = written {
+                        return Ok(Some(DecodePosition {
+                            packet: offset,
+                            index,
+                            within: written,
+                            ..DecodePosition::default()
+                        }));
+                    }
+                }
+                Packet::Unsupported { ty, data } => {
+                    self.stats.unsupported_packets += 1;
+                    if let Some(handler) = self.events.as_mut() {
+                        handler(DecodeEvent::Unsupported {
+                            steam_id: voice_data.steam_id,
+                            position: self.position,
+                            ty,
+                            length: data.len(),
+                        });
+                    }
+                }
+                Packet::Marker(_) => {}
+                Packet::Unknown { ty, .. } => {
+                    self.stats.skipped_packets += 1;
+                    if let Some(handler) = self.events.as_mut() {
+                        handler(DecodeEvent::Skipped {
+                            steam_id: voice_data.steam_id,
+                            position: self.position,
+                            ty,
+                            length: data.len() - offset - rest.len(),
+                        });
+                    }
+                }
+                Packet::Silence(silence) => {
+                    let (silence, remainder) = self.silence_samples(silence);
+                    let silence = silence.saturating_sub(within);
+                    // don't split the channels of a frame across output buffers
+                    let channels = self.channels();
+                    let count = output
+                        .remaining()
+                        .map_or(silence, |remaining| remaining / channels * channels)
+                        .min(silence);
+                    output.silence(count, &mut self.filters)?;
+                    self.stats.silence_samples += count as u64;
+                    self.position += count as u64;
+                    if count < silence {
+                        return Ok(Some(DecodePosition {
+                            packet: offset,
+                            index,
+                            within: within + count,
+                            ..DecodePosition::default()
+                        }));
+                    }
+                    self.silence_remainder = remainder;
+                }
+            }
+            offset = data.len() - rest.len();
+            index += 1;
+            within = 0;
+        }
+        Ok(None)
+    }
+
+    /// Switch to an opus decoder for the new sample rate, reusing a cached decoder for the rate if there is one
+    fn set_sample_rate(&mut self, rate: u16) -> Result<(), SteamAudioError> {
+        let decode_rate = match self.options.output_rate {
+            Some(output_rate) => output_rate,
+            None => decode_rate(rate as u32)?,
+        };
+        if self.decoder.as_ref().map(|decoder| decoder.rate) != Some(decode_rate) {
+            let decoder = match self
+                .cached
+                .iter()
+                .position(|decoder| decoder.rate == decode_rate)
+            {
+                Some(index) => {
+                    // the cached decoder stopped at an earlier part of the stream, it doesn't continue that audio
+                    let mut decoder = self.cached.remove(index);
+                    decoder.reset()?;
+                    decoder
+                }
+                None => OpusDecoder::new(
+                    self.backend,
+                    decode_rate,
+                    self.options.channels,
+                    self.options.gain,
+                )?,
+            };
+            self.cache_decoder();
+            self.decoder = Some(decoder);
+            self.history.clear();
+        }
+        let fade = self.options.fade.as_secs_f64() * decode_rate as f64;
+        self.filters.fade.set_length(fade.round() as usize);
+        if self.options.dc_filter {
+            self.filters.dc = Some(DcBlocker::new(decode_rate));
+        }
+        self.pcm_resampler =
+            (decode_rate != rate as u32).then(|| LinearResampler::new(rate as u32, decode_rate, 1));
+        self.sample_rate = rate;
+        self.silence_remainder = 0;
+        Ok(())
+    }
+
+    /// Move the current opus decoder into the cache, evicting the least recently used decoder
+    fn cache_decoder(&mut self) {
+        if let Some(decoder) = self.decoder.take() {
+            if self.cached.len() == CACHED_DECODERS {
+                self.cached.remove(0);
+            }
+            self.cached.push(decoder);
+        }
+    }
+
+    /// Write uncompressed 16 bit samples to the output, skipping the `start` samples that were already written
+    ///
+    /// The samples are converted from the rate of the voice data to the output rate with a [`LinearResampler`].
+    /// Returns the number of samples written in total if the output is full.
+    fn decode_pcm<O: DecodeOutput>(
+        &mut self,
+        pcm: &[u8],
+        start: usize,
+        output: &mut O,
+    ) -> Result<Option<usize>, SteamAudioError> {
+        // without a sample rate packet the rate of the samples is unknown, even with a fixed output rate
+        if self.sample_rate == 0 {
+            return Err(SteamAudioError::NoSampleRate);
+        }
+        if pcm.len() % 2 != 0 {
+            return Err(SteamAudioError::InsufficientData);
+        }
+        let channels = self.channels();
+        // a packet that is continued was already converted
+        if start == 0 {
+            let samples = pcm
+                .chunks_exact(2)
+                .map(|sample| i16::from_le_bytes([sample[0], sample[1]]));
+            self.pcm.clear();
+            match self.pcm_resampler.as_mut() {
+                Some(resampler) => {
+                    self.pcm_input.clear();
+                    self.pcm_input.extend(samples);
+                    resampler.process(&self.pcm_input, &mut self.pcm);
+                }
+                None => self.pcm.extend(samples),
+            }
+
+            let gain = match self.options.gain {
+                0 => 1.0,
+                gain => 10f32.powf(gain as f32 / 256.0 / 20.0),
+            };
+            // spread the samples over the channels in place, back to front to read each sample before it's overwritten
+            let frames = self.pcm.len();
+            self.pcm.resize(frames * channels, 0);
+            for frame in (0..frames).rev() {
+                let sample = (self.pcm[frame] as f32 * gain) as i16;
+                self.pcm[frame * channels..(frame + 1) * channels].fill(sample);
+            }
+        }
+
+        let total = self.pcm.len();
+        let count = output
+            .remaining()
+            .map_or(total, |remaining| start + remaining / channels * channels)
+            .min(total)
+            .saturating_sub(start);
+        let samples = &mut self.pcm[start..start + count];
+        self.filters.apply(samples, Source::Audio);
+        output.pcm(samples)?;
+        self.position += count as u64;
+        Ok((start + count < total).then_some(start + count))
+    }
+
+    /// Number of output samples for a number of silent samples at the rate of the voice data
+    ///
+    /// When converting to a different output rate, the fraction of a sample that is left over is returned
+    /// so it can be carried over to the next silence, preventing the output from drifting.
+    fn silence_samples(&self, silence: u16) -> (usize, u64) {
+        let (silence, remainder) = match (self.output_rate(), self.sample_rate) {
+            (Some(output_rate), rate) if rate > 0 => {
+                let total = silence as u64 * output_rate as u64 + self.silence_remainder;
+                ((total / rate as u64) as usize, total % rate as u64)
+            }
+            _ => (silence as usize, 0),
+        };
+        (silence * self.channels(), remainder)
+    }
+
+    /// Decode the opus frames starting at byte `start` of the data,
+    /// returns the offset of the first frame that didn't fit into the output
+    fn decode_opus<O: DecodeOutput>(
+        &mut self,
+        steam_id: u64,
+        data: &[u8],
+        start: usize,
+        output: &mut O,
+    ) -> Result<Option<usize>, SteamAudioError> {
+        let Some(decoder) = self.decoder.as_mut() else {
+            return Err(SteamAudioError::NoSampleRate);
+        };
+
+        let mut frames = OpusFrameIterator::new(&data[start.min(data.len())..]);
+        loop {
+            let frame_start = data.len() - frames.remaining().len();
+            let Some(frame) = frames.next() else {
+                break;
+            };
+            let (seq, frame) = match frame? {
+                OpusFrame::Reset => {
+                    decoder.reset()?;
+                    self.history.clear();
+                    self.filters.fade.discontinuity();
+                    self.stats.resets += 1;
+                    if let Some(handler) = self.events.as_mut() {
+                        handler(DecodeEvent::Reset {
+                            steam_id,
+                            position: self.position,
+                            reason: ResetReason::Marker,
+                        });
+                    }
+                    self.seq = 0;
+                    continue;
+                }
+                OpusFrame::Frame { seq, data } => (seq, data),
+            };
+
+            let lost = frames_lost(self.seq, seq);
+            if lost.map_or(true, |lost| {
+                self.options.max_concealment.is_some_and(|max| lost > max)
+            }) {
+                decoder.reset()?;
+                self.history.clear();
+                self.filters.fade.discontinuity();
+                self.stats.resets += 1;
+                if let Some(handler) = self.events.as_mut() {
+                    let reason = match lost {
+                        Some(lost) => ResetReason::GapTooLarge { lost },
+                        None => ResetReason::OutOfOrder {
+                            expected: self.seq,
+                            seq,
+                        },
+                    };
+                    handler(DecodeEvent::Reset {
+                        steam_id,
+                        position: self.position,
+                        reason,
+                    });
+                }
+                self.seq = seq;
+            }
+
+            // an empty frame is lost, it is concealed with the duration of the last frame like the frames of a gap
+            let concealed_end = if frame.is_empty() {
+                seq.wrapping_add(1)
+            } else {
+                seq
+            };
+            let frame_size = decoder.concealment_size();
+            while self.seq != concealed_end {
+                if output
+                    .remaining()
+                    .is_some_and(|remaining| remaining < frame_size)
+                {
+                    return Ok(Some(frame_start));
+                }
+                // the frame following a loss can carry a low bitrate copy of the lost frame
+                let last_lost = self.seq.wrapping_add(1) == seq;
+                let fec = if last_lost && self.options.use_fec && decoder.backend.supports_fec() {
+                    frame
+                } else {
+                    &[]
+                };
+                let samples = output.conceal(decoder, frame_size, fec, &mut self.filters)?;
+                self.stats.concealed += 1;
+                if let Some(handler) = self.events.as_mut() {
+                    handler(DecodeEvent::Concealed {
+                        steam_id,
+                        start: self.position,
+                        samples,
+                        recovered: !fec.is_empty(),
+                    });
+                }
+                self.position += samples as u64;
+                self.seq = self.seq.wrapping_add(1);
+            }
+            if frame.is_empty() {
+                self.history.push(frame);
+                continue;
+            }
+
+            if let Some(remaining) = output.remaining() {
+                if decoder.samples(frame)? > remaining {
+                    return Ok(Some(frame_start));
+                }
+            }
+
+            self.seq = seq.wrapping_add(1);
+            let samples = output.opus(decoder, frame, &mut self.filters)?;
+            self.position += samples as u64;
+            self.stats.frames += 1;
+            self.history.push(frame);
+        }
+
+        let trailing = frames.remaining();
+        if self.options.reject_trailing_bytes && !trailing.is_empty() {
+            return Err(SteamAudioError::TrailingBytes {
+                count: trailing.len(),
+                offset: data.len() - trailing.len(),
+            });
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(all(
+    test,
+    any(feature = "opus", feature = "audiopus", feature = "opus-decoder")
+))]
+mod tests {
+    use super::*;
+    use crate::test_data::{opus, payload, raw, sample_rate, silence, FRAME};
+
+    fn limited(samples: usize) -> SteamVoiceDecoder {
+        SteamVoiceDecoder::builder()
+            .max_output_samples(samples)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn payload_limit() {
+        let payload = payload(&[sample_rate(24000), silence(60000)]);
+        let voice_data = SteamVoiceData::new(&payload).unwrap();
+        let mut output = vec![0; 60000];
+        assert!(matches!(
+            limited(1000).decode(voice_data, &mut output),
+            Err(SteamAudioError::TooManySamples { limit: 1000 })
+        ));
+        assert_eq!(
+            limited(60000).decode(voice_data, &mut output).unwrap(),
+            60000
+        );
+    }
+
+    #[test]
+    fn payload_limit_on_resume() {
+        let payload = payload(&[sample_rate(24000), silence(60000)]);
+        let voice_data = SteamVoiceData::new(&payload).unwrap();
+        let mut decoder = limited(1000);
+        let mut output = vec![0; 500];
+        let mut position = DecodePosition::default();
+        let result = loop {
+            match decoder.decode_from(&voice_data, position, &mut output) {
+                Ok(DecodeStatus::Incomplete { resume, .. }) => position = resume,
+                result => break result,
+            }
+        };
+        assert!(matches!(
+            result,
+            Err(SteamAudioError::TooManySamples { limit: 1000 })
+        ));
+    }
+
+    #[test]
+    fn payload_limit_with_lost_frame() {
+        let payload = payload(&[sample_rate(24000), opus(&[(0, &[])])]);
+        let voice_data = SteamVoiceData::new(&payload).unwrap();
+        let mut output = Vec::new();
+        // the lost frame is concealed with 20ms, backends without a previous frame can conceal it with nothing
+        let samples = limited(480).decode_append(voice_data, &mut output).unwrap();
+        assert!(samples <= 480);
+    }
+
+    #[test]
+    fn decode_lost_frame_into_slice() {
+        let payload = payload(&[sample_rate(24000), opus(&[(0, FRAME), (1, &[])])]);
+        let voice_data = SteamVoiceData::new(&payload).unwrap();
+        let mut appended = Vec::new();
+        let expected = SteamVoiceDecoder::new()
+            .decode_append(voice_data, &mut appended)
+            .unwrap();
+        let mut output = [0; 2048];
+        let samples = SteamVoiceDecoder::new()
+            .decode(voice_data, &mut output)
+            .unwrap();
+        assert_eq!(samples, expected);
+    }
+
+    #[test]
+    fn conceals_one_frame_per_lost_packet() {
+        let payload = payload(&[sample_rate(24000), opus(&[(0, FRAME), (3, FRAME)])]);
+        let voice_data = SteamVoiceData::new(&payload).unwrap();
+        let mut output = Vec::new();
+        let samples = SteamVoiceDecoder::new()
+            .decode_append(voice_data, &mut output)
+            .unwrap();
+        assert_eq!(samples, 4 * 480);
+    }
+
+    #[test]
+    fn conceals_with_last_frame_duration() {
+        // 10ms CELT frames with one lost between them
+        const SHORT_FRAME: &[u8] = &[0xF0, 0xFF, 0xFE];
+        let payload = payload(&[
+            sample_rate(24000),
+            opus(&[(0, SHORT_FRAME), (2, SHORT_FRAME)]),
+        ]);
+        let voice_data = SteamVoiceData::new(&payload).unwrap();
+        let mut chunks = Vec::new();
+        SteamVoiceDecoder::new()
+            .decode_with(voice_data, |samples| chunks.push(samples.len()))
+            .unwrap();
+        assert_eq!(chunks, [240, 240, 240]);
+
+        let mut output = [0; 4096];
+        let samples = SteamVoiceDecoder::new()
+            .decode(voice_data, &mut output)
+            .unwrap();
+        assert_eq!(samples, 3 * 240);
+    }
+
+    #[test]
+    fn empty_frame_is_concealed() {
+        let payload = payload(&[
+            sample_rate(24000),
+            opus(&[(0, FRAME), (1, &[]), (3, FRAME)]),
+        ]);
+        let voice_data = SteamVoiceData::new(&payload).unwrap();
+        let mut decoder = SteamVoiceDecoder::new();
+        let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let handler_events = events.clone();
+        decoder.set_event_handler(move |event| handler_events.lock().unwrap().push(event));
+        let samples = decoder.decode_append(voice_data, &mut Vec::new()).unwrap();
+        assert_eq!(samples, 4 * 480);
+        // the empty frame is concealed like the frame lost in the gap after it
+        assert_eq!(decoder.stats().frames, 2);
+        assert_eq!(decoder.stats().concealed, 2);
+        let starts: Vec<_> = events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|event| match event {
+                DecodeEvent::Concealed { start, .. } => Some(*start),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(starts, [480, 960]);
+    }
+
+    /// Backend without forward error correction, decoding every frame to 20ms of a constant
+    struct NoFec;
+
+    impl OpusBackend for NoFec {
+        fn decode(
+            &mut self,
+            packet: &[u8],
+            output: &mut [i16],
+            fec: bool,
+        ) -> Result<usize, SteamAudioError> {
+            assert!(!fec && (packet.is_empty() || packet == FRAME));
+            output[..480].fill(if packet.is_empty() { 0 } else { 1 });
+            Ok(480)
+        }
+
+        fn reset(&mut self) -> Result<(), SteamAudioError> {
+            Ok(())
+        }
+
+        fn supports_fec(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn no_fec_for_backends_without_it() {
+        let payload = payload(&[sample_rate(24000), opus(&[(0, FRAME), (2, FRAME)])]);
+        let voice_data = SteamVoiceData::new(&payload).unwrap();
+        let mut decoder =
+            SteamVoiceDecoder::with_backend(DecoderOptions::default(), |_, _| Ok(Box::new(NoFec)))
+                .unwrap();
+        let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let handler_events = events.clone();
+        decoder.set_event_handler(move |event| handler_events.lock().unwrap().push(event));
+        let mut output = Vec::new();
+        decoder.decode_append(voice_data, &mut output).unwrap();
+        assert_eq!(output.len(), 3 * 480);
+        assert!(output[480..960].iter().all(|sample| *sample == 0));
+        assert!(matches!(
+            events.lock().unwrap()[..],
+            [DecodeEvent::Concealed {
+                recovered: false,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn reset_forgets_sample_rate() {
+        let mut decoder = SteamVoiceDecoder::new();
+        let first = payload(&[sample_rate(24000), opus(&[(0, FRAME)])]);
+        decoder
+            .decode_append(SteamVoiceData::new(&first).unwrap(), &mut Vec::new())
+            .unwrap();
+        decoder.reset().unwrap();
+        assert_eq!(decoder.sample_rate(), None);
+        assert_eq!(decoder.output_rate(), None);
+
+        let second = payload(&[opus(&[(0, FRAME)])]);
+        let error = decoder
+            .decode_append(SteamVoiceData::new(&second).unwrap(), &mut Vec::new())
+            .unwrap_err();
+        assert!(matches!(error.root(), SteamAudioError::NoSampleRate));
+        // the stream decodes again once it sets the sample rate
+        let samples = decoder
+            .decode_append(SteamVoiceData::new(&first).unwrap(), &mut Vec::new())
+            .unwrap();
+        assert_eq!(samples, 480);
+    }
+
+    #[test]
+    fn sequence_wraparound() {
+        let payload = payload(&[
+            sample_rate(24000),
+            opus(&[(65534, FRAME), (65535, FRAME), (0, FRAME), (2, FRAME)]),
+        ]);
+        let voice_data = SteamVoiceData::new(&payload).unwrap();
+        let mut decoder = SteamVoiceDecoder::new();
+        let mut output = Vec::new();
+        let samples = decoder.decode_append(voice_data, &mut output).unwrap();
+        // the first frame doesn't follow the initial sequence number, only frame 1 is lost after the wraparound
+        assert_eq!(samples, 5 * 480);
+        assert_eq!(decoder.stats().resets, 1);
+        assert_eq!(decoder.stats().concealed, 1);
+    }
+
+    #[test]
+    fn duplicate_and_reordered_frames_reset() {
+        let payload = payload(&[
+            sample_rate(24000),
+            opus(&[(0, FRAME), (1, FRAME), (1, FRAME), (0, FRAME)]),
+        ]);
+        let voice_data = SteamVoiceData::new(&payload).unwrap();
+        let mut decoder = SteamVoiceDecoder::new();
+        let mut output = Vec::new();
+        let samples = decoder.decode_append(voice_data, &mut output).unwrap();
+        // frames behind the expected sequence number aren't concealed as a gap of 65535 frames
+        assert_eq!(samples, 4 * 480);
+        assert_eq!(decoder.stats().resets, 2);
+        assert_eq!(decoder.stats().concealed, 0);
+    }
+
+    #[test]
+    fn raw_pcm_stereo() {
+        let payload = payload(&[sample_rate(24000), raw(&[1, -2, 3])]);
+        let voice_data = SteamVoiceData::new(&payload).unwrap();
+        let mut decoder = SteamVoiceDecoder::builder()
+            .channels(OutputChannels::Stereo)
+            .build()
+            .unwrap();
+        let mut output = Vec::new();
+        decoder.decode_append(voice_data, &mut output).unwrap();
+        assert_eq!(output, [1, 1, -2, -2, 3, 3]);
+    }
+
+    #[test]
+    fn raw_pcm_without_sample_rate() {
+        let payload = payload(&[raw(&[1, 2, 3])]);
+        let voice_data = SteamVoiceData::new(&payload).unwrap();
+        let mut decoder = SteamVoiceDecoder::builder()
+            .output_rate(48000)
+            .build()
+            .unwrap();
+        let error = decoder
+            .decode_append(voice_data, &mut Vec::new())
+            .unwrap_err();
+        assert!(matches!(error.root(), SteamAudioError::NoSampleRate));
+    }
+
+    #[test]
+    fn raw_pcm_odd_length() {
+        let mut packet = raw(&[1, 2]);
+        packet[1] = 3;
+        packet.pop();
+        let payload = payload(&[sample_rate(24000), packet]);
+        let voice_data = SteamVoiceData::new(&payload).unwrap();
+        let error = SteamVoiceDecoder::new()
+            .decode_append(voice_data, &mut Vec::new())
+            .unwrap_err();
+        assert!(matches!(error.root(), SteamAudioError::InsufficientData));
+    }
+}
@@ -0,0 +1,174 @@
+//! An equivalent of `ISteamUser::DecompressVoice` from the Steamworks SDK
+
+use crate::{
+    LinearResampler, Resampler, ResamplerConstructor, SteamAudioError, SteamVoiceData,
+    SteamVoiceDecoder,
+};
+use std::fmt::Debug;
+
+/// Lowest sample rate that can be requested from `DecompressVoice`
+pub const MIN_DESIRED_SAMPLE_RATE: u32 = 11025;
+/// Highest sample rate that can be requested from `DecompressVoice`
+pub const MAX_DESIRED_SAMPLE_RATE: u32 = 48000;
+
+/// Decompress steam voice payloads the way `ISteamUser::DecompressVoice` does
+///
+/// Like the Steamworks function, every payload is decoded as a continuation of the previous payloads
+/// and the audio is converted to the sample rate requested by the caller, which can change between calls.
+/// The voice data is decoded at its native rate and resampled with a [`LinearResampler`], or another
+/// [`Resampler`] set with [`set_resampler`](Self::set_resampler). The resampler state carries over between
+/// payloads so consecutive chunks join up without clicks.
+///
+/// Use a separate decompressor for every speaker, as the Steamworks API expects separate calls per
+/// speaker as well.
+pub struct VoiceDecompressor {
+    decoder: SteamVoiceDecoder,
+    resampler: Option<Box<dyn Resampler>>,
+    resampler_constructor: ResamplerConstructor,
+    samples: Vec<i16>,
+}
+
+impl Default for VoiceDecompressor {
+    fn default() -> Self {
+        VoiceDecompressor {
+            decoder: SteamVoiceDecoder::default(),
+            resampler: None,
+            resampler_constructor: |from, to, channels| {
+                Box::new(LinearResampler::new(from, to, channels))
+            },
+            samples: Vec::new(),
+        }
+    }
+}
+
+impl Debug for VoiceDecompressor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VoiceDecompressor")
+            .field("decoder", &self.decoder)
+            .field("resampler", &self.resampler)
+            .finish_non_exhaustive()
+    }
+}
+
+impl VoiceDecompressor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a decompressor that decodes with a decoder with non-default options
+    ///
+    /// The decoder should decode at the rate of the voice data, without a fixed output rate.
+    pub fn with_decoder(decoder: SteamVoiceDecoder) -> Self {
+        VoiceDecompressor {
+            decoder,
+            ..Self::default()
+        }
+    }
+
+    /// Use resamplers created by `constructor` to convert to the desired rate
+    ///
+    /// With the `resample` feature, a [`SincResampler`](crate::SincResampler) gives better quality than the
+    /// default [`LinearResampler`]:
+    ///
+    /// ```ignore
+    /// decompressor.set_resampler(|from, to, channels| Box::new(SincResampler::new(from, to, channels)));
+    /// ```
+    pub fn set_resampler(&mut self, constructor: ResamplerConstructor) {
+        self.resampler_constructor = constructor;
+        self.resampler = None;
+    }
+
+    /// The sample rate the voice data decodes to without resampling, like `GetVoiceOptimalSampleRate`
+    ///
+    /// Only known once a payload setting the sample rate has been decompressed.
+    pub fn optimal_sample_rate(&self) -> Option<u32> {
+        self.decoder.output_rate()
+    }
+
+    /// Decompress a payload to 16 bit mono samples at `desired_rate`
+    ///
+    /// Fails with [`SteamAudioError::UnsupportedSampleRate`] if the rate is outside of
+    /// [`MIN_DESIRED_SAMPLE_RATE`] to [`MAX_DESIRED_SAMPLE_RATE`].
+    pub fn decompress(
+        &mut self,
+        payload: &[u8],
+        desired_rate: u32,
+    ) -> Result<Vec<i16>, SteamAudioError> {
+        let mut output = Vec::new();
+        self.decompress_append(payload, desired_rate, &mut output)?;
+        Ok(output)
+    }
+
+    /// Decompress a payload into `output`, returns the number of samples written
+    ///
+    /// Fails with [`SteamAudioError::InsufficientOutputBuffer`] if the samples don't fit, the counterpart of
+    /// `k_EVoiceResultBufferTooSmall`. The payload is consumed either way, so a larger buffer can't be
+    /// retried with the same payload.
+    pub fn decompress_into(
+        &mut self,
+        payload: &[u8],
+        desired_rate: u32,
+        output: &mut [i16],
+    ) -> Result<usize, SteamAudioError> {
+        let samples = self.decompress(payload, desired_rate)?;
+        let output = output
+            .get_mut(..samples.len())
+            .ok_or(SteamAudioError::InsufficientOutputBuffer)?;
+        output.copy_from_slice(&samples);
+        Ok(samples.len())
+    }
+
+    /// Decompress a payload, appending the samples to `output`, returns the number of samples appended
+    pub fn decompress_append(
+        &mut self,
+        payload: &[u8],
+        desired_rate: u32,
+        output: &mut Vec<i16>,
+    ) -> Result<usize, SteamAudioError> {
+        if !(MIN_DESIRED_SAMPLE_RATE..=MAX_DESIRED_SAMPLE_RATE).contains(&desired_rate) {
+            return Err(SteamAudioError::UnsupportedSampleRate { rate: desired_rate });
+        }
+        let voice_data = SteamVoiceData::new(payload)?;
+        self.samples.clear();
+        self.decoder.decode_append(voice_data, &mut self.samples)?;
+        let Some(rate) = self.decoder.output_rate() else {
+            return Ok(0);
+        };
+
+        let resampler = match self.resampler.as_mut() {
+            Some(resampler)
+                if resampler.input_rate() == rate && resampler.output_rate() == desired_rate =>
+            {
+                resampler
+            }
+            _ => self.resampler.insert((self.resampler_constructor)(
+                rate,
+                desired_rate,
+                self.decoder.options().channels.count(),
+            )),
+        };
+        let start = output.len();
+        resampler.process(&self.samples, output);
+        Ok(output.len() - start)
+    }
+
+    /// Reset the decoder and resampler, to start decompressing an unrelated stream
+    pub fn reset(&mut self) -> Result<(), SteamAudioError> {
+        self.resampler = None;
+        self.decoder.reset()
+    }
+
+    /// Get back the decoder
+    pub fn into_decoder(self) -> SteamVoiceDecoder {
+        self.decoder
+    }
+}
+
+/// Decompress a single payload to 16 bit mono samples at `desired_rate`, like `ISteamUser::DecompressVoice`
+///
+/// Every call starts with a fresh decoder, to decompress consecutive payloads of a speaker use a
+/// [`VoiceDecompressor`] instead, which keeps the decoder state between payloads like the Steamworks
+/// implementation does.
+pub fn decompress_voice(payload: &[u8], desired_rate: u32) -> Result<Vec<i16>, SteamAudioError> {
+    VoiceDecompressor::new().decompress(payload, desired_rate)
+}
@@ -0,0 +1,199 @@
+//! Container style access to streams of voice payloads, with a track per speaker
+
+use crate::{DecoderOptions, SteamAudioError, SteamVoiceData, SteamVoiceDecoder};
+use std::fmt::Debug;
+
+/// Timestamps of packets are in 48kHz samples, like the timestamps of opus in other containers
+pub const PACKET_TIME_BASE: u32 = 48000;
+
+/// A speaker in a stream of voice payloads, the equivalent of a track in a container
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct VoiceTrack {
+    /// Id of the track, assigned in the order the speakers are first seen
+    pub id: u32,
+    pub steam_id: u64,
+    /// The last sample rate of the voice data of the speaker
+    pub sample_rate: Option<u32>,
+}
+
+/// A voice payload of a speaker with its timing, the equivalent of a packet in a container
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoicePacket {
+    pub track_id: u32,
+    pub steam_id: u64,
+    /// Position of the packet in the audio of the speaker, in [`PACKET_TIME_BASE`] samples
+    pub ts: u64,
+    /// Duration of the packet in [`PACKET_TIME_BASE`] samples
+    pub dur: u64,
+    /// The payload
+    pub data: Vec<u8>,
+}
+
+/// Reads a stream of voice payloads of any number of speakers as tracks and packets
+///
+/// The [`tracks`](Self::tracks) are the speakers and every payload is a [`VoicePacket`] of the track of its
+/// speaker, timed by the duration of the voice data of the speaker before it. The payloads are decoded with a
+/// [`VoicePacketDecoder`] per track.
+///
+/// Unlike the tracks of most containers, the speakers aren't known up front, new tracks are added as their first
+/// payload is read.
+pub struct VoiceFormatReader<I> {
+    payloads: I,
+    tracks: Vec<VoiceTrack>,
+    /// Position after the last packet of every track
+    positions: Vec<u64>,
+}
+
+impl<I> Debug for VoiceFormatReader<I> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VoiceFormatReader")
+            .field("tracks", &self.tracks)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<I, T> VoiceFormatReader<I>
+where
+    I: Iterator<Item = T>,
+    T: AsRef<[u8]>,
+{
+    pub fn new<P: IntoIterator<IntoIter = I>>(payloads: P) -> Self {
+        VoiceFormatReader {
+            payloads: payloads.into_iter(),
+            tracks: Vec::new(),
+            positions: Vec::new(),
+        }
+    }
+
+    /// The tracks of the speakers seen so far
+    pub fn tracks(&self) -> &[VoiceTrack] {
+        &self.tracks
+    }
+
+    /// The track of a speaker, if a payload of the speaker was read
+    pub fn track(&self, steam_id: u64) -> Option<&VoiceTrack> {
+        self.tracks.iter().find(|track| track.steam_id == steam_id)
+    }
+
+    /// Read the next packet, `None` once there are no more payloads
+    pub fn next_packet(&mut self) -> Result<Option<VoicePacket>, SteamAudioError> {
+        let Some(payload) = self.payloads.next() else {
+            return Ok(None);
+        };
+        let payload = payload.as_ref();
+        let voice_data = SteamVoiceData::new(payload)?;
+        let summary = voice_data.summary()?;
+        let steam_id = voice_data.steam_id;
+
+        let index = match self
+            .tracks
+            .iter()
+            .position(|track| track.steam_id == steam_id)
+        {
+            Some(index) => index,
+            None => {
+                self.tracks.push(VoiceTrack {
+                    id: self.tracks.len() as u32,
+                    steam_id,
+                    sample_rate: None,
+                });
+                self.positions.push(0);
+                self.tracks.len() - 1
+            }
+        };
+        let track = &mut self.tracks[index];
+        if let Some(sample_rate) = summary.sample_rate {
+            track.sample_rate = Some(sample_rate as u32);
+        }
+        let dur = (summary.duration.as_nanos() * PACKET_TIME_BASE as u128 / 1_000_000_000) as u64;
+        let ts = self.positions[index];
+        self.positions[index] += dur;
+        Ok(Some(VoicePacket {
+            track_id: track.id,
+            steam_id,
+            ts,
+            dur,
+            data: payload.to_vec(),
+        }))
+    }
+
+    /// Get back the payloads that weren't read yet
+    pub fn into_inner(self) -> I {
+        self.payloads
+    }
+}
+
+impl<I, T> Iterator for VoiceFormatReader<I>
+where
+    I: Iterator<Item = T>,
+    T: AsRef<[u8]>,
+{
+    type Item = Result<VoicePacket, SteamAudioError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_packet().transpose()
+    }
+}
+
+/// Decodes the [`VoicePacket`]s of a single track of a [`VoiceFormatReader`]
+///
+/// Every packet decodes to a buffer of interleaved samples that stays valid until the next packet is decoded.
+#[derive(Debug)]
+pub struct VoicePacketDecoder {
+    decoder: SteamVoiceDecoder,
+    track_id: u32,
+    samples: Vec<i16>,
+}
+
+impl VoicePacketDecoder {
+    /// Create a decoder for the packets of `track`, decoding with `options`
+    pub fn new(track: &VoiceTrack, options: DecoderOptions) -> Result<Self, SteamAudioError> {
+        Ok(VoicePacketDecoder {
+            decoder: SteamVoiceDecoder::with_options(options)?,
+            track_id: track.id,
+            samples: Vec::new(),
+        })
+    }
+
+    pub fn track_id(&self) -> u32 {
+        self.track_id
+    }
+
+    /// Sample rate of the decoded audio, once a packet set it
+    pub fn sample_rate(&self) -> Option<u32> {
+        self.decoder.output_rate()
+    }
+
+    pub fn channels(&self) -> usize {
+        self.decoder.options().channels.count()
+    }
+
+    /// Decode a packet of the track, returning the interleaved samples
+    ///
+    /// Packets of other tracks are ignored and decode to no samples.
+    pub fn decode(&mut self, packet: &VoicePacket) -> Result<&[i16], SteamAudioError> {
+        self.samples.clear();
+        if packet.track_id == self.track_id {
+            let voice_data = SteamVoiceData::new(&packet.data)?;
+            self.decoder.decode_append(voice_data, &mut self.samples)?;
+        }
+        Ok(self.last_decoded())
+    }
+
+    /// The samples of the last decoded packet
+    pub fn last_decoded(&self) -> &[i16] {
+        &self.samples
+    }
+
+    /// Reset the decoder, e.g. after seeking
+    pub fn reset(&mut self) -> Result<(), SteamAudioError> {
+        self.samples.clear();
+        self.decoder.reset()
+    }
+
+    /// Get back the decoder
+    pub fn into_decoder(self) -> SteamVoiceDecoder {
+        self.decoder
+    }
+}
@@ -0,0 +1,293 @@
+//! Removing background noise from decoded speech
+
+use std::fmt::Debug;
+
+/// Number of samples of a frame processed by a [`DenoiseBackend`], 10ms at 48kHz
+pub const DENOISE_FRAME_SAMPLES: usize = 480;
+/// Sample rate of the audio processed by a [`DenoiseBackend`]
+pub const DENOISE_SAMPLE_RATE: u32 = 48000;
+
+/// A speech denoiser working on frames of 10ms of mono audio at 48kHz
+///
+/// The frames match the interface of RNNoise and its rust port `nnnoiseless`, whose `DenoiseState::process_frame`
+/// can be called directly. The `denoise` feature has the built-in `SpectralDenoiser`.
+pub trait DenoiseBackend: Send {
+    /// Denoise a frame of samples in the range of 16 bit samples, returns the probability that the frame contains speech
+    fn process_frame(
+        &mut self,
+        output: &mut [f32; DENOISE_FRAME_SAMPLES],
+        input: &[f32; DENOISE_FRAME_SAMPLES],
+    ) -> f32;
+}
+
+/// Creates a [`DenoiseBackend`] for a speaker
+pub type DenoiserConstructor = fn() -> Box<dyn DenoiseBackend>;
+
+/// Length of the FFT of [`SpectralDenoiser`], covering two frames
+#[cfg(feature = "denoise")]
+const FFT_SIZE: usize = 1024;
+/// Number of frequency bins of the FFT, up to the Nyquist frequency
+#[cfg(feature = "denoise")]
+const BINS: usize = FFT_SIZE / 2 + 1;
+/// Lowest gain applied to a bin, attenuating noise by at most 20dB keeps the remaining noise from sounding musical
+#[cfg(feature = "denoise")]
+const GAIN_FLOOR: f32 = 0.1;
+/// Number of frames the noise is learned from at the start, before it is only updated in bins without speech
+#[cfg(feature = "denoise")]
+const LEARN_FRAMES: u32 = 10;
+
+/// How many times the noise estimate is subtracted, to also remove the bins where the noise is above its average
+#[cfg(feature = "denoise")]
+const OVER_SUBTRACTION: f32 = 2.0;
+
+/// Built-in denoiser removing stationary background noise, like hum, hiss and fans, by spectral subtraction
+///
+/// The noise spectrum is learned from the first 100ms and updated from the bins that don't rise far above it,
+/// every bin is attenuated by its estimated share of noise. The frames are analyzed with half overlapping windows
+/// of two frames, which delays the output by one frame.
+#[cfg(feature = "denoise")]
+pub struct SpectralDenoiser {
+    /// The previous input frame, the first half of the analysis window
+    history: [f32; DENOISE_FRAME_SAMPLES],
+    /// The second half of the previous output window, added to the first half of the next one
+    overlap: [f32; DENOISE_FRAME_SAMPLES],
+    window: Vec<f32>,
+    real: Vec<f32>,
+    imaginary: Vec<f32>,
+    /// Estimated noise power of every bin
+    noise: Vec<f32>,
+    gains: Vec<f32>,
+    frames: u32,
+}
+
+#[cfg(feature = "denoise")]
+impl Debug for SpectralDenoiser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpectralDenoiser")
+            .field("frames", &self.frames)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "denoise")]
+impl Default for SpectralDenoiser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "denoise")]
+impl SpectralDenoiser {
+    pub fn new() -> Self {
+        let length = 2 * DENOISE_FRAME_SAMPLES;
+        // a square root Hann window for both analysis and synthesis, which add up to one at half overlap
+        let window = (0..length)
+            .map(|i| (std::f32::consts::PI * i as f32 / length as f32).sin())
+            .collect();
+        SpectralDenoiser {
+            history: [0.0; DENOISE_FRAME_SAMPLES],
+            overlap: [0.0; DENOISE_FRAME_SAMPLES],
+            window,
+            real: vec![0.0; FFT_SIZE],
+            imaginary: vec![0.0; FFT_SIZE],
+            noise: vec![0.0; BINS],
+            gains: vec![1.0; BINS],
+            frames: 0,
+        }
+    }
+
+    /// [`DenoiserConstructor`] for this backend
+    pub fn boxed() -> Box<dyn DenoiseBackend> {
+        Box::new(Self::new())
+    }
+}
+
+#[cfg(feature = "denoise")]
+impl DenoiseBackend for SpectralDenoiser {
+    fn process_frame(
+        &mut self,
+        output: &mut [f32; DENOISE_FRAME_SAMPLES],
+        input: &[f32; DENOISE_FRAME_SAMPLES],
+    ) -> f32 {
+        let samples = self.history.iter().chain(input.iter());
+        for ((real, sample), window) in self.real.iter_mut().zip(samples).zip(&self.window) {
+            *real = sample * window;
+        }
+        self.real[self.window.len()..].fill(0.0);
+        self.imaginary.fill(0.0);
+        self.history = *input;
+        crate::fingerprint::fft(&mut self.real, &mut self.imaginary);
+
+        self.frames = self.frames.saturating_add(1);
+        let mut total = 0.0;
+        let mut noise_total = 0.0;
+        for bin in 0..BINS {
+            let power = self.real[bin] * self.real[bin] + self.imaginary[bin] * self.imaginary[bin];
+            let noise = &mut self.noise[bin];
+            if self.frames <= LEARN_FRAMES {
+                *noise += (power - *noise) / self.frames as f32;
+            } else if power < *noise * 4.0 {
+                *noise += (power - *noise) * 0.05;
+            } else {
+                // slowly follow noise that got louder, which would otherwise be mistaken for speech forever
+                *noise *= 1.005;
+            }
+            total += power;
+            noise_total += noise.min(power);
+
+            let gain = if power > 0.0 {
+                (1.0 - OVER_SUBTRACTION * *noise / power).max(GAIN_FLOOR)
+            } else {
+                GAIN_FLOOR
+            };
+            // smooth the gain over time against isolated bins of noise popping through
+            self.gains[bin] = 0.6 * gain + 0.4 * self.gains[bin];
+        }
+        for bin in 0..BINS {
+            let gain = self.gains[bin];
+            // the spectrum of a real signal is symmetric, the mirrored bins get the same gain, conjugated for the
+            // inverse transform
+            self.real[bin] *= gain;
+            self.imaginary[bin] *= -gain;
+            if bin > 0 && bin < FFT_SIZE / 2 {
+                self.real[FFT_SIZE - bin] *= gain;
+                self.imaginary[FFT_SIZE - bin] *= -gain;
+            }
+        }
+        crate::fingerprint::fft(&mut self.real, &mut self.imaginary);
+
+        let scale = 1.0 / FFT_SIZE as f32;
+        for (i, output) in output.iter_mut().enumerate() {
+            *output = self.overlap[i] + self.real[i] * scale * self.window[i];
+        }
+        for (i, overlap) in self.overlap.iter_mut().enumerate() {
+            let index = DENOISE_FRAME_SAMPLES + i;
+            *overlap = self.real[index] * scale * self.window[index];
+        }
+
+        if total > 0.0 {
+            1.0 - noise_total / total
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Runs decoded speech through a [`DenoiseBackend`] in frames of [`DENOISE_FRAME_SAMPLES`]
+///
+/// Audio is processed in place, a chunk of audio that doesn't end on a frame boundary is padded with silence
+/// for its last frame. Decoded opus frames are multiples of 10ms, so the decoded audio of a payload can be
+/// processed as is. Use a separate reducer for every speaker.
+pub struct NoiseReducer {
+    backend: Box<dyn DenoiseBackend>,
+    input: [f32; DENOISE_FRAME_SAMPLES],
+    output: [f32; DENOISE_FRAME_SAMPLES],
+    /// Speech probability of the last processed frame
+    speech_probability: f32,
+}
+
+impl Debug for NoiseReducer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NoiseReducer")
+            .field("speech_probability", &self.speech_probability)
+            .finish_non_exhaustive()
+    }
+}
+
+impl NoiseReducer {
+    pub fn new(backend: Box<dyn DenoiseBackend>) -> Self {
+        NoiseReducer {
+            backend,
+            input: [0.0; DENOISE_FRAME_SAMPLES],
+            output: [0.0; DENOISE_FRAME_SAMPLES],
+            speech_probability: 0.0,
+        }
+    }
+
+    /// Probability that the last processed frame contains speech, as reported by the backend
+    pub fn speech_probability(&self) -> f32 {
+        self.speech_probability
+    }
+
+    /// Denoise interleaved audio at 48kHz with `channels` channels in place
+    ///
+    /// The voice is mono, so the first channel is denoised and copied into the other channels.
+    pub fn process(&mut self, samples: &mut [i16], channels: usize) {
+        let channels = channels.max(1);
+        for chunk in samples.chunks_mut(DENOISE_FRAME_SAMPLES * channels) {
+            let frames = chunk.len() / channels;
+            for (input, frame) in self.input.iter_mut().zip(chunk.chunks(channels)) {
+                *input = frame[0] as f32;
+            }
+            self.input[frames..].fill(0.0);
+            self.speech_probability = self.backend.process_frame(&mut self.output, &self.input);
+            for (output, frame) in self.output.iter().zip(chunk.chunks_mut(channels)) {
+                let sample = output.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+                frame.fill(sample);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "denoise"))]
+mod tests {
+    use super::*;
+
+    /// Deterministic white noise with the given amplitude
+    fn noise(count: usize, amplitude: f32) -> Vec<f32> {
+        let mut state = 0x1234_5678u32;
+        (0..count)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                ((state >> 8) as f32 / (1 << 24) as f32 * 2.0 - 1.0) * amplitude
+            })
+            .collect()
+    }
+
+    fn energy(samples: &[f32]) -> f32 {
+        samples.iter().map(|sample| sample * sample).sum()
+    }
+
+    fn denoise(input: &[f32]) -> Vec<f32> {
+        let mut denoiser = SpectralDenoiser::new();
+        let mut output = Vec::new();
+        let mut frame = [0.0; DENOISE_FRAME_SAMPLES];
+        for chunk in input.chunks_exact(DENOISE_FRAME_SAMPLES) {
+            denoiser.process_frame(&mut frame, chunk.try_into().unwrap());
+            output.extend_from_slice(&frame);
+        }
+        output
+    }
+
+    #[test]
+    fn removes_stationary_noise() {
+        let input = noise(DENOISE_FRAME_SAMPLES * 200, 2000.0);
+        let output = denoise(&input);
+        let tail = DENOISE_FRAME_SAMPLES * 100;
+        let ratio = energy(&output[tail..]) / energy(&input[tail..]);
+        assert!(ratio < 0.05, "ratio {ratio}");
+    }
+
+    #[test]
+    fn keeps_tone_over_noise() {
+        let noise = noise(DENOISE_FRAME_SAMPLES * 200, 500.0);
+        // 10 frames of noise to learn from, followed by a 440Hz tone in the noise
+        let input: Vec<f32> = noise
+            .iter()
+            .enumerate()
+            .map(|(i, noise)| {
+                let tone = (i as f32 * 440.0 * 2.0 * std::f32::consts::PI / 48000.0).sin() * 8000.0;
+                noise
+                    + if i >= DENOISE_FRAME_SAMPLES * 10 {
+                        tone
+                    } else {
+                        0.0
+                    }
+            })
+            .collect();
+        let output = denoise(&input);
+        let tail = DENOISE_FRAME_SAMPLES * 100;
+        let kept = energy(&output[tail..]) / energy(&input[tail..]);
+        assert!(kept > 0.8 && kept < 1.05, "kept {kept}");
+    }
+}
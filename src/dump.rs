@@ -0,0 +1,129 @@
+//! Human readable dump of the packets in voice data
+
+use crate::{OpusFrame, OpusFrameIterator, Packet, SteamVoiceData, HEADER_SIZE};
+use std::fmt::{self, Display, Formatter};
+
+/// Number of bytes shown in the hex preview of packet and frame data
+const PREVIEW_BYTES: usize = 8;
+
+/// Renders voice data as a tree of its packets and opus frames, see [`SteamVoiceData::dump`]
+///
+/// Every packet is listed with its index, offset in the payload, type and value or length.
+/// Malformed packets are listed as errors, after which the dump resumes at the next plausible packet.
+#[derive(Debug, Clone, Copy)]
+pub struct PayloadDump<'a> {
+    voice_data: SteamVoiceData<'a>,
+}
+
+impl<'a> PayloadDump<'a> {
+    pub(crate) fn new(voice_data: SteamVoiceData<'a>) -> Self {
+        PayloadDump { voice_data }
+    }
+}
+
+impl Display for PayloadDump<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let packet_data = self.voice_data.packet_data;
+        writeln!(
+            f,
+            "steam id {} ({} bytes of packet data)",
+            self.voice_data.steam_id,
+            packet_data.len()
+        )?;
+        let mut data = packet_data;
+        let mut index = 0;
+        while !data.is_empty() {
+            let offset = HEADER_SIZE + packet_data.len() - data.len();
+            write!(f, "  [{index}] @{offset} ")?;
+            let length = match Packet::read_lenient(data) {
+                Ok((packet, rest)) => {
+                    writeln!(f, "{}", PacketDump(&packet))?;
+                    if let Packet::OpusPlc(opus) = packet {
+                        dump_frames(f, opus.data)?;
+                    }
+                    data.len() - rest.len()
+                }
+                Err(e) => {
+                    let skip = Packet::resync(data);
+                    writeln!(f, "error: {e}, skipping {skip} bytes: {}", Hex(data))?;
+                    skip
+                }
+            };
+            data = &data[length..];
+            index += 1;
+        }
+        Ok(())
+    }
+}
+
+fn dump_frames(f: &mut Formatter<'_>, data: &[u8]) -> fmt::Result {
+    let mut frames = OpusFrameIterator::new(data);
+    for frame in frames.by_ref() {
+        match frame {
+            Ok(OpusFrame::Reset) => writeln!(f, "      reset")?,
+            Ok(OpusFrame::Frame { seq, data }) => writeln!(
+                f,
+                "      frame seq {seq}, {} bytes: {}",
+                data.len(),
+                Hex(data)
+            )?,
+            Err(e) => writeln!(f, "      error: {e}")?,
+        }
+    }
+    let trailing = frames.remaining();
+    if !trailing.is_empty() {
+        writeln!(
+            f,
+            "      {} trailing bytes: {}",
+            trailing.len(),
+            Hex(trailing)
+        )?;
+    }
+    Ok(())
+}
+
+/// A single packet, without its opus frames
+struct PacketDump<'a, 'b>(&'b Packet<'a>);
+
+impl Display for PacketDump<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Packet::Silence(samples) => write!(f, "Silence {samples} samples"),
+            Packet::OpusPlc(opus) => write!(f, "OpusPlc {} bytes", opus.data.len()),
+            Packet::SampleRate(rate) => write!(f, "SampleRate {rate}Hz"),
+            Packet::RawPcm(data) => write!(f, "RawPcm {} bytes: {}", data.len(), Hex(data)),
+            Packet::Unsupported { ty, data } => {
+                write!(f, "{ty:?} {} bytes: {}", data.len(), Hex(data))
+            }
+            Packet::Marker(value) => write!(f, "Marker {value}"),
+            Packet::Unknown {
+                ty,
+                value,
+                data: [],
+            } => {
+                write!(f, "Unknown type {ty}, value {value}")
+            }
+            Packet::Unknown { ty, data, .. } => {
+                write!(f, "Unknown type {ty}, {} bytes: {}", data.len(), Hex(data))
+            }
+        }
+    }
+}
+
+/// Hex preview of the first bytes of some data
+struct Hex<'a>(&'a [u8]);
+
+impl Display for Hex<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.0.iter().take(PREVIEW_BYTES).enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{byte:02x}")?;
+        }
+        if self.0.len() > PREVIEW_BYTES {
+            f.write_str(" ..")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+//! Voice data of the `vaudio_opus` engine codec
+//!
+//! Unlike the `steam` codec, the engine opus codec doesn't wrap its frames in a steam voice payload:
+//! there is no steam id, no packet types and no checksum, and silence isn't encoded. The voice data
+//! consists only of the frame stream that the steam codec puts in its [`OpusPlc`](crate::PacketType::OpusPlc)
+//! packets, sequence numbered frames with a length of `0xFFFF` marking an encoder reset.
+//! The sample rate isn't part of the voice data either, it is sent in the `VoiceInit` message.
+
+use crate::{
+    push_opus_packet, OpusFrameIterator, OwnedSteamVoiceData, PacketType, SteamAudioError,
+};
+
+/// Voice data of the `vaudio_opus` engine codec
+#[derive(Debug, Clone, Copy)]
+pub struct EngineOpusData<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> EngineOpusData<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        EngineOpusData { data }
+    }
+
+    /// The encoded frame stream
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Wrap the frames in steam voice data with the sample rate of the `VoiceInit` message
+    ///
+    /// The resulting voice data can be used with anything that takes steam voice data,
+    /// like a [`SteamVoiceDecoder`](crate::SteamVoiceDecoder) or a [`FrameExtractor`](crate::FrameExtractor).
+    /// Frames continue across payloads by their sequence numbers, same as with the steam codec.
+    pub fn to_voice_data(
+        &self,
+        steam_id: u64,
+        sample_rate: u16,
+    ) -> Result<OwnedSteamVoiceData, SteamAudioError> {
+        let mut packet_data = vec![PacketType::SampleRate.into()];
+        packet_data.extend_from_slice(&sample_rate.to_le_bytes());
+
+        // a packet can hold at most 64KiB of frames, so longer voice data is split at frame boundaries
+        let mut frames = OpusFrameIterator::new(self.data);
+        let (mut start, mut end) = (0, 0);
+        while let Some(frame) = frames.next() {
+            frame?;
+            let offset = self.data.len() - frames.remaining().len();
+            if offset - start > u16::MAX as usize {
+                if end == start {
+                    return Err(SteamAudioError::InvalidOpusPacket);
+                }
+                push_opus_packet(&mut packet_data, &self.data[start..end]);
+                start = end;
+            }
+            end = offset;
+        }
+        // bytes after the last frame are kept, so they are handled like those in steam voice data
+        if self.data.len() - start > u16::MAX as usize {
+            push_opus_packet(&mut packet_data, &self.data[start..end]);
+            start = end;
+        }
+        push_opus_packet(&mut packet_data, &self.data[start..]);
+        Ok(OwnedSteamVoiceData::from_parts(steam_id, packet_data))
+    }
+}
@@ -10,8 +10,99 @@ pub enum SteamAudioError {
     InsufficientOutputBuffer,
     #[error("unknown packet type {ty}")]
     UnknownPacketType { ty: u8 },
+    #[cfg(feature = "opus")]
     #[error(transparent)]
     Opus(#[from] opus::Error),
+    #[cfg(feature = "audiopus")]
+    #[error(transparent)]
+    Audiopus(#[from] audiopus::Error),
+    #[cfg(feature = "opus-decoder")]
+    #[error(transparent)]
+    RustOpus(#[from] opus_decoder::OpusError),
+    #[error("no opus backend available, enable an opus backend feature or provide a backend")]
+    NoBackend,
+    #[error("the opus backend doesn't support setting a gain")]
+    GainUnsupported,
     #[error("audio data received before sample rate is set")]
     NoSampleRate,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[cfg(feature = "wav")]
+    #[error(transparent)]
+    Wav(#[from] hound::Error),
+    #[error("invalid opus packet")]
+    InvalidOpusPacket,
+    #[error("unsupported sample rate {rate}")]
+    UnsupportedSampleRate { rate: u32 },
+    #[error("malformed voice message")]
+    InvalidMessage,
+    #[error("invalid {codec} frame")]
+    InvalidFrame { codec: &'static str },
+    #[error("unsupported voice codec {codec}")]
+    UnsupportedCodec { codec: String },
+    #[error("voice data decodes to more than {limit} samples")]
+    TooManySamples { limit: usize },
+    #[error("decoding exceeds the limit of {limit} samples in total")]
+    TotalSamplesExceeded { limit: u64 },
+    #[error("{count} trailing bytes at offset {offset} of opus data")]
+    TrailingBytes { count: usize, offset: usize },
+    #[cfg(feature = "cpal")]
+    #[error("no audio output device available")]
+    NoOutputDevice,
+    #[cfg(feature = "cpal")]
+    #[error("unsupported output sample format {format}")]
+    UnsupportedSampleFormat { format: cpal::SampleFormat },
+    #[cfg(feature = "cpal")]
+    #[error(transparent)]
+    OutputConfig(#[from] cpal::DefaultStreamConfigError),
+    #[cfg(feature = "cpal")]
+    #[error(transparent)]
+    BuildStream(#[from] cpal::BuildStreamError),
+    #[cfg(feature = "cpal")]
+    #[error(transparent)]
+    PlayStream(#[from] cpal::PlayStreamError),
+    #[error("{source} in packet {index} at offset {offset}")]
+    InPacket {
+        /// Index of the packet in the payload
+        index: usize,
+        /// Offset of the packet in the payload, including the steam id
+        offset: usize,
+        source: Box<SteamAudioError>,
+    },
+}
+
+impl SteamAudioError {
+    /// The error without the packet it occurred in
+    pub fn root(&self) -> &SteamAudioError {
+        match self {
+            SteamAudioError::InPacket { source, .. } => source.root(),
+            error => error,
+        }
+    }
+
+    /// Add the packet to errors caused by the packet data, errors caused by the output or the decoder are kept as is
+    pub(crate) fn in_packet(self, index: usize, offset: usize) -> Self {
+        match self {
+            error @ SteamAudioError::InPacket { .. } => error,
+            error if !error.caused_by_data() => error,
+            error => SteamAudioError::InPacket {
+                index,
+                offset,
+                source: Box::new(error),
+            },
+        }
+    }
+
+    /// Whether the error is caused by the voice data, instead of the output or the decoder
+    pub(crate) fn caused_by_data(&self) -> bool {
+        !matches!(
+            self.root(),
+            SteamAudioError::InsufficientOutputBuffer
+                | SteamAudioError::TooManySamples { .. }
+                | SteamAudioError::TotalSamplesExceeded { .. }
+                | SteamAudioError::NoBackend
+                | SteamAudioError::GainUnsupported
+                | SteamAudioError::Io(_)
+        )
+    }
 }
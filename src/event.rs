@@ -0,0 +1,49 @@
+use crate::PacketType;
+
+/// Why the opus state of a decoder was reset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetReason {
+    /// The voice data signalled that the encoder was restarted
+    Marker,
+    /// A frame arrived with a sequence number before the expected one
+    OutOfOrder { expected: u16, seq: u16 },
+    /// More frames were lost than the configured maximum concealment
+    GapTooLarge { lost: u16 },
+}
+
+/// Notable events while decoding, see [`SteamVoiceDecoder::set_event_handler`](crate::SteamVoiceDecoder::set_event_handler)
+///
+/// Positions are in output samples, counted from the creation or the last [`reset`](crate::SteamVoiceDecoder::reset) of the decoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeEvent {
+    /// A lost frame was replaced by generated audio
+    Concealed {
+        steam_id: u64,
+        start: u64,
+        samples: usize,
+        /// Whether the frame was recovered from the forward error correction data of the next frame
+        recovered: bool,
+    },
+    /// The opus state was reset, the audio before and after `position` isn't continuous
+    Reset {
+        steam_id: u64,
+        position: u64,
+        reason: ResetReason,
+    },
+    /// A packet of an unknown type was skipped in [lenient](crate::ParseMode::Lenient) parsing
+    Skipped {
+        steam_id: u64,
+        position: u64,
+        ty: u8,
+        /// Number of bytes skipped, including the packet header
+        length: usize,
+    },
+    /// A packet of a codec that isn't supported was left out of the output
+    Unsupported {
+        steam_id: u64,
+        position: u64,
+        ty: PacketType,
+        /// Number of bytes of audio data in the packet
+        length: usize,
+    },
+}
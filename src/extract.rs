@@ -0,0 +1,202 @@
+//! Extraction of the opus frames in voice data without decoding them
+
+use crate::toc::packet_samples;
+use crate::{
+    frames_lost, OpusFrame, OpusFrameIterator, Packet, SteamAudioError, SteamVoiceData,
+    DEFAULT_MAX_CONCEALMENT,
+};
+
+/// Timestamps of extracted frames are in 48kHz samples, the rate opus frame durations are defined in
+const TIMESTAMP_RATE: u64 = 48_000;
+
+/// An opus frame extracted from voice data, with its timing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtractedFrame<'a> {
+    pub seq: u16,
+    /// The opus packet of the frame
+    pub data: &'a [u8],
+    /// Sample rate the frame was encoded at
+    pub sample_rate: u16,
+    /// Start of the frame in 48kHz samples
+    pub start: u64,
+    /// Duration of the frame in 48kHz samples
+    pub duration: u64,
+    /// Number of frames lost directly before this frame
+    pub lost: u16,
+    /// Whether the encoder was reset before this frame, the frame doesn't continue the previous frames
+    pub reset: bool,
+}
+
+/// Extracts the opus frames from voice payloads without decoding them
+///
+/// The timing of the frames follows from the silence packets and the durations of the frames,
+/// lost frames are assumed to have the duration of the frame before them.
+/// Gaps of more than [`DEFAULT_MAX_CONCEALMENT`] lost frames are treated as a reset, like a decoder does,
+/// instead of advancing the timing by the lost frames.
+/// Frames of multiple payloads of the same speaker are timed continuously.
+#[derive(Debug, Default)]
+pub struct FrameExtractor {
+    sample_rate: Option<u16>,
+    /// Current position in 48kHz samples
+    position: u64,
+    /// Silence that didn't add up to a whole 48kHz sample, in samples times the sample rate
+    silence_remainder: u64,
+    seq: Option<u16>,
+    /// Duration of the last frame in 48kHz samples
+    last_duration: u64,
+    reset: bool,
+}
+
+impl FrameExtractor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current position in 48kHz samples, the end of the extracted audio so far
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Forget the state of previous payloads, the next frame is extracted at position 0
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Extract the opus frames of a payload
+    pub fn extract<'a>(
+        &mut self,
+        voice_data: &SteamVoiceData<'a>,
+    ) -> Result<Vec<ExtractedFrame<'a>>, SteamAudioError> {
+        let mut frames = Vec::new();
+        for packet in voice_data.packets() {
+            match packet? {
+                Packet::SampleRate(0) => {
+                    return Err(SteamAudioError::UnsupportedSampleRate { rate: 0 })
+                }
+                Packet::SampleRate(rate) => {
+                    if self.sample_rate != Some(rate) {
+                        self.sample_rate = Some(rate);
+                        self.silence_remainder = 0;
+                    }
+                }
+                Packet::Silence(silence) => {
+                    let rate = self.sample_rate.ok_or(SteamAudioError::NoSampleRate)? as u64;
+                    let scaled = silence as u64 * TIMESTAMP_RATE + self.silence_remainder;
+                    self.position += scaled / rate;
+                    self.silence_remainder = scaled % rate;
+                }
+                Packet::OpusPlc(opus) => {
+                    let rate = self.sample_rate.ok_or(SteamAudioError::NoSampleRate)?;
+                    for frame in OpusFrameIterator::new(opus.data) {
+                        let (seq, data) = match frame? {
+                            OpusFrame::Reset => {
+                                self.seq = Some(0);
+                                self.reset = true;
+                                continue;
+                            }
+                            OpusFrame::Frame { seq, data } => (seq, data),
+                        };
+
+                        let lost = match self.seq.map(|expected| frames_lost(expected, seq)) {
+                            Some(Some(lost)) if lost <= DEFAULT_MAX_CONCEALMENT => lost,
+                            // out of order or too large a gap to conceal, the frame can't continue
+                            // the previous frames
+                            Some(_) => {
+                                self.reset = true;
+                                0
+                            }
+                            None => 0,
+                        };
+                        self.position += lost as u64 * self.last_duration;
+
+                        // empty frames are handled as lost, by still expecting them
+                        if data.is_empty() {
+                            self.seq = Some(seq);
+                            continue;
+                        }
+                        self.seq = Some(seq.wrapping_add(1));
+                        let duration = packet_samples(data, TIMESTAMP_RATE as u32)? as u64;
+                        frames.push(ExtractedFrame {
+                            seq,
+                            data,
+                            sample_rate: rate,
+                            start: self.position,
+                            duration,
+                            lost,
+                            reset: std::mem::take(&mut self.reset),
+                        });
+                        self.position += duration;
+                        self.last_duration = duration;
+                    }
+                }
+                Packet::RawPcm(_)
+                | Packet::Unsupported { .. }
+                | Packet::Marker(_)
+                | Packet::Unknown { .. } => {}
+            }
+        }
+        Ok(frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data::{opus, payload, sample_rate, silence, FRAME};
+
+    #[test]
+    fn rejects_sample_rate_zero() {
+        let payload = payload(&[sample_rate(0), silence(480)]);
+        let voice_data = SteamVoiceData::new(&payload).unwrap();
+        assert!(matches!(
+            FrameExtractor::new().extract(&voice_data),
+            Err(SteamAudioError::UnsupportedSampleRate { rate: 0 })
+        ));
+    }
+
+    #[test]
+    fn times_frames_after_silence() {
+        let payload = payload(&[
+            sample_rate(24000),
+            silence(240),
+            opus(&[(0, FRAME), (2, FRAME)]),
+        ]);
+        let voice_data = SteamVoiceData::new(&payload).unwrap();
+        let frames = FrameExtractor::new().extract(&voice_data).unwrap();
+        let starts: Vec<_> = frames
+            .iter()
+            .map(|frame| (frame.start, frame.lost))
+            .collect();
+        assert_eq!(starts, [(480, 0), (2400, 1)]);
+    }
+
+    #[test]
+    fn large_gap_resets() {
+        let payload = payload(&[
+            sample_rate(24000),
+            opus(&[
+                (0, FRAME),
+                (1 + DEFAULT_MAX_CONCEALMENT, FRAME),
+                (30000, FRAME),
+            ]),
+        ]);
+        let voice_data = SteamVoiceData::new(&payload).unwrap();
+        let frames = FrameExtractor::new().extract(&voice_data).unwrap();
+        let timing: Vec<_> = frames
+            .iter()
+            .map(|frame| (frame.start, frame.lost, frame.reset))
+            .collect();
+        assert_eq!(
+            timing,
+            [
+                (0, 0, false),
+                (
+                    960 * (1 + DEFAULT_MAX_CONCEALMENT as u64),
+                    DEFAULT_MAX_CONCEALMENT,
+                    false
+                ),
+                (960 * (2 + DEFAULT_MAX_CONCEALMENT as u64), 0, true)
+            ]
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! Smoothing the edges between decoded audio, concealment and silence
+
+/// What produced a chunk of decoded samples
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum Source {
+    #[default]
+    Silence,
+    Audio,
+    Concealed,
+    /// The opus state was reset, the next audio isn't continuous with the previous audio
+    Reset,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+enum Ramp {
+    #[default]
+    None,
+    /// Fade in from silence
+    FadeIn,
+    /// Difference between the last sample before the edge and the first sample after it per channel,
+    /// added to the samples after the edge and faded out
+    Offset([i32; 2]),
+}
+
+/// Applies short fades at the edges between the sources of the decoded audio, which would otherwise click
+///
+/// Audio following silence is faded in. At any other edge the step between the last sample before it and
+/// the first sample after it is faded out over the length of the fade, which for silence following audio
+/// fades the audio out to zero.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct EdgeFade {
+    /// Length of a fade in samples per channel, 0 disables fading
+    length: usize,
+    /// Last written sample of the first two channels
+    last: [i16; 2],
+    previous: Source,
+    ramp: Ramp,
+    /// Number of samples per channel of the current ramp that were already written
+    done: usize,
+}
+
+impl EdgeFade {
+    pub fn set_length(&mut self, length: usize) {
+        self.length = length;
+    }
+
+    /// Forget the previous audio, the next audio fades in
+    pub fn reset(&mut self) {
+        *self = EdgeFade {
+            length: self.length,
+            ..EdgeFade::default()
+        };
+    }
+
+    /// Mark that the next audio isn't continuous with the previous audio
+    pub fn discontinuity(&mut self) {
+        if self.previous != Source::Silence {
+            self.previous = Source::Reset;
+        }
+    }
+
+    /// Apply the fades to the next chunk of interleaved samples
+    pub fn apply(&mut self, samples: &mut [i16], source: Source, channels: usize) {
+        let channels = channels.max(1);
+        if samples.len() < channels {
+            if source != self.previous {
+                self.previous = source;
+                self.ramp = Ramp::None;
+                self.last = [0; 2];
+            }
+            return;
+        }
+        if self.length == 0 {
+            self.previous = source;
+            return;
+        }
+
+        if source != self.previous {
+            self.done = 0;
+            self.ramp = match self.previous {
+                Source::Silence => Ramp::FadeIn,
+                _ => {
+                    let mut offset = [0; 2];
+                    for (channel, offset) in offset.iter_mut().enumerate().take(channels) {
+                        *offset = self.last[channel] as i32 - samples[channel] as i32;
+                    }
+                    Ramp::Offset(offset)
+                }
+            };
+            self.previous = source;
+        }
+
+        let frames = samples.chunks_exact_mut(channels);
+        let remaining = self.length.saturating_sub(self.done);
+        for (index, frame) in frames.take(remaining).enumerate() {
+            let progress = (self.done + index) as f32 / self.length as f32;
+            match self.ramp {
+                Ramp::None => break,
+                Ramp::FadeIn => {
+                    for sample in frame {
+                        *sample = (*sample as f32 * progress) as i16;
+                    }
+                }
+                Ramp::Offset(offset) => {
+                    for (channel, sample) in frame.iter_mut().enumerate() {
+                        let offset = offset[channel.min(1)] as f32 * (1.0 - progress);
+                        *sample = (*sample as i32 + offset as i32)
+                            .clamp(i16::MIN as i32, i16::MAX as i32)
+                            as i16;
+                    }
+                }
+            }
+        }
+        self.done = (self.done + samples.len() / channels).min(self.length);
+
+        let last = &samples[(samples.len() / channels - 1) * channels..];
+        self.last = [last[0], last[(channels - 1).min(1)]];
+    }
+}
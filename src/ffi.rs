@@ -0,0 +1,322 @@
+//! A C ABI for the decoder, for C and C++ demo tools and game server plugins
+//!
+//! The header for these functions is `include/steam_audio_codec.h`. The crate is only built as a Rust library,
+//! the shared library for C is built with `cargo rustc --release --crate-type cdylib --features ffi`.
+
+use crate::{
+    DecoderOptions, OutputChannels, SteamAudioError, SteamVoiceData, SteamVoiceDecoder as Decoder,
+};
+use std::ffi::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// Opaque decoder handle for C, created with [`steam_voice_decoder_new`] and freed with
+/// [`steam_voice_decoder_free`]
+pub struct SteamVoiceDecoder {
+    decoder: Decoder,
+}
+
+/// Result codes of the C functions, negative values are errors
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SteamAudioErrorCode {
+    Ok = 0,
+    /// A required pointer argument was null
+    NullPointer = -1,
+    /// The checksum of the payload doesn't match
+    CrcMismatch = -2,
+    /// The payload is truncated
+    InsufficientData = -3,
+    /// The output buffer is too small for the decoded audio
+    InsufficientOutputBuffer = -4,
+    /// The payload contains a packet of an unknown type
+    UnknownPacketType = -5,
+    /// The opus decoder failed
+    Opus = -6,
+    /// The library was built without an opus decoder
+    NoBackend = -7,
+    /// The payload has audio before its sample rate
+    NoSampleRate = -8,
+    InvalidOpusPacket = -9,
+    UnsupportedSampleRate = -10,
+    UnsupportedCodec = -11,
+    /// The payload decodes to more samples than allowed
+    TooManySamples = -12,
+    /// The opus data has trailing bytes
+    TrailingBytes = -13,
+    /// The decoder panicked, it shouldn't be used anymore
+    Panic = -14,
+    /// Any other error
+    Other = -15,
+}
+
+impl From<&SteamAudioError> for SteamAudioErrorCode {
+    fn from(error: &SteamAudioError) -> Self {
+        match error.root() {
+            SteamAudioError::CrcMismatch { .. } => SteamAudioErrorCode::CrcMismatch,
+            SteamAudioError::InsufficientData => SteamAudioErrorCode::InsufficientData,
+            SteamAudioError::InsufficientOutputBuffer => {
+                SteamAudioErrorCode::InsufficientOutputBuffer
+            }
+            SteamAudioError::UnknownPacketType { .. } => SteamAudioErrorCode::UnknownPacketType,
+            #[cfg(feature = "opus")]
+            SteamAudioError::Opus(_) => SteamAudioErrorCode::Opus,
+            #[cfg(feature = "audiopus")]
+            SteamAudioError::Audiopus(_) => SteamAudioErrorCode::Opus,
+            #[cfg(feature = "opus-decoder")]
+            SteamAudioError::RustOpus(_) => SteamAudioErrorCode::Opus,
+            SteamAudioError::NoBackend => SteamAudioErrorCode::NoBackend,
+            SteamAudioError::NoSampleRate => SteamAudioErrorCode::NoSampleRate,
+            SteamAudioError::InvalidOpusPacket => SteamAudioErrorCode::InvalidOpusPacket,
+            SteamAudioError::UnsupportedSampleRate { .. } => {
+                SteamAudioErrorCode::UnsupportedSampleRate
+            }
+            SteamAudioError::UnsupportedCodec { .. } => SteamAudioErrorCode::UnsupportedCodec,
+            SteamAudioError::TooManySamples { .. }
+            | SteamAudioError::TotalSamplesExceeded { .. } => SteamAudioErrorCode::TooManySamples,
+            SteamAudioError::TrailingBytes { .. } => SteamAudioErrorCode::TrailingBytes,
+            _ => SteamAudioErrorCode::Other,
+        }
+    }
+}
+
+impl SteamAudioErrorCode {
+    fn message(&self) -> &'static [u8] {
+        match self {
+            SteamAudioErrorCode::Ok => b"no error\0",
+            SteamAudioErrorCode::NullPointer => b"null pointer argument\0",
+            SteamAudioErrorCode::CrcMismatch => b"crc mismatch for payload\0",
+            SteamAudioErrorCode::InsufficientData => b"insufficient number of bytes provided\0",
+            SteamAudioErrorCode::InsufficientOutputBuffer => {
+                b"insufficient space in output buffer\0"
+            }
+            SteamAudioErrorCode::UnknownPacketType => b"unknown packet type\0",
+            SteamAudioErrorCode::Opus => b"opus decoder error\0",
+            SteamAudioErrorCode::NoBackend => b"no opus backend available\0",
+            SteamAudioErrorCode::NoSampleRate => b"audio data received before sample rate is set\0",
+            SteamAudioErrorCode::InvalidOpusPacket => b"invalid opus packet\0",
+            SteamAudioErrorCode::UnsupportedSampleRate => b"unsupported sample rate\0",
+            SteamAudioErrorCode::UnsupportedCodec => b"unsupported voice codec\0",
+            SteamAudioErrorCode::TooManySamples => b"voice data decodes to too many samples\0",
+            SteamAudioErrorCode::TrailingBytes => b"trailing bytes in opus data\0",
+            SteamAudioErrorCode::Panic => b"decoder panicked\0",
+            SteamAudioErrorCode::Other => b"decoding failed\0",
+        }
+    }
+}
+
+/// Run `decode` on the decoder behind `decoder`, turning errors and panics into error codes
+///
+/// # Safety
+///
+/// `decoder` has to be null or a pointer returned by one of the constructors that wasn't freed.
+unsafe fn with_decoder<F: FnOnce(&mut Decoder) -> Result<i64, SteamAudioError>>(
+    decoder: *mut SteamVoiceDecoder,
+    decode: F,
+) -> i64 {
+    let Some(decoder) = decoder.as_mut() else {
+        return SteamAudioErrorCode::NullPointer as i64;
+    };
+    match catch_unwind(AssertUnwindSafe(|| decode(&mut decoder.decoder))) {
+        Ok(Ok(result)) => result,
+        Ok(Err(error)) => SteamAudioErrorCode::from(&error) as i64,
+        Err(_) => SteamAudioErrorCode::Panic as i64,
+    }
+}
+
+fn new_decoder(options: DecoderOptions) -> *mut SteamVoiceDecoder {
+    // without a built-in backend the decoder would only fail once the first payload sets the sample rate
+    if cfg!(not(any(
+        feature = "opus",
+        feature = "audiopus",
+        feature = "opus-decoder"
+    ))) {
+        return std::ptr::null_mut();
+    }
+    match catch_unwind(|| Decoder::with_options(options)) {
+        Ok(Ok(decoder)) => Box::into_raw(Box::new(SteamVoiceDecoder { decoder })),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// Create a decoder with the default options, decoding to mono at the sample rate of the voice data
+///
+/// Returns null if the library was built without an opus decoder. The decoder has to be freed with
+/// [`steam_voice_decoder_free`].
+#[no_mangle]
+pub extern "C" fn steam_voice_decoder_new() -> *mut SteamVoiceDecoder {
+    new_decoder(DecoderOptions::default())
+}
+
+/// Create a decoder decoding at `output_rate`, or the sample rate of the voice data if it is 0, to mono or
+/// interleaved stereo
+///
+/// Returns null if the sample rate isn't supported or the library was built without an opus decoder. The decoder
+/// has to be freed with [`steam_voice_decoder_free`].
+#[no_mangle]
+pub extern "C" fn steam_voice_decoder_new_with_options(
+    output_rate: u32,
+    stereo: bool,
+) -> *mut SteamVoiceDecoder {
+    new_decoder(DecoderOptions {
+        output_rate: (output_rate > 0).then_some(output_rate),
+        channels: if stereo {
+            OutputChannels::Stereo
+        } else {
+            OutputChannels::Mono
+        },
+        ..DecoderOptions::default()
+    })
+}
+
+/// Free a decoder, passing null does nothing
+///
+/// # Safety
+///
+/// `decoder` has to be null or a pointer returned by one of the constructors that wasn't freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn steam_voice_decoder_free(decoder: *mut SteamVoiceDecoder) {
+    if !decoder.is_null() {
+        drop(Box::from_raw(decoder));
+    }
+}
+
+/// Decode a voice payload into `output`, which has room for `output_len` samples
+///
+/// Returns the number of samples written, or a negative [`SteamAudioErrorCode`]. 20ms of audio at 48kHz stereo
+/// takes 1920 samples, a payload usually holds up to a few hundred milliseconds.
+///
+/// # Safety
+///
+/// `decoder` has to be a pointer returned by one of the constructors that wasn't freed, `payload` has to point
+/// to `payload_len` readable bytes and `output` to `output_len` writable samples.
+#[no_mangle]
+pub unsafe extern "C" fn steam_voice_decoder_decode(
+    decoder: *mut SteamVoiceDecoder,
+    payload: *const u8,
+    payload_len: usize,
+    output: *mut i16,
+    output_len: usize,
+) -> i64 {
+    if payload.is_null() || (output.is_null() && output_len > 0) {
+        return SteamAudioErrorCode::NullPointer as i64;
+    }
+    let payload = std::slice::from_raw_parts(payload, payload_len);
+    let output: &mut [i16] = if output_len > 0 {
+        std::slice::from_raw_parts_mut(output, output_len)
+    } else {
+        &mut []
+    };
+    with_decoder(decoder, |decoder| {
+        let voice_data = SteamVoiceData::new(payload)?;
+        Ok(decoder.decode(voice_data, output)? as i64)
+    })
+}
+
+/// Reset the decoder to decode a new, unrelated, stream of voice data
+///
+/// Returns 0, or a negative [`SteamAudioErrorCode`].
+///
+/// # Safety
+///
+/// `decoder` has to be a pointer returned by one of the constructors that wasn't freed.
+#[no_mangle]
+pub unsafe extern "C" fn steam_voice_decoder_reset(decoder: *mut SteamVoiceDecoder) -> i32 {
+    with_decoder(decoder, |decoder| decoder.reset().map(|_| 0)) as i32
+}
+
+/// The sample rate of the decoded audio, 0 until a payload set it
+///
+/// # Safety
+///
+/// `decoder` has to be null or a pointer returned by one of the constructors that wasn't freed.
+#[no_mangle]
+pub unsafe extern "C" fn steam_voice_decoder_sample_rate(decoder: *const SteamVoiceDecoder) -> u32 {
+    decoder
+        .as_ref()
+        .and_then(|decoder| decoder.decoder.output_rate())
+        .unwrap_or(0)
+}
+
+/// The number of interleaved channels of the decoded audio
+///
+/// # Safety
+///
+/// `decoder` has to be null or a pointer returned by one of the constructors that wasn't freed.
+#[no_mangle]
+pub unsafe extern "C" fn steam_voice_decoder_channels(decoder: *const SteamVoiceDecoder) -> u32 {
+    decoder.as_ref().map_or(0, |decoder| {
+        decoder.decoder.options().channels.count() as u32
+    })
+}
+
+/// Read the steam id of the speaker of a payload into `steam_id`, without decoding it
+///
+/// Returns 0, or a negative [`SteamAudioErrorCode`].
+///
+/// # Safety
+///
+/// `payload` has to point to `payload_len` readable bytes and `steam_id` to a writable `uint64_t`.
+#[no_mangle]
+pub unsafe extern "C" fn steam_voice_payload_steam_id(
+    payload: *const u8,
+    payload_len: usize,
+    steam_id: *mut u64,
+) -> i32 {
+    if payload.is_null() || steam_id.is_null() {
+        return SteamAudioErrorCode::NullPointer as i32;
+    }
+    let payload = std::slice::from_raw_parts(payload, payload_len);
+    match SteamVoiceData::new(payload) {
+        Ok(voice_data) => {
+            *steam_id = voice_data.steam_id;
+            SteamAudioErrorCode::Ok as i32
+        }
+        Err(error) => SteamAudioErrorCode::from(&error) as i32,
+    }
+}
+
+/// A static, nul terminated, description of a result code, results that aren't negative aren't errors
+#[no_mangle]
+pub extern "C" fn steam_audio_error_message(code: i64) -> *const c_char {
+    let code = [
+        SteamAudioErrorCode::NullPointer,
+        SteamAudioErrorCode::CrcMismatch,
+        SteamAudioErrorCode::InsufficientData,
+        SteamAudioErrorCode::InsufficientOutputBuffer,
+        SteamAudioErrorCode::UnknownPacketType,
+        SteamAudioErrorCode::Opus,
+        SteamAudioErrorCode::NoBackend,
+        SteamAudioErrorCode::NoSampleRate,
+        SteamAudioErrorCode::InvalidOpusPacket,
+        SteamAudioErrorCode::UnsupportedSampleRate,
+        SteamAudioErrorCode::UnsupportedCodec,
+        SteamAudioErrorCode::TooManySamples,
+        SteamAudioErrorCode::TrailingBytes,
+        SteamAudioErrorCode::Panic,
+    ]
+    .into_iter()
+    .find(|known| *known as i64 == code)
+    .unwrap_or(if code >= 0 {
+        SteamAudioErrorCode::Ok
+    } else {
+        SteamAudioErrorCode::Other
+    });
+    code.message().as_ptr() as *const c_char
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_decoder_needs_a_backend() {
+        let decoder = steam_voice_decoder_new();
+        let has_backend = cfg!(any(
+            feature = "opus",
+            feature = "audiopus",
+            feature = "opus-decoder"
+        ));
+        assert_eq!(decoder.is_null(), !has_backend);
+        unsafe { steam_voice_decoder_free(decoder) };
+    }
+}
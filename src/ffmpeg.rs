@@ -0,0 +1,126 @@
+//! Streaming decoded audio into an ffmpeg process
+
+use crate::{PcmSink, RawFormat, RawPcmSink, SteamAudioError};
+use std::ffi::OsStr;
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+
+/// Name of the ffmpeg executable, looked up on the `PATH`
+pub const FFMPEG_PROGRAM: &str = "ffmpeg";
+
+/// A [`PcmSink`] streaming audio into the standard input of an ffmpeg process, for encoding or transcoding the
+/// voice with anything ffmpeg supports
+///
+/// The process is started with the raw PCM input arguments for the sample rate and channels of the audio, followed
+/// by the output arguments, e.g. `["-c:a", "libopus", "voice.opus"]`. [`wait`](Self::wait) closes the input once
+/// all audio is written and waits for ffmpeg to finish writing the output.
+#[derive(Debug)]
+pub struct FfmpegSink {
+    child: Child,
+    sink: Option<RawPcmSink<ChildStdin>>,
+}
+
+impl FfmpegSink {
+    /// The arguments for reading 16 bit audio at `sample_rate` with `channels` interleaved channels from stdin
+    pub fn input_args(sample_rate: u32, channels: usize) -> Vec<String> {
+        vec![
+            "-f".into(),
+            RawFormat::S16Le.ffmpeg_name().into(),
+            "-ar".into(),
+            sample_rate.to_string(),
+            "-ac".into(),
+            channels.to_string(),
+            "-i".into(),
+            "-".into(),
+        ]
+    }
+
+    /// A command running ffmpeg with the input arguments for the audio, to add output arguments to before
+    /// starting it with [`spawn`](Self::spawn)
+    pub fn command(sample_rate: u32, channels: usize) -> Command {
+        let mut command = Command::new(FFMPEG_PROGRAM);
+        command
+            .args(["-hide_banner", "-loglevel", "error", "-y"])
+            .args(Self::input_args(sample_rate, channels));
+        command
+    }
+
+    /// Start ffmpeg for audio at `sample_rate` with `channels` interleaved channels, writing the output described
+    /// by `output_args`
+    pub fn spawn_output<I, S>(
+        sample_rate: u32,
+        channels: usize,
+        output_args: I,
+    ) -> Result<Self, SteamAudioError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut command = Self::command(sample_rate, channels);
+        command.args(output_args);
+        Self::spawn(command)
+    }
+
+    /// Start ffmpeg for the audio produced by `decoder`, writing the output described by `output_args`
+    ///
+    /// The sample rate is only known once the decoder decoded a payload setting it, before that this fails with
+    /// [`SteamAudioError::NoSampleRate`] unless the decoder has a fixed output rate.
+    #[cfg(feature = "decoder")]
+    pub fn for_decoder<I, S>(
+        decoder: &crate::SteamVoiceDecoder,
+        output_args: I,
+    ) -> Result<Self, SteamAudioError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let sample_rate = decoder.output_rate().ok_or(SteamAudioError::NoSampleRate)?;
+        Self::spawn_output(sample_rate, decoder.options().channels.count(), output_args)
+    }
+
+    /// Start a command reading raw 16 bit audio from its standard input, like the one from
+    /// [`command`](Self::command)
+    pub fn spawn(mut command: Command) -> Result<Self, SteamAudioError> {
+        let mut child = command.stdin(Stdio::piped()).spawn()?;
+        let stdin = child.stdin.take().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::BrokenPipe, "no stdin for the process")
+        })?;
+        Ok(FfmpegSink {
+            child,
+            sink: Some(RawPcmSink::new(stdin)),
+        })
+    }
+
+    /// The ffmpeg process
+    pub fn child(&self) -> &Child {
+        &self.child
+    }
+
+    pub fn child_mut(&mut self) -> &mut Child {
+        &mut self.child
+    }
+
+    /// Close the input of ffmpeg and wait for it to exit
+    pub fn wait(mut self) -> Result<ExitStatus, SteamAudioError> {
+        if let Some(mut sink) = self.sink.take() {
+            sink.finish()?;
+        }
+        Ok(self.child.wait()?)
+    }
+}
+
+impl PcmSink for FfmpegSink {
+    fn write_samples(&mut self, samples: &[i16]) -> Result<(), SteamAudioError> {
+        match self.sink.as_mut() {
+            Some(sink) => sink.write_samples(samples),
+            None => Ok(()),
+        }
+    }
+
+    /// Flush the audio to ffmpeg and close its input, so it finishes the output
+    fn finish(&mut self) -> Result<(), SteamAudioError> {
+        match self.sink.take() {
+            Some(mut sink) => sink.finish(),
+            None => Ok(()),
+        }
+    }
+}
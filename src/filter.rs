@@ -0,0 +1,109 @@
+//! User supplied processing of decoded audio
+
+use crate::{Agc, DcBlocker};
+use std::fmt::Debug;
+
+/// Processing applied to decoded audio in place, like gain, equalization or custom effects
+///
+/// Filters can be run by the decoder with [`SteamVoiceDecoder::set_filter`](crate::SteamVoiceDecoder::set_filter),
+/// or per speaker by a [`VoiceSession`](crate::VoiceSession) with [`set_filters`](crate::VoiceSession::set_filters).
+pub trait AudioFilter: Send {
+    /// Process interleaved samples with `channels` channels in place
+    fn process(&mut self, samples: &mut [i16], channels: usize);
+
+    /// Forget any state of previous audio, e.g. when the decoder is reset
+    fn reset(&mut self) {}
+}
+
+/// Creates the filters for a speaker, from its steam id
+pub type FilterConstructor = fn(u64) -> FilterChain;
+
+/// A sequence of filters run one after the other
+#[derive(Default)]
+pub struct FilterChain {
+    filters: Vec<Box<dyn AudioFilter>>,
+}
+
+impl Debug for FilterChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FilterChain")
+            .field("filters", &self.filters.len())
+            .finish()
+    }
+}
+
+impl FilterChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a filter to the end of the chain
+    pub fn with<F: AudioFilter + 'static>(mut self, filter: F) -> Self {
+        self.push(filter);
+        self
+    }
+
+    /// Add a filter to the end of the chain
+    pub fn push<F: AudioFilter + 'static>(&mut self, filter: F) {
+        self.filters.push(Box::new(filter));
+    }
+
+    pub fn len(&self) -> usize {
+        self.filters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+}
+
+impl AudioFilter for FilterChain {
+    fn process(&mut self, samples: &mut [i16], channels: usize) {
+        for filter in &mut self.filters {
+            filter.process(samples, channels);
+        }
+    }
+
+    fn reset(&mut self) {
+        for filter in &mut self.filters {
+            filter.reset();
+        }
+    }
+}
+
+impl<F: AudioFilter + ?Sized> AudioFilter for Box<F> {
+    fn process(&mut self, samples: &mut [i16], channels: usize) {
+        (**self).process(samples, channels);
+    }
+
+    fn reset(&mut self) {
+        (**self).reset();
+    }
+}
+
+impl AudioFilter for Agc {
+    fn process(&mut self, samples: &mut [i16], channels: usize) {
+        Agc::process(self, samples, channels);
+    }
+
+    fn reset(&mut self) {
+        Agc::reset(self);
+    }
+}
+
+impl AudioFilter for DcBlocker {
+    fn process(&mut self, samples: &mut [i16], channels: usize) {
+        DcBlocker::process(self, samples, channels);
+    }
+
+    fn reset(&mut self) {
+        DcBlocker::reset(self);
+    }
+}
+
+#[cfg(feature = "denoise-backend")]
+impl AudioFilter for crate::NoiseReducer {
+    fn process(&mut self, samples: &mut [i16], channels: usize) {
+        crate::NoiseReducer::process(self, samples, channels);
+    }
+}
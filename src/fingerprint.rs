@@ -0,0 +1,361 @@
+//! Compact acoustic fingerprints of decoded speech, for finding the same audio in different recordings
+
+use crate::LinearResampler;
+use std::f32::consts::PI;
+use std::time::Duration;
+
+/// Sample rate the audio is analyzed at
+const ANALYSIS_RATE: u32 = 8000;
+/// Number of samples of an analysis frame at [`ANALYSIS_RATE`], 128ms
+const FRAME_LENGTH: usize = 1024;
+/// Number of samples between the starts of two analysis frames at [`ANALYSIS_RATE`], 32ms
+const HOP_LENGTH: usize = 256;
+/// Number of frequency bands, the differences of 33 bands give the 32 bits of a hash
+const BANDS: usize = 33;
+/// Lowest frequency of the bands in Hz
+const LOW_FREQUENCY: f32 = 300.0;
+/// Highest frequency of the bands in Hz
+const HIGH_FREQUENCY: f32 = 2000.0;
+/// Frames with a mean square below this, -90 dBFS, are silence and don't produce a hash
+const SILENCE_POWER: f32 = 1e-9;
+
+/// Bit error rate below which two fingerprints are considered to contain the same audio
+pub const FINGERPRINT_MATCH_THRESHOLD: f32 = 0.35;
+
+/// The fingerprint of a stretch of audio, made of a 32 bit hash for every 32ms of sound
+///
+/// Identical audio gives identical fingerprints, so the fingerprints of the same speaker in the demos of
+/// different players of a match can be compared directly to deduplicate them. Audio that went through different
+/// processing still gives similar fingerprints, which can be found with [`bit_error_rate`](Self::bit_error_rate).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Fingerprint {
+    hashes: Vec<u32>,
+}
+
+impl Fingerprint {
+    pub fn from_hashes(hashes: Vec<u32>) -> Self {
+        Fingerprint { hashes }
+    }
+
+    /// Read a fingerprint from the bytes written by [`to_bytes`](Self::to_bytes)
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Fingerprint {
+            hashes: bytes
+                .chunks_exact(4)
+                .map(|hash| u32::from_le_bytes(hash.try_into().unwrap()))
+                .collect(),
+        }
+    }
+
+    pub fn hashes(&self) -> &[u32] {
+        &self.hashes
+    }
+
+    /// The hashes as little endian bytes, for storing the fingerprint
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.hashes
+            .iter()
+            .flat_map(|hash| hash.to_le_bytes())
+            .collect()
+    }
+
+    /// Duration of the sound covered by the fingerprint, without silence
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.hashes.len() as f64 * HOP_LENGTH as f64 / ANALYSIS_RATE as f64)
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// The fraction of differing bits between the fingerprints, at the best alignment of up to `max_offset`
+    /// hashes in either direction
+    ///
+    /// Only the overlapping hashes are compared, `None` is returned if the fingerprints don't overlap at any
+    /// offset. Fingerprints of the same audio have a bit error rate below [`FINGERPRINT_MATCH_THRESHOLD`],
+    /// unrelated audio has a bit error rate of around 0.5.
+    pub fn bit_error_rate(&self, other: &Fingerprint, max_offset: usize) -> Option<f32> {
+        let max_offset = max_offset as isize;
+        (-max_offset..=max_offset)
+            .filter_map(|offset| {
+                let (a, b) = if offset < 0 {
+                    (self.hashes.get(offset.unsigned_abs()..)?, &other.hashes[..])
+                } else {
+                    (&self.hashes[..], other.hashes.get(offset as usize..)?)
+                };
+                let overlap = a.len().min(b.len());
+                if overlap == 0 {
+                    return None;
+                }
+                let errors: u32 = a.iter().zip(b).map(|(a, b)| (a ^ b).count_ones()).sum();
+                Some(errors as f32 / (overlap * 32) as f32)
+            })
+            .min_by(f32::total_cmp)
+    }
+}
+
+/// Computes the [`Fingerprint`] of audio as it is decoded
+///
+/// The audio is mixed down to mono and analyzed at 8kHz in overlapping frames of 128ms, every 32ms. The energy
+/// of every frame is measured in 33 bands between 300Hz and 2kHz, covering the range where speech has most of
+/// its energy, and every bit of the hash of a frame tells whether the energy difference of two neighbouring
+/// bands grew or shrunk compared to the previous frame. Silent frames are skipped, so the gaps between the
+/// utterances of a speaker don't affect the fingerprint.
+///
+/// Use a separate fingerprinter for every speaker, e.g. with
+/// [`VoiceSession::set_fingerprinting`](crate::VoiceSession::set_fingerprinting).
+#[derive(Debug, Clone)]
+pub struct Fingerprinter {
+    sample_rate: u32,
+    channels: usize,
+    resampler: LinearResampler,
+    /// Mono input downmixed for the resampler
+    mono: Vec<i16>,
+    /// Resampled audio that hasn't been analyzed yet
+    pending: Vec<i16>,
+    window: Vec<f32>,
+    /// First and last FFT bin of every band
+    bands: Vec<(usize, usize)>,
+    real: Vec<f32>,
+    imaginary: Vec<f32>,
+    /// Band energies of the previous frame with sound
+    previous: Option<[f32; BANDS]>,
+    hashes: Vec<u32>,
+}
+
+impl Fingerprinter {
+    /// Create a fingerprinter for audio at `sample_rate` with `channels` interleaved channels
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        let window = (0..FRAME_LENGTH)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FRAME_LENGTH as f32).cos())
+            .collect();
+        let bin = |frequency: f32| {
+            (frequency * FRAME_LENGTH as f32 / ANALYSIS_RATE as f32).round() as usize
+        };
+        let step = (HIGH_FREQUENCY / LOW_FREQUENCY).powf(1.0 / BANDS as f32);
+        let bands = (0..BANDS)
+            .map(|band| {
+                let low = bin(LOW_FREQUENCY * step.powi(band as i32));
+                let high = bin(LOW_FREQUENCY * step.powi(band as i32 + 1));
+                (low, high.max(low + 1))
+            })
+            .collect();
+        Fingerprinter {
+            sample_rate,
+            channels: channels.max(1),
+            resampler: LinearResampler::new(sample_rate, ANALYSIS_RATE, 1),
+            mono: Vec::new(),
+            pending: Vec::new(),
+            window,
+            bands,
+            real: vec![0.0; FRAME_LENGTH],
+            imaginary: vec![0.0; FRAME_LENGTH],
+            previous: None,
+            hashes: Vec::new(),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Change the sample rate of the following audio, keeping the fingerprint so far
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.resampler = LinearResampler::new(sample_rate, ANALYSIS_RATE, 1);
+        }
+    }
+
+    /// Analyze the next chunk of interleaved audio
+    pub fn process(&mut self, samples: &[i16]) {
+        self.mono.clear();
+        self.mono
+            .extend(samples.chunks_exact(self.channels).map(|frame| {
+                (frame.iter().map(|sample| *sample as i32).sum::<i32>() / self.channels as i32)
+                    as i16
+            }));
+        self.resampler.process(&self.mono, &mut self.pending);
+
+        let mut start = 0;
+        while self.pending.len() - start >= FRAME_LENGTH {
+            self.analyze(start);
+            start += HOP_LENGTH;
+        }
+        self.pending.drain(..start);
+    }
+
+    /// The fingerprint of the audio so far
+    pub fn fingerprint(&self) -> Fingerprint {
+        Fingerprint::from_hashes(self.hashes.clone())
+    }
+
+    /// Finish the fingerprint, the audio of the last incomplete frame isn't included
+    pub fn into_fingerprint(self) -> Fingerprint {
+        Fingerprint::from_hashes(self.hashes)
+    }
+
+    /// Forget the audio so far
+    pub fn reset(&mut self) {
+        *self = Fingerprinter::new(self.sample_rate, self.channels);
+    }
+
+    /// Hash the frame starting at `start` in the pending audio
+    fn analyze(&mut self, start: usize) {
+        let frame = &self.pending[start..start + FRAME_LENGTH];
+        let mut power = 0.0;
+        for (((real, imaginary), sample), window) in self
+            .real
+            .iter_mut()
+            .zip(&mut self.imaginary)
+            .zip(frame)
+            .zip(&self.window)
+        {
+            let sample = *sample as f32 / 32768.0;
+            power += sample * sample;
+            *real = sample * window;
+            *imaginary = 0.0;
+        }
+        if power / (FRAME_LENGTH as f32) < SILENCE_POWER {
+            return;
+        }
+        fft(&mut self.real, &mut self.imaginary);
+
+        let mut energies = [0.0; BANDS];
+        for (energy, (low, high)) in energies.iter_mut().zip(&self.bands) {
+            *energy = (*low..*high)
+                .map(|bin| {
+                    self.real[bin] * self.real[bin] + self.imaginary[bin] * self.imaginary[bin]
+                })
+                .sum();
+        }
+        if let Some(previous) = &self.previous {
+            let mut hash = 0;
+            for band in 0..BANDS - 1 {
+                let difference =
+                    (energies[band] - energies[band + 1]) - (previous[band] - previous[band + 1]);
+                if difference > 0.0 {
+                    hash |= 1 << band;
+                }
+            }
+            self.hashes.push(hash);
+        }
+        self.previous = Some(energies);
+    }
+}
+
+/// In place radix 2 FFT, the length has to be a power of two
+pub(crate) fn fft(real: &mut [f32], imaginary: &mut [f32]) {
+    let length = real.len();
+    let mut j = 0;
+    for i in 1..length {
+        let mut bit = length >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            real.swap(i, j);
+            imaginary.swap(i, j);
+        }
+    }
+
+    let mut size = 2;
+    while size <= length {
+        let angle = -2.0 * PI / size as f32;
+        for start in (0..length).step_by(size) {
+            for k in 0..size / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let even = start + k;
+                let odd = even + size / 2;
+                let odd_real = real[odd] * cos - imaginary[odd] * sin;
+                let odd_imaginary = real[odd] * sin + imaginary[odd] * cos;
+                real[odd] = real[even] - odd_real;
+                imaginary[odd] = imaginary[even] - odd_imaginary;
+                real[even] += odd_real;
+                imaginary[even] += odd_imaginary;
+            }
+        }
+        size <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tones between 300Hz and 2kHz that change every 100ms, like the formants of speech
+    fn babble(seed: u32, sample_rate: u32, seconds: u32) -> Vec<i16> {
+        let mut state = seed;
+        let mut random = move || {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            (state >> 8) as f32 / (1 << 24) as f32
+        };
+        let segment = sample_rate as usize / 10;
+        let mut phases = [0.0f32; 3];
+        let mut samples = Vec::new();
+        for _ in 0..seconds * 10 {
+            let tones: Vec<(f32, f32)> = (0..3)
+                .map(|_| (300.0 + random() * 1700.0, 0.1 + random() * 0.2))
+                .collect();
+            for _ in 0..segment {
+                let mut sample = 0.0;
+                for ((frequency, amplitude), phase) in tones.iter().zip(&mut phases) {
+                    *phase += 2.0 * PI * frequency / sample_rate as f32;
+                    sample += amplitude * phase.sin();
+                }
+                samples.push((sample * 32767.0) as i16);
+            }
+        }
+        samples
+    }
+
+    fn fingerprint(samples: &[i16], sample_rate: u32, chunk: usize) -> Fingerprint {
+        let mut fingerprinter = Fingerprinter::new(sample_rate, 1);
+        for chunk in samples.chunks(chunk) {
+            fingerprinter.process(chunk);
+        }
+        fingerprinter.into_fingerprint()
+    }
+
+    #[test]
+    fn matches_same_audio() {
+        let audio = babble(1, 16000, 5);
+        let reference = fingerprint(&audio, 16000, 320);
+        // 5 seconds of 32ms hashes, without the first frame and the incomplete frames at the end
+        assert!(reference.len() > 140, "{}", reference.len());
+        assert_eq!(fingerprint(&audio, 16000, 1000), reference);
+        assert_eq!(Fingerprint::from_bytes(&reference.to_bytes()), reference);
+
+        // quieter audio with a pause of 16 hops in between still matches
+        let processed: Vec<i16> = audio[..16000]
+            .iter()
+            .map(|sample| sample / 2)
+            .chain(std::iter::repeat(0).take(8192))
+            .chain(audio[16000..].iter().map(|sample| sample / 2))
+            .collect();
+        let rate = reference
+            .bit_error_rate(&fingerprint(&processed, 16000, 320), 4)
+            .unwrap();
+        assert!(rate < FINGERPRINT_MATCH_THRESHOLD, "{rate}");
+
+        let other = fingerprint(&babble(2, 16000, 5), 16000, 320);
+        let rate = reference.bit_error_rate(&other, 4).unwrap();
+        assert!(rate > 0.4, "{rate}");
+    }
+
+    #[test]
+    fn skips_silence() {
+        assert!(fingerprint(&[0; 48000], 48000, 960).is_empty());
+    }
+}
@@ -0,0 +1,503 @@
+//! Lossless compression of decoded audio into FLAC files
+
+use crate::{PcmSink, SteamAudioError};
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Number of samples per channel in a FLAC frame
+const BLOCK_SIZE: usize = 4096;
+/// Highest order of the fixed predictors
+const MAX_ORDER: usize = 4;
+/// Highest rice parameter that can be coded with 4 bits, 15 is reserved as escape code
+const MAX_RICE_PARAMETER: u32 = 14;
+/// Offset of the STREAMINFO block from the start of the file, after the magic and the block header
+const STREAMINFO_OFFSET: u64 = 8;
+
+/// Collects bits into bytes, most significant bit first
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    /// Bits that don't make up a whole byte yet, in the lowest `count` bits
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u64, bits: u32) {
+        debug_assert!(bits <= 32);
+        self.bits = (self.bits << bits) | (value & ((1 << bits) - 1));
+        self.count += bits;
+        while self.count >= 8 {
+            self.count -= 8;
+            self.bytes.push((self.bits >> self.count) as u8);
+        }
+    }
+
+    fn write_signed(&mut self, value: i32, bits: u32) {
+        self.write(value as u32 as u64, bits);
+    }
+
+    fn write_unary(&mut self, zeros: u32) {
+        let mut zeros = zeros;
+        while zeros >= 32 {
+            self.write(0, 32);
+            zeros -= 32;
+        }
+        self.write(1, zeros + 1);
+    }
+
+    /// Pad to a whole byte with zero bits
+    fn align(&mut self) {
+        if self.count > 0 {
+            self.write(0, 8 - self.count);
+        }
+    }
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for &byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// The residual of the fixed predictor of `order` for the samples after the warm-up samples
+fn fixed_residual(samples: &[i32], order: usize, residual: &mut Vec<i32>) {
+    residual.clear();
+    residual.extend(samples.windows(order + 1).map(|window| {
+        let s = |back: usize| window[order - back];
+        match order {
+            0 => s(0),
+            1 => s(0) - s(1),
+            2 => s(0) - 2 * s(1) + s(2),
+            3 => s(0) - 3 * s(1) + 3 * s(2) - s(3),
+            _ => s(0) - 4 * s(1) + 6 * s(2) - 4 * s(3) + s(4),
+        }
+    }));
+}
+
+fn zigzag(value: i32) -> u32 {
+    ((value << 1) ^ (value >> 31)) as u32
+}
+
+/// The rice parameter with the smallest encoding of the residual, and the size in bits
+fn rice_parameter(residual: &[i32]) -> (u32, u64) {
+    (0..=MAX_RICE_PARAMETER)
+        .map(|parameter| {
+            let bits = residual
+                .iter()
+                .map(|value| (zigzag(*value) >> parameter) as u64 + 1 + parameter as u64)
+                .sum();
+            (parameter, bits)
+        })
+        .min_by_key(|(_, bits)| *bits)
+        .unwrap_or((0, 0))
+}
+
+/// Encode the samples of a channel with `bits` bits per sample as the smallest subframe
+fn write_subframe(output: &mut BitWriter, samples: &[i32], bits: u32, residual: &mut Vec<i32>) {
+    if samples.iter().all(|sample| *sample == samples[0]) {
+        output.write(0b0000_0000, 8);
+        output.write_signed(samples[0], bits);
+        return;
+    }
+
+    let verbatim = samples.len() as u64 * bits as u64;
+    let best = (0..=MAX_ORDER.min(samples.len().saturating_sub(1)))
+        .map(|order| {
+            fixed_residual(samples, order, residual);
+            let (parameter, size) = rice_parameter(residual);
+            (order, parameter, size + order as u64 * bits as u64 + 10)
+        })
+        .min_by_key(|(_, _, size)| *size);
+
+    match best {
+        Some((order, parameter, size)) if size < verbatim => {
+            output.write(0b0001_0000 | (order as u64) << 1, 8);
+            for sample in &samples[..order] {
+                output.write_signed(*sample, bits);
+            }
+            fixed_residual(samples, order, residual);
+            // rice coding with 4 bit parameters in a single partition
+            output.write(0, 2);
+            output.write(0, 4);
+            output.write(parameter as u64, 4);
+            for value in residual.iter() {
+                let value = zigzag(*value);
+                output.write_unary(value >> parameter);
+                output.write(value as u64, parameter);
+            }
+        }
+        _ => {
+            output.write(0b0000_0010, 8);
+            for sample in samples {
+                output.write_signed(*sample, bits);
+            }
+        }
+    }
+}
+
+/// A [`PcmSink`] compressing 16 bit audio into a FLAC file
+///
+/// The audio is encoded in blocks of 4096 samples with the fixed linear predictors of FLAC and rice coded
+/// residuals, stereo audio is coded as left and side channel, so the voice duplicated into both channels costs
+/// next to nothing for the second channel. The stream info at the start of the file is updated with the total
+/// number of samples when the sink is [finished](PcmSink::finish). The MD5 checksum of the audio is left unset.
+pub struct FlacSink<W: Write + Seek> {
+    writer: W,
+    sample_rate: u32,
+    channels: usize,
+    /// Position of the start of the file in the writer
+    start: u64,
+    /// Interleaved samples of the incomplete block
+    pending: Vec<i16>,
+    frame: u64,
+    samples: u64,
+    min_frame_size: u32,
+    max_frame_size: u32,
+    channel: Vec<i32>,
+    residual: Vec<i32>,
+}
+
+impl<W: Write + Seek> Debug for FlacSink<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FlacSink")
+            .field("sample_rate", &self.sample_rate)
+            .field("channels", &self.channels)
+            .field("samples", &self.samples)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FlacSink<BufWriter<File>> {
+    /// Create a FLAC file at `path` for audio at `sample_rate` with `channels` interleaved channels
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        sample_rate: u32,
+        channels: usize,
+    ) -> Result<Self, SteamAudioError> {
+        FlacSink::new(BufWriter::new(File::create(path)?), sample_rate, channels)
+    }
+
+    /// Create a FLAC file at `path` for the audio produced by `decoder`
+    ///
+    /// The sample rate is only known once the decoder decoded a payload setting it, before that this fails with
+    /// [`SteamAudioError::NoSampleRate`] unless the decoder has a fixed output rate.
+    #[cfg(feature = "decoder")]
+    pub fn for_decoder<P: AsRef<Path>>(
+        path: P,
+        decoder: &crate::SteamVoiceDecoder,
+    ) -> Result<Self, SteamAudioError> {
+        let sample_rate = decoder.output_rate().ok_or(SteamAudioError::NoSampleRate)?;
+        FlacSink::create(path, sample_rate, decoder.options().channels.count())
+    }
+}
+
+impl<W: Write + Seek> FlacSink<W> {
+    /// Write audio at `sample_rate` with `channels` interleaved channels to `writer`
+    ///
+    /// FLAC supports 1 to 8 channels and sample rates up to 655350Hz.
+    pub fn new(mut writer: W, sample_rate: u32, channels: usize) -> Result<Self, SteamAudioError> {
+        if !(1..=655_350).contains(&sample_rate) {
+            return Err(SteamAudioError::UnsupportedSampleRate { rate: sample_rate });
+        }
+        if !(1..=8).contains(&channels) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "FLAC supports 1 to 8 channels",
+            )
+            .into());
+        }
+        let start = writer.stream_position()?;
+        let mut sink = FlacSink {
+            writer,
+            sample_rate,
+            channels,
+            start,
+            pending: Vec::with_capacity(BLOCK_SIZE * channels),
+            frame: 0,
+            samples: 0,
+            min_frame_size: 0,
+            max_frame_size: 0,
+            channel: Vec::with_capacity(BLOCK_SIZE),
+            residual: Vec::with_capacity(BLOCK_SIZE),
+        };
+        sink.writer.write_all(b"fLaC")?;
+        // last metadata block, of type STREAMINFO
+        sink.writer.write_all(&[0x80, 0, 0, 34])?;
+        let stream_info = sink.stream_info();
+        sink.writer.write_all(&stream_info)?;
+        Ok(sink)
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Number of samples per channel written so far
+    pub fn duration(&self) -> u64 {
+        self.samples + (self.pending.len() / self.channels) as u64
+    }
+
+    /// Encode the remaining audio, update the stream info and return the underlying writer
+    pub fn finalize(mut self) -> Result<W, SteamAudioError> {
+        self.finish()?;
+        Ok(self.writer)
+    }
+
+    fn stream_info(&self) -> [u8; 34] {
+        let mut info = BitWriter::default();
+        info.write(BLOCK_SIZE as u64, 16);
+        info.write(BLOCK_SIZE as u64, 16);
+        info.write(self.min_frame_size as u64, 24);
+        info.write(self.max_frame_size as u64, 24);
+        info.write(self.sample_rate as u64, 20);
+        info.write(self.channels as u64 - 1, 3);
+        info.write(15, 5);
+        info.write(self.samples >> 32, 4);
+        info.write(self.samples & 0xFFFF_FFFF, 32);
+        // no MD5 checksum
+        for _ in 0..4 {
+            info.write(0, 32);
+        }
+        info.bytes.try_into().unwrap_or([0; 34])
+    }
+
+    /// Encode a block of interleaved samples as a frame
+    fn write_frame(&mut self, block: &[i16]) -> Result<(), SteamAudioError> {
+        let length = block.len() / self.channels;
+        let mut output = BitWriter::default();
+        output.write(0xFFF8, 16);
+        let block_code = if length == BLOCK_SIZE { 0b1100 } else { 0b0111 };
+        output.write(block_code, 4);
+        // sample rate from the stream info
+        output.write(0, 4);
+        let side = self.channels == 2;
+        output.write(
+            if side {
+                0b1000
+            } else {
+                self.channels as u64 - 1
+            },
+            4,
+        );
+        // 16 bits per sample
+        output.write(0b1000, 4);
+        write_utf8(&mut output, self.frame);
+        if block_code == 0b0111 {
+            output.write(length as u64 - 1, 16);
+        }
+        let crc = crc8(&output.bytes);
+        output.write(crc as u64, 8);
+
+        for channel in 0..self.channels {
+            self.channel.clear();
+            if side && channel == 1 {
+                // side channel, left minus right, with one bit more
+                self.channel.extend(
+                    block
+                        .chunks_exact(2)
+                        .map(|frame| frame[0] as i32 - frame[1] as i32),
+                );
+            } else {
+                self.channel.extend(
+                    block
+                        .iter()
+                        .skip(channel)
+                        .step_by(self.channels)
+                        .map(|sample| *sample as i32),
+                );
+            }
+            let bits = if side && channel == 1 { 17 } else { 16 };
+            write_subframe(&mut output, &self.channel, bits, &mut self.residual);
+        }
+        output.align();
+        let crc = crc16(&output.bytes);
+        output.write(crc as u64, 16);
+
+        self.writer.write_all(&output.bytes)?;
+        let size = output.bytes.len() as u32;
+        if self.frame == 0 || size < self.min_frame_size {
+            self.min_frame_size = size;
+        }
+        self.max_frame_size = self.max_frame_size.max(size);
+        self.frame += 1;
+        self.samples += length as u64;
+        Ok(())
+    }
+}
+
+/// Write a frame number in the extended UTF-8 coding of FLAC
+fn write_utf8(output: &mut BitWriter, value: u64) {
+    if value < 0x80 {
+        output.write(value, 8);
+        return;
+    }
+    let bits = 64 - value.leading_zeros();
+    // every continuation byte holds 6 bits, the first byte 6 - continuation bytes
+    let mut continuation = 1;
+    while bits > 6 - continuation + 6 * continuation {
+        continuation += 1;
+    }
+    let prefix = (0xFF00u64 >> (continuation + 1)) & 0xFF;
+    output.write(prefix | (value >> (6 * continuation)), 8);
+    for byte in (0..continuation).rev() {
+        output.write(0x80 | ((value >> (6 * byte)) & 0x3F), 8);
+    }
+}
+
+impl<W: Write + Seek> PcmSink for FlacSink<W> {
+    fn write_samples(&mut self, samples: &[i16]) -> Result<(), SteamAudioError> {
+        let block_samples = BLOCK_SIZE * self.channels;
+        let mut samples = samples;
+        while !samples.is_empty() {
+            let take = (block_samples - self.pending.len()).min(samples.len());
+            self.pending.extend_from_slice(&samples[..take]);
+            samples = &samples[take..];
+            if self.pending.len() == block_samples {
+                let block = std::mem::take(&mut self.pending);
+                self.write_frame(&block)?;
+                self.pending = block;
+                self.pending.clear();
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), SteamAudioError> {
+        // partial frames are dropped, the stream can only contain whole samples of every channel
+        let length = self.pending.len() / self.channels * self.channels;
+        if length > 0 {
+            let block = std::mem::take(&mut self.pending);
+            self.write_frame(&block[..length])?;
+        }
+        self.pending.clear();
+
+        let end = self.writer.stream_position()?;
+        self.writer
+            .seek(SeekFrom::Start(self.start + STREAMINFO_OFFSET))?;
+        let stream_info = self.stream_info();
+        self.writer.write_all(&stream_info)?;
+        self.writer.seek(SeekFrom::Start(end))?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Write interleaved audio at `sample_rate` with `channels` channels to a FLAC file at `path`
+pub fn write_flac<P: AsRef<Path>>(
+    path: P,
+    samples: &[i16],
+    sample_rate: u32,
+    channels: usize,
+) -> Result<(), SteamAudioError> {
+    let mut sink = FlacSink::create(path, sample_rate, channels)?;
+    sink.write_samples(samples)?;
+    sink.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use symphonia::core::audio::SampleBuffer;
+    use symphonia::core::codecs::{Decoder, DecoderOptions};
+    use symphonia::core::errors::Error;
+    use symphonia::core::formats::{FormatOptions, FormatReader};
+    use symphonia::core::io::MediaSourceStream;
+    use symphonia::default::codecs::FlacDecoder;
+    use symphonia::default::formats::FlacReader;
+
+    /// Audio exercising the predictors: a sine, noise, digital silence and full scale steps
+    fn audio(frames: usize, channels: usize) -> Vec<i16> {
+        let mut state = 1u32;
+        (0..frames * channels)
+            .map(|index| {
+                let (frame, channel) = (index / channels, index % channels);
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                match (frame / 1000 + channel) % 4 {
+                    0 => ((frame as f32 * 0.05).sin() * 20000.0) as i16,
+                    1 => (state >> 16) as i16,
+                    2 => 0,
+                    _ if frame % 2 == 0 => i16::MAX,
+                    _ => i16::MIN,
+                }
+            })
+            .collect()
+    }
+
+    /// Decode a FLAC file with symphonia, returning the sample rate, channels and interleaved samples
+    fn decode(data: Vec<u8>) -> (u32, usize, Vec<i16>) {
+        let source = MediaSourceStream::new(Box::new(Cursor::new(data)), Default::default());
+        let mut reader = FlacReader::try_new(source, &FormatOptions::default()).unwrap();
+        let params = reader.default_track().unwrap().codec_params.clone();
+        let mut decoder = FlacDecoder::try_new(&params, &DecoderOptions::default()).unwrap();
+        let mut samples = Vec::new();
+        loop {
+            let packet = match reader.next_packet() {
+                Ok(packet) => packet,
+                Err(Error::IoError(error)) if error.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    break
+                }
+                Err(error) => panic!("{error}"),
+            };
+            let decoded = decoder.decode(&packet).unwrap();
+            let mut buffer = SampleBuffer::<i16>::new(decoded.capacity() as u64, *decoded.spec());
+            buffer.copy_interleaved_ref(decoded);
+            samples.extend_from_slice(buffer.samples());
+        }
+        assert_eq!(
+            params.n_frames,
+            Some((samples.len() / params.channels.unwrap().count()) as u64)
+        );
+        (
+            params.sample_rate.unwrap(),
+            params.channels.unwrap().count(),
+            samples,
+        )
+    }
+
+    #[test]
+    fn round_trip() {
+        for channels in [1, 2] {
+            // two whole blocks and a shorter final block
+            let samples = audio(2 * BLOCK_SIZE + 1000, channels);
+            let mut sink = FlacSink::new(Cursor::new(Vec::new()), 24000, channels).unwrap();
+            // written in pieces that don't line up with the blocks
+            for chunk in samples.chunks(1234 * channels) {
+                sink.write_samples(chunk).unwrap();
+            }
+            let data = sink.finalize().unwrap().into_inner();
+            assert_eq!(decode(data), (24000, channels, samples));
+        }
+    }
+}
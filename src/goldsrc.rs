@@ -0,0 +1,116 @@
+//! Voice data of GoldSrc (Half-Life engine) games
+//!
+//! GoldSrc sends voice in `svc_voicedata` messages, containing the index of the speaking client
+//! followed by the length prefixed codec data. Unlike steam voice payloads there is no steam id,
+//! checksum or packet framing, the codec data is a sequence of fixed size frames of the codec
+//! set by the `sv_voicecodec` and `sv_voicequality` cvars.
+
+use crate::speex::{speex_frame_size, SPEEX_FRAME_SAMPLES, SPEEX_SAMPLE_RATE};
+use crate::{read_bytes, read_u16, SteamAudioError};
+use std::slice::ChunksExact;
+use std::time::Duration;
+
+/// The voice codec of a GoldSrc server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum GoldSrcCodec {
+    /// `voice_speex`, narrowband speex at the voice quality of 1 to 5
+    Speex { quality: u8 },
+    /// `voice_miles`, the Miles Sound System voice codec
+    Miles,
+}
+
+impl GoldSrcCodec {
+    /// The codec for the values of the `sv_voicecodec` and `sv_voicequality` cvars
+    ///
+    /// Fails for unknown codecs and for speex with a quality outside of 1 to 5.
+    pub fn from_cvars(codec: &str, quality: u8) -> Result<Self, SteamAudioError> {
+        let codec = match codec {
+            "voice_speex" => GoldSrcCodec::Speex { quality },
+            "voice_miles" => GoldSrcCodec::Miles,
+            _ => {
+                return Err(SteamAudioError::UnsupportedCodec {
+                    codec: codec.into(),
+                })
+            }
+        };
+        if let GoldSrcCodec::Speex { .. } = codec {
+            codec.frame_size()?;
+        }
+        Ok(codec)
+    }
+
+    /// Size of an encoded frame
+    ///
+    /// Fails for the Miles codec, as it isn't supported, and for speex with a quality outside of 1 to 5.
+    pub fn frame_size(&self) -> Result<usize, SteamAudioError> {
+        match self {
+            GoldSrcCodec::Speex { quality } => {
+                speex_frame_size(*quality).ok_or(SteamAudioError::UnsupportedCodec {
+                    codec: format!("voice_speex quality {quality}"),
+                })
+            }
+            GoldSrcCodec::Miles => Err(SteamAudioError::UnsupportedCodec {
+                codec: "voice_miles".into(),
+            }),
+        }
+    }
+
+    /// Number of samples an encoded frame decodes to
+    pub fn frame_samples(&self) -> usize {
+        SPEEX_FRAME_SAMPLES
+    }
+
+    /// Sample rate of the decoded audio
+    pub fn sample_rate(&self) -> u32 {
+        SPEEX_SAMPLE_RATE
+    }
+}
+
+/// The contents of a GoldSrc `svc_voicedata` message
+#[derive(Debug, Clone, Copy)]
+pub struct GoldSrcVoiceData<'a> {
+    /// Index of the speaking client, the entity index minus one
+    pub client: u8,
+    data: &'a [u8],
+}
+
+impl<'a> GoldSrcVoiceData<'a> {
+    /// Parse the body of an `svc_voicedata` message, without the message type
+    pub fn new(message: &'a [u8]) -> Result<Self, SteamAudioError> {
+        let ([client], data) = read_bytes(message)?;
+        let (length, data) = read_u16(data)?;
+        let data = data
+            .get(..length as usize)
+            .ok_or(SteamAudioError::InsufficientData)?;
+        Ok(GoldSrcVoiceData { client, data })
+    }
+
+    /// The encoded voice data
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Split the voice data into the encoded frames of the codec, for use with a speex decoder
+    ///
+    /// Fails with [`SteamAudioError::InsufficientData`] if the data ends with a partial frame.
+    pub fn frames(&self, codec: GoldSrcCodec) -> Result<ChunksExact<'a, u8>, SteamAudioError> {
+        let frames = self.data.chunks_exact(codec.frame_size()?);
+        if !frames.remainder().is_empty() {
+            return Err(SteamAudioError::InsufficientData);
+        }
+        Ok(frames)
+    }
+
+    /// Number of samples the voice data decodes to
+    pub fn sample_count(&self, codec: GoldSrcCodec) -> Result<usize, SteamAudioError> {
+        Ok(self.frames(codec)?.len() * codec.frame_samples())
+    }
+
+    /// Duration of the voice data
+    pub fn duration(&self, codec: GoldSrcCodec) -> Result<Duration, SteamAudioError> {
+        Ok(Duration::from_secs_f64(
+            self.sample_count(codec)? as f64 / codec.sample_rate() as f64,
+        ))
+    }
+}
@@ -0,0 +1,342 @@
+//! Reordering and smoothing the arrival of live voice payloads
+
+use crate::{
+    frames_lost, OpusFrame, OpusFrameIterator, OwnedSteamVoiceData, Packet, SteamAudioError,
+    SteamVoiceData,
+};
+use std::time::Duration;
+
+/// Default for [`JitterBuffer::target_latency`]
+pub const DEFAULT_TARGET_LATENCY: Duration = Duration::from_millis(100);
+
+/// Counters of the payloads that passed through a [`JitterBuffer`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JitterStats {
+    /// Payloads pushed into the buffer
+    pub received: u64,
+    /// Payloads released from the buffer
+    pub released: u64,
+    /// Payloads that arrived before a payload with earlier frames and were put back in order
+    pub reordered: u64,
+    /// Payloads that arrived after later frames were already released and were dropped
+    pub late: u64,
+}
+
+#[derive(Debug)]
+struct Pending {
+    /// Sequence number of the first frame, if the payload contains any frames
+    seq: Option<u16>,
+    /// Sequence number following the last frame
+    end: Option<u16>,
+    /// Number of resets before this payload, payloads after a reset sort after all payloads before it
+    epoch: u64,
+    /// Order of arrival, for payloads without frames
+    index: u64,
+    deadline: Duration,
+    payload: OwnedSteamVoiceData,
+}
+
+/// Buffers the voice payloads of a single speaker, releasing them in order after a fixed delay
+///
+/// Payloads of live streams, like SourceTV relays, can arrive out of order and at irregular intervals.
+/// Every payload is held for the [`target_latency`](Self::target_latency) after it arrives, payloads that
+/// arrive within that time are put back in the order of their frame sequence numbers. Payloads that arrive
+/// after later frames were already released are dropped, when the following payload is decoded the decoder
+/// conceals the missing frames from the gap in the sequence numbers.
+///
+/// Times are passed in as the time since an arbitrary start, e.g. the elapsed time of an [`Instant`](std::time::Instant).
+#[derive(Debug)]
+pub struct JitterBuffer {
+    target_latency: Duration,
+    pending: Vec<Pending>,
+    /// Sequence number expected for the next released frame
+    next_seq: Option<u16>,
+    /// Number of resets seen in the pushed payloads
+    epoch: u64,
+    /// Epoch of the last released payload
+    released_epoch: u64,
+    index: u64,
+    stats: JitterStats,
+}
+
+impl Default for JitterBuffer {
+    fn default() -> Self {
+        JitterBuffer::new(DEFAULT_TARGET_LATENCY)
+    }
+}
+
+impl JitterBuffer {
+    pub fn new(target_latency: Duration) -> Self {
+        JitterBuffer {
+            target_latency,
+            pending: Vec::new(),
+            next_seq: None,
+            epoch: 0,
+            released_epoch: 0,
+            index: 0,
+            stats: JitterStats::default(),
+        }
+    }
+
+    /// The delay between the arrival of a payload and its release
+    pub fn target_latency(&self) -> Duration {
+        self.target_latency
+    }
+
+    pub fn set_target_latency(&mut self, target_latency: Duration) {
+        self.target_latency = target_latency;
+    }
+
+    pub fn stats(&self) -> &JitterStats {
+        &self.stats
+    }
+
+    /// Number of payloads waiting for their release
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Add a payload that arrived at time `now`
+    ///
+    /// Fails if the packets of the payload can't be parsed, late payloads are dropped without an error
+    /// and counted in the [`stats`](Self::stats).
+    pub fn push(
+        &mut self,
+        now: Duration,
+        payload: OwnedSteamVoiceData,
+    ) -> Result<(), SteamAudioError> {
+        self.stats.received += 1;
+        let (seq, end, reset) = frame_range(&payload.as_voice_data())?;
+        if reset {
+            self.epoch += 1;
+        }
+        if let (Some(seq), Some(next_seq)) = (seq, self.next_seq) {
+            if self.epoch == self.released_epoch && frames_lost(next_seq, seq).is_none() {
+                self.stats.late += 1;
+                return Ok(());
+            }
+        }
+
+        let pending = Pending {
+            seq,
+            end,
+            epoch: self.epoch,
+            index: self.index,
+            deadline: now + self.target_latency,
+            payload,
+        };
+        self.index += 1;
+        if self
+            .pending
+            .iter()
+            .any(|other| self.order(other) > self.order(&pending))
+        {
+            self.stats.reordered += 1;
+        }
+        self.pending.push(pending);
+        Ok(())
+    }
+
+    /// Release the next payload if its delay has passed at time `now`
+    ///
+    /// The payload with the earliest frames is released once the payload that arrived first has waited
+    /// for the target latency, so a payload that was overtaken doesn't have to wait longer than the rest.
+    pub fn pop(&mut self, now: Duration) -> Option<OwnedSteamVoiceData> {
+        let deadline = self.pending.iter().map(|pending| pending.deadline).min()?;
+        if deadline > now {
+            return None;
+        }
+        let (index, _) = self
+            .pending
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, pending)| self.order(pending))?;
+        let pending = self.pending.remove(index);
+        if pending.epoch != self.released_epoch {
+            self.released_epoch = pending.epoch;
+            self.next_seq = None;
+        }
+        if pending.end.is_some() {
+            self.next_seq = pending.end;
+        }
+        self.stats.released += 1;
+        Some(pending.payload)
+    }
+
+    /// Release all payloads regardless of their delay, e.g. at the end of the stream
+    pub fn drain(&mut self) -> Vec<OwnedSteamVoiceData> {
+        let mut payloads = Vec::with_capacity(self.pending.len());
+        while let Some(payload) = self.pop(Duration::MAX) {
+            payloads.push(payload);
+        }
+        payloads
+    }
+
+    /// Forget all buffered payloads and the sequence of the released frames
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.next_seq = None;
+        self.released_epoch = self.epoch;
+    }
+
+    /// Sort key of a pending payload, its distance to the next expected frame within its epoch
+    ///
+    /// Sequence numbers restart at 0 after a reset, so payloads of later epochs count from 0.
+    fn order(&self, pending: &Pending) -> (u64, u16, u64) {
+        let base = match self.next_seq {
+            Some(next_seq) if pending.epoch == self.released_epoch => next_seq,
+            _ => 0,
+        };
+        let distance = pending
+            .seq
+            .map_or(0, |seq| frames_lost(base, seq).unwrap_or(0));
+        (pending.epoch, distance, pending.index)
+    }
+}
+
+/// The sequence numbers of the first frame and after the last frame of a payload,
+/// and whether the encoder was reset before the first frame
+fn frame_range(
+    voice_data: &SteamVoiceData,
+) -> Result<(Option<u16>, Option<u16>, bool), SteamAudioError> {
+    let mut first = None;
+    let mut end = None;
+    let mut reset = false;
+    for packet in voice_data.packets() {
+        if let Packet::OpusPlc(opus) = packet? {
+            for frame in OpusFrameIterator::new(opus.data) {
+                match frame? {
+                    OpusFrame::Reset if first.is_none() => reset = true,
+                    OpusFrame::Reset => {}
+                    OpusFrame::Frame { seq, .. } => {
+                        first.get_or_insert(seq);
+                        end = Some(seq.wrapping_add(1));
+                    }
+                }
+            }
+        }
+    }
+    Ok((first, end, reset))
+}
+
+#[cfg(feature = "decoder")]
+impl JitterBuffer {
+    /// Decode all payloads that are due at time `now`, appending the samples to `output`,
+    /// returns the number of samples appended
+    pub fn decode_ready(
+        &mut self,
+        now: Duration,
+        decoder: &mut crate::SteamVoiceDecoder,
+        output: &mut Vec<i16>,
+    ) -> Result<usize, SteamAudioError> {
+        let start = output.len();
+        while let Some(payload) = self.pop(now) {
+            decoder.decode_append(payload.as_voice_data(), output)?;
+        }
+        Ok(output.len() - start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::push_opus_packet;
+    use crate::test_data::{opus, payload, sample_rate, FRAME};
+
+    const MS: Duration = Duration::from_millis(1);
+
+    /// A payload with a frame for every sequence number, after a reset of the encoder if `reset` is set
+    fn frames(seqs: &[u16], reset: bool) -> OwnedSteamVoiceData {
+        let frames: Vec<_> = seqs.iter().map(|seq| (*seq, FRAME)).collect();
+        let mut packet = opus(&frames);
+        if reset {
+            let mut data = vec![0xFF, 0xFF];
+            data.extend_from_slice(&packet[3..]);
+            packet.clear();
+            push_opus_packet(&mut packet, &data);
+        }
+        OwnedSteamVoiceData::new(payload(&[sample_rate(24000), packet])).unwrap()
+    }
+
+    /// The sequence number of the first frame of released payloads
+    fn first_seq(payload: Option<OwnedSteamVoiceData>) -> Option<u16> {
+        frame_range(&payload?.as_voice_data()).unwrap().0
+    }
+
+    #[test]
+    fn reorders_within_latency() {
+        let mut buffer = JitterBuffer::new(100 * MS);
+        buffer.push(Duration::ZERO, frames(&[2], false)).unwrap();
+        buffer.push(10 * MS, frames(&[0], false)).unwrap();
+        buffer.push(20 * MS, frames(&[1], false)).unwrap();
+        assert_eq!(buffer.stats().reordered, 2);
+
+        assert!(buffer.pop(99 * MS).is_none());
+        // the first payload to arrive is due, the earliest frames are released first
+        assert_eq!(first_seq(buffer.pop(100 * MS)), Some(0));
+        assert_eq!(first_seq(buffer.pop(100 * MS)), Some(1));
+        assert_eq!(first_seq(buffer.pop(100 * MS)), Some(2));
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.stats().released, 3);
+    }
+
+    #[test]
+    fn drops_late_payloads() {
+        let mut buffer = JitterBuffer::new(Duration::ZERO);
+        buffer.push(Duration::ZERO, frames(&[0, 1], false)).unwrap();
+        buffer.push(Duration::ZERO, frames(&[2, 3], false)).unwrap();
+        assert_eq!(buffer.drain().len(), 2);
+
+        buffer.push(MS, frames(&[1], false)).unwrap();
+        buffer.push(MS, frames(&[3], false)).unwrap();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.stats().late, 2);
+        // a gap is left for the decoder to conceal
+        buffer.push(MS, frames(&[6], false)).unwrap();
+        assert_eq!(first_seq(buffer.pop(MS)), Some(6));
+        assert_eq!(buffer.stats().received, 5);
+    }
+
+    #[test]
+    fn reset_starts_new_epoch() {
+        let mut buffer = JitterBuffer::new(10 * MS);
+        buffer
+            .push(Duration::ZERO, frames(&[40, 41], false))
+            .unwrap();
+        assert_eq!(first_seq(buffer.pop(10 * MS)), Some(40));
+
+        // frames after a reset of the encoder restart at 0 and sort after the frames before it
+        buffer.push(11 * MS, frames(&[0], true)).unwrap();
+        buffer.push(12 * MS, frames(&[1], false)).unwrap();
+        buffer.push(12 * MS, frames(&[42], false)).unwrap();
+        let released: Vec<_> = buffer
+            .drain()
+            .into_iter()
+            .map(|payload| first_seq(Some(payload)))
+            .collect();
+        assert_eq!(released, [Some(0), Some(1), Some(42)]);
+        assert_eq!(buffer.stats().late, 0);
+
+        // after resetting the buffer, earlier frames aren't late
+        buffer.reset();
+        buffer.push(20 * MS, frames(&[0], false)).unwrap();
+        assert_eq!(buffer.len(), 1);
+    }
+
+    #[test]
+    fn releases_at_deadline() {
+        let mut buffer = JitterBuffer::new(50 * MS);
+        buffer.push(10 * MS, frames(&[0], false)).unwrap();
+        buffer.set_target_latency(20 * MS);
+        buffer.push(20 * MS, frames(&[1], false)).unwrap();
+        assert!(buffer.pop(39 * MS).is_none());
+        // once the second payload is due the earlier frames are released first, without waiting any longer
+        assert_eq!(first_seq(buffer.pop(40 * MS)), Some(0));
+        assert_eq!(first_seq(buffer.pop(40 * MS)), Some(1));
+        assert!(buffer.pop(Duration::MAX).is_none());
+    }
+}
@@ -0,0 +1,141 @@
+//! Variants of the payload framing around the voice packets
+
+use crate::{crc32, verify_crc, SteamAudioError, SteamVoiceData, HEADER_SIZE, MIN_PAYLOAD_SIZE};
+
+/// Largest trailer that [`PayloadLayout::detect`] looks for
+const MAX_DETECTED_TRAILER: usize = 16;
+
+/// The framing of a voice payload around its packets
+///
+/// Payloads as sent by steam start with the steam id of the speaker and end with the checksum of everything
+/// before it. Some captures strip the steam id, or append further fields after the checksum, which would
+/// otherwise be misread as part of the steam id or the packets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PayloadLayout {
+    /// The packets are preceded by the 8 byte steam id
+    pub steam_id: bool,
+    /// The packets are followed by the 4 byte checksum
+    pub crc: bool,
+    /// Number of bytes after the checksum that don't belong to the payload
+    pub trailer: usize,
+}
+
+impl Default for PayloadLayout {
+    fn default() -> Self {
+        PayloadLayout::STEAM
+    }
+}
+
+impl PayloadLayout {
+    /// The layout of payloads as sent by steam
+    pub const STEAM: PayloadLayout = PayloadLayout {
+        steam_id: true,
+        crc: true,
+        trailer: 0,
+    };
+
+    /// Number of bytes before the packets
+    pub fn header_size(&self) -> usize {
+        if self.steam_id {
+            HEADER_SIZE
+        } else {
+            0
+        }
+    }
+
+    /// Number of bytes after the packets
+    pub fn footer_size(&self) -> usize {
+        if self.crc {
+            4 + self.trailer
+        } else {
+            self.trailer
+        }
+    }
+
+    /// Guess the layout of a payload
+    ///
+    /// The checksum is searched for with up to 16 trailing bytes after it, preferring the shortest trailer.
+    /// Since the checksum covers the steam id when present, the steam id is detected by the packets that follow
+    /// it: the layout with a steam id is chosen when its packets are valid, otherwise the layout without one.
+    /// Returns `None` if the data is shorter than a steam id and checksum, no checksum is found or there are no
+    /// valid packets in either layout.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.len() < MIN_PAYLOAD_SIZE {
+            return None;
+        }
+        (0..=MAX_DETECTED_TRAILER.min(data.len() - 4))
+            .filter(|trailer| {
+                let end = data.len() - trailer;
+                crc32(&data[..end - 4]).to_le_bytes() == data[end - 4..end]
+            })
+            .flat_map(|trailer| {
+                [true, false].map(|steam_id| PayloadLayout {
+                    steam_id,
+                    crc: true,
+                    trailer,
+                })
+            })
+            .find(|layout| {
+                SteamVoiceData::with_layout_unchecked(data, *layout).is_ok_and(|voice_data| {
+                    !voice_data.packet_data.is_empty() && voice_data.validate().is_ok()
+                })
+            })
+    }
+
+    /// Split a payload into its steam id and packet data, verifying the checksum if the layout has one
+    pub(crate) fn split<'a>(
+        &self,
+        data: &'a [u8],
+        verify: bool,
+    ) -> Result<(u64, &'a [u8]), SteamAudioError> {
+        if data.len() < self.header_size() + self.footer_size() {
+            return Err(SteamAudioError::InsufficientData);
+        }
+        let data = &data[..data.len() - self.trailer];
+        let data = match self.crc {
+            true if verify => verify_crc(data)?,
+            true => &data[..data.len() - 4],
+            false => data,
+        };
+        match self.steam_id {
+            true => {
+                let (steam_id, packet_data) = data.split_at(HEADER_SIZE);
+                Ok((
+                    u64::from_le_bytes(steam_id.try_into().unwrap()),
+                    packet_data,
+                ))
+            }
+            false => Ok((0, data)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data::{payload, sample_rate, silence};
+
+    #[test]
+    fn detect_short_input() {
+        for length in 0..MIN_PAYLOAD_SIZE {
+            assert_eq!(
+                PayloadLayout::detect(&[1; MIN_PAYLOAD_SIZE][..length]),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn detect_trailer() {
+        let mut data = payload(&[sample_rate(24000), silence(480)]);
+        assert_eq!(PayloadLayout::detect(&data), Some(PayloadLayout::STEAM));
+        data.extend_from_slice(&[0; 3]);
+        assert_eq!(
+            PayloadLayout::detect(&data),
+            Some(PayloadLayout {
+                trailer: 3,
+                ..PayloadLayout::STEAM
+            })
+        );
+    }
+}
@@ -1,30 +1,324 @@
+pub use crate::abuse::{AbuseFlag, AbuseThresholds, VoiceAnalyzer, VoiceReport, DEFAULT_LOUD_DB};
+pub use crate::agc::{
+    Agc, DEFAULT_AGC_ATTACK, DEFAULT_AGC_GATE_DB, DEFAULT_AGC_MAX_GAIN_DB, DEFAULT_AGC_RELEASE,
+    DEFAULT_AGC_TARGET_DB,
+};
+#[cfg(feature = "audiopus")]
+pub use crate::backend::Audiopus;
+#[cfg(feature = "opus")]
+pub use crate::backend::LibOpus;
+#[cfg(feature = "opus-decoder")]
+pub use crate::backend::RustOpus;
+#[cfg(feature = "decoder")]
+pub use crate::backend::{BackendConstructor, OpusBackend};
+#[cfg(feature = "decoder")]
+pub use crate::clip::{SpeechClip, SpeechClipper};
+#[cfg(feature = "tokio")]
+pub use crate::codec::VoicePayloadCodec;
+#[cfg(all(feature = "tokio", feature = "decoder"))]
+pub use crate::codec::VoicePcmCodec;
+pub use crate::crc::{append_crc, crc32, verify_crc};
+#[cfg(feature = "cs2")]
+pub use crate::cs2::{Cs2VoiceAudio, Cs2VoiceData, Cs2VoiceFormat, VoiceMessage};
+#[cfg(feature = "csgo")]
+pub use crate::csgo::{CsgoVoiceData, CsgoVoiceFormat};
+pub use crate::dc::{DcBlocker, DEFAULT_DC_CUTOFF};
+#[cfg(feature = "decoder")]
+pub use crate::decoder::{DecodePosition, DecodeStatus, DecoderSnapshot, SteamVoiceDecoder};
+#[cfg(feature = "decoder")]
+pub use crate::decompress::{
+    decompress_voice, VoiceDecompressor, MAX_DESIRED_SAMPLE_RATE, MIN_DESIRED_SAMPLE_RATE,
+};
+#[cfg(feature = "decoder")]
+pub use crate::demux::{
+    VoiceFormatReader, VoicePacket, VoicePacketDecoder, VoiceTrack, PACKET_TIME_BASE,
+};
+#[cfg(feature = "denoise")]
+pub use crate::denoise::SpectralDenoiser;
+#[cfg(feature = "denoise-backend")]
+pub use crate::denoise::{
+    DenoiseBackend, DenoiserConstructor, NoiseReducer, DENOISE_FRAME_SAMPLES, DENOISE_SAMPLE_RATE,
+};
+pub use crate::dump::PayloadDump;
+pub use crate::engine_opus::EngineOpusData;
 pub use crate::error::SteamAudioError;
-use opus::{Channels, Decoder};
+#[cfg(feature = "decoder")]
+pub use crate::event::{DecodeEvent, ResetReason};
+pub use crate::extract::{ExtractedFrame, FrameExtractor};
+#[cfg(feature = "ffi")]
+pub use crate::ffi::SteamAudioErrorCode;
+pub use crate::ffmpeg::{FfmpegSink, FFMPEG_PROGRAM};
+pub use crate::filter::{AudioFilter, FilterChain, FilterConstructor};
+pub use crate::fingerprint::{Fingerprint, Fingerprinter, FINGERPRINT_MATCH_THRESHOLD};
+#[cfg(feature = "flac")]
+pub use crate::flac::{write_flac, FlacSink};
+#[cfg(feature = "goldsrc")]
+pub use crate::goldsrc::{GoldSrcCodec, GoldSrcVoiceData};
+pub use crate::jitter::{JitterBuffer, JitterStats, DEFAULT_TARGET_LATENCY};
+pub use crate::layout::PayloadLayout;
+pub use crate::limiter::{
+    Limiter, DEFAULT_LIMITER_KNEE_DB, DEFAULT_LIMITER_RELEASE, DEFAULT_LIMITER_THRESHOLD_DB,
+};
+pub use crate::loudness::{apply_gain, normalize_loudness, LoudnessMeter, DEFAULT_LOUDNESS_TARGET};
+#[cfg(feature = "decoder")]
+pub use crate::mix::{
+    Ducking, Mixer, DEFAULT_DUCKING_ATTACK, DEFAULT_DUCKING_DEPTH_DB, DEFAULT_DUCKING_RELEASE,
+    DEFAULT_DUCKING_THRESHOLD_DB,
+};
+#[cfg(feature = "mka")]
+pub use crate::mka::{write_mka, MkaWriter};
+#[cfg(feature = "decoder")]
+pub use crate::multitrack::MultiTrack;
+#[cfg(feature = "ogg")]
+pub use crate::ogg::{OggOpusStream, OggOpusWriter};
+#[cfg(feature = "decoder")]
+pub use crate::options::{DecoderOptions, OutputChannels, ParseMode, SteamVoiceDecoderBuilder};
+pub use crate::owned::OwnedSteamVoiceData;
+#[cfg(feature = "decoder")]
+pub use crate::pipeline::{VoiceBufferDecoder, STEAM_VOICE_MEDIA_TYPE};
+#[cfg(feature = "decoder")]
+pub use crate::playback::{VoicePlayback, VoiceSource};
+#[cfg(feature = "decoder")]
+pub use crate::pool::DecoderPool;
+pub use crate::priority::{
+    ActiveSpeaker, FirstComeFirstServed, LoudestFirst, MixDecision, Ranking, SpeakerLimit,
+    SpeakerPriority,
+};
+pub use crate::rate::{
+    decode_rate, is_legacy_rate, is_opus_rate, LinearResampler, Resampler, ResamplerConstructor,
+};
+#[cfg(feature = "decoder")]
+pub use crate::reader::PcmReader;
+pub use crate::repacketize::{OpusPacket, Repacketizer};
+#[cfg(feature = "resample")]
+pub use crate::resample::{resample, SincResampler};
+#[cfg(feature = "decoder")]
+pub use crate::ring::{RingDecode, SampleRing};
+pub use crate::rolling::{RollingStats, WindowStats};
+pub use crate::selftest::{self_test, SelfTestFailure, SelfTestReport};
+pub use crate::session::{SpeakerStream, VoiceSession};
+#[cfg(feature = "decoder")]
+pub use crate::set::VoiceDecoderSet;
+pub use crate::sink::{PcmSink, RawFormat, RawPcmSink};
+pub use crate::sniff::{sniff, DetectedFormat};
+#[cfg(feature = "decoder")]
+pub use crate::spatial::{
+    Listener, PositionSource, Spatializer, DEFAULT_MAX_DISTANCE, DEFAULT_REFERENCE_DISTANCE,
+};
+pub use crate::speaking::{SpeakingEvent, SpeakingLog, Utterance};
+pub use crate::split::{InactivitySplitter, SegmentStart};
+#[cfg(feature = "decoder")]
+pub use crate::stats::DecodeStats;
+#[cfg(feature = "async")]
+pub use crate::stream::VoiceStream;
+pub use crate::summary::PayloadSummary;
+#[cfg(feature = "symphonia")]
+pub use crate::symphonia::{SymphoniaVoiceDecoder, SymphoniaVoiceReader, CODEC_TYPE_STEAM_VOICE};
+pub use crate::talk::TalkStats;
+pub use crate::timeline::{TickTimeline, DEFAULT_MAX_JITTER};
+pub use crate::trim::{SilenceTrimmer, TimingMap, TimingMapEntry, DEFAULT_MAX_GAP};
+pub use crate::vad::{
+    EnergyVad, SpeechSegment, VadSegmenter, VoiceActivityDetector, DEFAULT_MIN_SILENCE,
+    DEFAULT_VAD_THRESHOLD_DB,
+};
+pub use crate::voice_codec::{CeltMode, VoiceCodec};
+#[cfg(feature = "wasm")]
+pub use crate::wasm::WasmVoiceDecoder;
+#[cfg(feature = "wav")]
+pub use crate::wav::{write_wav, WavSink};
 use std::fmt::Debug;
+#[cfg(feature = "steamid")]
+pub use steamid_ng::SteamID;
 
+mod abuse;
+mod agc;
+#[cfg(feature = "decoder")]
+mod backend;
+#[cfg(feature = "decoder")]
+mod clip;
+#[cfg(feature = "tokio")]
+mod codec;
+mod crc;
+#[cfg(feature = "cs2")]
+mod cs2;
+#[cfg(feature = "csgo")]
+mod csgo;
+mod dc;
+#[cfg(feature = "decoder")]
+mod decoder;
+#[cfg(feature = "decoder")]
+mod decompress;
+#[cfg(feature = "decoder")]
+mod demux;
+#[cfg(feature = "denoise-backend")]
+mod denoise;
+mod dump;
+mod engine_opus;
 mod error;
+#[cfg(feature = "decoder")]
+mod event;
+mod extract;
+#[cfg(feature = "decoder")]
+mod fade;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod ffmpeg;
+mod filter;
+mod fingerprint;
+#[cfg(feature = "flac")]
+mod flac;
+#[cfg(feature = "goldsrc")]
+mod goldsrc;
+mod jitter;
+mod layout;
+mod limiter;
+mod loudness;
+#[cfg(feature = "decoder")]
+mod mix;
+#[cfg(feature = "mka")]
+mod mka;
+#[cfg(feature = "decoder")]
+mod multitrack;
+#[cfg(feature = "ogg")]
+mod ogg;
+#[cfg(feature = "decoder")]
+mod options;
+mod owned;
+#[cfg(feature = "decoder")]
+mod pipeline;
+#[cfg(feature = "decoder")]
+mod playback;
+#[cfg(feature = "decoder")]
+mod pool;
+mod priority;
+#[cfg(feature = "csgo")]
+mod protobuf;
+mod rate;
+#[cfg(feature = "decoder")]
+mod reader;
+mod repacketize;
+#[cfg(feature = "resample")]
+mod resample;
+#[cfg(feature = "decoder")]
+mod ring;
+mod rolling;
+mod selftest;
+#[cfg(feature = "serde")]
+mod serialize;
+mod session;
+#[cfg(feature = "decoder")]
+mod set;
+mod sink;
+mod sniff;
+#[cfg(feature = "decoder")]
+mod spatial;
+mod speaking;
+mod speex;
+mod split;
+#[cfg(feature = "decoder")]
+mod stats;
+#[cfg(feature = "steamid")]
+mod steamid;
+#[cfg(feature = "async")]
+mod stream;
+mod summary;
+#[cfg(feature = "symphonia")]
+mod symphonia;
+mod talk;
+mod timeline;
+mod toc;
+mod trim;
+mod vad;
+mod voice_codec;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "wav")]
+mod wav;
 
-#[derive(Debug)]
-#[repr(u8)]
-enum PacketType {
-    Silence = 0,
-    OpusPlc = 6,
-    SampleRate = 11,
+/// The type of a packet in a voice payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub enum PacketType {
+    /// A number of samples of silence
+    Silence,
+    /// Audio encoded with the legacy voice codec
+    CodecLegacy,
+    /// Audio encoded with speex
+    CodecSpeex,
+    /// Uncompressed 16 bit samples
+    CodecRaw,
+    /// Audio encoded with silk
+    CodecSilk,
+    /// Opus frames without sequence numbers
+    CodecOpus,
+    /// Opus frames with sequence numbers for packet loss concealment
+    OpusPlc,
+    /// A value of unknown meaning
+    Marker,
+    /// The sample rate for the following packets
+    SampleRate,
+    /// A packet type that isn't known
+    Unknown(u8),
 }
 
-impl TryFrom<u8> for PacketType {
-    type Error = SteamAudioError;
+impl PacketType {
+    /// Whether the `u16` following the packet type is the length of the packet data, instead of a value
+    fn has_data(&self) -> bool {
+        !matches!(
+            self,
+            PacketType::Silence | PacketType::Marker | PacketType::SampleRate
+        )
+    }
 
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
+    /// Fail for unknown packet types, as their length can't be determined
+    fn known(self) -> Result<Self, SteamAudioError> {
+        match self {
+            PacketType::Unknown(ty) => Err(SteamAudioError::UnknownPacketType { ty }),
+            ty => Ok(ty),
+        }
+    }
+}
+
+impl From<u8> for PacketType {
+    fn from(value: u8) -> Self {
         match value {
-            0 => Ok(Self::Silence),
-            6 => Ok(Self::OpusPlc),
-            11 => Ok(Self::SampleRate),
-            _ => Err(SteamAudioError::UnknownPacketType { ty: value }),
+            0 => Self::Silence,
+            1 => Self::CodecLegacy,
+            2 => Self::CodecSpeex,
+            3 => Self::CodecRaw,
+            4 => Self::CodecSilk,
+            5 => Self::CodecOpus,
+            6 => Self::OpusPlc,
+            10 => Self::Marker,
+            11 => Self::SampleRate,
+            ty => Self::Unknown(ty),
+        }
+    }
+}
+
+impl From<PacketType> for u8 {
+    fn from(ty: PacketType) -> Self {
+        match ty {
+            PacketType::Silence => 0,
+            PacketType::CodecLegacy => 1,
+            PacketType::CodecSpeex => 2,
+            PacketType::CodecRaw => 3,
+            PacketType::CodecSilk => 4,
+            PacketType::CodecOpus => 5,
+            PacketType::OpusPlc => 6,
+            PacketType::Marker => 10,
+            PacketType::SampleRate => 11,
+            PacketType::Unknown(ty) => ty,
         }
     }
 }
 
+/// Size of the steam id that precedes the packets of a payload
+pub(crate) const HEADER_SIZE: usize = 8;
+/// Size of a payload without any packets, the steam id followed by the checksum
+pub(crate) const MIN_PAYLOAD_SIZE: usize = HEADER_SIZE + 4;
+
 fn read_bytes<const N: usize>(data: &[u8]) -> Result<([u8; N], &[u8]), SteamAudioError> {
     if data.len() < N {
         Err(SteamAudioError::InsufficientData)
@@ -39,6 +333,15 @@ fn read_u16(data: &[u8]) -> Result<(u16, &[u8]), SteamAudioError> {
     Ok((u16::from_le_bytes(bytes), data))
 }
 
+/// Append an [`OpusPlc`](PacketType::OpusPlc) packet containing the frames to the packet data
+pub(crate) fn push_opus_packet(packet_data: &mut Vec<u8>, frames: &[u8]) {
+    if !frames.is_empty() {
+        packet_data.push(PacketType::OpusPlc.into());
+        packet_data.extend_from_slice(&(frames.len() as u16).to_le_bytes());
+        packet_data.extend_from_slice(frames);
+    }
+}
+
 #[derive(Debug)]
 pub enum Packet<'a> {
     /// A number of samples of silence
@@ -47,31 +350,174 @@ pub enum Packet<'a> {
     OpusPlc(SteamOpusData<'a>),
     /// The sample rate for the opus packets
     SampleRate(u16),
+    /// Uncompressed 16 bit little endian samples at the sample rate of the voice data
+    RawPcm(&'a [u8]),
+    /// Audio encoded with a codec that isn't supported, like the legacy, silk or speex codecs
+    Unsupported { ty: PacketType, data: &'a [u8] },
+    /// A value of unknown meaning that can be ignored
+    Marker(u16),
+    /// A packet of an unknown type skipped by [`Packet::read_lenient`], `data` is empty if the
+    /// packet was judged to only contain a value
+    Unknown { ty: u8, value: u16, data: &'a [u8] },
 }
 
 impl<'a> Packet<'a> {
     pub fn read(data: &'a [u8]) -> Result<(Self, &'a [u8]), SteamAudioError> {
-        let ty = PacketType::try_from(*data.first().ok_or(SteamAudioError::InsufficientData)?)?;
+        let ty =
+            PacketType::from(*data.first().ok_or(SteamAudioError::InsufficientData)?).known()?;
         let data = &data[1..];
 
         let (next, data) = read_u16(data)?;
 
+        if !ty.has_data() {
+            return Ok(match ty {
+                PacketType::Silence => (Packet::Silence(next), data),
+                PacketType::SampleRate => (Packet::SampleRate(next), data),
+                _ => (Packet::Marker(next), data),
+            });
+        }
+
+        if data.len() < next as usize {
+            return Err(SteamAudioError::InsufficientData);
+        }
+        let (result, data) = data.split_at(next as usize);
         Ok(match ty {
-            PacketType::Silence => (Packet::Silence(next), data),
-            PacketType::OpusPlc => {
-                if data.len() < next as usize {
-                    return Err(SteamAudioError::InsufficientData);
-                } else {
-                    let (result, data) = data.split_at(next as usize);
-                    (Packet::OpusPlc(SteamOpusData { data: result }), data)
-                }
-            }
-            PacketType::SampleRate => (Packet::SampleRate(next), data),
+            PacketType::OpusPlc => (Packet::OpusPlc(SteamOpusData { data: result }), data),
+            PacketType::CodecRaw => (Packet::RawPcm(result), data),
+            ty => (Packet::Unsupported { ty, data: result }, data),
         })
     }
+
+    /// The type of the packet
+    pub fn packet_type(&self) -> PacketType {
+        match self {
+            Packet::Silence(_) => PacketType::Silence,
+            Packet::OpusPlc(_) => PacketType::OpusPlc,
+            Packet::SampleRate(_) => PacketType::SampleRate,
+            Packet::RawPcm(_) => PacketType::CodecRaw,
+            Packet::Unsupported { ty, .. } => *ty,
+            Packet::Marker(_) => PacketType::Marker,
+            Packet::Unknown { ty, .. } => PacketType::Unknown(*ty),
+        }
+    }
+
+    /// Find the offset of the next plausible packet header after a packet that failed to parse
+    ///
+    /// A header is plausible if it has a known packet type, its data fits into the payload and it is
+    /// followed by the end of the payload or another known packet type. Sample rates have to be a
+    /// common rate and opus data has to consist of complete frames.
+    /// Returns the length of `data` if no plausible header is found.
+    pub fn resync(data: &[u8]) -> usize {
+        (1..data.len())
+            .find(|&offset| plausible_packet(&data[offset..]))
+            .unwrap_or(data.len())
+    }
+
+    /// Read a packet, skipping over packets of unknown types instead of failing
+    ///
+    /// The length of an unknown packet is guessed: the `u16` following the type is taken as the
+    /// length of the packet data if that ends at the end of the payload or at a known packet type,
+    /// or if the packet can't be followed by a known packet type without data.
+    /// Otherwise the packet is assumed to only contain the value.
+    pub fn read_lenient(data: &'a [u8]) -> Result<(Self, &'a [u8]), SteamAudioError> {
+        match Self::read(data) {
+            Err(SteamAudioError::UnknownPacketType { ty }) => {
+                let (value, data) = read_u16(&data[1..])?;
+                let length = value as usize;
+                let known_at = |offset: usize| match data.get(offset) {
+                    Some(&next) => !matches!(PacketType::from(next), PacketType::Unknown(_)),
+                    None => offset == data.len(),
+                };
+                let is_length = length <= data.len() && (known_at(length) || !known_at(0));
+                let (result, data) = data.split_at(if is_length { length } else { 0 });
+                Ok((
+                    Packet::Unknown {
+                        ty,
+                        value,
+                        data: result,
+                    },
+                    data,
+                ))
+            }
+            result => result,
+        }
+    }
 }
 
-#[derive(Debug)]
+/// Whether `data` starts with a packet that is likely to be valid
+fn plausible_packet(data: &[u8]) -> bool {
+    let Ok((packet, rest)) = Packet::read(data) else {
+        return false;
+    };
+    let followed_by_packet = rest.first().map_or(true, |&next| {
+        !matches!(PacketType::from(next), PacketType::Unknown(_))
+    });
+    followed_by_packet
+        && match packet {
+            Packet::SampleRate(rate) => plausible_rate(rate),
+            Packet::OpusPlc(opus) => OpusFrameIterator::new(opus.data).all(|frame| frame.is_ok()),
+            _ => true,
+        }
+}
+
+/// Whether the sample rate is a rate that voice data is commonly recorded at
+fn plausible_rate(rate: u16) -> bool {
+    matches!(
+        rate,
+        8000 | 11025 | 12000 | 16000 | 22050 | 24000 | 32000 | 44100 | 48000
+    )
+}
+
+/// Check that the opus data of a packet consists of complete frames with valid TOC bytes
+fn validate_frames(data: &[u8]) -> Result<(), SteamAudioError> {
+    let mut frames = OpusFrameIterator::new(data);
+    for frame in frames.by_ref() {
+        if let OpusFrame::Frame { data, .. } = frame? {
+            if !data.is_empty() {
+                toc::packet_samples(data, 48000)?;
+            }
+        }
+    }
+    let trailing = frames.remaining();
+    if !trailing.is_empty() {
+        return Err(SteamAudioError::TrailingBytes {
+            count: trailing.len(),
+            offset: data.len() - trailing.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Finds the length of the first complete payload in `data`, if there is one
+///
+/// Since payloads don't contain their own length, the packets are walked until
+/// the 4 bytes following a packet match the checksum of the data up to that point.
+#[cfg(any(feature = "tokio", feature = "symphonia"))]
+pub(crate) fn payload_length(data: &[u8]) -> Result<Option<usize>, SteamAudioError> {
+    let mut position = HEADER_SIZE;
+    let mut index = 0;
+    loop {
+        if data.len() < position + 4 {
+            return Ok(None);
+        }
+        let crc = u32::from_le_bytes(data[position..position + 4].try_into().unwrap());
+        if crc == crc32(&data[..position]) {
+            return Ok(Some(position + 4));
+        }
+
+        let ty = PacketType::from(data[position])
+            .known()
+            .map_err(|e| e.in_packet(index, position))?;
+        let length = u16::from_le_bytes([data[position + 1], data[position + 2]]) as usize;
+        position += 3;
+        if ty.has_data() {
+            position += length;
+        }
+        index += 1;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
 pub struct SteamVoiceData<'a> {
     pub steam_id: u64,
     packet_data: &'a [u8],
@@ -79,16 +525,25 @@ pub struct SteamVoiceData<'a> {
 
 impl<'a> SteamVoiceData<'a> {
     pub fn new(data: &'a [u8]) -> Result<Self, SteamAudioError> {
-        let (data, crc_data) = data.split_at(data.len() - 4);
-        let expected_crc = u32::from_le_bytes(crc_data.try_into().unwrap());
-        let calculated_crc = crc32b(data);
-        if expected_crc != calculated_crc {
-            return Err(SteamAudioError::CrcMismatch {
-                actual: calculated_crc,
-                expected: expected_crc,
-            });
+        if data.len() < MIN_PAYLOAD_SIZE {
+            return Err(SteamAudioError::InsufficientData);
         }
+        Self::without_crc(verify_crc(data)?)
+    }
+
+    /// Parse a payload without verifying its checksum, for data that has already been validated
+    ///
+    /// The payload still has to end with the 4 checksum bytes, use [`without_crc`](Self::without_crc)
+    /// for payloads that don't contain a checksum.
+    pub fn new_unchecked(data: &'a [u8]) -> Result<Self, SteamAudioError> {
+        if data.len() < MIN_PAYLOAD_SIZE {
+            return Err(SteamAudioError::InsufficientData);
+        }
+        Self::without_crc(&data[..data.len() - 4])
+    }
 
+    /// Parse a payload that doesn't end with a checksum, e.g. because the checksum was truncated
+    pub fn without_crc(data: &'a [u8]) -> Result<Self, SteamAudioError> {
         let (steam_id_bytes, data) = read_bytes(data)?;
         let steam_id = u64::from_le_bytes(steam_id_bytes);
         Ok(SteamVoiceData {
@@ -97,16 +552,132 @@ impl<'a> SteamVoiceData<'a> {
         })
     }
 
+    /// Parse a payload with a different framing than the payloads sent by steam
+    ///
+    /// Voice data of payloads without a steam id has a steam id of `0`.
+    /// Use [`PayloadLayout::detect`] to guess the layout of a payload.
+    pub fn with_layout(data: &'a [u8], layout: PayloadLayout) -> Result<Self, SteamAudioError> {
+        let (steam_id, packet_data) = layout.split(data, true)?;
+        Ok(SteamVoiceData {
+            steam_id,
+            packet_data,
+        })
+    }
+
+    /// Parse a payload with a different framing without verifying its checksum
+    pub fn with_layout_unchecked(
+        data: &'a [u8],
+        layout: PayloadLayout,
+    ) -> Result<Self, SteamAudioError> {
+        let (steam_id, packet_data) = layout.split(data, false)?;
+        Ok(SteamVoiceData {
+            steam_id,
+            packet_data,
+        })
+    }
+
+    /// Copy the packet data, so the voice data can outlive the buffer it was parsed from
+    pub fn into_owned(self) -> OwnedSteamVoiceData {
+        OwnedSteamVoiceData::from_parts(self.steam_id, self.packet_data.to_vec())
+    }
+
+    /// Render the packets and opus frames as a human readable tree, for debugging
+    pub fn dump(&self) -> PayloadDump<'a> {
+        PayloadDump::new(*self)
+    }
+
+    /// Check the structure of all packets and opus frames without decoding them
+    ///
+    /// The checksum is verified when the voice data is created with [`new`](Self::new), this checks that every
+    /// packet has a known type and fits in the payload, that the opus packets consist of complete frames without
+    /// trailing bytes and that every opus frame has a valid TOC byte.
+    pub fn validate(&self) -> Result<(), SteamAudioError> {
+        let mut data = self.packet_data;
+        let mut index = 0;
+        while !data.is_empty() {
+            let offset = HEADER_SIZE + self.packet_data.len() - data.len();
+            let (packet, rest) = Packet::read(data).map_err(|e| e.in_packet(index, offset))?;
+            if let Packet::OpusPlc(opus) = packet {
+                validate_frames(opus.data).map_err(|e| e.in_packet(index, offset))?;
+            }
+            data = rest;
+            index += 1;
+        }
+        Ok(())
+    }
+
+    /// Number of samples the voice data decodes to, without decoding it
+    ///
+    /// This is the output length of a decoder with default options that hasn't lost any frames:
+    /// mono samples at the [`decode_rate`] of the voice data, with concealment of lost frames not included.
+    /// Audio that the decoder resamples, like uncompressed samples at a legacy rate, can come out a few samples
+    /// shorter.
+    /// Fails with [`SteamAudioError::NoSampleRate`] if the payload contains opus frames before its sample rate,
+    /// and with [`SteamAudioError::UnsupportedSampleRate`] if the sample rate can't be decoded.
+    pub fn sample_count_hint(&self) -> Result<usize, SteamAudioError> {
+        self.count_samples(None)
+    }
+
+    /// Number of samples per channel the voice data decodes to at a fixed `output_rate`, without decoding it
+    ///
+    /// Like [`sample_count_hint`](Self::sample_count_hint), for a decoder with the output rate set in its options.
+    pub fn sample_count_hint_at(&self, output_rate: u32) -> Result<usize, SteamAudioError> {
+        self.count_samples(Some(output_rate))
+    }
+
+    fn count_samples(&self, output_rate: Option<u32>) -> Result<usize, SteamAudioError> {
+        let timing = summary::PacketClock::default().advance(self, output_rate, Some(0), false)?;
+        Ok(timing.samples)
+    }
+
+    /// Count the packets, opus frames and samples of the voice data without decoding it
+    ///
+    /// Fails with [`SteamAudioError::NoSampleRate`] if the payload contains opus frames before its sample rate.
+    pub fn summary(&self) -> Result<PayloadSummary, SteamAudioError> {
+        PayloadSummary::new(self)
+    }
+
     /// Get the voice
     pub fn packets(&self) -> impl Iterator<Item = Result<Packet<'a>, SteamAudioError>> {
         SteamPacketIterator {
             data: self.packet_data,
+            offset: HEADER_SIZE,
+            index: 0,
+            lenient: false,
+        }
+    }
+
+    /// Get the voice packets, yielding packets of unknown types as [`Packet::Unknown`] instead of failing,
+    /// see [`Packet::read_lenient`]
+    pub fn packets_lenient(&self) -> impl Iterator<Item = Result<Packet<'a>, SteamAudioError>> {
+        SteamPacketIterator {
+            data: self.packet_data,
+            offset: HEADER_SIZE,
+            index: 0,
+            lenient: true,
+        }
+    }
+
+    /// Get the voice packets, skipping ahead to the next plausible packet after a malformed packet
+    ///
+    /// Each malformed packet is yielded as an error, after which parsing resumes at the offset found
+    /// by [`Packet::resync`]. Packets of unknown types are read as with [`packets_lenient`](Self::packets_lenient).
+    pub fn packets_resync(&self) -> ResyncPackets<'a> {
+        ResyncPackets {
+            data: self.packet_data,
+            offset: HEADER_SIZE,
+            index: 0,
+            skipped: 0,
         }
     }
 }
 
 struct SteamPacketIterator<'a> {
     data: &'a [u8],
+    /// Offset of `data` in the payload
+    offset: usize,
+    index: usize,
+    lenient: bool,
 }
 
 impl Debug for SteamPacketIterator<'_> {
@@ -124,34 +695,72 @@ impl<'a> Iterator for SteamPacketIterator<'a> {
         if self.data.is_empty() {
             None
         } else {
-            match Packet::read(self.data) {
+            let packet = if self.lenient {
+                Packet::read_lenient(self.data)
+            } else {
+                Packet::read(self.data)
+            };
+            match packet {
                 Ok((packet, rest)) => {
+                    self.offset += self.data.len() - rest.len();
+                    self.index += 1;
                     self.data = rest;
                     Some(Ok(packet))
                 }
-                Err(e) => Some(Err(e)),
+                Err(e) => {
+                    self.data = &[];
+                    Some(Err(e.in_packet(self.index, self.offset)))
+                }
             }
         }
     }
 }
 
-fn crc32b(data: &[u8]) -> u32 {
-    let mut crc: u32 = 0xFFFFFFFF;
-    for &byte in data {
-        crc ^= byte as u32;
-        for _ in 0..8 {
-            let mask = (-((crc & 1) as i32)) as u32;
-            crc = (crc >> 1) ^ (0xEDB88320 & mask);
-        }
+/// Iterator over the packets of a payload that resumes after malformed packets, see [`SteamVoiceData::packets_resync`]
+pub struct ResyncPackets<'a> {
+    data: &'a [u8],
+    /// Offset of `data` in the payload
+    offset: usize,
+    index: usize,
+    skipped: usize,
+}
+
+impl ResyncPackets<'_> {
+    /// Total number of bytes skipped to resynchronize so far
+    pub fn skipped(&self) -> usize {
+        self.skipped
     }
-    !crc
 }
 
-#[derive(Default)]
-pub struct SteamVoiceDecoder {
-    decoder: Option<Decoder>,
-    sample_rate: u16,
-    seq: u16,
+impl Debug for ResyncPackets<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResyncPackets")
+            .field("data_length", &self.data.len())
+            .field("skipped", &self.skipped)
+            .finish()
+    }
+}
+
+impl<'a> Iterator for ResyncPackets<'a> {
+    type Item = Result<Packet<'a>, SteamAudioError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let (result, length) = match Packet::read_lenient(self.data) {
+            Ok((packet, rest)) => (Ok(packet), self.data.len() - rest.len()),
+            Err(e) => {
+                let skip = Packet::resync(self.data);
+                self.skipped += skip;
+                (Err(e.in_packet(self.index, self.offset)), skip)
+            }
+        };
+        self.data = &self.data[length..];
+        self.offset += length;
+        self.index += 1;
+        Some(result)
+    }
 }
 
 pub struct SteamOpusData<'a> {
@@ -166,90 +775,281 @@ impl Debug for SteamOpusData<'_> {
     }
 }
 
-impl SteamVoiceDecoder {
-    pub fn new() -> Self {
-        Self::default()
+/// A single entry in the frame stream of an [`Packet::OpusPlc`] packet
+#[derive(Debug)]
+pub(crate) enum OpusFrame<'a> {
+    /// The encoder was reset, sequence numbers restart at 0
+    Reset,
+    /// An opus frame with its sequence number
+    Frame { seq: u16, data: &'a [u8] },
+}
+
+/// Default maximum number of lost frames that are concealed, one second of 20ms frames
+pub const DEFAULT_MAX_CONCEALMENT: u16 = 50;
+
+/// Number of frames lost between the `expected` sequence number and a received `seq`,
+/// using serial number arithmetic so the sequence can wrap around from 65535 to 0
+///
+/// Returns `None` if `seq` lies before `expected`, i.e. the frame arrived out of order.
+pub(crate) fn frames_lost(expected: u16, seq: u16) -> Option<u16> {
+    let distance = seq.wrapping_sub(expected);
+    (distance < 0x8000).then_some(distance)
+}
+
+pub(crate) struct OpusFrameIterator<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> OpusFrameIterator<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        OpusFrameIterator { data }
     }
 
-    pub fn decode(
-        &mut self,
-        voice_data: SteamVoiceData,
-        output_buffer: &mut [i16],
-    ) -> Result<usize, SteamAudioError> {
-        let mut total = 0;
-        for packet in voice_data.packets() {
-            let packet = packet?;
-            match packet {
-                Packet::SampleRate(rate) => {
-                    if self.sample_rate != rate {
-                        self.decoder = Some(Decoder::new(rate as u32, Channels::Mono)?);
-                        self.sample_rate = rate;
-                    }
-                }
-                Packet::OpusPlc(opus) => {
-                    let count = self.decode_opus(opus.data, &mut output_buffer[total..])?;
-                    total += count;
-                    if total >= output_buffer.len() {
-                        return Err(SteamAudioError::InsufficientOutputBuffer);
-                    }
-                }
-                Packet::Silence(silence) => {
-                    total += silence as usize;
-                }
-            }
+    /// The data that hasn't been read yet
+    pub(crate) fn remaining(&self) -> &'a [u8] {
+        self.data
+    }
+
+    fn read_frame(&mut self) -> Result<OpusFrame<'a>, SteamAudioError> {
+        let (len, data) = read_u16(self.data)?;
+        if len == u16::MAX {
+            self.data = data;
+            return Ok(OpusFrame::Reset);
+        }
+        let (seq, data) = read_u16(data)?;
+        let len = len as usize;
+
+        if data.len() < len {
+            return Err(SteamAudioError::InsufficientData);
         }
-        Ok(total)
+        let (frame, rest) = data.split_at(len);
+        self.data = rest;
+        Ok(OpusFrame::Frame { seq, data: frame })
     }
+}
 
-    fn decode_opus(
-        &mut self,
-        mut data: &[u8],
-        output_buffer: &mut [i16],
-    ) -> Result<usize, SteamAudioError> {
-        let mut total = 0;
-        let Some(decoder) = self.decoder.as_mut() else {
-            return Err(SteamAudioError::NoSampleRate);
-        };
+impl<'a> Iterator for OpusFrameIterator<'a> {
+    type Item = Result<OpusFrame<'a>, SteamAudioError>;
 
-        while data.len() > 2 {
-            let (len, remainder) = read_u16(data)?;
-            data = remainder;
-            if len == u16::MAX {
-                decoder.reset_state()?;
-                self.seq = 0;
-                continue;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.len() > 2 {
+            let frame = self.read_frame();
+            if frame.is_err() {
+                self.data = &[];
             }
-            let (seq, remainder) = read_u16(data)?;
-            data = remainder;
+            Some(frame)
+        } else {
+            None
+        }
+    }
+}
 
-            if seq < self.seq {
-                decoder.reset_state()?;
-            } else {
-                let lost = seq - self.seq;
-                for _ in 0..lost {
-                    let count = decoder.decode(&[], &mut output_buffer[total..], false)?;
-                    total += count;
-                    if total >= output_buffer.len() {
-                        return Err(SteamAudioError::InsufficientOutputBuffer);
-                    }
-                }
-            }
-            let len = len as usize;
+/// Voice data for the tests, not every combination of features uses all of it
+#[cfg(test)]
+#[allow(dead_code)]
+pub(crate) mod test_data {
+    use crate::{append_crc, push_opus_packet, PacketType};
+
+    /// 20ms CELT frame that decodes to digital silence
+    pub const FRAME: &[u8] = &[0xF8, 0xFF, 0xFE];
+
+    /// A payload of steam id 1 containing `packets`, with a valid checksum
+    pub fn payload(packets: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = 1u64.to_le_bytes().to_vec();
+        for packet in packets {
+            data.extend_from_slice(packet);
+        }
+        append_crc(&mut data);
+        data
+    }
+
+    pub fn sample_rate(rate: u16) -> Vec<u8> {
+        value_packet(PacketType::SampleRate, rate)
+    }
+
+    pub fn silence(samples: u16) -> Vec<u8> {
+        value_packet(PacketType::Silence, samples)
+    }
 
-            self.seq = seq + 1;
+    /// An opus packet with the frames and their sequence numbers
+    pub fn opus(frames: &[(u16, &[u8])]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (seq, frame) in frames {
+            data.extend_from_slice(&(frame.len() as u16).to_le_bytes());
+            data.extend_from_slice(&seq.to_le_bytes());
+            data.extend_from_slice(frame);
+        }
+        let mut packet = Vec::new();
+        push_opus_packet(&mut packet, &data);
+        packet
+    }
+
+    /// An uncompressed packet with the samples
+    pub fn raw(samples: &[i16]) -> Vec<u8> {
+        let mut packet = vec![PacketType::CodecRaw.into()];
+        packet.extend_from_slice(&(samples.len() as u16 * 2).to_le_bytes());
+        for sample in samples {
+            packet.extend_from_slice(&sample.to_le_bytes());
+        }
+        packet
+    }
 
-            if data.len() < len {
-                return Err(SteamAudioError::InsufficientData);
+    fn value_packet(ty: PacketType, value: u16) -> Vec<u8> {
+        let mut packet = vec![ty.into()];
+        packet.extend_from_slice(&value.to_le_bytes());
+        packet
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_lost_in_order() {
+        assert_eq!(frames_lost(0, 0), Some(0));
+        assert_eq!(frames_lost(10, 13), Some(3));
+        assert_eq!(frames_lost(0, 0x7FFF), Some(0x7FFF));
+    }
+
+    #[test]
+    fn frames_lost_wraparound() {
+        assert_eq!(frames_lost(65535, 65535), Some(0));
+        assert_eq!(frames_lost(65535, 0), Some(1));
+        assert_eq!(frames_lost(0, 0), Some(0));
+        assert_eq!(frames_lost(65534, 1), Some(3));
+        assert_eq!(frames_lost(0x8001, 0), Some(0x7FFF));
+    }
+
+    #[test]
+    fn frames_lost_out_of_order() {
+        // duplicate of the previous frame
+        assert_eq!(frames_lost(6, 5), None);
+        // reordered across the wraparound
+        assert_eq!(frames_lost(1, 65535), None);
+        // half the sequence space away is taken as behind
+        assert_eq!(frames_lost(0x8000, 0), None);
+        assert_eq!(frames_lost(0, 0x8000), None);
+    }
+
+    #[test]
+    fn short_payloads() {
+        for length in [0, 11] {
+            let data = vec![0; length];
+            assert!(matches!(
+                SteamVoiceData::new(&data),
+                Err(SteamAudioError::InsufficientData)
+            ));
+            assert!(matches!(
+                SteamVoiceData::new_unchecked(&data),
+                Err(SteamAudioError::InsufficientData)
+            ));
+        }
+
+        let data = test_data::payload(&[]);
+        assert_eq!(data.len(), MIN_PAYLOAD_SIZE);
+        let voice_data = SteamVoiceData::new(&data).unwrap();
+        assert_eq!(voice_data.steam_id, 1);
+        assert_eq!(voice_data.packets().count(), 0);
+        let voice_data = SteamVoiceData::new_unchecked(&data).unwrap();
+        assert_eq!(voice_data.packets().count(), 0);
+    }
+
+    #[test]
+    fn lenient_skips_unknown_types() {
+        let payload = test_data::payload(&[
+            test_data::sample_rate(24000),
+            // followed by a known type after 3 bytes, so the value is the length
+            vec![7, 3, 0, 1, 2, 3],
+            test_data::silence(480),
+            // too long to be a length, only a value
+            vec![9, 0xE8, 0x03],
+            test_data::silence(240),
+        ]);
+        let voice_data = SteamVoiceData::new(&payload).unwrap();
+
+        let mut strict = voice_data.packets();
+        assert!(matches!(strict.next(), Some(Ok(Packet::SampleRate(24000)))));
+        assert!(matches!(
+            strict.next(),
+            Some(Err(SteamAudioError::InPacket {
+                index: 1,
+                offset: 11,
+                ..
+            }))
+        ));
+        assert!(strict.next().is_none());
+
+        let packets: Vec<_> = voice_data
+            .packets_lenient()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(packets.len(), 5);
+        assert!(matches!(
+            packets[1],
+            Packet::Unknown {
+                ty: 7,
+                value: 3,
+                data: [1, 2, 3]
             }
+        ));
+        assert!(matches!(packets[2], Packet::Silence(480)));
+        assert!(matches!(
+            packets[3],
+            Packet::Unknown {
+                ty: 9,
+                value: 1000,
+                data: []
+            }
+        ));
+        assert!(matches!(packets[4], Packet::Silence(240)));
+        assert_eq!(packets[1].packet_type(), PacketType::Unknown(7));
+    }
+
+    #[test]
+    fn resync_after_corrupted_packet() {
+        let payload = test_data::payload(&[
+            test_data::sample_rate(24000),
+            // opus packet claiming more data than the payload has
+            vec![PacketType::OpusPlc.into(), 0xFF, 0xFF],
+            test_data::silence(480),
+            test_data::opus(&[(0, test_data::FRAME)]),
+        ]);
+        let voice_data = SteamVoiceData::new(&payload).unwrap();
 
-            let count = decoder.decode(&data[0..len], &mut output_buffer[total..], false)?;
-            data = &data[len..];
-            total += count;
-            if total >= output_buffer.len() {
-                return Err(SteamAudioError::InsufficientOutputBuffer);
+        let mut packets = voice_data.packets_resync();
+        assert!(matches!(
+            packets.next(),
+            Some(Ok(Packet::SampleRate(24000)))
+        ));
+        let error = packets.next().unwrap().unwrap_err();
+        assert!(matches!(
+            error,
+            SteamAudioError::InPacket {
+                index: 1,
+                offset: 11,
+                ..
             }
-        }
+        ));
+        assert!(matches!(error.root(), SteamAudioError::InsufficientData));
+        assert_eq!(packets.skipped(), 3);
+        assert!(matches!(packets.next(), Some(Ok(Packet::Silence(480)))));
+        assert!(matches!(packets.next(), Some(Ok(Packet::OpusPlc(_)))));
+        assert!(packets.next().is_none());
+        assert_eq!(packets.skipped(), 3);
+    }
 
-        Ok(total)
+    #[test]
+    fn resync_offsets() {
+        // skips the unknown type bytes to the silence packet followed by a sample rate
+        assert_eq!(
+            Packet::resync(&[0xFF, 0xFE, 0, 0xE0, 0x01, 11, 0xC0, 0x5D]),
+            2
+        );
+        // a sample rate that isn't a common rate isn't a plausible header
+        assert_eq!(Packet::resync(&[0xFF, 11, 0x34, 0x12, 0]), 5);
+        // opus data has to consist of complete frames
+        assert_eq!(Packet::resync(&[0xFF, 6, 3, 0, 20, 0xFF, 0xFF]), 7);
+        assert_eq!(Packet::resync(&[]), 0);
     }
 }
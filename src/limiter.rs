@@ -0,0 +1,179 @@
+//! Keeping the level of mixed audio below full scale
+
+use std::time::Duration;
+
+/// Default for [`Limiter::threshold_db`]
+pub const DEFAULT_LIMITER_THRESHOLD_DB: f32 = -1.0;
+/// Default for [`Limiter::knee_db`]
+pub const DEFAULT_LIMITER_KNEE_DB: f32 = 6.0;
+/// Default for [`Limiter::release`]
+pub const DEFAULT_LIMITER_RELEASE: Duration = Duration::from_millis(50);
+
+/// Soft-knee peak limiter for audio that can exceed the range of 16 bit samples, like the sum of a [`Mixer`](crate::Mixer)
+///
+/// Peaks above the [`threshold_db`](Self::threshold_db) are turned down to the threshold instantly, the gain
+/// recovers over the [`release`](Self::release) time once the peaks are gone. Within the knee around the threshold
+/// the gain reduction sets in gradually, so the limiting is less audible than hard clipping.
+/// All channels are turned down together, keeping the stereo image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limiter {
+    sample_rate: u32,
+    threshold_db: f32,
+    knee_db: f32,
+    release: Duration,
+    /// Factor the distance of the gain to its target is multiplied with every sample while releasing
+    release_coefficient: f32,
+    gain: f32,
+}
+
+impl Limiter {
+    /// Create a limiter for audio at `sample_rate` with the default threshold, knee and release
+    pub fn new(sample_rate: u32) -> Self {
+        Limiter {
+            sample_rate,
+            threshold_db: DEFAULT_LIMITER_THRESHOLD_DB,
+            knee_db: DEFAULT_LIMITER_KNEE_DB,
+            release: DEFAULT_LIMITER_RELEASE,
+            release_coefficient: release_coefficient(DEFAULT_LIMITER_RELEASE, sample_rate),
+            gain: 1.0,
+        }
+    }
+
+    /// Set the level in dBFS that peaks are limited to
+    pub fn with_threshold_db(mut self, threshold_db: f32) -> Self {
+        self.threshold_db = threshold_db;
+        self
+    }
+
+    /// Set the width in dB of the range around the threshold in which the limiting sets in
+    pub fn with_knee_db(mut self, knee_db: f32) -> Self {
+        self.knee_db = knee_db.max(0.0);
+        self
+    }
+
+    /// Set the time the gain takes to recover after a peak
+    pub fn with_release(mut self, release: Duration) -> Self {
+        self.release = release;
+        self.release_coefficient = release_coefficient(release, self.sample_rate);
+        self
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn threshold_db(&self) -> f32 {
+        self.threshold_db
+    }
+
+    pub fn knee_db(&self) -> f32 {
+        self.knee_db
+    }
+
+    pub fn release(&self) -> Duration {
+        self.release
+    }
+
+    /// The current gain reduction in dB, 0 when the audio isn't limited
+    pub fn reduction_db(&self) -> f32 {
+        20.0 * self.gain.log10()
+    }
+
+    /// The static gain change in dB for a peak at `level_db`
+    pub fn gain_db(&self, level_db: f32) -> f32 {
+        let over = level_db - self.threshold_db;
+        if 2.0 * over < -self.knee_db {
+            0.0
+        } else if 2.0 * over <= self.knee_db {
+            let knee = over + self.knee_db / 2.0;
+            -(knee * knee) / (2.0 * self.knee_db)
+        } else {
+            -over
+        }
+    }
+
+    /// Limit interleaved samples with `channels` channels, appending them to `output` as 16 bit samples
+    pub fn process(&mut self, samples: &[i32], channels: usize, output: &mut Vec<i16>) {
+        let channels = channels.max(1);
+        output.reserve(samples.len());
+        for frame in samples.chunks(channels) {
+            let peak = frame
+                .iter()
+                .map(|sample| sample.unsigned_abs())
+                .max()
+                .unwrap_or_default();
+            let target = match peak {
+                0 => 1.0,
+                peak => {
+                    let level_db = 20.0 * (peak as f32 / 32768.0).log10();
+                    10f32.powf(self.gain_db(level_db) / 20.0)
+                }
+            };
+            self.gain = if target < self.gain {
+                target
+            } else {
+                target + (self.gain - target) * self.release_coefficient
+            };
+            output.extend(frame.iter().map(|sample| {
+                (*sample as f32 * self.gain).clamp(i16::MIN as f32, i16::MAX as f32) as i16
+            }));
+        }
+    }
+
+    /// Forget the gain reduction of previous audio
+    pub fn reset(&mut self) {
+        self.gain = 1.0;
+    }
+}
+
+fn release_coefficient(release: Duration, sample_rate: u32) -> f32 {
+    let samples = release.as_secs_f32() * sample_rate as f32;
+    if samples > 0.0 {
+        (-1.0 / samples).exp()
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn soft_knee() {
+        let limiter = Limiter::new(48000)
+            .with_threshold_db(-6.0)
+            .with_knee_db(6.0);
+        // untouched below the knee, limited to the threshold above it
+        assert_eq!(limiter.gain_db(-20.0), 0.0);
+        assert_eq!(limiter.gain_db(-9.0), 0.0);
+        assert_eq!(limiter.gain_db(0.0), -6.0);
+        // within the knee the reduction rises gradually, reaching a quarter of the knee at the threshold
+        assert_eq!(limiter.gain_db(-6.0), -0.75);
+        assert!(limiter.gain_db(-7.5) > -0.75 && limiter.gain_db(-7.5) < 0.0);
+        assert!((limiter.gain_db(-3.0) + 3.0).abs() < 1e-5);
+
+        let hard = limiter.with_knee_db(0.0);
+        assert_eq!(hard.gain_db(-6.5), 0.0);
+        assert_eq!(hard.gain_db(-3.0), -3.0);
+    }
+
+    #[test]
+    fn limits_peaks_and_releases() {
+        let mut limiter = Limiter::new(48000);
+        let mut output = Vec::new();
+        limiter.process(&[60000, -60000], 2, &mut output);
+        // both channels are turned down together, to the threshold of -1dBFS
+        assert_eq!(output.len(), 2);
+        assert!((29000..=29300).contains(&output[0]));
+        assert_eq!(output[1], -output[0]);
+
+        output.clear();
+        limiter.process(&[1000; 24000], 1, &mut output);
+        // the gain recovers within a few times the release time
+        assert!(output[0] < 600);
+        assert!(output[2400] > output[0] && output[2400] < 900);
+        assert!(output[23999] >= 999);
+        assert!(limiter.reduction_db() > -0.01);
+    }
+}
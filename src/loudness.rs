@@ -0,0 +1,209 @@
+//! Measuring and normalizing the loudness of decoded audio
+
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+/// Default loudness target of [`normalize_loudness`], in LUFS
+pub const DEFAULT_LOUDNESS_TARGET: f64 = -16.0;
+/// Blocks quieter than this don't count towards the integrated loudness, in LUFS
+const ABSOLUTE_GATE: f64 = -70.0;
+/// Blocks more than this below the loudness of the blocks above the absolute gate don't count, in LU
+const RELATIVE_GATE: f64 = -10.0;
+
+/// Second order IIR filter in direct form 1
+#[derive(Debug, Clone, Copy, Default)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    /// Create a filter from the coefficients, normalized by `a0`
+    fn new(b: [f64; 3], a: [f64; 3]) -> Self {
+        Biquad {
+            b: [b[0] / a[0], b[1] / a[0], b[2] / a[0]],
+            a: [a[1] / a[0], a[2] / a[0]],
+            ..Biquad::default()
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// The two stage K-weighting filter of ITU-R BS.1770 at `sample_rate`
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let rate = sample_rate as f64;
+
+    // high shelf modelling the acoustic effect of the head
+    let k = (PI * 1_681.974_450_955_533 / rate).tan();
+    let q = 0.707_175_236_955_419_6;
+    let vh = 10f64.powf(3.999_843_853_973_347 / 20.0);
+    let vb = vh.powf(0.499_666_774_154_541_6);
+    let shelf = Biquad::new(
+        [
+            vh + vb * k / q + k * k,
+            2.0 * (k * k - vh),
+            vh - vb * k / q + k * k,
+        ],
+        [
+            1.0 + k / q + k * k,
+            2.0 * (k * k - 1.0),
+            1.0 - k / q + k * k,
+        ],
+    );
+
+    // high pass removing the lowest frequencies
+    let k = (PI * 38.135_470_876_024_44 / rate).tan();
+    let q = 0.500_327_037_323_877_3;
+    let high_pass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [
+            1.0 + k / q + k * k,
+            2.0 * (k * k - 1.0),
+            1.0 - k / q + k * k,
+        ],
+    );
+
+    [shelf, high_pass]
+}
+
+/// Measures the integrated loudness of audio as specified by ITU-R BS.1770 and EBU R 128
+///
+/// The audio is K-weighted and its power is measured in blocks of 400ms overlapping by 75%. Blocks below
+/// -70 LUFS, and blocks more than 10 LU below the loudness of the remaining blocks, are ignored, so the
+/// pauses in speech don't lower the measurement. All channels are weighted equally.
+///
+/// The meter can measure the audio of a single speaker, e.g. a [`SpeakerStream`](crate::SpeakerStream), or a final mix.
+#[derive(Debug, Clone)]
+pub struct LoudnessMeter {
+    sample_rate: u32,
+    channels: usize,
+    /// K-weighting filters of every channel
+    filters: Vec<[Biquad; 2]>,
+    /// Sum of the squares of the weighted samples of the current 100ms step
+    step_sum: f64,
+    step_samples: usize,
+    step_length: usize,
+    /// Sums of the last complete steps, a block is made of 4 steps
+    steps: VecDeque<f64>,
+    /// Mean square of every complete block
+    blocks: Vec<f64>,
+}
+
+impl LoudnessMeter {
+    /// Create a meter for audio at `sample_rate` with `channels` interleaved channels
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        let channels = channels.max(1);
+        LoudnessMeter {
+            sample_rate,
+            channels,
+            filters: vec![k_weighting(sample_rate); channels],
+            step_sum: 0.0,
+            step_samples: 0,
+            step_length: (sample_rate as usize / 10).max(1),
+            steps: VecDeque::with_capacity(4),
+            blocks: Vec::new(),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Measure the next chunk of interleaved audio
+    pub fn process(&mut self, samples: &[i16]) {
+        for frame in samples.chunks_exact(self.channels) {
+            for (sample, filters) in frame.iter().zip(&mut self.filters) {
+                let sample = *sample as f64 / 32768.0;
+                let shelved = filters[0].process(sample);
+                let weighted = filters[1].process(shelved);
+                self.step_sum += weighted * weighted;
+            }
+            self.step_samples += 1;
+            if self.step_samples == self.step_length {
+                if self.steps.len() == 4 {
+                    self.steps.pop_front();
+                }
+                self.steps.push_back(self.step_sum);
+                if self.steps.len() == 4 {
+                    let block_length = (self.step_length * 4) as f64;
+                    self.blocks
+                        .push(self.steps.iter().sum::<f64>() / block_length);
+                }
+                self.step_sum = 0.0;
+                self.step_samples = 0;
+            }
+        }
+    }
+
+    /// The integrated loudness of the audio so far in LUFS, if any block is above the absolute gate
+    pub fn integrated(&self) -> Option<f64> {
+        let gated_mean = |gate: f64| {
+            let (sum, count) = self
+                .blocks
+                .iter()
+                .filter(|power| loudness(**power) > gate)
+                .fold((0.0, 0usize), |(sum, count), power| {
+                    (sum + power, count + 1)
+                });
+            (count > 0).then(|| sum / count as f64)
+        };
+        let relative_gate = loudness(gated_mean(ABSOLUTE_GATE)?) + RELATIVE_GATE;
+        gated_mean(relative_gate.max(ABSOLUTE_GATE)).map(loudness)
+    }
+
+    /// The gain in dB that brings the audio so far to `target` LUFS
+    pub fn gain_to(&self, target: f64) -> Option<f64> {
+        Some(target - self.integrated()?)
+    }
+
+    /// Forget the audio measured so far
+    pub fn reset(&mut self) {
+        *self = LoudnessMeter::new(self.sample_rate, self.channels);
+    }
+}
+
+/// Loudness in LUFS of the mean square of K-weighted audio
+fn loudness(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+/// Amplify the samples by `db` decibels, saturating at the limits of 16 bit samples
+pub fn apply_gain(samples: &mut [i16], db: f64) {
+    let gain = 10f64.powf(db / 20.0);
+    for sample in samples {
+        *sample = (*sample as f64 * gain)
+            .round()
+            .clamp(i16::MIN as f64, i16::MAX as f64) as i16;
+    }
+}
+
+/// Bring interleaved audio to a loudness of `target` LUFS, returns the applied gain in dB
+///
+/// Audio that is too quiet to be measured is left unchanged and `None` is returned. Loud peaks of quiet audio
+/// can saturate when it is amplified, run the audio through a [`Limiter`](crate::Limiter) for large gains.
+pub fn normalize_loudness(
+    samples: &mut [i16],
+    sample_rate: u32,
+    channels: usize,
+    target: f64,
+) -> Option<f64> {
+    let mut meter = LoudnessMeter::new(sample_rate, channels);
+    meter.process(samples);
+    let gain = meter.gain_to(target)?;
+    apply_gain(samples, gain);
+    Some(gain)
+}
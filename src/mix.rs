@@ -0,0 +1,720 @@
+//! Mixing the audio of multiple speakers into a single stream
+
+use crate::{
+    ActiveSpeaker, Limiter, LoudestFirst, MixDecision, OutputChannels, PcmSink, SpeakerLimit,
+    SpeakerPriority, SteamAudioError,
+};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Pan positions handed out to speakers by [`Mixer::set_auto_pan`], in the order the speakers are first added
+const AUTO_PANS: [f32; 7] = [-0.5, 0.5, -0.25, 0.25, -0.75, 0.75, 0.0];
+
+/// Default for [`Ducking::depth_db`]
+pub const DEFAULT_DUCKING_DEPTH_DB: f32 = -12.0;
+/// Default for [`Ducking::threshold_db`]
+pub const DEFAULT_DUCKING_THRESHOLD_DB: f32 = -40.0;
+/// Default for [`Ducking::attack`]
+pub const DEFAULT_DUCKING_ATTACK: Duration = Duration::from_millis(20);
+/// Default for [`Ducking::release`]
+pub const DEFAULT_DUCKING_RELEASE: Duration = Duration::from_millis(500);
+/// Time the level of the leader takes to fall after it stops talking
+const DUCKING_HOLD: Duration = Duration::from_millis(100);
+/// Number of samples, 10 ms at 48 kHz, for which the speaker limit decides which speakers to keep
+const SPEAKER_LIMIT_BLOCK: u64 = 480;
+
+/// Turning down the other speakers while a designated speaker, like the in-game leader, talks
+///
+/// While the level of the leader is above the [`threshold_db`](Self::threshold_db), the other speakers are turned
+/// down by the [`depth_db`](Self::depth_db), fading within the [`attack`](Self::attack) time and back up within
+/// the [`release`](Self::release) time once the leader stops.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ducking {
+    leader: u64,
+    sample_rate: u32,
+    depth_db: f32,
+    threshold_db: f32,
+    attack: Duration,
+    release: Duration,
+    /// Peak level of the leader, falling within the hold time
+    level: f32,
+    gain: f32,
+}
+
+impl Ducking {
+    /// Duck the other speakers while `leader` talks, for a mix at `sample_rate`
+    pub fn new(leader: u64, sample_rate: u32) -> Self {
+        Ducking {
+            leader,
+            sample_rate,
+            depth_db: DEFAULT_DUCKING_DEPTH_DB,
+            threshold_db: DEFAULT_DUCKING_THRESHOLD_DB,
+            attack: DEFAULT_DUCKING_ATTACK,
+            release: DEFAULT_DUCKING_RELEASE,
+            level: 0.0,
+            gain: 1.0,
+        }
+    }
+
+    /// Set how far the other speakers are turned down in dB
+    pub fn with_depth_db(mut self, depth_db: f32) -> Self {
+        self.depth_db = -depth_db.abs();
+        self
+    }
+
+    /// Set the peak level in dBFS above which the leader counts as talking
+    pub fn with_threshold_db(mut self, threshold_db: f32) -> Self {
+        self.threshold_db = threshold_db;
+        self
+    }
+
+    /// Set the time the other speakers take to be turned down
+    pub fn with_attack(mut self, attack: Duration) -> Self {
+        self.attack = attack;
+        self
+    }
+
+    /// Set the time the other speakers take to come back up
+    pub fn with_release(mut self, release: Duration) -> Self {
+        self.release = release;
+        self
+    }
+
+    pub fn leader(&self) -> u64 {
+        self.leader
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn depth_db(&self) -> f32 {
+        self.depth_db
+    }
+
+    pub fn threshold_db(&self) -> f32 {
+        self.threshold_db
+    }
+
+    pub fn attack(&self) -> Duration {
+        self.attack
+    }
+
+    pub fn release(&self) -> Duration {
+        self.release
+    }
+
+    /// The gain currently applied to the other speakers in dB
+    pub fn gain_db(&self) -> f32 {
+        20.0 * self.gain.log10()
+    }
+
+    /// Add the audio of the other speakers to the mix, ducked by the level of the leader
+    fn apply(&mut self, mix: &mut [i32], ducked: &[i32], leader: &[i32], channels: usize) {
+        let hold = coefficient(DUCKING_HOLD, self.sample_rate);
+        let attack = coefficient(self.attack, self.sample_rate);
+        let release = coefficient(self.release, self.sample_rate);
+        let threshold = 10f32.powf(self.threshold_db / 20.0);
+        let depth = 10f32.powf(self.depth_db / 20.0);
+        for ((mix, ducked), leader) in mix
+            .chunks_exact_mut(channels)
+            .zip(ducked.chunks_exact(channels))
+            .zip(leader)
+        {
+            let key = leader.unsigned_abs() as f32 / 32768.0;
+            self.level = key.max(self.level * hold);
+            let (target, coefficient) = if self.level > threshold {
+                (depth, attack)
+            } else {
+                (1.0, release)
+            };
+            self.gain = target + (self.gain - target) * coefficient;
+            for (mix, ducked) in mix.iter_mut().zip(ducked) {
+                *mix += (*ducked as f32 * self.gain) as i32;
+            }
+        }
+    }
+}
+
+/// Factor the distance to a target is multiplied with every sample to approach it within `duration`
+fn coefficient(duration: Duration, sample_rate: u32) -> f32 {
+    let samples = duration.as_secs_f32() * sample_rate as f32;
+    if samples > 0.0 {
+        (-1.0 / samples).exp()
+    } else {
+        0.0
+    }
+}
+
+/// Sums the mono audio of any number of speakers into a single mono or stereo stream
+///
+/// The audio of every speaker is added at a sample position on a shared timeline. The sum is kept at a higher
+/// precision until it is taken out of the mixer, at which point it is clamped to the range of 16 bit samples,
+/// so overlapping loud speakers saturate instead of wrapping around.
+///
+/// For stereo output every speaker can be given a pan position, so speakers talking at once are easier
+/// to tell apart. Speakers are centered unless they have a pan position or auto panning is enabled.
+///
+/// Speakers can be muted, or soloed to only hear the soloed speakers, between adding audio. With [`Ducking`]
+/// the other speakers are turned down while a designated speaker talks.
+///
+/// With a [`SpeakerLimit`] only the most important speakers are mixed when too many talk at once.
+///
+/// A [`Limiter`] can be set to turn the mix down when it gets too loud, instead of clipping it.
+#[derive(Debug, Clone, Default)]
+pub struct Mixer {
+    channels: OutputChannels,
+    /// Sum of the audio of all speakers starting at `start`, interleaved for stereo output
+    mix: Vec<i32>,
+    start: u64,
+    pans: HashMap<u64, f32>,
+    auto_pan: bool,
+    muted: HashSet<u64>,
+    soloed: HashSet<u64>,
+    ducking: Option<Ducking>,
+    /// Sum of the audio of the speakers other than the ducking leader, laid out like `mix`
+    ducked: Vec<i32>,
+    /// Mono audio of the ducking leader, one sample for every frame of `mix`
+    leader: Vec<i32>,
+    limiter: Option<Limiter>,
+    speaker_limit: Option<SpeakerLimit>,
+    priority: Option<Priority>,
+    /// Audio of every speaker while the speaker limit is set, laid out like `mix`
+    limited: HashMap<u64, LimitedSpeaker>,
+}
+
+#[derive(Clone)]
+struct Priority(Arc<dyn SpeakerPriority + Send + Sync>);
+
+impl Debug for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Priority").finish_non_exhaustive()
+    }
+}
+
+/// Audio of a speaker waiting for the speaker limit to decide how it is mixed
+#[derive(Debug, Clone, Default)]
+struct LimitedSpeaker {
+    samples: Vec<i32>,
+    /// Timeline position the speaker started talking at, `None` while it is silent
+    started: Option<u64>,
+    /// Gain the last mixed sample of the speaker was mixed with
+    gain: f32,
+}
+
+impl Mixer {
+    pub fn new(channels: OutputChannels) -> Self {
+        Mixer {
+            channels,
+            ..Mixer::default()
+        }
+    }
+
+    pub fn channels(&self) -> OutputChannels {
+        self.channels
+    }
+
+    /// Set the pan position of a speaker, from `-1.0` for fully left to `1.0` for fully right
+    ///
+    /// Panning away from the center lowers the opposite channel, the center keeps both channels at full volume.
+    pub fn set_pan(&mut self, steam_id: u64, pan: f32) {
+        self.pans.insert(steam_id, pan.clamp(-1.0, 1.0));
+    }
+
+    /// The pan position of a speaker
+    pub fn pan(&self, steam_id: u64) -> f32 {
+        self.pans.get(&steam_id).copied().unwrap_or(0.0)
+    }
+
+    /// Spread speakers without a pan position across the stereo field, in the order they are first added
+    pub fn set_auto_pan(&mut self, auto_pan: bool) {
+        self.auto_pan = auto_pan;
+    }
+
+    /// Mute or unmute a speaker, the audio of a muted speaker added afterwards is left out of the mix
+    pub fn set_muted(&mut self, steam_id: u64, muted: bool) {
+        if muted {
+            self.muted.insert(steam_id);
+        } else {
+            self.muted.remove(&steam_id);
+        }
+    }
+
+    pub fn is_muted(&self, steam_id: u64) -> bool {
+        self.muted.contains(&steam_id)
+    }
+
+    /// Solo or unsolo a speaker, while any speaker is soloed only the audio of soloed speakers is mixed
+    pub fn set_solo(&mut self, steam_id: u64, solo: bool) {
+        if solo {
+            self.soloed.insert(steam_id);
+        } else {
+            self.soloed.remove(&steam_id);
+        }
+    }
+
+    pub fn is_soloed(&self, steam_id: u64) -> bool {
+        self.soloed.contains(&steam_id)
+    }
+
+    /// Unmute and unsolo all speakers
+    pub fn clear_mute_solo(&mut self) {
+        self.muted.clear();
+        self.soloed.clear();
+    }
+
+    /// Whether the audio of a speaker is mixed, it isn't muted and either it is soloed or no speaker is
+    ///
+    /// A muted speaker stays silent even when soloed.
+    pub fn is_audible(&self, steam_id: u64) -> bool {
+        !self.muted.contains(&steam_id)
+            && (self.soloed.is_empty() || self.soloed.contains(&steam_id))
+    }
+
+    /// Turn down the other speakers while the leader of `ducking` talks, `None` disables ducking
+    ///
+    /// The ducking is applied when the audio is taken out of the mixer, so the audio of the leader has to be
+    /// added up to the position the mix is taken to, as [`VoiceSession::mix`](crate::VoiceSession::mix) does.
+    pub fn set_ducking(&mut self, ducking: Option<Ducking>) {
+        for (mix, ducked) in self.mix.iter_mut().zip(self.ducked.drain(..)) {
+            *mix += ducked;
+        }
+        self.leader.clear();
+        self.ducking = ducking;
+    }
+
+    pub fn ducking(&self) -> Option<&Ducking> {
+        self.ducking.as_ref()
+    }
+
+    /// Limit the mixed audio as it is taken out of the mixer, `None` clips it instead
+    pub fn set_limiter(&mut self, limiter: Option<Limiter>) {
+        self.limiter = limiter;
+    }
+
+    pub fn limiter(&self) -> Option<&Limiter> {
+        self.limiter.as_ref()
+    }
+
+    /// Only mix the most important speakers that talk at the same time, `None` mixes all speakers
+    ///
+    /// Which speakers are kept is decided by the [priority](Self::set_speaker_priority) for every 480 samples
+    /// when the audio is taken out of the mixer, so the audio of all speakers has to be added up to the position
+    /// the mix is taken to, as [`VoiceSession::mix`](crate::VoiceSession::mix) does. Ducked speakers are turned
+    /// down by [`DEFAULT_DUCKING_DEPTH_DB`].
+    pub fn set_speaker_limit(&mut self, speaker_limit: Option<SpeakerLimit>) {
+        if speaker_limit.is_none() {
+            let count = self.mix.len() / self.channels.count();
+            self.mix_limited(0, count, |_| MixDecision::Include);
+            self.limited.clear();
+        }
+        self.speaker_limit = speaker_limit;
+    }
+
+    pub fn speaker_limit(&self) -> Option<SpeakerLimit> {
+        self.speaker_limit
+    }
+
+    /// Set the policy deciding which speakers are kept by the speaker limit, [`LoudestFirst`] by default
+    pub fn set_speaker_priority<P: SpeakerPriority + Send + Sync + 'static>(
+        &mut self,
+        priority: P,
+    ) {
+        self.priority = Some(Priority(Arc::new(priority)));
+    }
+
+    /// Timeline position of the first sample that hasn't been taken out of the mixer yet
+    pub fn position(&self) -> u64 {
+        self.start
+    }
+
+    /// Timeline position directly after the last sample added to the mixer
+    pub fn end(&self) -> u64 {
+        self.start + (self.mix.len() / self.channels.count()) as u64
+    }
+
+    /// Add centered mono samples, starting at sample `position` of the timeline
+    ///
+    /// Samples before the [`position`](Self::position) of the mixer have already been taken out and are dropped.
+    pub fn add(&mut self, position: u64, samples: &[i16]) {
+        self.add_with_gains(position, samples, [1.0, 1.0]);
+    }
+
+    /// Add mono samples of a speaker at its pan position, starting at sample `position` of the timeline
+    ///
+    /// The audio of speakers that aren't [audible](Self::is_audible) is left out, the mix is still extended
+    /// to cover it, so the length of the mix doesn't depend on who is muted.
+    pub fn add_speaker(&mut self, steam_id: u64, position: u64, samples: &[i16]) {
+        if self.auto_pan && !self.pans.contains_key(&steam_id) {
+            let pan = AUTO_PANS[self.pans.len() % AUTO_PANS.len()];
+            self.pans.insert(steam_id, pan);
+        }
+        let pan = self.pan(steam_id);
+        self.add_speaker_with_gains(
+            steam_id,
+            position,
+            samples,
+            [(1.0 - pan).min(1.0), (1.0 + pan).min(1.0)],
+        );
+    }
+
+    /// Add mono samples of a speaker with a separate gain for the left and right channel
+    ///
+    /// Like [`add_speaker`](Self::add_speaker), the speaker can be muted or ducked.
+    pub fn add_speaker_with_gains(
+        &mut self,
+        steam_id: u64,
+        position: u64,
+        samples: &[i16],
+        gains: [f32; 2],
+    ) {
+        let gains = if self.is_audible(steam_id) {
+            gains
+        } else {
+            [0.0, 0.0]
+        };
+        if self.speaker_limit.is_some() {
+            self.add_to(Target::Limited(steam_id), position, samples, gains);
+            return;
+        }
+        match &self.ducking {
+            Some(ducking) if ducking.leader == steam_id => {
+                self.add_to(Target::Leader, position, samples, gains)
+            }
+            Some(_) => self.add_to(Target::Ducked, position, samples, gains),
+            None => self.add_to(Target::Mix, position, samples, gains),
+        }
+    }
+
+    /// Add mono samples with a separate gain for the left and right channel
+    ///
+    /// For mono output the larger of the two gains is used, so panning doesn't change the volume.
+    pub fn add_with_gains(&mut self, position: u64, samples: &[i16], gains: [f32; 2]) {
+        self.add_to(Target::Mix, position, samples, gains);
+    }
+
+    fn add_to(&mut self, target: Target, position: u64, samples: &[i16], gains: [f32; 2]) {
+        let skip = self.start.saturating_sub(position) as usize;
+        let Some(samples) = samples.get(skip..) else {
+            return;
+        };
+        let channels = self.channels.count();
+        let frame = (position.max(self.start) - self.start) as usize;
+        let offset = frame * channels;
+        self.resize(frame + samples.len());
+        if target == Target::Leader {
+            for (leader, &sample) in self.leader[frame..].iter_mut().zip(samples) {
+                *leader += (sample as f32 * gains[0].max(gains[1])) as i32;
+            }
+        }
+        let buffer = match target {
+            Target::Ducked => &mut self.ducked,
+            Target::Limited(steam_id) => {
+                let limited = &mut self.limited.entry(steam_id).or_default().samples;
+                if limited.len() < self.mix.len() {
+                    limited.resize(self.mix.len(), 0);
+                }
+                limited
+            }
+            _ => &mut self.mix,
+        };
+        let mixed = buffer[offset..].chunks_exact_mut(channels);
+        match self.channels {
+            OutputChannels::Mono => {
+                let gain = gains[0].max(gains[1]);
+                for (mixed, &sample) in mixed.zip(samples) {
+                    mixed[0] += (sample as f32 * gain) as i32;
+                }
+            }
+            OutputChannels::Stereo => {
+                for (mixed, &sample) in mixed.zip(samples) {
+                    mixed[0] += (sample as f32 * gains[0]) as i32;
+                    mixed[1] += (sample as f32 * gains[1]) as i32;
+                }
+            }
+        }
+    }
+
+    /// Take the mixed audio up to timeline position `end` out of the mixer, interleaved for stereo output
+    ///
+    /// Parts of the timeline without any audio are filled with silence.
+    pub fn take(&mut self, end: u64) -> Vec<i16> {
+        let mut output = Vec::new();
+        self.take_append(end, &mut output);
+        output
+    }
+
+    /// Take all audio added to the mixer so far
+    pub fn take_all(&mut self) -> Vec<i16> {
+        self.take(self.end())
+    }
+
+    /// Take the mixed audio up to timeline position `end`, appending it to `output`,
+    /// returns the number of samples appended
+    pub fn take_append(&mut self, end: u64, output: &mut Vec<i16>) -> usize {
+        let count = end.saturating_sub(self.start) as usize;
+        let channels = self.channels.count();
+        self.resize(count);
+        if self.speaker_limit.is_some() {
+            self.apply_speaker_limit(count);
+        }
+        if let Some(ducking) = self.ducking.as_mut() {
+            ducking.apply(
+                &mut self.mix[..count * channels],
+                &self.ducked[..count * channels],
+                &self.leader[..count],
+                channels,
+            );
+            self.ducked.drain(..count * channels);
+            self.leader.drain(..count);
+        }
+        let start = output.len();
+        match self.limiter.as_mut() {
+            Some(limiter) => {
+                limiter.process(&self.mix[..count * channels], channels, output);
+                self.mix.drain(..count * channels);
+            }
+            None => output.extend(
+                self.mix
+                    .drain(..count * channels)
+                    .map(|mixed| mixed.clamp(i16::MIN as i32, i16::MAX as i32) as i16),
+            ),
+        }
+        self.start += count as u64;
+        output.len() - start
+    }
+
+    /// Mix the audio of the speakers up to frame `count` from the start, keeping the speakers with the highest
+    /// priority for every block of [`SPEAKER_LIMIT_BLOCK`] samples of the timeline
+    fn apply_speaker_limit(&mut self, count: usize) {
+        let Some(speaker_limit) = self.speaker_limit else {
+            return;
+        };
+        let priority = self.priority.clone();
+        let channels = self.channels.count();
+        let mut frame = 0;
+        while frame < count {
+            let position = self.start + frame as u64;
+            let block_end = (position / SPEAKER_LIMIT_BLOCK + 1) * SPEAKER_LIMIT_BLOCK;
+            let end = ((block_end - self.start) as usize).min(count);
+
+            let mut speakers = Vec::new();
+            for (&steam_id, speaker) in self.limited.iter_mut() {
+                let samples = speaker
+                    .samples
+                    .get(frame * channels..end * channels)
+                    .unwrap_or_default();
+                let energy: f64 = samples.iter().map(|&sample| (sample as f64).powi(2)).sum();
+                if energy == 0.0 {
+                    speaker.started = None;
+                    continue;
+                }
+                let level = ((energy / samples.len() as f64).sqrt() / 32768.0) as f32;
+                let started = *speaker.started.get_or_insert(position);
+                speakers.push(ActiveSpeaker {
+                    steam_id,
+                    level,
+                    started,
+                });
+            }
+            let decisions = match &priority {
+                Some(Priority(priority)) => speaker_limit.decide(priority.as_ref(), &speakers),
+                None => speaker_limit.decide(&LoudestFirst, &speakers),
+            };
+            let decisions: HashMap<u64, MixDecision> = speakers
+                .iter()
+                .map(|speaker| speaker.steam_id)
+                .zip(decisions)
+                .collect();
+            self.mix_limited(frame, end, |steam_id| {
+                decisions
+                    .get(&steam_id)
+                    .copied()
+                    .unwrap_or(MixDecision::Exclude)
+            });
+            frame = end;
+        }
+        for speaker in self.limited.values_mut() {
+            speaker
+                .samples
+                .drain(..(count * channels).min(speaker.samples.len()));
+        }
+        self.limited
+            .retain(|_, speaker| !speaker.samples.is_empty() || speaker.started.is_some());
+    }
+
+    /// Add the audio of the speakers between frame `start` and `end` from the start to the mix, fading the gain of
+    /// every speaker to the gain of its decision over the frames
+    fn mix_limited<D: Fn(u64) -> MixDecision>(&mut self, start: usize, end: usize, decide: D) {
+        let channels = self.channels.count();
+        let duck = 10f32.powf(DEFAULT_DUCKING_DEPTH_DB / 20.0);
+        let leader = self.ducking.as_ref().map(|ducking| ducking.leader);
+        self.resize(end);
+        for (&steam_id, speaker) in self.limited.iter_mut() {
+            let gain = match decide(steam_id) {
+                MixDecision::Include => 1.0,
+                MixDecision::Duck => duck,
+                MixDecision::Exclude => 0.0,
+            };
+            let samples = speaker
+                .samples
+                .get(start * channels..end * channels)
+                .unwrap_or_default();
+            if samples.is_empty() {
+                speaker.gain = gain;
+                continue;
+            }
+            let frames = samples.len() / channels;
+            let from = if speaker.started == Some(self.start + start as u64) {
+                gain
+            } else {
+                speaker.gain
+            };
+            let buffer = match leader {
+                Some(leader) if leader != steam_id => &mut self.ducked,
+                _ => &mut self.mix,
+            };
+            let offset = start * channels;
+            for (index, (mixed, samples)) in buffer[offset..]
+                .chunks_exact_mut(channels)
+                .zip(samples.chunks_exact(channels))
+                .enumerate()
+            {
+                let gain = from + (gain - from) * (index + 1) as f32 / frames as f32;
+                for (mixed, &sample) in mixed.iter_mut().zip(samples) {
+                    *mixed += (sample as f32 * gain) as i32;
+                }
+                if leader == Some(steam_id) {
+                    let peak = samples.iter().map(|sample| sample.abs()).max();
+                    self.leader[start + index] += (peak.unwrap_or(0) as f32 * gain) as i32;
+                }
+            }
+            speaker.gain = gain;
+        }
+    }
+
+    /// Grow the buffers to hold at least `frames` frames from the start
+    fn resize(&mut self, frames: usize) {
+        let channels = self.channels.count();
+        if self.mix.len() < frames * channels {
+            self.mix.resize(frames * channels, 0);
+        }
+        if self.ducking.is_some() {
+            let frames = self.mix.len() / channels;
+            self.ducked.resize(frames * channels, 0);
+            self.leader.resize(frames, 0);
+        }
+    }
+
+    /// Take the mixed audio up to timeline position `end`, writing it to the sink
+    pub fn write_to<S: PcmSink + ?Sized>(
+        &mut self,
+        end: u64,
+        sink: &mut S,
+    ) -> Result<(), SteamAudioError> {
+        let samples = self.take(end);
+        sink.write_samples(&samples)
+    }
+}
+
+/// Buffer of the mixer that audio is added to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    Mix,
+    /// Audio of the speakers that are ducked
+    Ducked,
+    /// Audio of the ducking leader, added to the mix and the level of the leader
+    Leader,
+    /// Audio of a speaker that is mixed by the speaker limit
+    Limited(u64),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saturates_instead_of_wrapping() {
+        let mut mixer = Mixer::new(OutputChannels::Mono);
+        mixer.add(0, &[30000, -30000, 100]);
+        mixer.add(1, &[-30000, 30000]);
+        mixer.add(0, &[30000]);
+        assert_eq!(mixer.take_all(), [i16::MAX, i16::MIN, 30100]);
+        assert_eq!(mixer.position(), 3);
+    }
+
+    #[test]
+    fn pan_law() {
+        let mut mixer = Mixer::new(OutputChannels::Stereo);
+        mixer.set_pan(1, -0.5);
+        mixer.set_pan(2, 1.0);
+        mixer.add_speaker(1, 0, &[1000]);
+        mixer.add_speaker(2, 1, &[1000]);
+        mixer.add_speaker(3, 2, &[1000]);
+        // the opposite channel is lowered, the near channel stays at full volume
+        assert_eq!(mixer.take_all(), [1000, 500, 0, 1000, 1000, 1000]);
+
+        let mut mixer = Mixer::new(OutputChannels::Mono);
+        mixer.set_pan(1, -1.0);
+        mixer.add_speaker(1, 0, &[1000]);
+        assert_eq!(mixer.take_all(), [1000]);
+    }
+
+    #[test]
+    fn auto_pan_in_order_of_speakers() {
+        let mut mixer = Mixer::new(OutputChannels::Stereo);
+        mixer.set_auto_pan(true);
+        mixer.set_pan(7, 0.0);
+        mixer.add_speaker(5, 0, &[0]);
+        mixer.add_speaker(6, 0, &[0]);
+        mixer.add_speaker(7, 0, &[0]);
+        // speakers with a pan position keep it and count towards the auto pan positions handed out
+        assert_eq!(mixer.pan(5), 0.5);
+        assert_eq!(mixer.pan(6), -0.25);
+        assert_eq!(mixer.pan(7), 0.0);
+    }
+
+    #[test]
+    fn mute_wins_over_solo() {
+        let mut mixer = Mixer::new(OutputChannels::Mono);
+        mixer.set_solo(1, true);
+        mixer.set_solo(2, true);
+        mixer.set_muted(2, true);
+        assert!(mixer.is_audible(1));
+        assert!(!mixer.is_audible(2));
+        assert!(!mixer.is_audible(3));
+        mixer.add_speaker(1, 0, &[1]);
+        mixer.add_speaker(2, 0, &[10]);
+        mixer.add_speaker(3, 0, &[100, 100]);
+        // the audio of silent speakers still extends the mix
+        assert_eq!(mixer.take_all(), [1, 0]);
+
+        mixer.clear_mute_solo();
+        mixer.add_speaker(2, 2, &[10]);
+        mixer.add_speaker(3, 2, &[100]);
+        assert_eq!(mixer.take_all(), [110]);
+    }
+
+    #[test]
+    fn duck_envelope() {
+        let mut mixer = Mixer::new(OutputChannels::Mono);
+        mixer.set_ducking(Some(Ducking::new(1, 48000)));
+        // the leader talks for the first half second, the other speaker for two seconds
+        mixer.add_speaker(1, 0, &[10000; 24000]);
+        mixer.add_speaker(2, 0, &[10000; 96000]);
+        let mix = mixer.take_all();
+        let other = |index: usize| mix[index] - if index < 24000 { 10000 } else { 0 };
+
+        // turned down by 12dB within the attack time
+        assert!(other(0) > 9000);
+        assert!((2400..2600).contains(&other(4800)));
+        assert!((2400..2600).contains(&other(23999)));
+        // back up within the release time after the level of the leader fell
+        assert!(other(24000 + 4800) < 3000);
+        assert!(other(48000) < other(72000));
+        assert!(other(95999) > 9000);
+        assert!(mixer.ducking().unwrap().gain_db() > -1.0);
+    }
+}
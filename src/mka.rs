@@ -0,0 +1,635 @@
+//! Exporting the audio of all speakers of a session to a single Matroska file
+
+use crate::{MultiTrack, SteamAudioError};
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const EBML: u32 = 0x1A45_DFA3;
+const EBML_VERSION: u32 = 0x4286;
+const EBML_READ_VERSION: u32 = 0x42F7;
+const EBML_MAX_ID_LENGTH: u32 = 0x42F2;
+const EBML_MAX_SIZE_LENGTH: u32 = 0x42F3;
+const DOC_TYPE: u32 = 0x4282;
+const DOC_TYPE_VERSION: u32 = 0x4287;
+const DOC_TYPE_READ_VERSION: u32 = 0x4285;
+const VOID: u32 = 0xEC;
+const SEGMENT: u32 = 0x1853_8067;
+const SEEK_HEAD: u32 = 0x114D_9B74;
+const SEEK: u32 = 0x4DBB;
+const SEEK_ID: u32 = 0x53AB;
+const SEEK_POSITION: u32 = 0x53AC;
+const INFO: u32 = 0x1549_A966;
+const TIMESTAMP_SCALE: u32 = 0x2A_D7B1;
+const DURATION: u32 = 0x4489;
+const MUXING_APP: u32 = 0x4D80;
+const WRITING_APP: u32 = 0x5741;
+const TRACKS: u32 = 0x1654_AE6B;
+const TRACK_ENTRY: u32 = 0xAE;
+const TRACK_NUMBER: u32 = 0xD7;
+const TRACK_UID: u32 = 0x73C5;
+const TRACK_TYPE: u32 = 0x83;
+const FLAG_LACING: u32 = 0x9C;
+const NAME: u32 = 0x536E;
+const CODEC_ID: u32 = 0x86;
+const AUDIO: u32 = 0xE1;
+const SAMPLING_FREQUENCY: u32 = 0xB5;
+const CHANNELS: u32 = 0x9F;
+const BIT_DEPTH: u32 = 0x6264;
+const CLUSTER: u32 = 0x1F43_B675;
+const TIMESTAMP: u32 = 0xE7;
+const SIMPLE_BLOCK: u32 = 0xA3;
+const CUES: u32 = 0x1C53_BB6B;
+const CUE_POINT: u32 = 0xBB;
+const CUE_TIME: u32 = 0xB3;
+const CUE_TRACK_POSITIONS: u32 = 0xB7;
+const CUE_TRACK: u32 = 0xF7;
+const CUE_CLUSTER_POSITION: u32 = 0xF1;
+const TAGS: u32 = 0x1254_C367;
+const TAG: u32 = 0x7373;
+const TARGETS: u32 = 0x63C0;
+const TAG_TRACK_UID: u32 = 0x63C5;
+const SIMPLE_TAG: u32 = 0x67C8;
+const TAG_NAME: u32 = 0x45A3;
+const TAG_STRING: u32 = 0x4487;
+
+/// Timestamps are in milliseconds
+const TIMESTAMP_SCALE_NS: u64 = 1_000_000;
+/// Size of a segment whose size isn't known yet, as an 8 byte vint
+const UNKNOWN_SIZE: [u8; 8] = [0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+/// Elements listed in the seek head, in order
+const SEEK_ELEMENTS: [u32; 4] = [INFO, TRACKS, CUES, TAGS];
+/// Length of the audio of a cluster, in seconds
+const CLUSTER_SECONDS: u64 = 1;
+
+/// Serializes EBML elements
+#[derive(Default)]
+struct Element {
+    bytes: Vec<u8>,
+}
+
+impl Element {
+    fn id(&mut self, id: u32) {
+        let bytes = id.to_be_bytes();
+        let skip = (id.leading_zeros() / 8) as usize;
+        self.bytes.extend_from_slice(&bytes[skip..]);
+    }
+
+    fn size(&mut self, size: u64) {
+        // the all ones value of every length is reserved for unknown sizes
+        let length = (1..8)
+            .find(|length| size < (1 << (7 * length)) - 1)
+            .unwrap_or(8);
+        let marked = size | 1 << (7 * length);
+        self.bytes
+            .extend_from_slice(&marked.to_be_bytes()[8 - length as usize..]);
+    }
+
+    fn binary(&mut self, id: u32, data: &[u8]) {
+        self.id(id);
+        self.size(data.len() as u64);
+        self.bytes.extend_from_slice(data);
+    }
+
+    fn uint(&mut self, id: u32, value: u64) {
+        let bytes = value.to_be_bytes();
+        let skip = ((value.leading_zeros() / 8) as usize).min(7);
+        self.binary(id, &bytes[skip..]);
+    }
+
+    /// An unsigned integer that always takes 8 bytes, so it can be overwritten with any other value
+    fn uint_fixed(&mut self, id: u32, value: u64) {
+        self.binary(id, &value.to_be_bytes());
+    }
+
+    fn float(&mut self, id: u32, value: f64) {
+        self.binary(id, &value.to_be_bytes());
+    }
+
+    fn string(&mut self, id: u32, value: &str) {
+        self.binary(id, value.as_bytes());
+    }
+
+    fn master(&mut self, id: u32, children: Element) {
+        self.binary(id, &children.bytes);
+    }
+
+    fn void(&mut self, length: usize) {
+        // a single byte size is enough for the seek head this reserves space for
+        debug_assert!((2..=128).contains(&length));
+        self.id(VOID);
+        self.size(length as u64 - 2);
+        self.bytes.resize(self.bytes.len() + length - 2, 0);
+    }
+}
+
+/// The track of a speaker in a Matroska file
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MkaTrack {
+    steam_id: u64,
+    name: Option<String>,
+    /// Samples that aren't written to a cluster yet
+    pending: Vec<i16>,
+}
+
+/// Writes the audio of all speakers of a session into a single Matroska audio (`.mka`) file, with one track per
+/// speaker
+///
+/// Every track holds the mono 16 bit PCM audio of a speaker, it is tagged with the steam id of the speaker in a
+/// `STEAM_ID` tag and can be named after the speaker with [`add_track`](Self::add_track). The audio is written in
+/// clusters of one second with an index of the clusters, so players can seek through a whole match.
+///
+/// The audio is passed as the [`MultiTrack`] sections taken out of a [`VoiceSession`](crate::VoiceSession). The
+/// tracks of a Matroska file have to be known before the first audio, so all speakers have to be added, either
+/// with [`add_track`](Self::add_track) or by being part of the first section, before the first cluster is written.
+/// Taking the audio of the whole session at once, up to its [`end`](crate::VoiceSession::end), makes sure that all
+/// speakers are known.
+pub struct MkaWriter<W: Write + Seek> {
+    writer: W,
+    sample_rate: u32,
+    tracks: Vec<MkaTrack>,
+    /// Position of the start of the segment data in the writer
+    segment: u64,
+    /// Offsets of the elements in the seek head from the start of the segment data
+    offsets: [u64; 4],
+    /// Offset of the duration in the info from the start of the segment data
+    duration_offset: u64,
+    /// Whether the tracks were written, after which no tracks can be added
+    started: bool,
+    /// Timeline position of the first pending sample
+    position: u64,
+    /// Number of samples per track in the file
+    samples: u64,
+    /// Timestamp and offset from the start of the segment data of every cluster
+    cues: Vec<(u64, u64)>,
+}
+
+impl<W: Write + Seek> Debug for MkaWriter<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MkaWriter")
+            .field("sample_rate", &self.sample_rate)
+            .field("tracks", &self.tracks.len())
+            .field("samples", &self.samples)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MkaWriter<BufWriter<File>> {
+    /// Create a Matroska file at `path` for audio at `sample_rate`
+    pub fn create<P: AsRef<Path>>(path: P, sample_rate: u32) -> Result<Self, SteamAudioError> {
+        MkaWriter::new(BufWriter::new(File::create(path)?), sample_rate)
+    }
+}
+
+impl<W: Write + Seek> MkaWriter<W> {
+    /// Write audio at `sample_rate` to `writer`
+    pub fn new(mut writer: W, sample_rate: u32) -> Result<Self, SteamAudioError> {
+        if sample_rate == 0 {
+            return Err(SteamAudioError::UnsupportedSampleRate { rate: sample_rate });
+        }
+        let mut header = Element::default();
+        let mut ebml = Element::default();
+        ebml.uint(EBML_VERSION, 1);
+        ebml.uint(EBML_READ_VERSION, 1);
+        ebml.uint(EBML_MAX_ID_LENGTH, 4);
+        ebml.uint(EBML_MAX_SIZE_LENGTH, 8);
+        ebml.string(DOC_TYPE, "matroska");
+        ebml.uint(DOC_TYPE_VERSION, 4);
+        ebml.uint(DOC_TYPE_READ_VERSION, 2);
+        header.master(EBML, ebml);
+        header.id(SEGMENT);
+        header.bytes.extend_from_slice(&UNKNOWN_SIZE);
+        writer.write_all(&header.bytes)?;
+        let segment = writer.stream_position()?;
+
+        let mut sink = MkaWriter {
+            writer,
+            sample_rate,
+            tracks: Vec::new(),
+            segment,
+            offsets: [0; 4],
+            duration_offset: 0,
+            started: false,
+            position: 0,
+            samples: 0,
+            cues: Vec::new(),
+        };
+
+        // reserve space for the seek head, which is written once the positions of all elements are known
+        let mut void = Element::default();
+        void.void(seek_head_length());
+        sink.writer.write_all(&void.bytes)?;
+
+        let mut info = Element::default();
+        info.uint(TIMESTAMP_SCALE, TIMESTAMP_SCALE_NS);
+        info.string(MUXING_APP, env!("CARGO_PKG_NAME"));
+        info.string(WRITING_APP, env!("CARGO_PKG_NAME"));
+        info.float(DURATION, 0.0);
+        sink.offsets[0] = sink.offset()?;
+        let mut element = Element::default();
+        element.master(INFO, info);
+        // the duration is the last child, its value the last 8 bytes of the element
+        sink.duration_offset = sink.offsets[0] + element.bytes.len() as u64 - 8;
+        sink.writer.write_all(&element.bytes)?;
+        Ok(sink)
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Steam ids of the speakers of every track, in track order
+    pub fn steam_ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.tracks.iter().map(|track| track.steam_id)
+    }
+
+    /// Number of samples per track written so far
+    pub fn duration(&self) -> u64 {
+        self.samples + self.tracks.first().map_or(0, |track| track.pending.len()) as u64
+    }
+
+    /// Add a track for a speaker, optionally named after the speaker
+    ///
+    /// Adding a track for a speaker that already has one sets the name of the track. Fails once the first cluster
+    /// has been written.
+    pub fn add_track(&mut self, steam_id: u64, name: Option<&str>) -> Result<(), SteamAudioError> {
+        if let Some(track) = self
+            .tracks
+            .iter_mut()
+            .find(|track| track.steam_id == steam_id)
+        {
+            if name.is_some() {
+                if self.started {
+                    return Err(tracks_written());
+                }
+                track.name = name.map(str::to_string);
+            }
+            return Ok(());
+        }
+        if self.started {
+            return Err(tracks_written());
+        }
+        self.tracks.push(MkaTrack {
+            steam_id,
+            name: name.map(str::to_string),
+            pending: vec![0; self.tracks.first().map_or(0, |track| track.pending.len())],
+        });
+        Ok(())
+    }
+
+    /// Write a section of audio taken out of a session
+    ///
+    /// Speakers without a track get a new one, which fails once the first cluster has been written, tracks of
+    /// speakers that aren't part of the section are silent. A section that doesn't continue where the previous
+    /// one ended starts a new cluster at its own position, leaving a gap in the file.
+    pub fn write_multitrack(&mut self, tracks: &MultiTrack) -> Result<(), SteamAudioError> {
+        for steam_id in tracks.steam_ids() {
+            self.add_track(*steam_id, None)?;
+        }
+        if tracks.is_empty() {
+            return Ok(());
+        }
+        if tracks.position() != self.position + self.pending() as u64 {
+            self.flush_cluster(self.pending())?;
+            self.position = tracks.position();
+        }
+        for track in &mut self.tracks {
+            match tracks.speaker(track.steam_id) {
+                Some(samples) => track.pending.extend_from_slice(samples),
+                None => track.pending.resize(track.pending.len() + tracks.len(), 0),
+            }
+        }
+        let cluster = (self.sample_rate as u64 * CLUSTER_SECONDS) as usize;
+        while self.pending() >= cluster {
+            self.flush_cluster(cluster)?;
+        }
+        Ok(())
+    }
+
+    /// Write the remaining audio, the index of the clusters and the tags, and return the underlying writer
+    pub fn finish(mut self) -> Result<W, SteamAudioError> {
+        self.flush_cluster(self.pending())?;
+        self.write_tracks()?;
+
+        let mut cues = Element::default();
+        for (time, position) in &self.cues {
+            let mut point = Element::default();
+            point.uint(CUE_TIME, *time);
+            let mut positions = Element::default();
+            positions.uint(CUE_TRACK, 1);
+            positions.uint(CUE_CLUSTER_POSITION, *position);
+            point.master(CUE_TRACK_POSITIONS, positions);
+            cues.master(CUE_POINT, point);
+        }
+        if !self.cues.is_empty() {
+            self.offsets[2] = self.offset()?;
+            let mut element = Element::default();
+            element.master(CUES, cues);
+            self.writer.write_all(&element.bytes)?;
+        }
+
+        let mut tags = Element::default();
+        for (index, track) in self.tracks.iter().enumerate() {
+            let mut tag = Element::default();
+            let mut targets = Element::default();
+            targets.uint(TAG_TRACK_UID, index as u64 + 1);
+            tag.master(TARGETS, targets);
+            let mut simple = Element::default();
+            simple.string(TAG_NAME, "STEAM_ID");
+            simple.string(TAG_STRING, &track.steam_id.to_string());
+            tag.master(SIMPLE_TAG, simple);
+            tags.master(TAG, tag);
+        }
+        if !self.tracks.is_empty() {
+            self.offsets[3] = self.offset()?;
+            let mut element = Element::default();
+            element.master(TAGS, tags);
+            self.writer.write_all(&element.bytes)?;
+        }
+
+        let end = self.writer.stream_position()?;
+        self.writer.seek(SeekFrom::Start(self.segment - 8))?;
+        self.writer
+            .write_all(&((end - self.segment) | 1 << 56).to_be_bytes())?;
+        // elements that weren't written are left out, with the rest of the reserved space filled up
+        let mut seek_head = seek_head(
+            SEEK_ELEMENTS
+                .into_iter()
+                .zip(self.offsets)
+                .filter(|(_, offset)| *offset > 0),
+        );
+        let length = seek_head.bytes.len();
+        if length < seek_head_length() {
+            seek_head.void(seek_head_length() - length);
+        }
+        self.writer.write_all(&seek_head.bytes)?;
+        let duration = self.samples as f64 * 1000.0 / self.sample_rate as f64;
+        self.writer
+            .seek(SeekFrom::Start(self.segment + self.duration_offset))?;
+        self.writer.write_all(&duration.to_be_bytes())?;
+        self.writer.seek(SeekFrom::Start(end))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    /// Number of samples per track that aren't written to a cluster yet
+    fn pending(&self) -> usize {
+        self.tracks.first().map_or(0, |track| track.pending.len())
+    }
+
+    /// Position in the writer relative to the start of the segment data
+    fn offset(&mut self) -> Result<u64, SteamAudioError> {
+        Ok(self.writer.stream_position()? - self.segment)
+    }
+
+    fn write_tracks(&mut self) -> Result<(), SteamAudioError> {
+        if self.started {
+            return Ok(());
+        }
+        self.started = true;
+        let mut tracks = Element::default();
+        for (index, track) in self.tracks.iter().enumerate() {
+            let mut entry = Element::default();
+            entry.uint(TRACK_NUMBER, index as u64 + 1);
+            entry.uint(TRACK_UID, index as u64 + 1);
+            entry.uint(TRACK_TYPE, 2);
+            entry.uint(FLAG_LACING, 0);
+            entry.string(
+                NAME,
+                track.name.as_deref().unwrap_or(&track.steam_id.to_string()),
+            );
+            entry.string(CODEC_ID, "A_PCM/INT/LIT");
+            let mut audio = Element::default();
+            audio.float(SAMPLING_FREQUENCY, self.sample_rate as f64);
+            audio.uint(CHANNELS, 1);
+            audio.uint(BIT_DEPTH, 16);
+            entry.master(AUDIO, audio);
+            tracks.master(TRACK_ENTRY, entry);
+        }
+        self.offsets[1] = self.offset()?;
+        let mut element = Element::default();
+        element.master(TRACKS, tracks);
+        self.writer.write_all(&element.bytes)?;
+        Ok(())
+    }
+
+    /// Write the first `length` pending samples of every track as a cluster
+    fn flush_cluster(&mut self, length: usize) -> Result<(), SteamAudioError> {
+        if length == 0 {
+            return Ok(());
+        }
+        self.write_tracks()?;
+        let time = self.position * 1000 / self.sample_rate as u64;
+        let mut cluster = Element::default();
+        cluster.uint(TIMESTAMP, time);
+        for (index, track) in self.tracks.iter_mut().enumerate() {
+            let mut block = Vec::with_capacity(length * 2 + 4);
+            // track number as a vint, relative timestamp and the keyframe flag
+            let number = index as u64 + 1;
+            if number < 0x7F {
+                block.push(0x80 | number as u8);
+            } else {
+                block.extend_from_slice(&(0x4000 | number as u16).to_be_bytes());
+            }
+            block.extend_from_slice(&0i16.to_be_bytes());
+            block.push(0x80);
+            block.extend(track.pending.drain(..length).flat_map(i16::to_le_bytes));
+            cluster.binary(SIMPLE_BLOCK, &block);
+        }
+        let offset = self.offset()?;
+        self.cues.push((time, offset));
+        let mut element = Element::default();
+        element.master(CLUSTER, cluster);
+        self.writer.write_all(&element.bytes)?;
+        self.position += length as u64;
+        self.samples += length as u64;
+        Ok(())
+    }
+}
+
+/// The seek head listing the elements at the given offsets from the start of the segment data
+fn seek_head(elements: impl Iterator<Item = (u32, u64)>) -> Element {
+    let mut seek_head = Element::default();
+    for (id, offset) in elements {
+        let mut seek = Element::default();
+        seek.binary(SEEK_ID, &id.to_be_bytes());
+        seek.uint_fixed(SEEK_POSITION, offset);
+        seek_head.master(SEEK, seek);
+    }
+    let mut element = Element::default();
+    element.master(SEEK_HEAD, seek_head);
+    element
+}
+
+/// Space reserved for the seek head, enough to list all elements
+fn seek_head_length() -> usize {
+    seek_head(SEEK_ELEMENTS.into_iter().map(|id| (id, 0)))
+        .bytes
+        .len()
+}
+
+fn tracks_written() -> SteamAudioError {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        "tracks can't be changed after audio was written",
+    )
+    .into()
+}
+
+/// Write the audio of all speakers of a section taken out of a session, at `sample_rate`, to a Matroska file at
+/// `path`
+pub fn write_mka<P: AsRef<Path>>(
+    path: P,
+    tracks: &MultiTrack,
+    sample_rate: u32,
+) -> Result<(), SteamAudioError> {
+    let mut writer = MkaWriter::create(path, sample_rate)?;
+    writer.write_multitrack(tracks)?;
+    writer.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Read a variable length integer, ids keep their length marker
+    fn vint(data: &[u8], marker: bool) -> (u64, usize) {
+        let length = data[0].leading_zeros() as usize + 1;
+        let value = data[..length]
+            .iter()
+            .fold(0, |value, byte| value << 8 | *byte as u64);
+        match marker {
+            true => (value, length),
+            false => (value & ((1 << (7 * length)) - 1), length),
+        }
+    }
+
+    /// Split data into its elements, with their id, data and offset
+    fn elements(data: &[u8]) -> Vec<(u32, &[u8], usize)> {
+        let mut elements = Vec::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            let (id, id_length) = vint(&data[offset..], true);
+            let (size, size_length) = vint(&data[offset + id_length..], false);
+            let start = offset + id_length + size_length;
+            elements.push((id as u32, &data[start..start + size as usize], offset));
+            offset = start + size as usize;
+        }
+        elements
+    }
+
+    fn ids(elements: &[(u32, &[u8], usize)]) -> Vec<u32> {
+        elements.iter().map(|(id, _, _)| *id).collect()
+    }
+
+    fn child<'a>(elements: &[(u32, &'a [u8], usize)], id: u32) -> &'a [u8] {
+        elements
+            .iter()
+            .find(|element| element.0 == id)
+            .map(|element| element.1)
+            .unwrap()
+    }
+
+    fn uint(data: &[u8]) -> u64 {
+        data.iter().fold(0, |value, byte| value << 8 | *byte as u64)
+    }
+
+    fn float(data: &[u8]) -> f64 {
+        f64::from_be_bytes(data.try_into().unwrap())
+    }
+
+    #[test]
+    fn two_speaker_layout() {
+        let mut tracks = MultiTrack::new(0);
+        tracks.push_channel(10, vec![1; 12000]);
+        tracks.push_channel(20, (0..12000).map(|sample| sample as i16).collect());
+        let mut writer = MkaWriter::new(Cursor::new(Vec::new()), 8000).unwrap();
+        writer.add_track(10, Some("alice")).unwrap();
+        writer.write_multitrack(&tracks).unwrap();
+        assert!(writer.add_track(30, None).is_err());
+        let data = writer.finish().unwrap().into_inner();
+
+        let file = elements(&data);
+        assert_eq!(ids(&file), [EBML, SEGMENT]);
+        let header = elements(file[0].1);
+        assert_eq!(child(&header, DOC_TYPE), b"matroska");
+        assert_eq!(uint(child(&header, EBML_READ_VERSION)), 1);
+        assert_eq!(uint(child(&header, DOC_TYPE_READ_VERSION)), 2);
+
+        let (segment, segment_start) = (file[1].1, data.len() - file[1].1.len());
+        let children = elements(segment);
+        // the tracks are written before the first of the 1 second clusters
+        assert_eq!(
+            ids(&children),
+            [SEEK_HEAD, INFO, TRACKS, CLUSTER, CLUSTER, CUES, TAGS]
+        );
+        for seek in elements(child(&children, SEEK_HEAD)) {
+            let seek = elements(seek.1);
+            let id = uint(child(&seek, SEEK_ID)) as u32;
+            let position = uint(child(&seek, SEEK_POSITION)) as usize;
+            assert!(children
+                .iter()
+                .any(|element| element.0 == id && element.2 == position));
+            assert_eq!(vint(&data[segment_start + position..], true).0, id as u64);
+        }
+        let info = elements(child(&children, INFO));
+        assert_eq!(float(child(&info, DURATION)), 1500.0);
+
+        let entries = elements(child(&children, TRACKS));
+        assert_eq!(ids(&entries), [TRACK_ENTRY, TRACK_ENTRY]);
+        for (index, (entry, name)) in entries.iter().zip(["alice", "20"]).enumerate() {
+            let entry = elements(entry.1);
+            assert_eq!(uint(child(&entry, TRACK_NUMBER)), index as u64 + 1);
+            assert_eq!(child(&entry, NAME), name.as_bytes());
+            assert_eq!(child(&entry, CODEC_ID), b"A_PCM/INT/LIT");
+            let audio = elements(child(&entry, AUDIO));
+            assert_eq!(float(child(&audio, SAMPLING_FREQUENCY)), 8000.0);
+            assert_eq!(uint(child(&audio, CHANNELS)), 1);
+        }
+
+        let clusters: Vec<_> = children
+            .iter()
+            .filter(|element| element.0 == CLUSTER)
+            .collect();
+        let mut decoded = vec![Vec::new(), Vec::new()];
+        for (cluster, time) in clusters.iter().zip([0, 1000]) {
+            let cluster = elements(cluster.1);
+            assert_eq!(uint(child(&cluster, TIMESTAMP)), time);
+            let blocks: Vec<_> = cluster[1..].iter().map(|block| block.1).collect();
+            assert_eq!(ids(&cluster[1..]), [SIMPLE_BLOCK, SIMPLE_BLOCK]);
+            for (track, block) in blocks.iter().enumerate() {
+                assert_eq!(block[..4], [0x81 + track as u8, 0, 0, 0x80]);
+                decoded[track].extend(
+                    block[4..]
+                        .chunks_exact(2)
+                        .map(|sample| i16::from_le_bytes([sample[0], sample[1]])),
+                );
+            }
+        }
+        assert_eq!(decoded, tracks.planar());
+
+        let cues = elements(child(&children, CUES));
+        let positions: Vec<_> = cues
+            .iter()
+            .map(|point| {
+                let point = elements(point.1);
+                let positions = elements(child(&point, CUE_TRACK_POSITIONS));
+                uint(child(&positions, CUE_CLUSTER_POSITION)) as usize
+            })
+            .collect();
+        assert_eq!(positions, [clusters[0].2, clusters[1].2]);
+
+        let tags: Vec<_> = elements(child(&children, TAGS))
+            .iter()
+            .map(|tag| {
+                let tag = elements(tag.1);
+                let simple = elements(child(&tag, SIMPLE_TAG));
+                String::from_utf8(child(&simple, TAG_STRING).to_vec()).unwrap()
+            })
+            .collect();
+        assert_eq!(tags, ["10", "20"]);
+    }
+}
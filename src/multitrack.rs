@@ -0,0 +1,78 @@
+//! Audio of multiple speakers with a separate channel for every speaker
+
+/// A section of the timeline of a [`VoiceSession`](crate::VoiceSession) with one mono channel per speaker
+///
+/// The channels are ordered by the first payload of the speakers, parts of the section where a speaker
+/// wasn't talking are silent.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MultiTrack {
+    steam_ids: Vec<u64>,
+    position: u64,
+    channels: Vec<Vec<i16>>,
+}
+
+impl MultiTrack {
+    pub(crate) fn new(position: u64) -> Self {
+        MultiTrack {
+            position,
+            ..MultiTrack::default()
+        }
+    }
+
+    pub(crate) fn push_channel(&mut self, steam_id: u64, samples: Vec<i16>) {
+        self.steam_ids.push(steam_id);
+        self.channels.push(samples);
+    }
+
+    /// The steam id of the speaker of every channel, in channel order
+    pub fn steam_ids(&self) -> &[u64] {
+        &self.steam_ids
+    }
+
+    /// Position of the first sample of the section on the timeline
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn channel_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Number of samples per channel
+    pub fn len(&self) -> usize {
+        self.channels.first().map_or(0, Vec::len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The samples of a single channel
+    pub fn channel(&self, index: usize) -> Option<&[i16]> {
+        self.channels.get(index).map(Vec::as_slice)
+    }
+
+    /// The samples of the speaker, if it has a channel
+    pub fn speaker(&self, steam_id: u64) -> Option<&[i16]> {
+        let index = self.steam_ids.iter().position(|id| *id == steam_id)?;
+        self.channel(index)
+    }
+
+    /// The samples of all channels, one buffer per channel
+    pub fn planar(&self) -> &[Vec<i16>] {
+        &self.channels
+    }
+
+    pub fn into_planar(self) -> Vec<Vec<i16>> {
+        self.channels
+    }
+
+    /// The samples of all channels interleaved, for writing to a multichannel audio file
+    pub fn interleaved(&self) -> Vec<i16> {
+        let mut output = Vec::with_capacity(self.len() * self.channel_count());
+        for index in 0..self.len() {
+            output.extend(self.channels.iter().map(|channel| channel[index]));
+        }
+        output
+    }
+}
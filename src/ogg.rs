@@ -0,0 +1,539 @@
+//! Ogg Opus output without re-encoding the voice data
+
+use crate::repacketize::{silence_packet, SILENCE_TOCS};
+use crate::toc::packet_samples;
+use crate::{
+    frames_lost, ExtractedFrame, FrameExtractor, OpusFrame, OpusFrameIterator, OpusPacket, Packet,
+    Repacketizer, SteamAudioError, SteamVoiceData, DEFAULT_MAX_CONCEALMENT,
+};
+use std::fmt::Debug;
+use std::io::Write;
+
+/// Ogg Opus timestamps are always in 48kHz samples, regardless of the encoded sample rate
+const GRANULE_RATE: u64 = 48_000;
+/// Encoder delay of libopus, which is used by steam to encode the voice data
+const PRE_SKIP: u16 = 312;
+/// 20ms silence packet, used to encode [`Packet::Silence`]
+const SILENCE_FRAME: (u8, u64) = SILENCE_TOCS[0];
+/// Pages of an [`OggOpusWriter`] are written once they hold this many bytes
+const PAGE_SIZE: usize = 4096;
+const VENDOR: &str = concat!("steam-audio-codec ", env!("CARGO_PKG_VERSION"));
+
+const HEADER_TYPE_CONTINUED: u8 = 0x01;
+const HEADER_TYPE_BOS: u8 = 0x02;
+const HEADER_TYPE_EOS: u8 = 0x04;
+const NO_GRANULE: u64 = u64::MAX;
+
+const OGG_CRC_TABLE: [u32; 256] = ogg_crc_table();
+
+const fn ogg_crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04C1_1DB7
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+fn ogg_crc(data: &[u8]) -> u32 {
+    data.iter().fold(0, |crc, &byte| {
+        (crc << 8) ^ OGG_CRC_TABLE[(((crc >> 24) as u8) ^ byte) as usize]
+    })
+}
+
+/// Packs packets into the pages of a single logical bitstream
+struct PageWriter {
+    serial: u32,
+    sequence: u32,
+    segments: Vec<u8>,
+    data: Vec<u8>,
+    /// Whether the page starts with the continuation of a packet from the previous page
+    continued: bool,
+    /// Granule position of the last packet that ends on this page
+    granule: u64,
+}
+
+impl PageWriter {
+    fn new(serial: u32) -> Self {
+        PageWriter {
+            serial,
+            sequence: 0,
+            segments: Vec::with_capacity(255),
+            data: Vec::new(),
+            continued: false,
+            granule: NO_GRANULE,
+        }
+    }
+
+    fn write_packet<W: Write>(
+        &mut self,
+        writer: &mut W,
+        packet: &[u8],
+        granule: u64,
+    ) -> std::io::Result<()> {
+        let mut remaining = packet;
+        let mut started = false;
+        loop {
+            if self.segments.len() == 255 {
+                self.flush(writer, 0)?;
+                self.continued = started;
+            }
+            let segment = remaining.len().min(255);
+            self.segments.push(segment as u8);
+            self.data.extend_from_slice(&remaining[..segment]);
+            remaining = &remaining[segment..];
+            started = true;
+            if segment < 255 {
+                break;
+            }
+        }
+        self.granule = granule;
+        Ok(())
+    }
+
+    fn flush<W: Write>(&mut self, writer: &mut W, header_type: u8) -> std::io::Result<()> {
+        if self.segments.is_empty() && header_type == 0 {
+            return Ok(());
+        }
+
+        let mut page = Vec::with_capacity(27 + self.segments.len() + self.data.len());
+        page.extend_from_slice(b"OggS");
+        page.push(0);
+        page.push(
+            header_type
+                | if self.continued {
+                    HEADER_TYPE_CONTINUED
+                } else {
+                    0
+                },
+        );
+        page.extend_from_slice(&self.granule.to_le_bytes());
+        page.extend_from_slice(&self.serial.to_le_bytes());
+        page.extend_from_slice(&self.sequence.to_le_bytes());
+        page.extend_from_slice(&[0; 4]);
+        page.push(self.segments.len() as u8);
+        page.extend_from_slice(&self.segments);
+        page.extend_from_slice(&self.data);
+        let crc = ogg_crc(&page);
+        page[22..26].copy_from_slice(&crc.to_le_bytes());
+        writer.write_all(&page)?;
+
+        self.sequence = self.sequence.wrapping_add(1);
+        self.segments.clear();
+        self.data.clear();
+        self.continued = false;
+        self.granule = NO_GRANULE;
+        Ok(())
+    }
+}
+
+/// Write the `OpusHead` and `OpusTags` headers of a logical bitstream, each on its own page
+fn write_headers<W: Write>(
+    page: &mut PageWriter,
+    writer: &mut W,
+    sample_rate: u32,
+    comments: &[String],
+) -> std::io::Result<()> {
+    let mut head = Vec::with_capacity(19);
+    head.extend_from_slice(b"OpusHead");
+    head.push(1);
+    head.push(1);
+    head.extend_from_slice(&PRE_SKIP.to_le_bytes());
+    head.extend_from_slice(&sample_rate.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes());
+    head.push(0);
+    page.write_packet(writer, &head, 0)?;
+    page.flush(writer, HEADER_TYPE_BOS)?;
+
+    let mut tags = Vec::new();
+    tags.extend_from_slice(b"OpusTags");
+    tags.extend_from_slice(&(VENDOR.len() as u32).to_le_bytes());
+    tags.extend_from_slice(VENDOR.as_bytes());
+    tags.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+    for comment in comments {
+        tags.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        tags.extend_from_slice(comment.as_bytes());
+    }
+    page.write_packet(writer, &tags, 0)?;
+    page.flush(writer, 0)
+}
+
+/// Writes steam voice data as a continuous, chained, Ogg Opus stream
+///
+/// The opus frames are copied into the stream as-is, silence is encoded as opus silence frames
+/// and lost frames are encoded as empty frames which triggers the packet loss concealment of the player.
+///
+/// A page is flushed after every voice payload to keep latency low when streaming live voice,
+/// and a new link is added to the chain every time the sample rate of the voice data changes
+/// or [`restart`](Self::restart) is called, so the output can be served directly to icecast-style
+/// streaming clients.
+pub struct OggOpusStream<W: Write> {
+    writer: W,
+    next_serial: u32,
+    comments: Vec<String>,
+    page: Option<PageWriter>,
+    sample_rate: Option<u16>,
+    granule: u64,
+    seq: Option<u16>,
+    last_toc: Option<u8>,
+    /// Silence that hasn't been written yet, in units of `1 / sample_rate` 48kHz samples
+    pending_silence: u64,
+}
+
+impl<W: Write> Debug for OggOpusStream<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OggOpusStream")
+            .field("sample_rate", &self.sample_rate)
+            .field("granule", &self.granule)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W: Write> OggOpusStream<W> {
+    /// Create a new stream, the first link of the chain will use the provided serial number
+    pub fn new(writer: W, serial: u32) -> Self {
+        OggOpusStream {
+            writer,
+            next_serial: serial,
+            comments: Vec::new(),
+            page: None,
+            sample_rate: None,
+            granule: 0,
+            seq: None,
+            last_toc: None,
+            pending_silence: 0,
+        }
+    }
+
+    /// Add a comment (e.g. `TITLE`) to the `OpusTags` header of all following links in the chain
+    pub fn add_comment(&mut self, tag: &str, value: &str) {
+        self.comments.push(format!("{tag}={value}"));
+    }
+
+    /// Remove all comments set with [`add_comment`](Self::add_comment)
+    pub fn clear_comments(&mut self) {
+        self.comments.clear();
+    }
+
+    /// Write the opus frames from a voice payload to the stream and flush the written pages
+    pub fn write_voice(&mut self, voice_data: &SteamVoiceData) -> Result<(), SteamAudioError> {
+        for packet in voice_data.packets() {
+            match packet? {
+                Packet::SampleRate(0) => {
+                    return Err(SteamAudioError::UnsupportedSampleRate { rate: 0 })
+                }
+                Packet::SampleRate(rate) => {
+                    if self.sample_rate != Some(rate) {
+                        self.end_link()?;
+                        self.sample_rate = Some(rate);
+                    }
+                }
+                Packet::Silence(silence) => {
+                    if let Some(rate) = self.sample_rate {
+                        self.start_link()?;
+                        self.pending_silence += silence as u64 * GRANULE_RATE;
+                        let (toc, samples) = SILENCE_FRAME;
+                        let frame_size = samples * rate as u64;
+                        while self.pending_silence >= frame_size {
+                            self.pending_silence -= frame_size;
+                            self.write_packet(&silence_packet(toc), samples)?;
+                        }
+                    }
+                }
+                Packet::RawPcm(_)
+                | Packet::Unsupported { .. }
+                | Packet::Marker(_)
+                | Packet::Unknown { .. } => {}
+                Packet::OpusPlc(opus) => {
+                    if self.sample_rate.is_none() {
+                        return Err(SteamAudioError::NoSampleRate);
+                    }
+                    self.start_link()?;
+                    for frame in OpusFrameIterator::new(opus.data) {
+                        match frame? {
+                            OpusFrame::Reset => self.seq = Some(0),
+                            OpusFrame::Frame { seq, data } => self.write_frame(seq, data)?,
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(page) = self.page.as_mut() {
+            page.flush(&mut self.writer, 0)?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// End the current link of the chain, the next voice data will start a new link with fresh headers
+    pub fn restart(&mut self) -> Result<(), SteamAudioError> {
+        self.end_link()?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// End the stream and return the underlying writer
+    pub fn finish(mut self) -> Result<W, SteamAudioError> {
+        self.restart()?;
+        Ok(self.writer)
+    }
+
+    fn write_frame(&mut self, seq: u16, data: &[u8]) -> Result<(), SteamAudioError> {
+        if let (Some(expected), Some(toc)) = (self.seq, self.last_toc) {
+            // larger gaps aren't concealed by the decoder either, it resets instead
+            if let Some(count) = frames_lost(expected, seq)
+                .filter(|count| (1..=DEFAULT_MAX_CONCEALMENT).contains(count))
+            {
+                // a code 0 packet with an empty frame signals a lost frame of the same duration
+                let lost = [toc & 0xFC];
+                let samples = packet_samples(&lost, GRANULE_RATE as u32)? as u64;
+                for _ in 0..count {
+                    self.write_packet(&lost, samples)?;
+                }
+            }
+        }
+        self.seq = Some(seq.wrapping_add(1));
+
+        if let Some(&toc) = data.first() {
+            let samples = packet_samples(data, GRANULE_RATE as u32)? as u64;
+            self.write_packet(data, samples)?;
+            self.last_toc = Some(toc);
+        }
+        Ok(())
+    }
+
+    fn write_packet(&mut self, packet: &[u8], samples: u64) -> Result<(), SteamAudioError> {
+        let page = self.page.as_mut().expect("link not started");
+        self.granule += samples;
+        page.write_packet(&mut self.writer, packet, self.granule)?;
+        Ok(())
+    }
+
+    fn start_link(&mut self) -> Result<(), SteamAudioError> {
+        if self.page.is_some() {
+            return Ok(());
+        }
+        let mut page = PageWriter::new(self.next_serial);
+        self.next_serial = self.next_serial.wrapping_add(1);
+        write_headers(
+            &mut page,
+            &mut self.writer,
+            self.sample_rate.unwrap_or_default() as u32,
+            &self.comments,
+        )?;
+        self.page = Some(page);
+        self.granule = 0;
+        Ok(())
+    }
+
+    fn end_link(&mut self) -> Result<(), SteamAudioError> {
+        if self.page.is_some() {
+            // the final page needs at least one packet to carry the end-of-stream flag
+            let (toc, samples) = SILENCE_FRAME;
+            self.write_packet(&silence_packet(toc), samples)?;
+            if let Some(mut page) = self.page.take() {
+                page.flush(&mut self.writer, HEADER_TYPE_EOS)?;
+            }
+        }
+        self.seq = None;
+        self.last_toc = None;
+        self.pending_silence = 0;
+        Ok(())
+    }
+}
+
+/// Writes the opus frames of a speaker into an Ogg Opus file without re-encoding them
+///
+/// Unlike the [`OggOpusStream`], which is meant for live streaming, the writer produces a single logical
+/// bitstream with full pages, as expected from a file. Frames are placed at their [`start`](ExtractedFrame::start)
+/// by a [`Repacketizer`], which fills the gaps before a frame with lost packets if frames were lost, and with opus
+/// silence otherwise, so the granule positions follow the timing of the voice data, including silence.
+///
+/// The frames can come from a [`FrameExtractor`], or be passed as voice payloads with
+/// [`write_voice`](Self::write_voice). Gaps that aren't part of the voice data, like the time between two
+/// utterances on the timeline of a demo, can be added with [`write_silence`](Self::write_silence).
+pub struct OggOpusWriter<W: Write> {
+    writer: W,
+    serial: u32,
+    comments: Vec<String>,
+    page: Option<PageWriter>,
+    extractor: FrameExtractor,
+    repacketizer: Repacketizer,
+    /// Offset of the positions of the frames passed to the writer, to continue after added silence
+    offset: u64,
+}
+
+impl<W: Write> Debug for OggOpusWriter<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OggOpusWriter")
+            .field("serial", &self.serial)
+            .field("position", &self.repacketizer.position())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W: Write> OggOpusWriter<W> {
+    /// Create a writer for a bitstream with the provided serial number
+    pub fn new(writer: W, serial: u32) -> Self {
+        OggOpusWriter {
+            writer,
+            serial,
+            comments: Vec::new(),
+            page: None,
+            extractor: FrameExtractor::new(),
+            repacketizer: Repacketizer::new(),
+            offset: 0,
+        }
+    }
+
+    /// Add a comment (e.g. `TITLE`) to the `OpusTags` header, only before the first frame is written
+    pub fn add_comment(&mut self, tag: &str, value: &str) {
+        self.comments.push(format!("{tag}={value}"));
+    }
+
+    /// End of the written audio in 48kHz samples
+    pub fn position(&self) -> u64 {
+        self.repacketizer.position()
+    }
+
+    /// Write the opus frames of a voice payload, timed by the silence and frames of the previous payloads
+    pub fn write_voice(&mut self, voice_data: &SteamVoiceData) -> Result<(), SteamAudioError> {
+        let offset = self.offset;
+        let frames = self.extractor.extract(voice_data)?;
+        for frame in &frames {
+            self.write_at(frame, offset + frame.start)?;
+        }
+        Ok(())
+    }
+
+    /// Write an extracted frame at its start, which is counted from the first frame written to this writer
+    ///
+    /// Frames starting before the end of the written audio are written directly after it.
+    pub fn write_frame(&mut self, frame: &ExtractedFrame) -> Result<(), SteamAudioError> {
+        self.write_at(frame, self.offset + frame.start)
+    }
+
+    /// Add `samples` 48kHz samples of silence after the written audio
+    ///
+    /// The positions of the following frames are shifted by the silence.
+    pub fn write_silence(&mut self, samples: u64) -> Result<(), SteamAudioError> {
+        self.start()?;
+        let packets = self
+            .repacketizer
+            .fill_to(self.repacketizer.position() + samples);
+        self.write_packets(&packets)?;
+        self.offset += samples;
+        Ok(())
+    }
+
+    /// Write the last page and return the underlying writer
+    pub fn finish(mut self) -> Result<W, SteamAudioError> {
+        self.start()?;
+        if let Some(mut page) = self.page.take() {
+            page.flush(&mut self.writer, HEADER_TYPE_EOS)?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_at(&mut self, frame: &ExtractedFrame, start: u64) -> Result<(), SteamAudioError> {
+        self.start()?;
+        let frame = ExtractedFrame { start, ..*frame };
+        let packets = self.repacketizer.push(&frame)?;
+        self.write_packets(&packets)
+    }
+
+    fn write_packets(&mut self, packets: &[OpusPacket]) -> Result<(), SteamAudioError> {
+        let page = self.page.as_mut().expect("headers not written");
+        for packet in packets {
+            page.write_packet(
+                &mut self.writer,
+                &packet.data,
+                packet.start + packet.duration,
+            )?;
+            if page.data.len() >= PAGE_SIZE {
+                page.flush(&mut self.writer, 0)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the headers if they haven't been written yet
+    fn start(&mut self) -> Result<(), SteamAudioError> {
+        if self.page.is_none() {
+            let mut page = PageWriter::new(self.serial);
+            write_headers(
+                &mut page,
+                &mut self.writer,
+                GRANULE_RATE as u32,
+                &self.comments,
+            )?;
+            self.page = Some(page);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data::{opus, payload, sample_rate, silence, FRAME};
+
+    #[test]
+    fn stream_rejects_sample_rate_zero() {
+        let payload = payload(&[sample_rate(0), silence(480)]);
+        let voice_data = SteamVoiceData::new(&payload).unwrap();
+        let mut stream = OggOpusStream::new(Vec::new(), 1);
+        assert!(matches!(
+            stream.write_voice(&voice_data),
+            Err(SteamAudioError::UnsupportedSampleRate { rate: 0 })
+        ));
+    }
+
+    #[test]
+    fn writer_rejects_sample_rate_zero() {
+        let payload = payload(&[sample_rate(0), silence(480)]);
+        let voice_data = SteamVoiceData::new(&payload).unwrap();
+        let mut writer = OggOpusWriter::new(Vec::new(), 1);
+        assert!(matches!(
+            writer.write_voice(&voice_data),
+            Err(SteamAudioError::UnsupportedSampleRate { rate: 0 })
+        ));
+    }
+
+    #[test]
+    fn writer_fills_gaps_with_silence() {
+        let payload = payload(&[sample_rate(24000), silence(600), opus(&[(0, FRAME)])]);
+        let mut writer = OggOpusWriter::new(Vec::new(), 1);
+        writer
+            .write_voice(&SteamVoiceData::new(&payload).unwrap())
+            .unwrap();
+        writer.write_silence(120).unwrap();
+        assert_eq!(writer.position(), 1200 + 960 + 120);
+    }
+
+    #[test]
+    fn stream_doesnt_conceal_large_gaps() {
+        let mut stream = OggOpusStream::new(Vec::new(), 1);
+        for seq in [0, 10, 60000] {
+            let payload = payload(&[sample_rate(24000), opus(&[(seq, FRAME)])]);
+            stream
+                .write_voice(&SteamVoiceData::new(&payload).unwrap())
+                .unwrap();
+        }
+        // the frames and the 9 lost frames of the first gap
+        assert_eq!(stream.granule, 12 * 960);
+    }
+}
@@ -0,0 +1,205 @@
+use crate::backend::{BackendConstructor, DEFAULT_BACKEND};
+use crate::{is_opus_rate, SteamAudioError, SteamVoiceDecoder, DEFAULT_MAX_CONCEALMENT};
+use std::time::Duration;
+
+/// Number of channels of the decoded audio
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputChannels {
+    #[default]
+    Mono,
+    /// Interleaved stereo, with the voice in both channels
+    Stereo,
+}
+
+impl OutputChannels {
+    pub fn count(&self) -> usize {
+        match self {
+            OutputChannels::Mono => 1,
+            OutputChannels::Stereo => 2,
+        }
+    }
+}
+
+/// How malformed voice data is handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Fail the decode on the first malformed packet
+    #[default]
+    Strict,
+    /// Skip malformed packets, resuming at the next plausible packet found by [`Packet::resync`](crate::Packet::resync).
+    /// Packets of unknown types are skipped, see [`Packet::read_lenient`](crate::Packet::read_lenient)
+    Lenient,
+}
+
+/// Configuration for a [`SteamVoiceDecoder`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecoderOptions {
+    /// Write zeros into the output for silence, when disabled the output buffer is only
+    /// advanced over the silence and keeps its previous contents. Enabled by default
+    pub zero_fill_silence: bool,
+    pub parse_mode: ParseMode,
+    /// Maximum number of lost frames that are concealed at once, larger gaps reset the decoder instead.
+    ///
+    /// Defaults to [`DEFAULT_MAX_CONCEALMENT`], `None` conceals gaps of any size
+    pub max_concealment: Option<u16>,
+    /// Recover the last frame of a loss from the forward error correction data in the frame
+    /// that follows it, instead of concealing it, if the backend supports it. Enabled by default
+    pub use_fec: bool,
+    /// Decode at a fixed sample rate instead of the sample rate of the voice data.
+    ///
+    /// Without a fixed rate, voice data with a legacy sample rate is decoded at its [`decode_rate`](crate::decode_rate).
+    /// Uncompressed samples are resampled to the rate the opus audio is decoded at
+    pub output_rate: Option<u32>,
+    pub channels: OutputChannels,
+    /// Maximum number of samples a single payload can decode to
+    pub max_output_samples: Option<usize>,
+    /// Maximum number of samples the decoder produces over all payloads, counted from the
+    /// [`position`](SteamVoiceDecoder::position) of the decoder
+    pub max_total_samples: Option<u64>,
+    /// Fail with [`SteamAudioError::TrailingBytes`] when the frames of an opus packet are followed
+    /// by bytes too short to hold another frame, instead of ignoring them. Disabled by default
+    pub reject_trailing_bytes: bool,
+    /// Gain applied to the decoded audio, including concealed frames, in 1/256 dB
+    pub gain: i16,
+    /// Length of the fades smoothing the edges between decoded audio, concealment and silence,
+    /// which would otherwise click. Zero disables the fades, the default
+    pub fade: Duration,
+    /// Remove a DC offset from the decoded audio with a [`DcBlocker`](crate::DcBlocker). Disabled by default
+    pub dc_filter: bool,
+}
+
+impl Default for DecoderOptions {
+    fn default() -> Self {
+        DecoderOptions {
+            zero_fill_silence: true,
+            parse_mode: ParseMode::default(),
+            max_concealment: Some(DEFAULT_MAX_CONCEALMENT),
+            use_fec: true,
+            output_rate: None,
+            channels: OutputChannels::default(),
+            max_output_samples: None,
+            max_total_samples: None,
+            reject_trailing_bytes: false,
+            gain: 0,
+            fade: Duration::ZERO,
+            dc_filter: false,
+        }
+    }
+}
+
+/// Builder for a [`SteamVoiceDecoder`] with non-default options
+#[derive(Debug, Clone)]
+pub struct SteamVoiceDecoderBuilder {
+    options: DecoderOptions,
+    backend: BackendConstructor,
+}
+
+impl Default for SteamVoiceDecoderBuilder {
+    fn default() -> Self {
+        SteamVoiceDecoderBuilder {
+            options: DecoderOptions::default(),
+            backend: DEFAULT_BACKEND,
+        }
+    }
+}
+
+impl SteamVoiceDecoderBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use a different opus implementation for decoding
+    pub fn backend(mut self, backend: BackendConstructor) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn zero_fill_silence(mut self, zero_fill: bool) -> Self {
+        self.options.zero_fill_silence = zero_fill;
+        self
+    }
+
+    pub fn parse_mode(mut self, mode: ParseMode) -> Self {
+        self.options.parse_mode = mode;
+        self
+    }
+
+    /// Set the maximum number of lost frames that are concealed at once, `None` removes the limit
+    pub fn max_concealment(mut self, frames: Option<u16>) -> Self {
+        self.options.max_concealment = frames;
+        self
+    }
+
+    pub fn use_fec(mut self, use_fec: bool) -> Self {
+        self.options.use_fec = use_fec;
+        self
+    }
+
+    /// Decode at a fixed sample rate, must be one of 8, 12, 16, 24 or 48 kHz
+    pub fn output_rate(mut self, rate: u32) -> Self {
+        self.options.output_rate = Some(rate);
+        self
+    }
+
+    pub fn channels(mut self, channels: OutputChannels) -> Self {
+        self.options.channels = channels;
+        self
+    }
+
+    pub fn max_output_samples(mut self, samples: usize) -> Self {
+        self.options.max_output_samples = Some(samples);
+        self
+    }
+
+    pub fn max_total_samples(mut self, samples: u64) -> Self {
+        self.options.max_total_samples = Some(samples);
+        self
+    }
+
+    pub fn reject_trailing_bytes(mut self, reject: bool) -> Self {
+        self.options.reject_trailing_bytes = reject;
+        self
+    }
+
+    /// Amplify the decoded audio by `db` decibels, negative values attenuate it
+    pub fn gain(mut self, db: f32) -> Self {
+        self.options.gain = gain_from_db(db);
+        self
+    }
+
+    /// Fade in audio after silence and smooth the edges between decoded audio, concealment and silence
+    /// over `fade`, a few milliseconds are enough to remove clicks
+    pub fn fade(mut self, fade: Duration) -> Self {
+        self.options.fade = fade;
+        self
+    }
+
+    pub fn dc_filter(mut self, dc_filter: bool) -> Self {
+        self.options.dc_filter = dc_filter;
+        self
+    }
+
+    pub fn options(&self) -> &DecoderOptions {
+        &self.options
+    }
+
+    pub fn build(self) -> Result<SteamVoiceDecoder, SteamAudioError> {
+        SteamVoiceDecoder::with_backend(self.options, self.backend)
+    }
+}
+
+/// Convert a gain in decibels to the 1/256 dB steps used by opus, saturating at the limits
+pub(crate) fn gain_from_db(db: f32) -> i16 {
+    (db * 256.0).round() as i16
+}
+
+impl DecoderOptions {
+    pub(crate) fn validate(&self) -> Result<(), SteamAudioError> {
+        match self.output_rate {
+            Some(rate) if !is_opus_rate(rate) => {
+                Err(SteamAudioError::UnsupportedSampleRate { rate })
+            }
+            _ => Ok(()),
+        }
+    }
+}
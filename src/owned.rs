@@ -0,0 +1,61 @@
+//! Voice data that owns its packet data
+
+use crate::{verify_crc, SteamAudioError, SteamVoiceData, HEADER_SIZE, MIN_PAYLOAD_SIZE};
+
+/// A [`SteamVoiceData`] that owns its packet data, so it can outlive the buffer it was parsed from
+///
+/// The packets are read by borrowing it as a [`SteamVoiceData`] with [`as_voice_data`](Self::as_voice_data).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OwnedSteamVoiceData {
+    pub steam_id: u64,
+    packet_data: Vec<u8>,
+}
+
+impl OwnedSteamVoiceData {
+    /// Parse a payload, verifying its checksum, reusing the buffer for the packet data
+    pub fn new(mut data: Vec<u8>) -> Result<Self, SteamAudioError> {
+        if data.len() < MIN_PAYLOAD_SIZE {
+            return Err(SteamAudioError::InsufficientData);
+        }
+        let length = verify_crc(&data)?.len();
+        data.truncate(length);
+        let steam_id = SteamVoiceData::without_crc(&data)?.steam_id;
+        data.drain(..HEADER_SIZE);
+        Ok(Self::from_parts(steam_id, data))
+    }
+
+    pub(crate) fn from_parts(steam_id: u64, packet_data: Vec<u8>) -> Self {
+        OwnedSteamVoiceData {
+            steam_id,
+            packet_data,
+        }
+    }
+
+    /// Borrow the voice data to read its packets or decode it
+    pub fn as_voice_data(&self) -> SteamVoiceData<'_> {
+        SteamVoiceData {
+            steam_id: self.steam_id,
+            packet_data: &self.packet_data,
+        }
+    }
+}
+
+impl From<SteamVoiceData<'_>> for OwnedSteamVoiceData {
+    fn from(voice_data: SteamVoiceData<'_>) -> Self {
+        voice_data.into_owned()
+    }
+}
+
+impl<'a> From<&'a OwnedSteamVoiceData> for SteamVoiceData<'a> {
+    fn from(voice_data: &'a OwnedSteamVoiceData) -> Self {
+        voice_data.as_voice_data()
+    }
+}
+
+impl TryFrom<Vec<u8>> for OwnedSteamVoiceData {
+    type Error = SteamAudioError;
+
+    fn try_from(data: Vec<u8>) -> Result<Self, Self::Error> {
+        Self::new(data)
+    }
+}
@@ -0,0 +1,167 @@
+//! Decoding voice payloads arriving as timestamped buffers, as in the elements of media pipelines
+
+use crate::{DecoderOptions, SteamAudioError, SteamVoiceData, SteamVoiceDecoder};
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+/// Media type of buffers holding a single steam voice payload
+pub const STEAM_VOICE_MEDIA_TYPE: &str = "audio/x-steam-voice";
+
+/// Decodes voice payloads arriving as buffers with presentation timestamps, the way a decoder element of a media
+/// pipeline does
+///
+/// Every buffer holds a single payload, it decodes to interleaved 16 bit PCM that is timestamped in nanoseconds.
+/// Buffers with a timestamp set the timestamp of their audio, buffers without one continue after the audio of the
+/// buffer before them. The format of the output is described as raw audio caps, with
+/// [`take_caps_change`](Self::take_caps_change) telling when the format has to be renegotiated, before pushing
+/// the audio that follows.
+#[derive(Debug)]
+pub struct VoiceBufferDecoder {
+    decoder: SteamVoiceDecoder,
+    samples: Vec<i16>,
+    /// Timestamp of the last decoded audio and the audio following it, in nanoseconds
+    pts: Option<u64>,
+    next_pts: Option<u64>,
+    /// Sample rate and channels of the negotiated output
+    format: Option<(u32, usize)>,
+    format_changed: bool,
+}
+
+impl VoiceBufferDecoder {
+    pub fn new(options: DecoderOptions) -> Result<Self, SteamAudioError> {
+        Ok(VoiceBufferDecoder {
+            decoder: SteamVoiceDecoder::with_options(options)?,
+            samples: Vec::new(),
+            pts: None,
+            next_pts: None,
+            format: None,
+            format_changed: false,
+        })
+    }
+
+    /// Decode the payload of a buffer with the presentation timestamp `pts` in nanoseconds, returning the
+    /// interleaved samples
+    pub fn decode(&mut self, buffer: &[u8], pts: Option<u64>) -> Result<&[i16], SteamAudioError> {
+        self.samples.clear();
+        let voice_data = SteamVoiceData::new(buffer)?;
+        self.decoder.decode_append(voice_data, &mut self.samples)?;
+
+        if let Some(sample_rate) = self.decoder.output_rate() {
+            let format = (sample_rate, self.channels());
+            if self.format != Some(format) {
+                self.format = Some(format);
+                self.format_changed = true;
+            }
+        }
+        self.pts = pts.or(self.next_pts);
+        self.next_pts = self.pts.map(|pts| pts + self.duration());
+        Ok(self.last_decoded())
+    }
+
+    /// The samples of the last decoded buffer
+    pub fn last_decoded(&self) -> &[i16] {
+        &self.samples
+    }
+
+    /// Presentation timestamp of the last decoded audio in nanoseconds, if any buffer had a timestamp
+    pub fn pts(&self) -> Option<u64> {
+        self.pts
+    }
+
+    /// Duration of the last decoded audio in nanoseconds
+    pub fn duration(&self) -> u64 {
+        match self.format {
+            Some((sample_rate, channels)) => {
+                (self.samples.len() / channels) as u64 * NANOS_PER_SECOND / sample_rate as u64
+            }
+            None => 0,
+        }
+    }
+
+    pub fn sample_rate(&self) -> Option<u32> {
+        self.format.map(|(sample_rate, _)| sample_rate)
+    }
+
+    pub fn channels(&self) -> usize {
+        self.decoder.options().channels.count()
+    }
+
+    /// The raw audio caps of the output, once the sample rate is known
+    pub fn caps(&self) -> Option<String> {
+        self.format.map(|(sample_rate, channels)| {
+            format!(
+                "audio/x-raw, format=(string)S16LE, layout=(string)interleaved, rate=(int){sample_rate}, channels=(int){channels}"
+            )
+        })
+    }
+
+    /// The caps of the output if they changed since the last call, to negotiate the new format before pushing the
+    /// last decoded audio
+    pub fn take_caps_change(&mut self) -> Option<String> {
+        if !std::mem::take(&mut self.format_changed) {
+            return None;
+        }
+        self.caps()
+    }
+
+    /// Reset the decoder after a discontinuity or flush, the next buffer starts a new, unrelated, stream
+    pub fn reset(&mut self) -> Result<(), SteamAudioError> {
+        self.samples.clear();
+        self.pts = None;
+        self.next_pts = None;
+        self.decoder.reset()
+    }
+
+    /// Get back the decoder
+    pub fn into_decoder(self) -> SteamVoiceDecoder {
+        self.decoder
+    }
+}
+
+#[cfg(all(
+    test,
+    any(feature = "opus", feature = "audiopus", feature = "opus-decoder")
+))]
+mod tests {
+    use super::*;
+    use crate::test_data::{payload, raw, sample_rate};
+    use crate::OutputChannels;
+
+    #[test]
+    fn timestamps_and_caps() {
+        let options = DecoderOptions {
+            channels: OutputChannels::Stereo,
+            ..DecoderOptions::default()
+        };
+        let mut decoder = VoiceBufferDecoder::new(options).unwrap();
+        assert_eq!(decoder.caps(), None);
+
+        let samples = [1i16; 240];
+        let buffer = payload(&[sample_rate(24000), raw(&samples)]);
+        assert_eq!(decoder.decode(&buffer, Some(1_000)).unwrap().len(), 480);
+        assert_eq!(decoder.pts(), Some(1_000));
+        assert_eq!(decoder.duration(), 10_000_000);
+        assert_eq!(
+            decoder.take_caps_change().as_deref(),
+            Some("audio/x-raw, format=(string)S16LE, layout=(string)interleaved, rate=(int)24000, channels=(int)2")
+        );
+
+        // buffers without a timestamp continue after the one before, the format only changes once
+        decoder.decode(&buffer, None).unwrap();
+        assert_eq!(decoder.pts(), Some(10_001_000));
+        assert_eq!(decoder.take_caps_change(), None);
+
+        let buffer = payload(&[sample_rate(16000), raw(&samples)]);
+        decoder.decode(&buffer, Some(50_000_000)).unwrap();
+        assert_eq!(decoder.pts(), Some(50_000_000));
+        assert_eq!(decoder.duration(), 15_000_000);
+        assert_eq!(decoder.sample_rate(), Some(16000));
+        assert!(decoder.take_caps_change().is_some());
+
+        decoder.reset().unwrap();
+        assert!(decoder.last_decoded().is_empty());
+        assert_eq!(decoder.pts(), None);
+        decoder.decode(&buffer, None).unwrap();
+        assert_eq!(decoder.pts(), None);
+    }
+}
@@ -0,0 +1,371 @@
+//! Live playback of the voice of a session on an audio output
+
+use crate::{
+    DecoderOptions, LinearResampler, Mixer, OutputChannels, Resampler, ResamplerConstructor,
+    SteamAudioError, TickTimeline, VoiceSession,
+};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Audio shared between a [`VoicePlayback`] and its [`VoiceSource`]
+#[derive(Debug, Default)]
+struct Queue {
+    samples: VecDeque<i16>,
+    /// Whether the playback ended, after which the source ends once the queue is empty
+    finished: bool,
+}
+
+fn lock(queue: &Mutex<Queue>) -> MutexGuard<'_, Queue> {
+    queue
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Decodes and mixes the voice of a demo for playback through a [`VoiceSource`], without writing it to a file first
+///
+/// Voice payloads are pushed with the demo tick they were received at, the audio of all speakers is placed on the
+/// timeline of the session and mixed, so playback keeps the timing of the demo, including the silence between
+/// utterances. The mixer can be changed during playback, to mute, solo or pan speakers or duck the others while
+/// the leader talks, which affects the audio mixed after the change.
+///
+/// The payloads are usually pushed from the thread parsing the demo while the source is played on the audio
+/// thread of an output library, the source keeps the output running with silence while it waits for more audio.
+/// Dropping the playback, or calling [`finish`](Self::finish), ends the source once it played all queued audio.
+///
+/// Audio outputs often only run at 44.1kHz or 48kHz, with [`with_output_rate`](Self::with_output_rate) the mix is
+/// converted to the rate of the output.
+///
+/// With the `cpal` feature `play_default_output` plays the source on the default audio output of the system.
+#[derive(Debug)]
+pub struct VoicePlayback {
+    session: VoiceSession,
+    mixer: Mixer,
+    queue: Arc<Mutex<Queue>>,
+    output_rate: u32,
+    /// Converts the mix from the rate of the timeline to the output rate, if they differ
+    resampler: Option<Box<dyn Resampler>>,
+    /// Interleaved mixed audio, before it is moved into the queue
+    buffer: Vec<i16>,
+    resampled: Vec<i16>,
+}
+
+impl VoicePlayback {
+    /// Create a playback for voice placed on `timeline`, mixed to `channels`, and the source playing it
+    pub fn new(
+        timeline: TickTimeline,
+        channels: OutputChannels,
+    ) -> Result<(Self, VoiceSource), SteamAudioError> {
+        let output_rate = timeline.sample_rate();
+        Self::with_output_rate(timeline, channels, output_rate)
+    }
+
+    /// Create a playback for voice placed on `timeline`, mixed to `channels` and played at `output_rate`, and the
+    /// source playing it
+    ///
+    /// The mix is converted from the rate of the timeline with a [`LinearResampler`], a better resampler can be
+    /// set with [`set_resampler`](Self::set_resampler).
+    pub fn with_output_rate(
+        timeline: TickTimeline,
+        channels: OutputChannels,
+        output_rate: u32,
+    ) -> Result<(Self, VoiceSource), SteamAudioError> {
+        if output_rate == 0 {
+            return Err(SteamAudioError::UnsupportedSampleRate { rate: output_rate });
+        }
+        let sample_rate = timeline.sample_rate();
+        let session = VoiceSession::with_timeline(DecoderOptions::default(), timeline)?;
+        let queue = Arc::new(Mutex::new(Queue::default()));
+        let source = VoiceSource {
+            queue: queue.clone(),
+            sample_rate: output_rate,
+            channels: channels.count() as u16,
+            silence: 0,
+        };
+        let resampler = (sample_rate != output_rate).then(|| {
+            Box::new(LinearResampler::new(
+                sample_rate,
+                output_rate,
+                channels.count(),
+            )) as Box<dyn Resampler>
+        });
+        let playback = VoicePlayback {
+            session,
+            mixer: Mixer::new(channels),
+            queue,
+            output_rate,
+            resampler,
+            buffer: Vec::new(),
+            resampled: Vec::new(),
+        };
+        Ok((playback, source))
+    }
+
+    /// Create a playback for voice placed on `timeline`, mixed to `channels`, playing on the default audio output
+    ///
+    /// The mix is converted to the default rate of the output device, `on_error` is called with errors that occur
+    /// on the audio thread while the stream is playing. The output stops when the returned stream is dropped.
+    #[cfg(feature = "cpal")]
+    pub fn play_default_output<E>(
+        timeline: TickTimeline,
+        channels: OutputChannels,
+        on_error: E,
+    ) -> Result<(Self, cpal::Stream), SteamAudioError>
+    where
+        E: FnMut(cpal::StreamError) + Send + 'static,
+    {
+        use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+        use cpal::SampleFormat;
+
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or(SteamAudioError::NoOutputDevice)?;
+        let supported = device.default_output_config()?;
+        let mut config = supported.config();
+        config.channels = channels.count() as u16;
+        let (playback, mut source) =
+            Self::with_output_rate(timeline, channels, config.sample_rate.0)?;
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => device.build_output_stream(
+                &config,
+                move |output: &mut [f32], _: &_| {
+                    source.fill_f32(output);
+                },
+                on_error,
+                None,
+            )?,
+            SampleFormat::I16 => device.build_output_stream(
+                &config,
+                move |output: &mut [i16], _: &_| {
+                    source.fill(output);
+                },
+                on_error,
+                None,
+            )?,
+            SampleFormat::U16 => device.build_output_stream(
+                &config,
+                move |output: &mut [u16], _: &_| {
+                    source.fill_with(output, |sample| (sample as u16) ^ 0x8000);
+                },
+                on_error,
+                None,
+            )?,
+            format => return Err(SteamAudioError::UnsupportedSampleFormat { format }),
+        };
+        stream.play()?;
+        Ok((playback, stream))
+    }
+
+    /// Convert the mix to the output rate with a resampler created by `constructor`
+    ///
+    /// This has no effect when the output rate matches the rate of the timeline.
+    pub fn set_resampler(&mut self, constructor: ResamplerConstructor) {
+        if let Some(resampler) = &self.resampler {
+            self.resampler = Some(constructor(
+                resampler.input_rate(),
+                resampler.output_rate(),
+                self.mixer.channels().count(),
+            ));
+        }
+    }
+
+    /// Sample rate of the audio of the source
+    pub fn output_rate(&self) -> u32 {
+        self.output_rate
+    }
+
+    pub fn session(&self) -> &VoiceSession {
+        &self.session
+    }
+
+    /// The session decoding the voice, for enabling processing of the speakers
+    ///
+    /// The session has to keep decoding to mono for the mixer.
+    pub fn session_mut(&mut self) -> &mut VoiceSession {
+        &mut self.session
+    }
+
+    pub fn mixer(&self) -> &Mixer {
+        &self.mixer
+    }
+
+    pub fn mixer_mut(&mut self) -> &mut Mixer {
+        &mut self.mixer
+    }
+
+    /// Decode a voice payload received at `tick` and queue the mixed audio for playback
+    ///
+    /// Returns the steam id of the speaker.
+    pub fn push(&mut self, tick: u32, payload: &[u8]) -> Result<u64, SteamAudioError> {
+        let steam_id = self.session.push(tick, payload)?;
+        self.mix();
+        Ok(steam_id)
+    }
+
+    /// Number of samples per channel that are queued but not played yet
+    pub fn queued(&self) -> usize {
+        lock(&self.queue).samples.len() / self.mixer.channels().count()
+    }
+
+    /// Duration of the audio that is queued but not played yet, for keeping the decoding ahead of the playback
+    /// without decoding a whole demo in advance
+    pub fn queued_duration(&self) -> Duration {
+        Duration::from_secs_f64(self.queued() as f64 / self.output_rate as f64)
+    }
+
+    /// End the playback, the source ends once it played the queued audio
+    pub fn finish(self) {}
+
+    /// Mix the decoded audio of all speakers up to the end of the session into the queue
+    fn mix(&mut self) {
+        self.session.mix(&mut self.mixer);
+        self.buffer.clear();
+        self.mixer.take_append(self.session.end(), &mut self.buffer);
+        let mixed = match self.resampler.as_mut() {
+            Some(resampler) => {
+                self.resampled.clear();
+                resampler.process(&self.buffer, &mut self.resampled);
+                &self.resampled
+            }
+            None => &self.buffer,
+        };
+        lock(&self.queue).samples.extend(mixed);
+    }
+}
+
+impl Drop for VoicePlayback {
+    fn drop(&mut self) {
+        lock(&self.queue).finished = true;
+    }
+}
+
+/// The audio of a [`VoicePlayback`], as an iterator of interleaved samples for an audio output
+///
+/// With the `rodio` feature the source implements `rodio::Source`, so it can be played with a `rodio` sink.
+///
+/// While the playback is running and no audio is queued the source produces silence, once the playback ended the
+/// source ends after the queued audio.
+#[derive(Debug)]
+pub struct VoiceSource {
+    queue: Arc<Mutex<Queue>>,
+    sample_rate: u32,
+    channels: u16,
+    /// Remaining samples of a frame of silence, so waiting for audio doesn't swap the channels
+    silence: u16,
+}
+
+impl VoiceSource {
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Number of samples until the format of the source changes, which it never does
+    pub fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    /// The length of the voice isn't known while it is being decoded
+    pub fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Fill the buffer of an audio output with interleaved samples, returns the number of samples taken from the
+    /// playback, the rest of the buffer is filled with silence
+    pub fn fill(&mut self, output: &mut [i16]) -> usize {
+        self.fill_with(output, |sample| sample)
+    }
+
+    /// Fill the buffer of an audio output with interleaved samples between -1 and 1, returns the number of samples
+    /// taken from the playback, the rest of the buffer is filled with silence
+    pub fn fill_f32(&mut self, output: &mut [f32]) -> usize {
+        self.fill_with(output, |sample| sample as f32 / 32768.0)
+    }
+
+    fn fill_with<T: Copy>(&mut self, output: &mut [T], convert: fn(i16) -> T) -> usize {
+        let zero = convert(0);
+        let silence = (self.silence as usize).min(output.len());
+        output[..silence].fill(zero);
+        self.silence -= silence as u16;
+        let output = &mut output[silence..];
+
+        let mut queue = lock(&self.queue);
+        let count = output.len().min(queue.samples.len());
+        for (output, sample) in output.iter_mut().zip(queue.samples.drain(..count)) {
+            *output = convert(sample);
+        }
+        let rest = &mut output[count..];
+        rest.fill(zero);
+        // the queue only holds whole frames, silence that ends within a frame continues in the next buffer
+        let channels = self.channels as usize;
+        if !queue.finished && rest.len() % channels != 0 {
+            self.silence = (channels - rest.len() % channels) as u16;
+        }
+        count
+    }
+
+    /// Whether the playback ended and all queued audio was played
+    pub fn is_finished(&self) -> bool {
+        let queue = lock(&self.queue);
+        queue.finished && queue.samples.is_empty()
+    }
+}
+
+impl Iterator for VoiceSource {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.silence > 0 {
+            self.silence -= 1;
+            return Some(0);
+        }
+        let mut queue = lock(&self.queue);
+        match queue.samples.pop_front() {
+            Some(sample) => Some(sample),
+            None if queue.finished => None,
+            None => {
+                self.silence = self.channels - 1;
+                Some(0)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rodio")]
+impl rodio::Source for VoiceSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        VoiceSource::current_frame_len(self)
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        VoiceSource::total_duration(self)
+    }
+}
+
+#[cfg(all(test, feature = "rodio"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rodio_source() {
+        let timeline = TickTimeline::new(24000, Duration::from_millis(15));
+        let (playback, source) =
+            VoicePlayback::with_output_rate(timeline, OutputChannels::Stereo, 48000).unwrap();
+        assert_eq!(rodio::Source::sample_rate(&source), 48000);
+        assert_eq!(rodio::Source::channels(&source), 2);
+        assert_eq!(rodio::Source::total_duration(&source), None);
+        playback.finish();
+        assert_eq!(source.count(), 0);
+    }
+}
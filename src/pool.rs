@@ -0,0 +1,74 @@
+//! Sharing decoders between threads
+
+use crate::{DecoderOptions, SteamAudioError, SteamVoiceDecoder};
+use std::sync::Mutex;
+
+/// A pool of idle decoders that can be shared between threads
+///
+/// Creating a decoder allocates a new libopus state for every sample rate it encounters, by checking out
+/// decoders from the pool and returning them after use, servers that handle many short connections can
+/// reuse the existing opus state instead.
+#[derive(Debug)]
+pub struct DecoderPool {
+    options: DecoderOptions,
+    max_idle: usize,
+    idle: Mutex<Vec<SteamVoiceDecoder>>,
+}
+
+// decoders are moved between threads through the pool
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    fn assert_sync<T: Sync>() {}
+    assert_send::<SteamVoiceDecoder>();
+    assert_sync::<DecoderPool>();
+};
+
+impl DecoderPool {
+    /// Create a pool for decoders with the provided options, keeping at most `max_idle` idle decoders
+    pub fn new(options: DecoderOptions, max_idle: usize) -> Result<Self, SteamAudioError> {
+        options.validate()?;
+        Ok(DecoderPool {
+            options,
+            max_idle,
+            idle: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub fn options(&self) -> &DecoderOptions {
+        &self.options
+    }
+
+    /// Take an idle decoder from the pool, or create a new one if there are no idle decoders
+    pub fn checkout(&self) -> Result<SteamVoiceDecoder, SteamAudioError> {
+        match self.lock().pop() {
+            Some(decoder) => Ok(decoder),
+            None => SteamVoiceDecoder::with_options(self.options),
+        }
+    }
+
+    /// Return a decoder to the pool, resetting its state
+    ///
+    /// The filter and event handler of the decoder are removed, so they don't carry over to the next user.
+    /// Decoders with different options than the pool, or that exceed the maximum number of idle decoders, are dropped.
+    pub fn checkin(&self, mut decoder: SteamVoiceDecoder) {
+        if *decoder.options() != self.options || decoder.reset().is_err() {
+            return;
+        }
+        decoder.set_filter(None);
+        decoder.clear_event_handler();
+        let mut idle = self.lock();
+        if idle.len() < self.max_idle {
+            idle.push(decoder);
+        }
+    }
+
+    /// Number of idle decoders in the pool
+    pub fn idle(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<SteamVoiceDecoder>> {
+        // the idle decoders are always in a consistent state, even if another thread panicked
+        self.idle.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,120 @@
+//! Deciding which speakers to keep when too many people talk at once
+
+use std::cmp::Ordering;
+
+/// A speaker that is currently talking
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ActiveSpeaker {
+    pub steam_id: u64,
+    /// Current level of the speaker as RMS, in the range `0.0..=1.0`
+    pub level: f32,
+    /// Sample position at which the speaker started talking
+    pub started: u64,
+}
+
+/// How a speaker should be treated by the mix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MixDecision {
+    /// Mix the speaker at full volume
+    Include,
+    /// Mix the speaker at reduced volume
+    Duck,
+    /// Leave the speaker out of the mix
+    Exclude,
+}
+
+/// Policy that orders speakers by importance, speakers with a higher priority are kept first
+///
+/// Any `Fn(&ActiveSpeaker) -> f32` returning the priority of a speaker can be used as a policy, which allows for
+/// things like "medics first" based on information from the demo.
+pub trait SpeakerPriority {
+    /// Compare the priority of two speakers, [`Ordering::Greater`] if `a` should be kept before `b`
+    fn compare(&self, a: &ActiveSpeaker, b: &ActiveSpeaker) -> Ordering;
+}
+
+impl<F: Fn(&ActiveSpeaker) -> f32> SpeakerPriority for F {
+    fn compare(&self, a: &ActiveSpeaker, b: &ActiveSpeaker) -> Ordering {
+        self(a).partial_cmp(&self(b)).unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Prioritize the loudest speakers
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoudestFirst;
+
+impl SpeakerPriority for LoudestFirst {
+    fn compare(&self, a: &ActiveSpeaker, b: &ActiveSpeaker) -> Ordering {
+        a.level.partial_cmp(&b.level).unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Prioritize the speakers that started talking first
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FirstComeFirstServed;
+
+impl SpeakerPriority for FirstComeFirstServed {
+    fn compare(&self, a: &ActiveSpeaker, b: &ActiveSpeaker) -> Ordering {
+        b.started.cmp(&a.started)
+    }
+}
+
+/// Prioritize speakers by their position in a caller-defined ranking,
+/// speakers not in the ranking come after all ranked speakers
+#[derive(Debug, Clone, Default)]
+pub struct Ranking {
+    steam_ids: Vec<u64>,
+}
+
+impl Ranking {
+    pub fn new(steam_ids: Vec<u64>) -> Self {
+        Ranking { steam_ids }
+    }
+
+    fn position(&self, speaker: &ActiveSpeaker) -> usize {
+        self.steam_ids
+            .iter()
+            .position(|id| *id == speaker.steam_id)
+            .unwrap_or(usize::MAX)
+    }
+}
+
+impl SpeakerPriority for Ranking {
+    fn compare(&self, a: &ActiveSpeaker, b: &ActiveSpeaker) -> Ordering {
+        self.position(b).cmp(&self.position(a))
+    }
+}
+
+/// Limits the number of speakers in a mix, applied by `Mixer::set_speaker_limit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpeakerLimit {
+    /// Number of speakers mixed at full volume
+    pub included: usize,
+    /// Number of speakers after the included speakers that are mixed ducked
+    pub ducked: usize,
+}
+
+impl SpeakerLimit {
+    /// Decide how each of the active speakers should be mixed,
+    /// the returned decisions are in the same order as the provided speakers
+    pub fn decide<P: SpeakerPriority + ?Sized>(
+        &self,
+        policy: &P,
+        speakers: &[ActiveSpeaker],
+    ) -> Vec<MixDecision> {
+        let mut order: Vec<usize> = (0..speakers.len()).collect();
+        // stable sort, ties keep the order in which the speakers were provided
+        order.sort_by(|&a, &b| policy.compare(&speakers[b], &speakers[a]));
+
+        let mut decisions = vec![MixDecision::Exclude; speakers.len()];
+        for (rank, index) in order.into_iter().enumerate() {
+            decisions[index] = if rank < self.included {
+                MixDecision::Include
+            } else if rank < self.included + self.ducked {
+                MixDecision::Duck
+            } else {
+                MixDecision::Exclude
+            };
+        }
+        decisions
+    }
+}
@@ -0,0 +1,78 @@
+//! Minimal protobuf reader for the voice messages of newer Source games
+
+use crate::{read_bytes, SteamAudioError};
+
+/// The value of a protobuf field
+pub(crate) enum Value<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    // only the cs2 messages contain fixed32 fields
+    #[cfg_attr(not(feature = "cs2"), allow(dead_code))]
+    Fixed32(u32),
+}
+
+/// Iterator over the fields of a protobuf message, as field number and value
+pub(crate) struct Fields<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Fields<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Fields { data }
+    }
+
+    fn read_field(&mut self) -> Result<(u64, Value<'a>), SteamAudioError> {
+        let key = read_varint(&mut self.data)?;
+        let value = match key & 0x07 {
+            0 => Value::Varint(read_varint(&mut self.data)?),
+            1 => Value::Fixed64(u64::from_le_bytes(read_n(&mut self.data)?)),
+            2 => {
+                let length = usize::try_from(read_varint(&mut self.data)?)
+                    .map_err(|_| SteamAudioError::InsufficientData)?;
+                if self.data.len() < length {
+                    return Err(SteamAudioError::InsufficientData);
+                }
+                let (data, rest) = self.data.split_at(length);
+                self.data = rest;
+                Value::Bytes(data)
+            }
+            5 => Value::Fixed32(u32::from_le_bytes(read_n(&mut self.data)?)),
+            _ => return Err(SteamAudioError::InvalidMessage),
+        };
+        Ok((key >> 3, value))
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Result<(u64, Value<'a>), SteamAudioError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let field = self.read_field();
+        if field.is_err() {
+            self.data = &[];
+        }
+        Some(field)
+    }
+}
+
+fn read_n<const N: usize>(data: &mut &[u8]) -> Result<[u8; N], SteamAudioError> {
+    let (bytes, rest) = read_bytes(data)?;
+    *data = rest;
+    Ok(bytes)
+}
+
+pub(crate) fn read_varint(data: &mut &[u8]) -> Result<u64, SteamAudioError> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let [byte] = read_n(data)?;
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(SteamAudioError::InvalidMessage)
+}
@@ -0,0 +1,152 @@
+//! Sample rates of the voice data and their mapping to the rates opus can decode at
+
+use crate::SteamAudioError;
+use std::fmt::Debug;
+
+/// Sample rates that opus can decode at
+pub(crate) const OPUS_RATES: [u32; 5] = [8000, 12000, 16000, 24000, 48000];
+
+/// Sample rates advertised by older captures that opus can't decode at
+const LEGACY_RATES: [u32; 3] = [11025, 22050, 44100];
+
+/// Whether opus can decode at the sample rate
+pub fn is_opus_rate(rate: u32) -> bool {
+    OPUS_RATES.contains(&rate)
+}
+
+/// Whether the sample rate is a legacy rate, one of 11025, 22050 or 44100 Hz
+pub fn is_legacy_rate(rate: u32) -> bool {
+    LEGACY_RATES.contains(&rate)
+}
+
+/// The rate voice data with the sample rate is decoded at
+///
+/// Opus rates are decoded as is, legacy rates are decoded at the nearest opus rate that isn't lower,
+/// so no bandwidth is lost: 11025 Hz at 12 kHz, 22050 Hz at 24 kHz and 44100 Hz at 48 kHz. Uncompressed samples at
+/// a legacy rate are resampled to the decode rate, so all audio of the voice data comes out at the same rate.
+/// Fails with [`SteamAudioError::UnsupportedSampleRate`] for any other rate.
+pub fn decode_rate(rate: u32) -> Result<u32, SteamAudioError> {
+    if is_opus_rate(rate) {
+        Ok(rate)
+    } else if is_legacy_rate(rate) {
+        Ok(OPUS_RATES
+            .into_iter()
+            .find(|&opus_rate| opus_rate >= rate)
+            .unwrap_or(48000))
+    } else {
+        Err(SteamAudioError::UnsupportedSampleRate { rate })
+    }
+}
+
+/// Converts streams of interleaved audio between two sample rates
+pub trait Resampler: Debug + Send {
+    /// Sample rate of the input
+    fn input_rate(&self) -> u32;
+
+    /// Sample rate of the output
+    fn output_rate(&self) -> u32;
+
+    /// Resample a chunk of interleaved samples, appending the output to `output`
+    fn process(&mut self, input: &[i16], output: &mut Vec<i16>);
+
+    /// Forget the previous input, e.g. after a discontinuity
+    fn reset(&mut self);
+}
+
+/// Creates a [`Resampler`] from rate `from` to rate `to` for audio with the given number of channels
+pub type ResamplerConstructor = fn(from: u32, to: u32, channels: usize) -> Box<dyn Resampler>;
+
+/// Streaming linear interpolation between two sample rates
+///
+/// Intended for converting audio decoded at the [`decode_rate`] of a legacy rate back to the rate
+/// advertised by the voice data, chunks of interleaved samples can be passed in as they are decoded.
+/// The decoder uses it to bring uncompressed samples to the output rate.
+#[derive(Debug, Clone)]
+pub struct LinearResampler {
+    from: u32,
+    to: u32,
+    channels: usize,
+    /// Time of the next output sample relative to `last`, in units of `1 / to` input samples
+    position: u64,
+    /// The last input sample of every channel
+    last: Vec<i16>,
+}
+
+impl LinearResampler {
+    /// Create a resampler from rate `from` to rate `to` for interleaved audio with `channels` channels
+    pub fn new(from: u32, to: u32, channels: usize) -> Self {
+        LinearResampler {
+            from,
+            to,
+            channels: channels.max(1),
+            position: to as u64,
+            last: vec![0; channels.max(1)],
+        }
+    }
+
+    pub fn from_rate(&self) -> u32 {
+        self.from
+    }
+
+    pub fn to_rate(&self) -> u32 {
+        self.to
+    }
+
+    /// Resample a chunk of interleaved samples, appending the output to `output`
+    ///
+    /// A trailing partial frame in `input` is ignored.
+    pub fn process(&mut self, input: &[i16], output: &mut Vec<i16>) {
+        let channels = self.channels;
+        let frames = input.len() / channels;
+        if self.from == self.to {
+            output.extend_from_slice(&input[..frames * channels]);
+            return;
+        }
+        let to = self.to as u64;
+        let end = frames as u64 * to;
+        // frame `0` is the last frame of the previous chunk, frame `n` is frame `n - 1` of the input
+        let sample = |frame: usize, channel: usize| match frame {
+            0 => self.last[channel],
+            frame => input[(frame - 1) * channels + channel],
+        };
+        while self.position < end {
+            let frame = (self.position / to) as usize;
+            let fraction = (self.position % to) as i64;
+            for channel in 0..channels {
+                let a = sample(frame, channel) as i64;
+                let b = sample(frame + 1, channel) as i64;
+                output.push((a + (b - a) * fraction / to as i64) as i16);
+            }
+            self.position += self.from as u64;
+        }
+        self.position -= end;
+        if frames > 0 {
+            self.last
+                .copy_from_slice(&input[(frames - 1) * channels..frames * channels]);
+        }
+    }
+
+    /// Forget the previous input, e.g. after a discontinuity
+    pub fn reset(&mut self) {
+        self.position = self.to as u64;
+        self.last.fill(0);
+    }
+}
+
+impl Resampler for LinearResampler {
+    fn input_rate(&self) -> u32 {
+        self.from
+    }
+
+    fn output_rate(&self) -> u32 {
+        self.to
+    }
+
+    fn process(&mut self, input: &[i16], output: &mut Vec<i16>) {
+        LinearResampler::process(self, input, output);
+    }
+
+    fn reset(&mut self) {
+        LinearResampler::reset(self);
+    }
+}
@@ -0,0 +1,80 @@
+use crate::{SteamAudioError, SteamVoiceData, SteamVoiceDecoder};
+use std::fmt::Debug;
+use std::io::{Error, ErrorKind, Read};
+
+/// Decodes a sequence of voice payloads on demand, producing signed 16-bit little endian PCM bytes
+pub struct PcmReader<I> {
+    decoder: SteamVoiceDecoder,
+    payloads: I,
+    samples: Vec<i16>,
+    pending: Vec<u8>,
+    position: usize,
+}
+
+impl<I> Debug for PcmReader<I> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PcmReader")
+            .field("pending", &(self.pending.len() - self.position))
+            .finish_non_exhaustive()
+    }
+}
+
+impl<I, T> PcmReader<I>
+where
+    I: Iterator<Item = T>,
+    T: AsRef<[u8]>,
+{
+    pub fn new<P: IntoIterator<IntoIter = I>>(decoder: SteamVoiceDecoder, payloads: P) -> Self {
+        PcmReader {
+            decoder,
+            payloads: payloads.into_iter(),
+            samples: Vec::new(),
+            pending: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// Get back the decoder
+    pub fn into_decoder(self) -> SteamVoiceDecoder {
+        self.decoder
+    }
+
+    /// Decode the next payload, returns `false` if there are no more payloads
+    fn fill(&mut self) -> Result<bool, SteamAudioError> {
+        let Some(payload) = self.payloads.next() else {
+            return Ok(false);
+        };
+        let voice_data = SteamVoiceData::new(payload.as_ref())?;
+        self.samples.clear();
+        self.decoder.decode_append(voice_data, &mut self.samples)?;
+
+        self.pending.clear();
+        self.pending
+            .extend(self.samples.iter().flat_map(|sample| sample.to_le_bytes()));
+        self.position = 0;
+        Ok(true)
+    }
+}
+
+impl<I, T> Read for PcmReader<I>
+where
+    I: Iterator<Item = T>,
+    T: AsRef<[u8]>,
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position >= self.pending.len() {
+            if !self
+                .fill()
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e))?
+            {
+                return Ok(0);
+            }
+        }
+
+        let available = &self.pending[self.position..];
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        self.position += count;
+        Ok(count)
+    }
+}
@@ -0,0 +1,113 @@
+//! Conversion of extracted frames into a continuous stream of standalone opus packets
+
+use crate::toc::packet_samples;
+use crate::{ExtractedFrame, SteamAudioError};
+use std::borrow::Cow;
+
+/// CELT-only TOC bytes for silence frames of 20, 10, 5 and 2.5ms with their durations in 48kHz samples
+pub(crate) const SILENCE_TOCS: [(u8, u64); 4] =
+    [(0xF8, 960), (0xF0, 480), (0xE8, 240), (0xE0, 120)];
+/// CELT frame payload that decodes to digital silence
+const SILENCE_PAYLOAD: [u8; 2] = [0xFF, 0xFE];
+
+/// A packet that decodes to digital silence, for one of the TOC bytes of [`SILENCE_TOCS`]
+pub(crate) fn silence_packet(toc: u8) -> [u8; 3] {
+    [toc, SILENCE_PAYLOAD[0], SILENCE_PAYLOAD[1]]
+}
+
+/// A standalone opus packet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpusPacket<'a> {
+    pub data: Cow<'a, [u8]>,
+    /// Start of the packet in 48kHz samples
+    pub start: u64,
+    /// Duration of the packet in 48kHz samples
+    pub duration: u64,
+    /// Whether the packet was generated to fill a gap instead of coming from the voice data
+    pub filler: bool,
+}
+
+/// Converts [extracted frames](crate::FrameExtractor) into standalone opus packets without gaps
+///
+/// Opus consumers and muxers expect every packet to directly follow the previous one. Gaps between the
+/// frames are filled with packets: lost frames with empty frames of the same configuration as the previous
+/// frame, which decoders conceal, and silence with CELT frames that decode to digital silence.
+/// Gaps shorter than 2.5ms can't be represented and are carried over to the next gap.
+#[derive(Debug, Default)]
+pub struct Repacketizer {
+    /// End of the last packet in 48kHz samples
+    position: u64,
+    last_toc: Option<u8>,
+}
+
+impl Repacketizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// End of the packets produced so far in 48kHz samples
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Convert a frame, returns the frame as a packet preceded by the packets filling the gap before it
+    pub fn push<'a>(
+        &mut self,
+        frame: &ExtractedFrame<'a>,
+    ) -> Result<Vec<OpusPacket<'a>>, SteamAudioError> {
+        let duration = packet_samples(frame.data, 48_000)? as u64;
+        let mut packets = Vec::new();
+
+        let gap = frame.start.saturating_sub(self.position);
+        // the lost frames directly precede the frame, with the silence before them
+        let lost = match self.last_toc {
+            // a code 0 packet with an empty frame signals a lost frame to the decoder
+            Some(toc) => Some(([toc & 0xFC], packet_samples(&[toc & 0xFC], 48_000)? as u64)),
+            None => None,
+        };
+        let lost_count = lost.map_or(0, |(_, duration)| (frame.lost as u64).min(gap / duration));
+        let silence = gap - lost.map_or(0, |(_, duration)| lost_count * duration);
+        self.fill_silence(&mut packets, silence);
+        if let Some((packet, duration)) = lost {
+            for _ in 0..lost_count {
+                self.fill(&mut packets, Cow::Owned(packet.to_vec()), duration);
+            }
+        }
+
+        packets.push(OpusPacket {
+            data: Cow::Borrowed(frame.data),
+            start: self.position,
+            duration,
+            filler: false,
+        });
+        self.position += duration;
+        self.last_toc = frame.data.first().copied();
+        Ok(packets)
+    }
+
+    /// Fill the gap up to `end` in 48kHz samples with silence, e.g. for silence after the last frame
+    pub fn fill_to(&mut self, end: u64) -> Vec<OpusPacket<'static>> {
+        let mut packets = Vec::new();
+        self.fill_silence(&mut packets, end.saturating_sub(self.position));
+        packets
+    }
+
+    fn fill_silence(&mut self, packets: &mut Vec<OpusPacket<'_>>, mut silence: u64) {
+        for (toc, duration) in SILENCE_TOCS {
+            while silence >= duration {
+                self.fill(packets, Cow::Owned(silence_packet(toc).to_vec()), duration);
+                silence -= duration;
+            }
+        }
+    }
+
+    fn fill<'a>(&mut self, packets: &mut Vec<OpusPacket<'a>>, data: Cow<'a, [u8]>, duration: u64) {
+        packets.push(OpusPacket {
+            data,
+            start: self.position,
+            duration,
+            filler: true,
+        });
+        self.position += duration;
+    }
+}
@@ -0,0 +1,227 @@
+//! High quality conversion between arbitrary sample rates
+
+use crate::Resampler;
+use std::f64::consts::PI;
+
+/// Number of input samples on either side of an output sample that the filter looks at when upsampling
+const HALF_WIDTH: usize = 16;
+/// Cutoff of the low pass filter, relative to the lower of the two nyquist frequencies
+const CUTOFF: f64 = 0.92;
+/// Largest number of filter phases that are precomputed, rate pairs needing more compute the filter per sample
+const MAX_TABLE_PHASES: u64 = 1024;
+
+/// Streaming windowed sinc resampler between any two sample rates
+///
+/// Unlike a [`LinearResampler`](crate::LinearResampler) the audio is band limited before it is resampled,
+/// so downsampling doesn't alias and upsampling doesn't add the harsh images of linear interpolation.
+/// This makes it suitable to convert the decoded voice to a fixed rate like 44.1kHz for muxing with
+/// video, or 16kHz for speech recognition.
+///
+/// The output lags behind the input by the width of the filter, call [`finish`](Self::finish) at the
+/// end of the stream to get the remaining samples.
+#[derive(Debug, Clone)]
+pub struct SincResampler {
+    from: u32,
+    to: u32,
+    channels: usize,
+    /// Input samples advanced for every output sample, in units of `1 / phases` input samples
+    step: u64,
+    /// Number of distinct positions of an output sample between two input samples
+    phases: u64,
+    /// Number of input samples on either side of an output sample
+    half_width: usize,
+    /// Cutoff of the filter, relative to the nyquist frequency of the input
+    cutoff: f64,
+    /// Filter weights of every phase, if the number of phases is small enough
+    table: Option<Vec<f32>>,
+    /// Buffered interleaved input, starting with the first tap of the next output sample
+    buffer: Vec<f32>,
+    /// Position of the first tap of the next output sample in the buffer, in units of `1 / phases`
+    position: u64,
+    /// Scratch space for the weights of a single output sample
+    weights: Vec<f32>,
+}
+
+impl SincResampler {
+    /// Create a resampler from rate `from` to rate `to` for audio with `channels` channels
+    pub fn new(from: u32, to: u32, channels: usize) -> Self {
+        let from = from.max(1);
+        let to = to.max(1);
+        let divisor = gcd(from as u64, to as u64);
+        let step = from as u64 / divisor;
+        let phases = to as u64 / divisor;
+        let ratio = (to as f64 / from as f64).min(1.0);
+        let half_width = (HALF_WIDTH as f64 / ratio).ceil() as usize;
+        let cutoff = CUTOFF * ratio;
+
+        let mut resampler = SincResampler {
+            from,
+            to,
+            channels: channels.max(1),
+            step,
+            phases,
+            half_width,
+            cutoff,
+            table: None,
+            buffer: Vec::new(),
+            position: 0,
+            weights: vec![0.0; half_width * 2],
+        };
+        if phases <= MAX_TABLE_PHASES {
+            let mut table = Vec::with_capacity(phases as usize * half_width * 2);
+            for phase in 0..phases {
+                resampler.compute_weights(phase);
+                table.extend_from_slice(&resampler.weights);
+            }
+            resampler.table = Some(table);
+        }
+        resampler.reset();
+        resampler
+    }
+
+    pub fn from_rate(&self) -> u32 {
+        self.from
+    }
+
+    pub fn to_rate(&self) -> u32 {
+        self.to
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Number of input samples per channel that the output lags behind the input
+    pub fn delay(&self) -> usize {
+        self.half_width
+    }
+
+    /// Resample a chunk of interleaved samples, appending the output to `output`
+    pub fn process(&mut self, input: &[i16], output: &mut Vec<i16>) {
+        self.buffer
+            .extend(input.iter().map(|sample| *sample as f32));
+        let channels = self.channels;
+        let taps = self.half_width * 2;
+        let available = self.buffer.len() / channels;
+
+        loop {
+            let frame = (self.position / self.phases) as usize;
+            // the output sample lies `half_width - 1` frames after `frame`, its taps start at `frame`
+            if frame + taps > available {
+                break;
+            }
+            let phase = self.position % self.phases;
+            let weights = match &self.table {
+                Some(table) => {
+                    let start = phase as usize * taps;
+                    &table[start..start + taps]
+                }
+                None => {
+                    self.compute_weights(phase);
+                    &self.weights[..]
+                }
+            };
+            let start = frame * channels;
+            for channel in 0..channels {
+                let sum: f32 = weights
+                    .iter()
+                    .zip(self.buffer[start + channel..].iter().step_by(channels))
+                    .map(|(weight, sample)| weight * sample)
+                    .sum();
+                output.push(sum.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16);
+            }
+            self.position += self.step;
+        }
+
+        let consumed = ((self.position / self.phases) as usize).min(available);
+        self.buffer.drain(..consumed * channels);
+        self.position -= consumed as u64 * self.phases;
+    }
+
+    /// Flush the samples still held back by the filter into `output` and reset the resampler
+    pub fn finish(&mut self, output: &mut Vec<i16>) {
+        let padding = vec![0; self.half_width * self.channels];
+        self.process(&padding, output);
+        self.reset();
+    }
+
+    /// Forget the previous input, e.g. after a discontinuity
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.buffer
+            .resize((self.half_width - 1) * self.channels, 0.0);
+        self.position = 0;
+    }
+
+    /// Compute the weights of the taps of an output sample at `phase` into `self.weights`
+    fn compute_weights(&mut self, phase: u64) {
+        let offset = phase as f64 / self.phases as f64;
+        let half_width = self.half_width as f64;
+        let mut sum = 0.0;
+        for (tap, weight) in self.weights.iter_mut().enumerate() {
+            // distance from the output sample to the input sample of the tap, in input samples
+            let x = offset + half_width - 1.0 - tap as f64;
+            let window = blackman(x / half_width);
+            let value = self.cutoff * sinc(self.cutoff * x) * window;
+            *weight = value as f32;
+            sum += value;
+        }
+        // normalize for unity gain at dc
+        if sum != 0.0 {
+            for weight in &mut self.weights {
+                *weight = (*weight as f64 / sum) as f32;
+            }
+        }
+    }
+}
+
+impl Resampler for SincResampler {
+    fn input_rate(&self) -> u32 {
+        self.from
+    }
+
+    fn output_rate(&self) -> u32 {
+        self.to
+    }
+
+    fn process(&mut self, input: &[i16], output: &mut Vec<i16>) {
+        SincResampler::process(self, input, output);
+    }
+
+    fn reset(&mut self) {
+        SincResampler::reset(self);
+    }
+}
+
+/// Resample a complete chunk of interleaved audio from rate `from` to rate `to` with a [`SincResampler`]
+pub fn resample(input: &[i16], from: u32, to: u32, channels: usize) -> Vec<i16> {
+    let mut resampler = SincResampler::new(from, to, channels);
+    let mut output = Vec::with_capacity(input.len() * to as usize / from.max(1) as usize + 1);
+    resampler.process(input, &mut output);
+    resampler.finish(&mut output);
+    output
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Blackman window over `-1..=1`
+fn blackman(x: f64) -> f64 {
+    if x.abs() >= 1.0 {
+        0.0
+    } else {
+        0.42 + 0.5 * (PI * x).cos() + 0.08 * (2.0 * PI * x).cos()
+    }
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.max(1)
+}
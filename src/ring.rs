@@ -0,0 +1,195 @@
+use crate::{SteamAudioError, SteamVoiceData, SteamVoiceDecoder};
+use std::fmt::Debug;
+
+/// Fixed size ring buffer of samples over caller provided storage
+///
+/// Samples that don't fit in the buffer when writing are dropped and counted as overrun.
+pub struct SampleRing<B> {
+    storage: B,
+    read: usize,
+    len: usize,
+    overrun: u64,
+}
+
+impl<B> Debug for SampleRing<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SampleRing")
+            .field("len", &self.len)
+            .field("overrun", &self.overrun)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<B: AsRef<[i16]> + AsMut<[i16]>> SampleRing<B> {
+    pub fn new(storage: B) -> Self {
+        SampleRing {
+            storage,
+            read: 0,
+            len: 0,
+            overrun: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.storage.as_ref().len()
+    }
+
+    /// Number of samples available for reading
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Total number of samples that have been dropped because the buffer was full
+    pub fn overrun(&self) -> u64 {
+        self.overrun
+    }
+
+    /// Write samples into the buffer, returns the number of samples written
+    pub fn push(&mut self, samples: &[i16]) -> usize {
+        let capacity = self.capacity();
+        let count = samples.len().min(capacity - self.len);
+        let write = (self.read + self.len) % capacity.max(1);
+        let first = count.min(capacity - write);
+
+        let storage = self.storage.as_mut();
+        storage[write..write + first].copy_from_slice(&samples[..first]);
+        storage[..count - first].copy_from_slice(&samples[first..count]);
+
+        self.len += count;
+        self.overrun += (samples.len() - count) as u64;
+        count
+    }
+
+    /// Write whole frames of interleaved samples into the buffer, the frames that don't fit are
+    /// dropped and counted as overrun. Returns the number of samples written
+    pub(crate) fn push_frames(&mut self, samples: &[i16], channels: usize) -> usize {
+        let space = (self.capacity() - self.len) / channels * channels;
+        let count = self.push(&samples[..samples.len().min(space)]);
+        self.overrun += (samples.len() - count) as u64;
+        count
+    }
+
+    /// Read samples from the buffer, returns the number of samples read
+    pub fn pop(&mut self, output: &mut [i16]) -> usize {
+        let capacity = self.capacity();
+        let count = output.len().min(self.len);
+        let first = count.min(capacity - self.read);
+
+        let storage = self.storage.as_ref();
+        output[..first].copy_from_slice(&storage[self.read..self.read + first]);
+        output[first..count].copy_from_slice(&storage[..count - first]);
+
+        self.read = (self.read + count) % capacity.max(1);
+        self.len -= count;
+        count
+    }
+
+    /// Discard all samples in the buffer
+    pub fn clear(&mut self) {
+        self.read = 0;
+        self.len = 0;
+    }
+
+    pub fn into_inner(self) -> B {
+        self.storage
+    }
+}
+
+/// Result of [`SteamVoiceDecoder::decode_ring`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RingDecode {
+    /// Number of samples written into the ring buffer
+    pub written: usize,
+    /// Number of samples dropped because the ring buffer was full
+    pub overrun: usize,
+}
+
+impl SteamVoiceDecoder {
+    /// Decode the voice data into a fixed size ring buffer
+    ///
+    /// Instead of failing when the buffer is too small, the samples that don't fit are dropped
+    /// and reported as overrun. Stereo output is only dropped in whole frames, so the channels
+    /// stay aligned.
+    pub fn decode_ring<B: AsRef<[i16]> + AsMut<[i16]>>(
+        &mut self,
+        voice_data: SteamVoiceData,
+        ring: &mut SampleRing<B>,
+    ) -> Result<RingDecode, SteamAudioError> {
+        let mut result = RingDecode {
+            written: 0,
+            overrun: 0,
+        };
+        let channels = self.options().channels.count();
+        self.decode_with(voice_data, |samples| {
+            let written = ring.push_frames(samples, channels);
+            result.written += written;
+            result.overrun += samples.len() - written;
+        })?;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_whole_frames() {
+        let mut ring = SampleRing::new([0; 5]);
+        assert_eq!(ring.push_frames(&[1, 2, 3, 4, 5, 6], 2), 4);
+        assert_eq!(ring.overrun(), 2);
+
+        let mut output = [0; 2];
+        assert_eq!(ring.pop(&mut output), 2);
+        assert_eq!(output, [1, 2]);
+
+        // only one of the frames fits in the 3 free samples, the write wraps around
+        assert_eq!(ring.push_frames(&[7, 8, 9, 10], 2), 2);
+        assert_eq!(ring.overrun(), 4);
+        assert_eq!(ring.len(), 4);
+
+        let mut output = [0; 5];
+        assert_eq!(ring.pop(&mut output), 4);
+        assert_eq!(output, [3, 4, 7, 8, 0]);
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn zero_capacity() {
+        let mut ring = SampleRing::new(Vec::new());
+        assert_eq!(ring.push(&[1, 2, 3]), 0);
+        assert_eq!(ring.push_frames(&[1, 2], 2), 0);
+        assert_eq!(ring.overrun(), 5);
+        assert_eq!(ring.pop(&mut [0; 2]), 0);
+        assert!(ring.is_empty());
+    }
+
+    #[cfg(any(feature = "opus", feature = "audiopus", feature = "opus-decoder"))]
+    #[test]
+    fn decode_stereo_overrun() {
+        use crate::test_data::{opus, payload, sample_rate, FRAME};
+        use crate::OutputChannels;
+
+        let payload = payload(&[sample_rate(24000), opus(&[(0, FRAME), (1, FRAME)])]);
+        let voice_data = SteamVoiceData::new(&payload).unwrap();
+        let mut decoder = SteamVoiceDecoder::builder()
+            .channels(OutputChannels::Stereo)
+            .build()
+            .unwrap();
+        let mut ring = SampleRing::new(vec![0; 1001]);
+        let result = decoder.decode_ring(voice_data, &mut ring).unwrap();
+        assert_eq!(
+            result,
+            RingDecode {
+                written: 1000,
+                overrun: 920
+            }
+        );
+        assert_eq!(ring.len(), 1000);
+        assert_eq!(ring.overrun(), 920);
+    }
+}
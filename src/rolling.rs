@@ -0,0 +1,199 @@
+//! Statistics over the most recent voice data of each speaker
+
+use crate::toc::packet_samples;
+use crate::{frames_lost, OpusFrame, OpusFrameIterator, Packet, SteamAudioError, SteamVoiceData};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+/// Statistics of a single payload
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    time: Duration,
+    bytes: usize,
+    frames: u32,
+    lost: u32,
+    voice: Duration,
+}
+
+#[derive(Debug, Clone, Default)]
+struct SpeakerWindow {
+    entries: VecDeque<Entry>,
+    sample_rate: u16,
+    seq: Option<u16>,
+}
+
+/// Statistics of a speaker over a window of time
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WindowStats {
+    /// Number of payloads received
+    pub payloads: usize,
+    /// Fraction of opus frames that were lost
+    pub loss_rate: f32,
+    /// Payload bitrate in bits per second
+    pub bitrate: f32,
+    /// Fraction of the window in which the speaker was talking
+    pub activity: f32,
+}
+
+/// Tracks statistics over the most recent window of voice data per speaker
+///
+/// Payloads older than the maximum window are discarded, so memory use stays bounded
+/// for long-running streams.
+#[derive(Debug, Clone)]
+pub struct RollingStats {
+    max_window: Duration,
+    now: Duration,
+    speakers: HashMap<u64, SpeakerWindow>,
+}
+
+impl Default for RollingStats {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(60))
+    }
+}
+
+impl RollingStats {
+    /// Create a tracker that can report statistics for windows up to `max_window`
+    pub fn new(max_window: Duration) -> Self {
+        RollingStats {
+            max_window,
+            now: Duration::ZERO,
+            speakers: HashMap::new(),
+        }
+    }
+
+    /// Record a payload received at `time`
+    pub fn record(
+        &mut self,
+        time: Duration,
+        voice_data: &SteamVoiceData,
+    ) -> Result<(), SteamAudioError> {
+        let speaker = self.speakers.entry(voice_data.steam_id).or_default();
+        let mut entry = Entry {
+            time,
+            // steam id and crc aren't included in the packet data
+            bytes: voice_data.packet_data.len() + 12,
+            frames: 0,
+            lost: 0,
+            voice: Duration::ZERO,
+        };
+
+        for packet in voice_data.packets() {
+            match packet? {
+                Packet::SampleRate(rate) => speaker.sample_rate = rate,
+                Packet::RawPcm(pcm) => {
+                    if speaker.sample_rate > 0 {
+                        entry.voice += Duration::from_secs_f64(
+                            (pcm.len() / 2) as f64 / speaker.sample_rate as f64,
+                        );
+                    }
+                }
+                Packet::Silence(_)
+                | Packet::Unsupported { .. }
+                | Packet::Marker(_)
+                | Packet::Unknown { .. } => {}
+                Packet::OpusPlc(opus) => {
+                    for frame in OpusFrameIterator::new(opus.data) {
+                        match frame? {
+                            OpusFrame::Reset => speaker.seq = Some(0),
+                            OpusFrame::Frame { seq, data } => {
+                                if let Some(lost) =
+                                    speaker.seq.and_then(|expected| frames_lost(expected, seq))
+                                {
+                                    entry.lost += lost as u32;
+                                }
+                                speaker.seq = Some(seq.wrapping_add(1));
+                                entry.frames += 1;
+                                if !data.is_empty() && speaker.sample_rate > 0 {
+                                    let samples = packet_samples(data, speaker.sample_rate as u32)?;
+                                    entry.voice += Duration::from_secs_f64(
+                                        samples as f64 / speaker.sample_rate as f64,
+                                    );
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        speaker.entries.push_back(entry);
+        self.now = self.now.max(time);
+        self.prune();
+        Ok(())
+    }
+
+    /// Advance the current time without receiving data, e.g. to let the activity of silent speakers decay
+    pub fn advance(&mut self, time: Duration) {
+        self.now = self.now.max(time);
+        self.prune();
+    }
+
+    fn prune(&mut self) {
+        let cutoff = self.now.saturating_sub(self.max_window);
+        for speaker in self.speakers.values_mut() {
+            while speaker
+                .entries
+                .front()
+                .is_some_and(|entry| entry.time < cutoff)
+            {
+                speaker.entries.pop_front();
+            }
+        }
+    }
+
+    /// Statistics for a speaker over the most recent `window`, capped to the maximum window
+    pub fn window(&self, steam_id: u64, window: Duration) -> WindowStats {
+        let window = window.min(self.max_window);
+        let Some(speaker) = self.speakers.get(&steam_id) else {
+            return WindowStats::default();
+        };
+        let cutoff = self.now.saturating_sub(window);
+
+        let mut payloads = 0;
+        let mut bytes = 0;
+        let mut frames = 0;
+        let mut lost = 0;
+        let mut voice = Duration::ZERO;
+        for entry in speaker.entries.iter().filter(|entry| entry.time >= cutoff) {
+            payloads += 1;
+            bytes += entry.bytes;
+            frames += entry.frames;
+            lost += entry.lost;
+            voice += entry.voice;
+        }
+
+        let seconds = window.as_secs_f32();
+        if seconds == 0.0 {
+            return WindowStats::default();
+        }
+        WindowStats {
+            payloads,
+            loss_rate: if frames + lost > 0 {
+                lost as f32 / (frames + lost) as f32
+            } else {
+                0.0
+            },
+            bitrate: bytes as f32 * 8.0 / seconds,
+            activity: (voice.as_secs_f32() / seconds).min(1.0),
+        }
+    }
+
+    /// Statistics over the last 10 seconds
+    pub fn last_10s(&self, steam_id: u64) -> WindowStats {
+        self.window(steam_id, Duration::from_secs(10))
+    }
+
+    /// Statistics over the last 60 seconds
+    pub fn last_60s(&self, steam_id: u64) -> WindowStats {
+        self.window(steam_id, Duration::from_secs(60))
+    }
+
+    /// All speakers that have data in the tracked window
+    pub fn speakers(&self) -> impl Iterator<Item = u64> + '_ {
+        self.speakers
+            .iter()
+            .filter(|(_, speaker)| !speaker.entries.is_empty())
+            .map(|(steam_id, _)| *steam_id)
+    }
+}
@@ -0,0 +1,109 @@
+//! Built-in self-test for verifying that a build of the crate, including the linked opus library, works
+
+#[cfg(feature = "decoder")]
+use crate::SteamVoiceDecoder;
+use crate::{crc32, OpusFrame, OpusFrameIterator, Packet, SteamAudioError, SteamVoiceData};
+use thiserror::Error;
+
+/// Payload from steam id `0x0110000100000001` containing a sample rate of 24kHz,
+/// a single 20ms opus frame of a 440Hz tone and 10ms of silence
+const PAYLOAD: &[u8] = &[
+    1, 0, 0, 0, 1, 0, 16, 1, 11, 192, 93, 6, 28, 0, 24, 0, 0, 0, 8, 131, 156, 2, 208, 28, 253, 249,
+    176, 205, 177, 148, 30, 25, 94, 245, 5, 157, 89, 225, 6, 133, 210, 254, 0, 240, 0, 202, 121,
+    232, 203,
+];
+const STEAM_ID: u64 = 0x0110_0001_0000_0001;
+#[cfg(feature = "decoder")]
+const TONE_SAMPLES: usize = 480;
+const SILENCE_SAMPLES: usize = 240;
+
+#[derive(Debug, Error)]
+pub enum SelfTestFailure {
+    #[error("unexpected result: {0}")]
+    Mismatch(&'static str),
+    #[error(transparent)]
+    Error(#[from] SteamAudioError),
+}
+
+/// Result of [`self_test`]
+#[derive(Debug)]
+pub struct SelfTestReport {
+    /// Checksum calculation
+    pub crc: Result<(), SelfTestFailure>,
+    /// Parsing of the payload header and packets
+    pub framing: Result<(), SelfTestFailure>,
+    /// Decoding of opus data
+    #[cfg(feature = "decoder")]
+    pub decode: Result<(), SelfTestFailure>,
+}
+
+impl SelfTestReport {
+    pub fn is_ok(&self) -> bool {
+        #[cfg(feature = "decoder")]
+        let decode_ok = self.decode.is_ok();
+        #[cfg(not(feature = "decoder"))]
+        let decode_ok = true;
+        self.crc.is_ok() && self.framing.is_ok() && decode_ok
+    }
+}
+
+/// Run the built-in test vectors through the crc, parsing and decoding code
+///
+/// This allows verifying that a build works (e.g. that libopus is linked correctly)
+/// before processing any user data. Decoding is tested with the default opus backend.
+pub fn self_test() -> SelfTestReport {
+    SelfTestReport {
+        crc: test_crc(),
+        framing: test_framing(),
+        #[cfg(feature = "decoder")]
+        decode: test_decode(),
+    }
+}
+
+fn check(condition: bool, message: &'static str) -> Result<(), SelfTestFailure> {
+    if condition {
+        Ok(())
+    } else {
+        Err(SelfTestFailure::Mismatch(message))
+    }
+}
+
+fn test_crc() -> Result<(), SelfTestFailure> {
+    check(crc32(b"123456789") == 0xCBF43926, "crc of check string")?;
+    let (data, crc) = PAYLOAD.split_at(PAYLOAD.len() - 4);
+    check(crc32(data).to_le_bytes() == crc, "crc of test payload")
+}
+
+fn test_framing() -> Result<(), SelfTestFailure> {
+    let voice_data = SteamVoiceData::new(PAYLOAD)?;
+    check(voice_data.steam_id == STEAM_ID, "steam id")?;
+
+    let packets = voice_data.packets().collect::<Result<Vec<_>, _>>()?;
+    let [Packet::SampleRate(24000), Packet::OpusPlc(opus), Packet::Silence(silence)] =
+        packets.as_slice()
+    else {
+        return Err(SelfTestFailure::Mismatch("packet types"));
+    };
+    check(*silence as usize == SILENCE_SAMPLES, "silence length")?;
+
+    let frames = OpusFrameIterator::new(opus.data).collect::<Result<Vec<_>, _>>()?;
+    check(
+        matches!(frames.as_slice(), [OpusFrame::Frame { seq: 0, data }] if data.len() == 24),
+        "opus frames",
+    )
+}
+
+#[cfg(feature = "decoder")]
+fn test_decode() -> Result<(), SelfTestFailure> {
+    let voice_data = SteamVoiceData::new(PAYLOAD)?;
+    let mut output = [0; 1024];
+    let count = SteamVoiceDecoder::new().decode(voice_data, &mut output)?;
+    check(
+        count == TONE_SAMPLES + SILENCE_SAMPLES,
+        "decoded sample count",
+    )?;
+    check(
+        output[..TONE_SAMPLES].iter().any(|sample| *sample != 0),
+        "decoded tone",
+    )
+}
@@ -0,0 +1,87 @@
+//! [`serde::Serialize`] implementations describing the structure of the voice data
+//!
+//! Only the metadata of the packets is serialized, the lengths of the packet and frame data are
+//! serialized instead of the data itself.
+
+use crate::{
+    OpusFrame, OpusFrameIterator, OwnedSteamVoiceData, Packet, SteamOpusData, SteamVoiceData,
+};
+use serde::ser::{Error, SerializeMap, SerializeSeq, SerializeStruct};
+use serde::{Serialize, Serializer};
+
+impl Serialize for SteamVoiceData<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("SteamVoiceData", 2)?;
+        state.serialize_field("steam_id", &self.steam_id)?;
+        state.serialize_field("packets", &Packets(self))?;
+        state.end()
+    }
+}
+
+impl Serialize for OwnedSteamVoiceData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.as_voice_data().serialize(serializer)
+    }
+}
+
+struct Packets<'a, 'b>(&'b SteamVoiceData<'a>);
+
+impl Serialize for Packets<'_, '_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(None)?;
+        for packet in self.0.packets() {
+            seq.serialize_element(&packet.map_err(S::Error::custom)?)?;
+        }
+        seq.end()
+    }
+}
+
+impl Serialize for Packet<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("type", &self.packet_type())?;
+        match self {
+            Packet::Silence(samples) => map.serialize_entry("samples", samples)?,
+            Packet::OpusPlc(opus) => {
+                map.serialize_entry("length", &opus.data.len())?;
+                map.serialize_entry("frames", opus)?;
+            }
+            Packet::SampleRate(rate) => map.serialize_entry("sample_rate", rate)?,
+            Packet::RawPcm(data) | Packet::Unsupported { data, .. } => {
+                map.serialize_entry("length", &data.len())?
+            }
+            Packet::Marker(value) => map.serialize_entry("value", value)?,
+            Packet::Unknown { value, data, .. } => {
+                map.serialize_entry("value", value)?;
+                map.serialize_entry("length", &data.len())?;
+            }
+        }
+        map.end()
+    }
+}
+
+/// Serialized as the list of frames, with `null` entries for encoder resets
+impl Serialize for SteamOpusData<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(None)?;
+        for frame in OpusFrameIterator::new(self.data) {
+            match frame.map_err(S::Error::custom)? {
+                OpusFrame::Reset => seq.serialize_element(&None::<FrameInfo>)?,
+                OpusFrame::Frame {
+                    seq: sequence,
+                    data,
+                } => seq.serialize_element(&FrameInfo {
+                    seq: sequence,
+                    length: data.len(),
+                })?,
+            }
+        }
+        seq.end()
+    }
+}
+
+#[derive(Serialize)]
+struct FrameInfo {
+    seq: u16,
+    length: usize,
+}
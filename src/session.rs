@@ -0,0 +1,974 @@
+//! Decoding the voice of all players of a demo or server
+
+use crate::summary::PacketClock;
+use crate::{
+    normalize_loudness, Agc, AudioFilter, FilterChain, FilterConstructor, Fingerprint,
+    Fingerprinter, RollingStats, SpeakingLog, SteamAudioError, SteamVoiceData, TalkStats,
+    TickTimeline, VoiceAnalyzer, VoiceReport,
+};
+#[cfg(feature = "decoder")]
+use crate::{
+    DecodeEvent, DecodeStats, DecoderOptions, Mixer, MultiTrack, PositionSource, Spatializer,
+    VoiceDecoderSet,
+};
+use std::collections::HashMap;
+#[cfg(feature = "decoder")]
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The decoded audio of a single speaker in a [`VoiceSession`]
+#[derive(Debug, Clone, Default)]
+pub struct SpeakerStream {
+    steam_id: u64,
+    first_tick: u32,
+    last_tick: u32,
+    payloads: usize,
+    sample_rate: Option<u32>,
+    samples: Vec<i16>,
+    channels: usize,
+    /// Position of the first sample of `samples`
+    position: u64,
+    /// Length of the audio after `samples` that was only timed from the packet metadata, in samples per channel
+    timed: u64,
+    /// Whether any audio was placed on the timeline yet
+    started: bool,
+    talk: TalkStats,
+    rolling: RollingStats,
+    agc: Option<Agc>,
+    fingerprinter: Option<Fingerprinter>,
+    analyzer: Option<VoiceAnalyzer>,
+    /// Whether the decoder of the speaker reports its events to the speaking log
+    #[cfg(feature = "decoder")]
+    event_handler: bool,
+}
+
+impl SpeakerStream {
+    fn new(steam_id: u64, tick: u32, channels: usize) -> Self {
+        SpeakerStream {
+            steam_id,
+            first_tick: tick,
+            last_tick: tick,
+            channels,
+            ..SpeakerStream::default()
+        }
+    }
+
+    pub fn steam_id(&self) -> u64 {
+        self.steam_id
+    }
+
+    /// Tick of the first payload of the speaker
+    pub fn first_tick(&self) -> u32 {
+        self.first_tick
+    }
+
+    /// Tick of the most recent payload of the speaker
+    pub fn last_tick(&self) -> u32 {
+        self.last_tick
+    }
+
+    /// Number of payloads received from the speaker
+    pub fn payloads(&self) -> usize {
+        self.payloads
+    }
+
+    /// Sample rate of the decoded audio, once the speaker sent a payload with a sample rate
+    pub fn sample_rate(&self) -> Option<u32> {
+        self.sample_rate
+    }
+
+    /// The decoded samples that haven't been taken out of the stream yet, interleaved for stereo output
+    ///
+    /// Sessions that don't decode the audio only time it, their streams have no samples.
+    pub fn samples(&self) -> &[i16] {
+        &self.samples
+    }
+
+    /// Bring the samples that haven't been taken out of the stream yet to a loudness of `target` LUFS,
+    /// returns the applied gain in dB, see [`normalize_loudness`]
+    pub fn normalize_loudness(&mut self, target: f64) -> Option<f64> {
+        let sample_rate = self.sample_rate?;
+        normalize_loudness(&mut self.samples, sample_rate, self.channels, target)
+    }
+
+    /// Write the samples that haven't been taken out of the stream yet to a WAV file at `path`
+    ///
+    /// Fails with [`SteamAudioError::NoSampleRate`] if the speaker didn't send a sample rate yet.
+    #[cfg(feature = "wav")]
+    pub fn write_wav<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), SteamAudioError> {
+        let sample_rate = self.sample_rate.ok_or(SteamAudioError::NoSampleRate)?;
+        crate::write_wav(path, &self.samples, sample_rate, self.channels as u16)
+    }
+
+    /// Write the samples that haven't been taken out of the stream yet to a FLAC file at `path`
+    ///
+    /// Fails with [`SteamAudioError::NoSampleRate`] if the speaker didn't send a sample rate yet.
+    #[cfg(feature = "flac")]
+    pub fn write_flac<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), SteamAudioError> {
+        let sample_rate = self.sample_rate.ok_or(SteamAudioError::NoSampleRate)?;
+        crate::write_flac(path, &self.samples, sample_rate, self.channels)
+    }
+
+    /// Position of the first sample in [`samples`](Self::samples), counting samples per channel
+    ///
+    /// For a session with a [`TickTimeline`] this is the position on the timeline,
+    /// otherwise the position within all audio decoded for the speaker.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// The gain control applied to the audio of the speaker, see [`VoiceSession::set_agc`]
+    pub fn agc(&self) -> Option<&Agc> {
+        self.agc.as_ref()
+    }
+
+    /// The fingerprint of all audio decoded for the speaker, if fingerprinting is enabled with
+    /// [`VoiceSession::set_fingerprinting`]
+    pub fn fingerprint(&self) -> Option<Fingerprint> {
+        self.fingerprinter.as_ref().map(Fingerprinter::fingerprint)
+    }
+
+    /// Talk time statistics of all audio decoded for the speaker, including audio taken out of the stream
+    pub fn talk_stats(&self) -> &TalkStats {
+        &self.talk
+    }
+
+    /// Loss rate, bitrate and activity of the speaker over the most recent payloads, e.g.
+    /// `rolling_stats().last_10s(steam_id)`, for monitoring the current conditions of a live session
+    ///
+    /// With a [`TickTimeline`] the payloads are timed by the tick they were received at and the window moves on
+    /// with the payloads of all speakers, so the statistics of a speaker that stopped talking decay. Without a
+    /// timeline the payloads are timed by the audio of the speaker.
+    pub fn rolling_stats(&self) -> &RollingStats {
+        &self.rolling
+    }
+
+    /// Position directly after the last decoded sample
+    pub fn end(&self) -> u64 {
+        self.position + (self.samples.len() / self.channels.max(1)) as u64 + self.timed
+    }
+
+    /// Take the decoded samples out of the stream, to process the audio incrementally
+    pub fn take_samples(&mut self) -> Vec<i16> {
+        self.position = self.end();
+        self.timed = 0;
+        std::mem::take(&mut self.samples)
+    }
+
+    /// Take the first channel of the audio between the timeline positions `start` and `end` out of the stream,
+    /// with silence where the stream has no audio
+    ///
+    /// Audio before `start` is dropped.
+    #[cfg(feature = "decoder")]
+    fn take_range(&mut self, start: u64, end: u64) -> Vec<i16> {
+        let channels = self.channels.max(1);
+        let mut output = vec![0; end.saturating_sub(start) as usize];
+        let from = self.position.max(start);
+        let to = self.end().min(end);
+        if from < to {
+            let skip = (from - self.position) as usize;
+            let offset = (from - start) as usize;
+            let frames = self
+                .samples
+                .get(skip * channels..)
+                .unwrap_or_default()
+                .chunks_exact(channels)
+                .take((to - from) as usize);
+            for (output, frame) in output[offset..].iter_mut().zip(frames) {
+                *output = frame[0];
+            }
+        }
+        let consumed = end.clamp(self.position, self.end());
+        let frames = (consumed - self.position) as usize;
+        let decoded = frames.min(self.samples.len() / channels);
+        self.samples.drain(..decoded * channels);
+        self.timed -= (frames - decoded) as u64;
+        self.position = consumed;
+        output
+    }
+}
+
+/// Decodes the interleaved voice payloads of all players, keeping a PCM stream for every speaker
+///
+/// Payloads are pushed together with the tick they were received at, as found in a demo or on a server.
+/// Every payload is decoded by the decoder for its steam id and the audio is appended to the stream of the
+/// speaker, which can be read as a whole at the end or taken out incrementally.
+///
+/// With a [`TickTimeline`], the audio is placed at the demo time it was received at, with silence between the
+/// audio of a speaker when it stops talking, so the streams of all speakers line up with each other and the demo.
+///
+/// Without an opus backend the session only times the audio from the packet metadata, see
+/// [`timing_only`](Self::timing_only). The streams then have no samples, but the positions of the speakers on the
+/// timeline, their talk time and the speaking log are the same as when decoding.
+#[derive(Debug, Default)]
+pub struct VoiceSession {
+    #[cfg(feature = "decoder")]
+    decoders: VoiceDecoderSet,
+    /// Whether the audio is only timed from the packet metadata instead of decoded
+    timing_only: bool,
+    clocks: HashMap<u64, PacketClock>,
+    speakers: HashMap<u64, SpeakerStream>,
+    timeline: Option<TickTimeline>,
+    buffer: Vec<i16>,
+    /// The steam ids of the speakers, in the order of their first payload
+    order: Vec<u64>,
+    /// Timeline position up to which multitrack audio was taken out of the session
+    #[cfg(feature = "decoder")]
+    taken: u64,
+    agc: Option<Agc>,
+    #[cfg(feature = "denoise-backend")]
+    denoiser: Option<crate::DenoiserConstructor>,
+    #[cfg(feature = "denoise-backend")]
+    noise_reducers: HashMap<u64, crate::NoiseReducer>,
+    filter_constructor: Option<FilterConstructor>,
+    filters: HashMap<u64, FilterChain>,
+    fingerprinting: bool,
+    analysis: bool,
+    speaking_log: Option<SpeakingLog>,
+    /// Events reported by the decoders, collected for the speaking log
+    #[cfg(feature = "decoder")]
+    decode_events: Arc<Mutex<Vec<DecodeEvent>>>,
+}
+
+/// The audio of a payload, decoded into the buffer of the session or only timed
+#[derive(Debug, Default)]
+struct Chunk {
+    /// Number of samples per channel
+    frames: usize,
+    sample_rate: Option<u32>,
+    /// Position of every concealed region within the chunk, with its number of samples per channel and whether it
+    /// was recovered from forward error correction data
+    concealed: Vec<(u64, u64, bool)>,
+    /// Whether the decoder of the speaker reports its events to the speaking log
+    #[cfg(feature = "decoder")]
+    event_handler: bool,
+}
+
+impl VoiceSession {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a session that only times the audio of the speakers from the packet metadata, without decoding it
+    ///
+    /// The durations of the opus frames are read from their TOC, silence is kept and lost frames are timed like
+    /// the concealment of the decoder, so the streams of the speakers are positioned as if the audio was decoded,
+    /// without any samples. With a `timeline` the audio is timed at the sample rate of the timeline, otherwise at
+    /// the [`decode_rate`](crate::decode_rate) of the voice data.
+    ///
+    /// Sessions decoding the audio fall back to this when the crate has no opus backend.
+    pub fn timing_only(timeline: Option<TickTimeline>) -> Self {
+        VoiceSession {
+            timing_only: true,
+            timeline,
+            ..Self::default()
+        }
+    }
+
+    /// Create a session where the decoder of every speaker is created with `options`
+    #[cfg(feature = "decoder")]
+    pub fn with_options(options: DecoderOptions) -> Result<Self, SteamAudioError> {
+        Ok(VoiceSession {
+            decoders: VoiceDecoderSet::with_options(options)?,
+            ..VoiceSession::default()
+        })
+    }
+
+    /// Create a session that places the audio of all speakers on a timeline of demo ticks
+    ///
+    /// The audio is decoded at the sample rate of the timeline, which has to be a sample rate supported by opus.
+    #[cfg(feature = "decoder")]
+    pub fn with_timeline(
+        mut options: DecoderOptions,
+        timeline: TickTimeline,
+    ) -> Result<Self, SteamAudioError> {
+        options.output_rate = Some(timeline.sample_rate());
+        Ok(VoiceSession {
+            timeline: Some(timeline),
+            ..Self::with_options(options)?
+        })
+    }
+
+    pub fn timeline(&self) -> Option<&TickTimeline> {
+        self.timeline.as_ref()
+    }
+
+    /// Whether the audio is decoded, or only timed from the packet metadata because there is no opus backend
+    pub fn decodes_audio(&self) -> bool {
+        cfg!(feature = "decoder") && !self.timing_only
+    }
+    /// Apply automatic gain control to the audio of every speaker as it is decoded, `None` disables it
+    ///
+    /// Every speaker gets its own copy of the gain control, starting from the state of `agc`. The talk statistics
+    /// are measured before the gain control, so they reflect the level the speaker was recorded at.
+    pub fn set_agc(&mut self, agc: Option<Agc>) {
+        for speaker in self.speakers.values_mut() {
+            speaker.agc.clone_from(&agc);
+        }
+        self.agc = agc;
+    }
+
+    /// Remove background noise from the audio of every speaker as it is decoded, `None` disables it
+    ///
+    /// Every speaker gets its own denoiser created by `denoiser`, which is run before the gain control. With the
+    /// `denoise` feature, `Some(SpectralDenoiser::boxed)` uses the built-in denoiser.
+    ///
+    /// The denoiser works on 48kHz audio, fails with [`SteamAudioError::UnsupportedSampleRate`] unless the session
+    /// decodes at [`DENOISE_SAMPLE_RATE`](crate::DENOISE_SAMPLE_RATE), set by the `output_rate` of the decoder
+    /// options or the sample rate of the timeline.
+    #[cfg(feature = "denoise-backend")]
+    pub fn set_denoiser(
+        &mut self,
+        denoiser: Option<crate::DenoiserConstructor>,
+    ) -> Result<(), SteamAudioError> {
+        let output_rate = self.decoders.options().output_rate;
+        if denoiser.is_some() && output_rate != Some(crate::DENOISE_SAMPLE_RATE) {
+            return Err(SteamAudioError::UnsupportedSampleRate {
+                rate: output_rate.unwrap_or_default(),
+            });
+        }
+        self.denoiser = denoiser;
+        self.noise_reducers.clear();
+        Ok(())
+    }
+
+    /// Run the audio of every speaker through a chain of filters as it is decoded, `None` removes the filters
+    ///
+    /// The chain of every speaker is created by `filters` from its steam id when the speaker first sends audio,
+    /// and runs after the denoiser and gain control.
+    pub fn set_filters(&mut self, filters: Option<FilterConstructor>) {
+        self.filter_constructor = filters;
+        self.filters.clear();
+    }
+
+    /// Compute a [`Fingerprint`] of the audio of every speaker as it is decoded
+    ///
+    /// The fingerprint is computed from the decoded audio before any processing, and without the silence
+    /// between utterances, so the same speaker in the demos of different players of a match gives the same
+    /// fingerprint. Disabling fingerprinting drops the fingerprints computed so far.
+    pub fn set_fingerprinting(&mut self, enabled: bool) {
+        self.fingerprinting = enabled;
+        if !enabled {
+            for speaker in self.speakers.values_mut() {
+                speaker.fingerprinter = None;
+            }
+        }
+    }
+
+    /// Analyze the audio of every speaker for signs of abusive voice as it is decoded,
+    /// see [`voice_report`](Self::voice_report)
+    ///
+    /// The audio is analyzed before any processing. Disabling the analysis drops the measurements so far.
+    pub fn set_voice_analysis(&mut self, enabled: bool) {
+        self.analysis = enabled;
+        if !enabled {
+            for speaker in self.speakers.values_mut() {
+                speaker.analyzer = None;
+            }
+        }
+    }
+
+    /// The abuse heuristics of the audio of a speaker, if the analysis is enabled with
+    /// [`set_voice_analysis`](Self::set_voice_analysis)
+    pub fn voice_report(&self, steam_id: u64) -> Option<VoiceReport> {
+        let speaker = self.speakers.get(&steam_id)?;
+        let mut report = speaker.analyzer.as_ref()?.report();
+        let total: f64 = self
+            .speakers
+            .values()
+            .map(|speaker| speaker.talk.speaking_time.as_secs_f64())
+            .sum();
+        if total > 0.0 {
+            report.talk_share = (speaker.talk.speaking_time.as_secs_f64() / total) as f32;
+        }
+        Some(report)
+    }
+
+    /// The abuse heuristics of all analyzed speakers, in the order of their first payload
+    pub fn voice_reports(&self) -> Vec<VoiceReport> {
+        self.order
+            .iter()
+            .filter_map(|steam_id| self.voice_report(*steam_id))
+            .collect()
+    }
+
+    /// Record who talked when in a [`SpeakingLog`], `false` drops the log
+    ///
+    /// The log records the utterances of every speaker, where speakers talked over each other and the regions
+    /// that were concealed after lost packets. Use a [`TickTimeline`] to get utterances and overlaps.
+    pub fn set_speaking_log(&mut self, enabled: bool) {
+        if enabled {
+            if self.speaking_log.is_none() {
+                self.speaking_log = Some(SpeakingLog::new(self.timeline.is_some()));
+            }
+            return;
+        }
+        self.speaking_log = None;
+        #[cfg(feature = "decoder")]
+        for speaker in self.speakers.values_mut() {
+            if speaker.event_handler {
+                if let Some(decoder) = self.decoders.get_mut(speaker.steam_id) {
+                    decoder.clear_event_handler();
+                }
+                speaker.event_handler = false;
+            }
+        }
+    }
+
+    /// The record of who talked when, if enabled with [`set_speaking_log`](Self::set_speaking_log)
+    pub fn speaking_log(&self) -> Option<&SpeakingLog> {
+        self.speaking_log.as_ref()
+    }
+
+    /// Parse and decode a payload received at `tick`, returns the steam id of the speaker
+    pub fn push(&mut self, tick: u32, payload: &[u8]) -> Result<u64, SteamAudioError> {
+        let voice_data = SteamVoiceData::new(payload)?;
+        self.push_voice_data(tick, voice_data)?;
+        Ok(voice_data.steam_id)
+    }
+
+    /// Decode voice data received at `tick`, returns the number of samples added to the stream of the speaker
+    ///
+    /// For a session that only times the audio, this is the number of samples the voice data decodes to.
+    pub fn push_voice_data(
+        &mut self,
+        tick: u32,
+        voice_data: SteamVoiceData,
+    ) -> Result<usize, SteamAudioError> {
+        let steam_id = voice_data.steam_id;
+        let channels = self.channels();
+
+        self.buffer.clear();
+        #[cfg(feature = "decoder")]
+        let chunk = match self.timing_only {
+            false => match self.decode(voice_data) {
+                Err(SteamAudioError::NoBackend) => {
+                    self.timing_only = true;
+                    None
+                }
+                result => Some(result?),
+            },
+            true => None,
+        };
+        #[cfg(not(feature = "decoder"))]
+        let chunk = None;
+        let chunk = match chunk {
+            Some(chunk) => chunk,
+            None => self.time(voice_data)?,
+        };
+        // the speaker is only added once its voice data could be decoded or timed
+        let speaker = self.speakers.entry(steam_id).or_insert_with(|| {
+            self.order.push(steam_id);
+            SpeakerStream {
+                agc: self.agc.clone(),
+                ..SpeakerStream::new(steam_id, tick, channels)
+            }
+        });
+        speaker.last_tick = tick;
+        speaker.payloads += 1;
+        #[cfg(feature = "decoder")]
+        {
+            speaker.event_handler |= chunk.event_handler;
+        }
+        speaker.sample_rate = chunk.sample_rate;
+        let mut frames = chunk.frames as u64;
+        let decoded = !self.buffer.is_empty();
+
+        let mut new_utterance = false;
+        let mut dropped = 0;
+        if let Some(timeline) = &self.timeline {
+            if frames > 0 {
+                let end = speaker.end();
+                let start = timeline.place(speaker.started.then_some(end), tick, chunk.frames);
+                if !speaker.started {
+                    speaker.position = start;
+                    speaker.started = true;
+                } else if start > end {
+                    new_utterance = !timeline.is_contiguous(end, start);
+                    if decoded {
+                        let silence = (start - end) as usize * channels;
+                        speaker.samples.resize(speaker.samples.len() + silence, 0);
+                    } else {
+                        speaker.timed += start - end;
+                    }
+                } else if start < end {
+                    // drift correction, drop the samples overlapping the previous audio before anything
+                    // processes them
+                    dropped = (end - start).min(frames);
+                    self.buffer
+                        .drain(..(dropped as usize * channels).min(self.buffer.len()));
+                    frames -= dropped;
+                }
+            }
+        }
+        if let (true, true, Some(sample_rate)) = (decoded, self.fingerprinting, speaker.sample_rate)
+        {
+            let fingerprinter = speaker
+                .fingerprinter
+                .get_or_insert_with(|| Fingerprinter::new(sample_rate, channels));
+            fingerprinter.set_sample_rate(sample_rate);
+            fingerprinter.process(&self.buffer);
+        }
+        if let (true, true, Some(sample_rate)) = (decoded, self.analysis, speaker.sample_rate) {
+            let analyzer = speaker
+                .analyzer
+                .get_or_insert_with(|| VoiceAnalyzer::new(steam_id, sample_rate, channels));
+            analyzer.set_sample_rate(sample_rate);
+            analyzer.process(&self.buffer);
+        }
+
+        match decoded {
+            true => speaker.talk.add(
+                &self.buffer,
+                channels,
+                speaker.sample_rate.unwrap_or_default(),
+                new_utterance,
+            ),
+            false => speaker.talk.add_time(
+                frames,
+                speaker.sample_rate.unwrap_or_default(),
+                new_utterance,
+            ),
+        }
+        #[cfg(feature = "denoise-backend")]
+        if let Some(denoiser) = self.denoiser {
+            self.noise_reducers
+                .entry(steam_id)
+                .or_insert_with(|| crate::NoiseReducer::new(denoiser()))
+                .process(&mut self.buffer, channels);
+        }
+        if let Some(agc) = speaker.agc.as_mut() {
+            agc.process(&mut self.buffer, channels);
+        }
+        if let Some(constructor) = self.filter_constructor {
+            self.filters
+                .entry(steam_id)
+                .or_insert_with(|| constructor(steam_id))
+                .process(&mut self.buffer, channels);
+        }
+        if let Some(log) = self.speaking_log.as_mut() {
+            let sample_rate = speaker.sample_rate.unwrap_or_default();
+            let start = speaker.end();
+            let end = start + frames;
+            log.add_audio(steam_id, start, end, sample_rate, new_utterance);
+            for (offset, samples, recovered) in chunk.concealed {
+                // concealment in the samples dropped by the drift correction isn't part of the audio
+                let samples = samples.saturating_sub(dropped.saturating_sub(offset));
+                if samples == 0 {
+                    continue;
+                }
+                log.add_concealed(
+                    steam_id,
+                    (start + offset.saturating_sub(dropped)).min(end),
+                    samples,
+                    sample_rate,
+                    recovered,
+                );
+            }
+        }
+        match decoded {
+            true => speaker.samples.extend_from_slice(&self.buffer),
+            false => speaker.timed += frames,
+        }
+
+        let time = match (&self.timeline, speaker.sample_rate) {
+            (Some(timeline), _) => timeline.time(timeline.tick_position(tick)),
+            (None, Some(sample_rate)) => {
+                Duration::from_secs_f64(speaker.end() as f64 / sample_rate as f64)
+            }
+            (None, None) => Duration::ZERO,
+        };
+        // voice data that a lenient decoder accepted despite malformed packets isn't counted
+        let _ = speaker.rolling.record(time, &voice_data);
+        if self.timeline.is_some() {
+            for speaker in self.speakers.values_mut() {
+                speaker.rolling.advance(time);
+            }
+        }
+        Ok(frames as usize * channels)
+    }
+
+    /// Decode voice data into the buffer
+    #[cfg(feature = "decoder")]
+    fn decode(&mut self, voice_data: SteamVoiceData) -> Result<Chunk, SteamAudioError> {
+        let steam_id = voice_data.steam_id;
+        let channels = self.channels();
+        let decoder_position = self
+            .decoders
+            .get(steam_id)
+            .map_or(0, |decoder| decoder.position());
+        // the handler has to be installed before decoding, to see the concealment in the first payload
+        let event_handler = self.speaking_log.is_some();
+        let installed = self
+            .speakers
+            .get(&steam_id)
+            .is_some_and(|speaker| speaker.event_handler);
+        if event_handler && !installed {
+            let events = self.decode_events.clone();
+            self.decoders
+                .get_or_insert(steam_id)?
+                .set_event_handler(move |event| {
+                    if let Ok(mut events) = events.lock() {
+                        events.push(event);
+                    }
+                });
+        }
+        let count = match self.decoders.decode_append(voice_data, &mut self.buffer) {
+            Ok(count) => count,
+            Err(error) => {
+                // the events of the failed payload would otherwise be logged with the next one
+                if let Ok(mut events) = self.decode_events.lock() {
+                    events.clear();
+                }
+                return Err(error);
+            }
+        };
+
+        let mut concealed = Vec::new();
+        if let Ok(mut events) = self.decode_events.lock() {
+            for event in events.drain(..) {
+                if let DecodeEvent::Concealed {
+                    start,
+                    samples,
+                    recovered,
+                    ..
+                } = event
+                {
+                    let offset = start.saturating_sub(decoder_position) / channels as u64;
+                    concealed.push((offset, (samples / channels) as u64, recovered));
+                }
+            }
+        }
+        Ok(Chunk {
+            frames: count / channels,
+            sample_rate: self
+                .decoders
+                .get(steam_id)
+                .and_then(|decoder| decoder.output_rate()),
+            concealed,
+            event_handler,
+        })
+    }
+
+    /// Time the audio of voice data from the packet metadata, without decoding it
+    fn time(&mut self, voice_data: SteamVoiceData) -> Result<Chunk, SteamAudioError> {
+        #[cfg(feature = "decoder")]
+        let (output_rate, max_concealment, use_fec) = {
+            let options = self.decoders.options();
+            (
+                options.output_rate,
+                options.max_concealment,
+                options.use_fec,
+            )
+        };
+        #[cfg(not(feature = "decoder"))]
+        let (output_rate, max_concealment, use_fec) =
+            (None, Some(crate::DEFAULT_MAX_CONCEALMENT), true);
+        let output_rate = self
+            .timeline
+            .as_ref()
+            .map(TickTimeline::sample_rate)
+            .or(output_rate);
+
+        let timing = self
+            .clocks
+            .entry(voice_data.steam_id)
+            .or_default()
+            .advance(&voice_data, output_rate, max_concealment, use_fec)?;
+        Ok(Chunk {
+            frames: timing.samples,
+            sample_rate: timing.sample_rate,
+            concealed: timing
+                .concealed
+                .into_iter()
+                .map(|(offset, samples, recovered)| (offset as u64, samples as u64, recovered))
+                .collect(),
+            #[cfg(feature = "decoder")]
+            event_handler: false,
+        })
+    }
+
+    /// Number of interleaved channels of the streams
+    fn channels(&self) -> usize {
+        #[cfg(feature = "decoder")]
+        return self.decoders.options().channels.count();
+        #[cfg(not(feature = "decoder"))]
+        return 1;
+    }
+
+    /// The stream of a speaker, if any voice data was received from it
+    pub fn speaker(&self, steam_id: u64) -> Option<&SpeakerStream> {
+        self.speakers.get(&steam_id)
+    }
+
+    pub fn speaker_mut(&mut self, steam_id: u64) -> Option<&mut SpeakerStream> {
+        self.speakers.get_mut(&steam_id)
+    }
+
+    /// The streams of all speakers, in no particular order
+    pub fn speakers(&self) -> impl Iterator<Item = &SpeakerStream> + '_ {
+        self.speakers.values()
+    }
+
+    pub fn speakers_mut(&mut self) -> impl Iterator<Item = &mut SpeakerStream> + '_ {
+        self.speakers.values_mut()
+    }
+
+    /// Remove a speaker and its decoder, e.g. when the player disconnects, returning its stream
+    pub fn remove(&mut self, steam_id: u64) -> Option<SpeakerStream> {
+        #[cfg(feature = "decoder")]
+        self.decoders.remove(steam_id);
+        self.clocks.remove(&steam_id);
+        #[cfg(feature = "denoise-backend")]
+        self.noise_reducers.remove(&steam_id);
+        self.filters.remove(&steam_id);
+        self.order.retain(|id| *id != steam_id);
+        self.speakers.remove(&steam_id)
+    }
+
+    /// Add the decoded audio of all speakers to the mixer at the position of their streams and
+    /// the pan position of the speaker, taking it out of the streams
+    ///
+    /// The mixer expects mono audio, so the session has to decode to mono.
+    #[cfg(feature = "decoder")]
+    pub fn mix(&mut self, mixer: &mut Mixer) {
+        for speaker in self.speakers.values_mut() {
+            let position = speaker.position;
+            mixer.add_speaker(speaker.steam_id, position, &speaker.take_samples());
+        }
+    }
+
+    /// Add the decoded audio of all speakers to the mixer with the gains for their positions in the game world,
+    /// taking it out of the streams
+    ///
+    /// The timeline of the spatializer should match the timeline of the session.
+    #[cfg(feature = "decoder")]
+    pub fn mix_spatial<S: PositionSource + ?Sized>(
+        &mut self,
+        mixer: &mut Mixer,
+        spatializer: &Spatializer,
+        source: &mut S,
+    ) {
+        for speaker in self.speakers.values_mut() {
+            let position = speaker.position;
+            let samples = speaker.take_samples();
+            spatializer.add(source, mixer, speaker.steam_id, position, &samples);
+        }
+    }
+
+    /// Timeline position directly after the last decoded sample of any speaker
+    pub fn end(&self) -> u64 {
+        self.speakers
+            .values()
+            .map(SpeakerStream::end)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Take the audio up to timeline position `end` out of the session, with one channel per speaker
+    ///
+    /// The section starts where the previous section ended, for stereo output only the first channel
+    /// of every speaker is used. Speakers that join later are added as new channels in later sections,
+    /// so to get a fixed channel layout for the whole session take the audio once, up to the [`end`](Self::end).
+    #[cfg(feature = "decoder")]
+    pub fn take_multitrack(&mut self, end: u64) -> MultiTrack {
+        let start = self.taken;
+        let end = end.max(start);
+        let mut tracks = MultiTrack::new(start);
+        for steam_id in &self.order {
+            if let Some(speaker) = self.speakers.get_mut(steam_id) {
+                tracks.push_channel(*steam_id, speaker.take_range(start, end));
+            }
+        }
+        self.taken = end;
+        tracks
+    }
+
+    /// Combined decode statistics of all speakers
+    #[cfg(feature = "decoder")]
+    pub fn stats(&self) -> DecodeStats {
+        self.decoders.stats()
+    }
+
+    /// End the session, returning the streams of all speakers ordered by their first tick
+    pub fn into_speakers(self) -> Vec<SpeakerStream> {
+        let mut speakers: Vec<_> = self.speakers.into_values().collect();
+        speakers.sort_by_key(|speaker| (speaker.first_tick, speaker.steam_id));
+        speakers
+    }
+
+    pub fn len(&self) -> usize {
+        self.speakers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.speakers.is_empty()
+    }
+}
+
+#[cfg(all(
+    test,
+    any(feature = "opus", feature = "audiopus", feature = "opus-decoder")
+))]
+mod tests {
+    use super::*;
+    use crate::test_data::{opus, payload, raw, sample_rate, FRAME};
+    use crate::SpeakingEvent;
+
+    #[test]
+    fn logs_concealment_in_first_payload() {
+        let payload = payload(&[sample_rate(24000), opus(&[(0, FRAME), (2, FRAME)])]);
+        let mut session = VoiceSession::new();
+        session.set_speaking_log(true);
+        session
+            .push_voice_data(0, SteamVoiceData::new(&payload).unwrap())
+            .unwrap();
+        let events = session.speaking_log().unwrap().events();
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, SpeakingEvent::Concealed { .. })));
+    }
+
+    #[test]
+    fn failed_payload_adds_no_speaker() {
+        // opus frames before the sample rate can't be decoded
+        let invalid = payload(&[opus(&[(0, FRAME)])]);
+        let valid = payload(&[sample_rate(24000), opus(&[(0, FRAME)])]);
+        let mut session = VoiceSession::new();
+        assert!(session
+            .push_voice_data(5, SteamVoiceData::new(&invalid).unwrap())
+            .is_err());
+        assert!(session.is_empty());
+
+        session
+            .push_voice_data(10, SteamVoiceData::new(&valid).unwrap())
+            .unwrap();
+        let speaker = session.speaker(1).unwrap();
+        assert_eq!(speaker.payloads(), 1);
+        assert_eq!(speaker.first_tick(), 10);
+    }
+
+    #[test]
+    fn failed_payload_logs_no_concealment() {
+        // the gap is concealed before the odd length raw packet fails the payload
+        let mut odd = raw(&[1, 2]);
+        odd[1] = 3;
+        odd.pop();
+        let invalid = payload(&[sample_rate(24000), opus(&[(0, FRAME), (2, FRAME)]), odd]);
+        let valid = payload(&[sample_rate(24000), opus(&[(3, FRAME)])]);
+        let mut session = VoiceSession::new();
+        session.set_speaking_log(true);
+        assert!(session
+            .push_voice_data(0, SteamVoiceData::new(&invalid).unwrap())
+            .is_err());
+        session
+            .push_voice_data(1, SteamVoiceData::new(&valid).unwrap())
+            .unwrap();
+        let events = session.speaking_log().unwrap().events();
+        assert!(!events
+            .iter()
+            .any(|event| matches!(event, SpeakingEvent::Concealed { .. })));
+    }
+
+    #[test]
+    fn timing_matches_decoding_after_resets() {
+        /// 10ms CELT frame
+        const SHORT_FRAME: &[u8] = &[0xF0, 0xFF, 0xFE];
+        let mut reset = vec![0xFF, 0xFF];
+        reset.extend_from_slice(&[0, 0, 0, 0]);
+        let mut reset_packet = Vec::new();
+        crate::push_opus_packet(&mut reset_packet, &reset);
+        let payloads = [
+            payload(&[
+                sample_rate(24000),
+                opus(&[(0, SHORT_FRAME), (1, SHORT_FRAME)]),
+            ]),
+            // the empty frame following a reset marker is concealed with 20ms
+            payload(&[sample_rate(24000), reset_packet]),
+            payload(&[sample_rate(24000), opus(&[(1, SHORT_FRAME), (200, &[])])]),
+            payload(&[sample_rate(24000), opus(&[(201, SHORT_FRAME)])]),
+            payload(&[sample_rate(16000), opus(&[(202, &[])])]),
+        ];
+
+        let mut decoded = VoiceSession::new();
+        let mut timed = VoiceSession::timing_only(None);
+        for payload in &payloads {
+            let voice_data = SteamVoiceData::new(payload).unwrap();
+            let samples = decoded.push_voice_data(0, voice_data).unwrap();
+            assert_eq!(timed.push_voice_data(0, voice_data).unwrap(), samples);
+        }
+        assert_eq!(
+            decoded.speaker(1).unwrap().end(),
+            timed.speaker(1).unwrap().end()
+        );
+    }
+
+    #[test]
+    fn drift_correction_count() {
+        let timeline = TickTimeline::new(24000, Duration::from_millis(15))
+            .with_drift_correction(Duration::from_millis(1));
+        let mut session = VoiceSession::with_timeline(DecoderOptions::default(), timeline).unwrap();
+        let first = payload(&[sample_rate(24000), opus(&[(0, FRAME), (1, FRAME)])]);
+        let voice_data = SteamVoiceData::new(&first).unwrap();
+        assert_eq!(session.push_voice_data(100, voice_data).unwrap(), 960);
+        assert_eq!(session.speaker(1).unwrap().end(), 36000);
+
+        // received at the same tick, the audio is pulled back by a twentieth of its length
+        let second = payload(&[sample_rate(24000), opus(&[(2, FRAME), (3, FRAME)])]);
+        let voice_data = SteamVoiceData::new(&second).unwrap();
+        assert_eq!(session.push_voice_data(100, voice_data).unwrap(), 912);
+        let speaker = session.speaker(1).unwrap();
+        assert_eq!(speaker.end(), 36912);
+        if session.decodes_audio() {
+            assert_eq!(speaker.samples().len(), 960 + 912);
+        }
+    }
+
+    #[cfg(feature = "denoise-backend")]
+    fn constant_denoiser() -> Box<dyn crate::DenoiseBackend> {
+        struct Constant;
+        impl crate::DenoiseBackend for Constant {
+            fn process_frame(
+                &mut self,
+                output: &mut [f32; crate::DENOISE_FRAME_SAMPLES],
+                _input: &[f32; crate::DENOISE_FRAME_SAMPLES],
+            ) -> f32 {
+                output.fill(1000.0);
+                1.0
+            }
+        }
+        Box::new(Constant)
+    }
+
+    #[test]
+    #[cfg(feature = "denoise-backend")]
+    fn denoiser_requires_48khz() {
+        let mut session = VoiceSession::new();
+        assert!(matches!(
+            session.set_denoiser(Some(constant_denoiser)),
+            Err(SteamAudioError::UnsupportedSampleRate { rate: 0 })
+        ));
+        let timeline = TickTimeline::new(24000, Duration::from_millis(15));
+        let mut session = VoiceSession::with_timeline(DecoderOptions::default(), timeline).unwrap();
+        assert!(session.set_denoiser(Some(constant_denoiser)).is_err());
+        assert!(session.set_denoiser(None).is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "denoise-backend")]
+    fn denoiser_runs() {
+        let options = DecoderOptions {
+            output_rate: Some(48000),
+            ..DecoderOptions::default()
+        };
+        let mut session = VoiceSession::with_options(options).unwrap();
+        session.set_denoiser(Some(constant_denoiser)).unwrap();
+        let payload = payload(&[sample_rate(24000), opus(&[(0, FRAME)])]);
+        session
+            .push_voice_data(0, SteamVoiceData::new(&payload).unwrap())
+            .unwrap();
+        let samples = session.speaker(1).unwrap().samples();
+        assert_eq!(samples.len(), 960);
+        assert!(samples.iter().all(|sample| *sample == 1000));
+    }
+}
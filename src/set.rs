@@ -0,0 +1,118 @@
+//! Decoding voice data of multiple speakers
+
+use crate::{DecodeStats, DecoderOptions, SteamAudioError, SteamVoiceData, SteamVoiceDecoder};
+use std::collections::HashMap;
+
+/// A set of decoders, one for every speaker
+///
+/// Voice payloads of different speakers are commonly interleaved, but the opus state of a decoder
+/// only follows a single stream. The set routes every payload to the decoder for its steam id,
+/// creating a decoder with the configured options on the first payload of a speaker.
+#[derive(Debug, Default)]
+pub struct VoiceDecoderSet {
+    options: DecoderOptions,
+    decoders: HashMap<u64, SteamVoiceDecoder>,
+}
+
+impl VoiceDecoderSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a set where every decoder is created with `options`
+    pub fn with_options(options: DecoderOptions) -> Result<Self, SteamAudioError> {
+        options.validate()?;
+        Ok(VoiceDecoderSet {
+            options,
+            decoders: HashMap::new(),
+        })
+    }
+
+    pub fn options(&self) -> &DecoderOptions {
+        &self.options
+    }
+
+    /// Decode the voice data with the decoder of its speaker, returns the number of samples written
+    pub fn decode(
+        &mut self,
+        voice_data: SteamVoiceData,
+        output_buffer: &mut [i16],
+    ) -> Result<usize, SteamAudioError> {
+        self.get_or_insert(voice_data.steam_id)?
+            .decode(voice_data, output_buffer)
+    }
+
+    /// Decode the voice data with the decoder of its speaker, passing the steam id of the speaker
+    /// and each decoded chunk of samples to the callback
+    pub fn decode_with<F: FnMut(u64, &[i16])>(
+        &mut self,
+        voice_data: SteamVoiceData,
+        mut callback: F,
+    ) -> Result<(), SteamAudioError> {
+        let steam_id = voice_data.steam_id;
+        self.get_or_insert(steam_id)?
+            .decode_with(voice_data, |samples| callback(steam_id, samples))
+    }
+
+    /// Decode the voice data with the decoder of its speaker, appending the decoded samples to `output`
+    pub fn decode_append(
+        &mut self,
+        voice_data: SteamVoiceData,
+        output: &mut Vec<i16>,
+    ) -> Result<usize, SteamAudioError> {
+        self.get_or_insert(voice_data.steam_id)?
+            .decode_append(voice_data, output)
+    }
+
+    /// The decoder for a speaker, creating it if no voice data was decoded for it yet
+    pub fn get_or_insert(
+        &mut self,
+        steam_id: u64,
+    ) -> Result<&mut SteamVoiceDecoder, SteamAudioError> {
+        if !self.decoders.contains_key(&steam_id) {
+            let decoder = SteamVoiceDecoder::with_options(self.options)?;
+            self.decoders.insert(steam_id, decoder);
+        }
+        Ok(self.decoders.get_mut(&steam_id).unwrap())
+    }
+
+    /// The decoder for a speaker, if any voice data was decoded for it
+    pub fn get(&self, steam_id: u64) -> Option<&SteamVoiceDecoder> {
+        self.decoders.get(&steam_id)
+    }
+
+    pub fn get_mut(&mut self, steam_id: u64) -> Option<&mut SteamVoiceDecoder> {
+        self.decoders.get_mut(&steam_id)
+    }
+
+    /// Remove the decoder of a speaker, e.g. when the player disconnects
+    pub fn remove(&mut self, steam_id: u64) -> Option<SteamVoiceDecoder> {
+        self.decoders.remove(&steam_id)
+    }
+
+    /// Combined statistics of the decoders of all speakers
+    pub fn stats(&self) -> DecodeStats {
+        let mut stats = DecodeStats::default();
+        for decoder in self.decoders.values() {
+            stats += *decoder.stats();
+        }
+        stats
+    }
+
+    /// The steam ids of all speakers with a decoder
+    pub fn speakers(&self) -> impl Iterator<Item = u64> + '_ {
+        self.decoders.keys().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.decoders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.decoders.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.decoders.clear();
+    }
+}
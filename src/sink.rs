@@ -0,0 +1,175 @@
+use crate::SteamAudioError;
+use std::io::Write;
+
+/// Destination for decoded audio
+pub trait PcmSink {
+    fn write_samples(&mut self, samples: &[i16]) -> Result<(), SteamAudioError>;
+
+    /// Finalize the output, no more samples will be written after this
+    fn finish(&mut self) -> Result<(), SteamAudioError> {
+        Ok(())
+    }
+}
+
+impl PcmSink for Vec<i16> {
+    fn write_samples(&mut self, samples: &[i16]) -> Result<(), SteamAudioError> {
+        self.extend_from_slice(samples);
+        Ok(())
+    }
+}
+
+impl<S: PcmSink + ?Sized> PcmSink for Box<S> {
+    fn write_samples(&mut self, samples: &[i16]) -> Result<(), SteamAudioError> {
+        (**self).write_samples(samples)
+    }
+
+    fn finish(&mut self) -> Result<(), SteamAudioError> {
+        (**self).finish()
+    }
+}
+
+/// Sample encoding of the audio written by a [`RawPcmSink`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum RawFormat {
+    /// Signed 16 bit little endian integers, `s16le` in ffmpeg and `-e signed -b 16` in sox
+    #[default]
+    S16Le,
+    /// 32 bit little endian floats between -1 and 1, `f32le` in ffmpeg and `-e float -b 32` in sox
+    F32Le,
+}
+
+impl RawFormat {
+    /// Name of the format in ffmpeg
+    pub fn ffmpeg_name(&self) -> &'static str {
+        match self {
+            RawFormat::S16Le => "s16le",
+            RawFormat::F32Le => "f32le",
+        }
+    }
+
+    /// Number of bytes per sample
+    pub fn sample_size(&self) -> usize {
+        match self {
+            RawFormat::S16Le => 2,
+            RawFormat::F32Le => 4,
+        }
+    }
+}
+
+/// A [`PcmSink`] writing interleaved samples as raw PCM to any writer, like stdout, for piping decoded audio into
+/// other tools
+///
+/// Without a header the output can be read with `ffmpeg -f s16le -ar 24000 -ac 1 -i -` or
+/// `sox -t raw -e signed -b 16 -r 24000 -c 1 - output.wav`, with the sample rate and channels of the audio. With
+/// [`with_wav_header`](Self::with_wav_header) the audio is preceded by a WAV header with the sizes left open, so
+/// the format is detected from the stream by `ffmpeg -i -` and `sox -t wav -`.
+#[derive(Debug)]
+pub struct RawPcmSink<W: Write> {
+    writer: W,
+    format: RawFormat,
+    header: Option<(u32, u16)>,
+    header_written: bool,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> RawPcmSink<W> {
+    /// Write headerless 16 bit samples to `writer`
+    pub fn new(writer: W) -> Self {
+        RawPcmSink {
+            writer,
+            format: RawFormat::default(),
+            header: None,
+            header_written: false,
+            buffer: Vec::new(),
+        }
+    }
+
+    pub fn with_format(mut self, format: RawFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Start the output with a WAV header for audio at `sample_rate` with `channels` interleaved channels
+    ///
+    /// The length of the audio isn't known up front, so the sizes in the header are set to the maximum, and readers
+    /// of streamed WAV read until the end of the stream.
+    pub fn with_wav_header(mut self, sample_rate: u32, channels: u16) -> Self {
+        self.header = Some((sample_rate, channels));
+        self
+    }
+
+    pub fn format(&self) -> RawFormat {
+        self.format
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_header(&mut self) -> Result<(), SteamAudioError> {
+        let Some((sample_rate, channels)) = self.header else {
+            return Ok(());
+        };
+        let sample_size = self.format.sample_size() as u16;
+        // 1 for integer PCM, 3 for IEEE floats
+        let format_tag: u16 = match self.format {
+            RawFormat::S16Le => 1,
+            RawFormat::F32Le => 3,
+        };
+        let block_align = channels.max(1) * sample_size;
+        // the largest size of whole frames that leaves room for the rest of the header in the RIFF size
+        let data_size = (u32::MAX - 36) / block_align as u32 * block_align as u32;
+        let mut header = Vec::with_capacity(44);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&(data_size + 36).to_le_bytes());
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        header.extend_from_slice(&format_tag.to_le_bytes());
+        header.extend_from_slice(&channels.to_le_bytes());
+        header.extend_from_slice(&sample_rate.to_le_bytes());
+        header
+            .extend_from_slice(&(sample_rate * channels as u32 * sample_size as u32).to_le_bytes());
+        header.extend_from_slice(&block_align.to_le_bytes());
+        header.extend_from_slice(&(sample_size * 8).to_le_bytes());
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&data_size.to_le_bytes());
+        self.writer.write_all(&header)?;
+        Ok(())
+    }
+}
+
+impl<W: Write> PcmSink for RawPcmSink<W> {
+    fn write_samples(&mut self, samples: &[i16]) -> Result<(), SteamAudioError> {
+        if !self.header_written {
+            self.header_written = true;
+            self.write_header()?;
+        }
+        self.buffer.clear();
+        match self.format {
+            RawFormat::S16Le => self
+                .buffer
+                .extend(samples.iter().flat_map(|sample| sample.to_le_bytes())),
+            RawFormat::F32Le => self.buffer.extend(
+                samples
+                    .iter()
+                    .flat_map(|sample| (*sample as f32 / 32768.0).to_le_bytes()),
+            ),
+        }
+        self.writer.write_all(&self.buffer)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), SteamAudioError> {
+        if !self.header_written {
+            self.header_written = true;
+            self.write_header()?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+//! Heuristic detection of the voice format of a payload without any `VoiceInit` context
+
+use crate::{crc32, plausible_rate, validate_frames, Packet, PacketType};
+
+/// The voice format that a payload most likely uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectedFormat {
+    /// Steam voice payload containing opus data (`steam` codec)
+    Steam,
+    /// Steam voice payload containing the legacy SILK codec
+    Silk,
+    /// Raw CELT frames (`vaudio_celt`)
+    Celt,
+    /// Raw Speex frames (`vaudio_speex`)
+    Speex,
+    Unknown,
+}
+
+/// Encoded frame sizes used by `vaudio_celt` for the different quality levels
+const CELT_FRAME_SIZES: [usize; 3] = [64, 60, 120];
+/// Encoded frame sizes of narrowband speex for the different quality levels
+const SPEEX_FRAME_SIZES: [usize; 8] = [6, 10, 15, 20, 28, 38, 46, 62];
+
+/// Guess the format of a voice payload
+///
+/// Steam payloads are recognized by their checksum or, if that is damaged, by their framing
+/// and the validity of the opus TOC bytes they contain.
+/// Raw engine codecs don't contain any framing and are guessed from their fixed frame sizes
+/// and the mode bits of speex frames, so payloads containing only a single frame can be ambiguous.
+pub fn sniff(data: &[u8]) -> DetectedFormat {
+    if data.len() >= 12 {
+        let (payload, crc) = data.split_at(data.len() - 4);
+        let crc_valid = crc32(payload).to_le_bytes() == crc;
+        match steam_codec(&payload[8..], crc_valid) {
+            Some(format) => return format,
+            None if crc_valid => return DetectedFormat::Steam,
+            None => {}
+        }
+    }
+
+    if let Some(frame_size) = SPEEX_FRAME_SIZES
+        .iter()
+        .copied()
+        .find(|size| is_speex(data, *size))
+    {
+        // speex frames that happen to also match the celt frame size are more likely celt
+        if !CELT_FRAME_SIZES.contains(&frame_size) || data.len() > frame_size {
+            return DetectedFormat::Speex;
+        }
+    }
+
+    if CELT_FRAME_SIZES
+        .iter()
+        .any(|size| !data.is_empty() && data.len() % size == 0)
+    {
+        return DetectedFormat::Celt;
+    }
+
+    DetectedFormat::Unknown
+}
+
+/// Walk the packets of a steam payload to determine which codec it contains
+///
+/// When the crc isn't valid, the payload is only accepted if all packets are of known types, it starts with a
+/// sample rate or codec data, the sample rates are plausible and all opus data consists of complete frames with
+/// valid TOC bytes.
+fn steam_codec(mut data: &[u8], crc_valid: bool) -> Option<DetectedFormat> {
+    let mut format = None;
+    let mut first = true;
+    while !data.is_empty() {
+        let (packet, rest) = if crc_valid {
+            Packet::read_lenient(data)
+        } else {
+            Packet::read(data)
+        }
+        .ok()?;
+        data = rest;
+        match packet {
+            Packet::SampleRate(rate) if !crc_valid && !plausible_rate(rate) => return None,
+            Packet::SampleRate(_) => {}
+            Packet::Unsupported {
+                ty: PacketType::CodecSilk,
+                ..
+            } => format = Some(DetectedFormat::Silk),
+            Packet::OpusPlc(opus) => {
+                if !crc_valid && validate_frames(opus.data).is_err() {
+                    return None;
+                }
+                format = format.or(Some(DetectedFormat::Steam));
+            }
+            _ if !crc_valid && first => return None,
+            _ => {}
+        }
+        first = false;
+    }
+    format
+}
+
+/// Check if the data consists of narrowband speex frames of the given size that all use the same mode
+fn is_speex(data: &[u8], frame_size: usize) -> bool {
+    if data.is_empty() || data.len() % frame_size != 0 {
+        return false;
+    }
+    // the first bit of a narrowband frame is 0, followed by 4 bits of mode id
+    let mode = |frame: &[u8]| (frame[0] & 0x80 == 0).then_some((frame[0] >> 3) & 0x0F);
+    let Some(first) = mode(data) else {
+        return false;
+    };
+    (1..=8).contains(&first)
+        && data
+            .chunks(frame_size)
+            .all(|frame| mode(frame) == Some(first))
+}
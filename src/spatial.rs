@@ -0,0 +1,151 @@
+//! Mixing speakers by their position in the game world
+
+use crate::{Mixer, TickTimeline};
+
+/// Default for [`Spatializer::reference_distance`], in world units
+pub const DEFAULT_REFERENCE_DISTANCE: f32 = 100.0;
+/// Default for [`Spatializer::max_distance`], in world units
+pub const DEFAULT_MAX_DISTANCE: f32 = 2000.0;
+
+/// The position and view direction of the listener
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Listener {
+    pub position: [f32; 3],
+    /// View direction around the vertical axis in degrees, with the engine convention of 0 facing
+    /// the positive x axis and 90 facing the positive y axis
+    pub yaw: f32,
+}
+
+/// Supplies the world positions of the speakers and the listener, e.g. from the entities of a demo
+pub trait PositionSource {
+    /// The position of the speaker at the tick, if known
+    fn speaker(&mut self, steam_id: u64, tick: u32) -> Option<[f32; 3]>;
+
+    /// The listener at the tick, if known
+    fn listener(&mut self, tick: u32) -> Option<Listener>;
+}
+
+/// Mixes speakers with distance attenuation and panning relative to a listener
+///
+/// The audio of a speaker is split at the ticks of the timeline and every tick is mixed with the positions
+/// from the [`PositionSource`] at that tick. The volume falls off with the inverse of the distance beyond the
+/// [`reference_distance`](Self::reference_distance), speakers further away than the
+/// [`max_distance`](Self::max_distance) are silent. Speakers are panned by their direction from the listener.
+/// Audio at ticks where the speaker or listener position isn't known is mixed centered, without attenuation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spatializer {
+    timeline: TickTimeline,
+    reference_distance: f32,
+    max_distance: f32,
+}
+
+impl Spatializer {
+    pub fn new(timeline: TickTimeline) -> Self {
+        Spatializer {
+            timeline,
+            reference_distance: DEFAULT_REFERENCE_DISTANCE,
+            max_distance: DEFAULT_MAX_DISTANCE,
+        }
+    }
+
+    /// Set the distance up to which speakers are heard at full volume
+    pub fn with_reference_distance(mut self, distance: f32) -> Self {
+        self.reference_distance = distance;
+        self
+    }
+
+    /// Set the distance beyond which speakers can't be heard
+    pub fn with_max_distance(mut self, distance: f32) -> Self {
+        self.max_distance = distance;
+        self
+    }
+
+    pub fn timeline(&self) -> &TickTimeline {
+        &self.timeline
+    }
+
+    pub fn reference_distance(&self) -> f32 {
+        self.reference_distance
+    }
+
+    pub fn max_distance(&self) -> f32 {
+        self.max_distance
+    }
+
+    /// The gains for the left and right channel of a speaker at `speaker` heard by the listener
+    pub fn gains(&self, listener: &Listener, speaker: [f32; 3]) -> [f32; 2] {
+        let offset = [
+            speaker[0] - listener.position[0],
+            speaker[1] - listener.position[1],
+            speaker[2] - listener.position[2],
+        ];
+        let distance = offset.iter().map(|d| d * d).sum::<f32>().sqrt();
+        if distance > self.max_distance {
+            return [0.0, 0.0];
+        }
+        let gain = if distance > self.reference_distance {
+            self.reference_distance / distance
+        } else {
+            1.0
+        };
+
+        let horizontal = offset[0].hypot(offset[1]);
+        let pan = if horizontal > 0.0 {
+            // the right of the listener is the view direction rotated clockwise
+            let yaw = listener.yaw.to_radians();
+            (offset[0] * yaw.sin() - offset[1] * yaw.cos()) / horizontal
+        } else {
+            0.0
+        };
+        [gain * (1.0 - pan).min(1.0), gain * (1.0 + pan).min(1.0)]
+    }
+
+    /// Add mono samples of a speaker starting at sample `position` of the timeline to the mixer,
+    /// with the gains for the positions at every tick
+    ///
+    /// Speakers that are muted in the mixer, or not soloed while others are, are silent, and other speakers are
+    /// ducked while the [`Ducking`](crate::Ducking) leader of the mixer talks.
+    pub fn add<S: PositionSource + ?Sized>(
+        &self,
+        source: &mut S,
+        mixer: &mut Mixer,
+        steam_id: u64,
+        position: u64,
+        samples: &[i16],
+    ) {
+        let mut start = 0;
+        while start < samples.len() {
+            let sample_position = position + start as u64;
+            let tick = self.tick_at(sample_position);
+            let tick_end = self
+                .timeline
+                .tick_position(tick + 1)
+                .max(sample_position + 1);
+            let end = samples.len().min((tick_end - position) as usize);
+            let gains = match (source.listener(tick), source.speaker(steam_id, tick)) {
+                (Some(listener), Some(speaker)) => self.gains(&listener, speaker),
+                _ => [1.0, 1.0],
+            };
+            mixer.add_speaker_with_gains(steam_id, sample_position, &samples[start..end], gains);
+            start = end;
+        }
+    }
+
+    /// The tick containing a sample position of the timeline
+    fn tick_at(&self, position: u64) -> u32 {
+        let interval = self.timeline.tick_interval().as_secs_f64();
+        if interval == 0.0 {
+            return 0;
+        }
+        let tick = self.timeline.time(position).as_secs_f64() / interval;
+        let tick = tick as u32;
+        // correct for rounding of the tick positions
+        if self.timeline.tick_position(tick + 1) <= position {
+            tick + 1
+        } else if tick > 0 && self.timeline.tick_position(tick) > position {
+            tick - 1
+        } else {
+            tick
+        }
+    }
+}
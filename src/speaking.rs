@@ -0,0 +1,349 @@
+//! A chronological record of who talked when
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// An event in the [`SpeakingLog`] of a session
+///
+/// Positions are in samples per channel on the timeline of the session, see
+/// [`SpeakerStream::position`](crate::SpeakerStream::position), and the time is the position converted to a duration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum SpeakingEvent {
+    /// The speaker started an utterance
+    Started {
+        steam_id: u64,
+        position: u64,
+        time: Duration,
+    },
+    /// The speaker ended an utterance
+    Stopped {
+        steam_id: u64,
+        position: u64,
+        time: Duration,
+    },
+    /// Two speakers talked at the same time
+    Overlap {
+        steam_id: u64,
+        /// The speaker that was already talking when `steam_id` started
+        with: u64,
+        position: u64,
+        time: Duration,
+        duration: Duration,
+    },
+    /// Lost audio of the speaker was replaced by generated audio
+    Concealed {
+        steam_id: u64,
+        position: u64,
+        time: Duration,
+        samples: u64,
+        /// Whether the audio was recovered from forward error correction data
+        recovered: bool,
+    },
+}
+
+impl SpeakingEvent {
+    pub fn steam_id(&self) -> u64 {
+        match self {
+            SpeakingEvent::Started { steam_id, .. }
+            | SpeakingEvent::Stopped { steam_id, .. }
+            | SpeakingEvent::Overlap { steam_id, .. }
+            | SpeakingEvent::Concealed { steam_id, .. } => *steam_id,
+        }
+    }
+
+    pub fn position(&self) -> u64 {
+        match self {
+            SpeakingEvent::Started { position, .. }
+            | SpeakingEvent::Stopped { position, .. }
+            | SpeakingEvent::Overlap { position, .. }
+            | SpeakingEvent::Concealed { position, .. } => *position,
+        }
+    }
+
+    pub fn time(&self) -> Duration {
+        match self {
+            SpeakingEvent::Started { time, .. }
+            | SpeakingEvent::Stopped { time, .. }
+            | SpeakingEvent::Overlap { time, .. }
+            | SpeakingEvent::Concealed { time, .. } => *time,
+        }
+    }
+
+    /// Order of events at the same position, an utterance ends before the next one starts
+    fn rank(&self) -> u8 {
+        match self {
+            SpeakingEvent::Stopped { .. } => 0,
+            SpeakingEvent::Started { .. } => 1,
+            SpeakingEvent::Overlap { .. } => 2,
+            SpeakingEvent::Concealed { .. } => 3,
+        }
+    }
+}
+
+/// A continuous stretch of audio of a speaker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Utterance {
+    pub steam_id: u64,
+    /// Position of the first sample
+    pub start: u64,
+    /// Position directly after the last sample
+    pub end: u64,
+    pub sample_rate: u32,
+}
+
+impl Utterance {
+    pub fn duration(&self) -> Duration {
+        to_time(self.end - self.start, self.sample_rate)
+    }
+}
+
+/// Records the utterances of all speakers of a [`VoiceSession`](crate::VoiceSession), and the regions that were
+/// concealed, to produce a searchable record of who talked when
+///
+/// Enable the log with [`VoiceSession::set_speaking_log`](crate::VoiceSession::set_speaking_log). Utterances are
+/// separated by the gaps of the [`TickTimeline`](crate::TickTimeline) of the session, without a timeline all audio
+/// of a speaker is a single utterance and the positions of different speakers can't be compared, so no overlaps are
+/// reported.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SpeakingLog {
+    utterances: Vec<Utterance>,
+    concealed: Vec<SpeakingEvent>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    current: HashMap<u64, usize>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    overlaps: bool,
+}
+
+impl SpeakingLog {
+    /// Create a log, `overlaps` tells whether the positions of all speakers are on a shared timeline
+    pub(crate) fn new(overlaps: bool) -> Self {
+        SpeakingLog {
+            overlaps,
+            ..SpeakingLog::default()
+        }
+    }
+
+    /// Record audio of a speaker between `start` and `end`
+    pub(crate) fn add_audio(
+        &mut self,
+        steam_id: u64,
+        start: u64,
+        end: u64,
+        sample_rate: u32,
+        new_utterance: bool,
+    ) {
+        if end <= start {
+            return;
+        }
+        match self.current.get(&steam_id) {
+            Some(index) if !new_utterance => {
+                let utterance = &mut self.utterances[*index];
+                utterance.end = utterance.end.max(end);
+            }
+            _ => {
+                self.current.insert(steam_id, self.utterances.len());
+                self.utterances.push(Utterance {
+                    steam_id,
+                    start,
+                    end,
+                    sample_rate,
+                });
+            }
+        }
+    }
+
+    /// Record a concealed region of a speaker
+    pub(crate) fn add_concealed(
+        &mut self,
+        steam_id: u64,
+        position: u64,
+        samples: u64,
+        sample_rate: u32,
+        recovered: bool,
+    ) {
+        self.concealed.push(SpeakingEvent::Concealed {
+            steam_id,
+            position,
+            time: to_time(position, sample_rate),
+            samples,
+            recovered,
+        });
+    }
+
+    /// The utterances of all speakers, in the order they were first received
+    pub fn utterances(&self) -> &[Utterance] {
+        &self.utterances
+    }
+
+    /// All events in chronological order
+    ///
+    /// The utterance that a speaker is still in counts as stopped after the last audio received so far.
+    pub fn events(&self) -> Vec<SpeakingEvent> {
+        let mut events = Vec::with_capacity(self.utterances.len() * 2 + self.concealed.len());
+        for utterance in &self.utterances {
+            events.push(SpeakingEvent::Started {
+                steam_id: utterance.steam_id,
+                position: utterance.start,
+                time: to_time(utterance.start, utterance.sample_rate),
+            });
+            events.push(SpeakingEvent::Stopped {
+                steam_id: utterance.steam_id,
+                position: utterance.end,
+                time: to_time(utterance.end, utterance.sample_rate),
+            });
+        }
+        if self.overlaps {
+            for (index, utterance) in self.utterances.iter().enumerate() {
+                for other in &self.utterances[index + 1..] {
+                    if other.steam_id == utterance.steam_id {
+                        continue;
+                    }
+                    let start = utterance.start.max(other.start);
+                    let end = utterance.end.min(other.end);
+                    if start >= end {
+                        continue;
+                    }
+                    // the speaker starting last overlaps with the one already talking
+                    let (later, earlier) = if other.start >= utterance.start {
+                        (other, utterance)
+                    } else {
+                        (utterance, other)
+                    };
+                    events.push(SpeakingEvent::Overlap {
+                        steam_id: later.steam_id,
+                        with: earlier.steam_id,
+                        position: start,
+                        time: to_time(start, later.sample_rate),
+                        duration: to_time(end - start, later.sample_rate),
+                    });
+                }
+            }
+        }
+        events.extend_from_slice(&self.concealed);
+        events.sort_by_key(|event| (event.position(), event.rank(), event.steam_id()));
+        events
+    }
+
+    /// The events of a single speaker in chronological order, including overlaps with others
+    pub fn speaker_events(&self, steam_id: u64) -> Vec<SpeakingEvent> {
+        let mut events = self.events();
+        events.retain(|event| {
+            event.steam_id() == steam_id
+                || matches!(event, SpeakingEvent::Overlap { with, .. } if *with == steam_id)
+        });
+        events
+    }
+
+    /// Forget all recorded events
+    pub fn clear(&mut self) {
+        self.utterances.clear();
+        self.concealed.clear();
+        self.current.clear();
+    }
+}
+
+fn to_time(position: u64, sample_rate: u32) -> Duration {
+    if sample_rate == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_secs_f64(position as f64 / sample_rate as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Record audio in 20ms chunks at 48kHz, as the session does while decoding
+    fn talk(log: &mut SpeakingLog, steam_id: u64, start: u64, end: u64) {
+        for (index, chunk) in (start..end).step_by(960).enumerate() {
+            log.add_audio(steam_id, chunk, (chunk + 960).min(end), 48000, index == 0);
+        }
+    }
+
+    #[test]
+    fn records_utterances_and_overlaps() {
+        let mut log = SpeakingLog::new(true);
+        talk(&mut log, 1, 0, 48000);
+        talk(&mut log, 2, 24000, 72000);
+        log.add_concealed(2, 48000, 960, 48000, false);
+        talk(&mut log, 1, 96000, 144000);
+
+        assert_eq!(log.utterances().len(), 3);
+        assert_eq!(log.utterances()[2].duration(), Duration::from_secs(1));
+
+        let secs = Duration::from_secs_f64;
+        assert_eq!(
+            log.events(),
+            vec![
+                SpeakingEvent::Started {
+                    steam_id: 1,
+                    position: 0,
+                    time: secs(0.0)
+                },
+                SpeakingEvent::Started {
+                    steam_id: 2,
+                    position: 24000,
+                    time: secs(0.5)
+                },
+                SpeakingEvent::Overlap {
+                    steam_id: 2,
+                    with: 1,
+                    position: 24000,
+                    time: secs(0.5),
+                    duration: secs(0.5)
+                },
+                SpeakingEvent::Stopped {
+                    steam_id: 1,
+                    position: 48000,
+                    time: secs(1.0)
+                },
+                SpeakingEvent::Concealed {
+                    steam_id: 2,
+                    position: 48000,
+                    time: secs(1.0),
+                    samples: 960,
+                    recovered: false
+                },
+                SpeakingEvent::Stopped {
+                    steam_id: 2,
+                    position: 72000,
+                    time: secs(1.5)
+                },
+                SpeakingEvent::Started {
+                    steam_id: 1,
+                    position: 96000,
+                    time: secs(2.0)
+                },
+                SpeakingEvent::Stopped {
+                    steam_id: 1,
+                    position: 144000,
+                    time: secs(3.0)
+                },
+            ]
+        );
+
+        let positions: Vec<_> = log
+            .speaker_events(1)
+            .iter()
+            .map(SpeakingEvent::position)
+            .collect();
+        assert_eq!(positions, vec![0, 24000, 48000, 96000, 144000]);
+    }
+
+    #[test]
+    fn no_overlaps_without_timeline() {
+        let mut log = SpeakingLog::new(false);
+        talk(&mut log, 1, 0, 48000);
+        talk(&mut log, 2, 0, 48000);
+        assert!(!log
+            .events()
+            .iter()
+            .any(|event| matches!(event, SpeakingEvent::Overlap { .. })));
+
+        log.clear();
+        assert!(log.events().is_empty());
+    }
+}
@@ -0,0 +1,24 @@
+//! Framing of the speex codec used by the engine voice codecs
+
+/// Speex is used in narrowband mode, at 8kHz
+#[cfg(feature = "goldsrc")]
+pub(crate) const SPEEX_SAMPLE_RATE: u32 = 8000;
+/// Number of samples of an encoded speex frame
+pub(crate) const SPEEX_FRAME_SAMPLES: usize = 160;
+
+/// Size of an encoded narrowband frame for each speex quality level
+const SPEEX_FRAME_SIZES: [usize; 11] = [6, 10, 15, 20, 20, 28, 28, 38, 38, 46, 62];
+
+/// The speex quality level the engine uses for a voice quality of 1 to 5
+///
+/// The engine maps its voice quality to every other speex quality level, starting at 0.
+pub(crate) fn speex_quality(voice_quality: u8) -> Option<u8> {
+    (1..=5)
+        .contains(&voice_quality)
+        .then(|| (voice_quality - 1) * 2)
+}
+
+/// Size of an encoded frame at the engine voice quality
+pub(crate) fn speex_frame_size(voice_quality: u8) -> Option<usize> {
+    speex_quality(voice_quality).map(|quality| SPEEX_FRAME_SIZES[quality as usize])
+}
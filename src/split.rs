@@ -0,0 +1,124 @@
+use crate::{PcmSink, SteamAudioError};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::time::Duration;
+
+/// The start of a new output segment for a speaker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SegmentStart {
+    pub steam_id: u64,
+    /// Position of the first sample of the segment on the timeline
+    pub start_sample: u64,
+    /// Time of the first sample of the segment
+    pub start: Duration,
+}
+
+impl SegmentStart {
+    /// File name for the segment containing the steam id and start time, e.g. `76561198012345678_01h02m03s.wav`
+    pub fn file_name(&self, extension: &str) -> String {
+        let seconds = self.start.as_secs();
+        format!(
+            "{}_{:02}h{:02}m{:02}s.{}",
+            self.steam_id,
+            seconds / 3600,
+            (seconds / 60) % 60,
+            seconds % 60,
+            extension
+        )
+    }
+}
+
+struct Segment<S> {
+    sink: S,
+    /// Timeline position directly after the last written sample
+    end: u64,
+}
+
+/// Splits the audio of each speaker into multiple outputs when the speaker is inactive for too long
+///
+/// Short gaps between the writes of a speaker are filled with silence,
+/// when the gap exceeds the threshold the current output for the speaker is finished
+/// and a new one is opened through the `create` callback.
+pub struct InactivitySplitter<S, F> {
+    sample_rate: u32,
+    max_gap: u64,
+    create: F,
+    segments: HashMap<u64, Segment<S>>,
+}
+
+impl<S, F> Debug for InactivitySplitter<S, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InactivitySplitter")
+            .field("sample_rate", &self.sample_rate)
+            .field("max_gap", &self.max_gap)
+            .field("speakers", &self.segments.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, F> InactivitySplitter<S, F>
+where
+    S: PcmSink,
+    F: FnMut(SegmentStart) -> Result<S, SteamAudioError>,
+{
+    pub fn new(sample_rate: u32, max_gap: Duration, create: F) -> Self {
+        InactivitySplitter {
+            sample_rate,
+            max_gap: (max_gap.as_secs_f64() * sample_rate as f64) as u64,
+            create,
+            segments: HashMap::new(),
+        }
+    }
+
+    /// Write audio for a speaker, starting at the given sample position on the timeline
+    pub fn write(
+        &mut self,
+        steam_id: u64,
+        start_sample: u64,
+        samples: &[i16],
+    ) -> Result<(), SteamAudioError> {
+        if let Some(segment) = self.segments.get_mut(&steam_id) {
+            let gap = start_sample.saturating_sub(segment.end);
+            if gap <= self.max_gap {
+                write_silence(&mut segment.sink, gap)?;
+                segment.sink.write_samples(samples)?;
+                segment.end = segment.end.max(start_sample) + samples.len() as u64;
+                return Ok(());
+            }
+            segment.sink.finish()?;
+        }
+
+        let mut sink = (self.create)(SegmentStart {
+            steam_id,
+            start_sample,
+            start: Duration::from_secs_f64(start_sample as f64 / self.sample_rate as f64),
+        })?;
+        sink.write_samples(samples)?;
+        self.segments.insert(
+            steam_id,
+            Segment {
+                sink,
+                end: start_sample + samples.len() as u64,
+            },
+        );
+        Ok(())
+    }
+
+    /// Finish the open outputs of all speakers
+    pub fn finish(self) -> Result<(), SteamAudioError> {
+        for (_, mut segment) in self.segments {
+            segment.sink.finish()?;
+        }
+        Ok(())
+    }
+}
+
+fn write_silence<S: PcmSink>(sink: &mut S, mut count: u64) -> Result<(), SteamAudioError> {
+    const ZEROS: [i16; 1024] = [0; 1024];
+    while count > 0 {
+        let chunk = count.min(ZEROS.len() as u64) as usize;
+        sink.write_samples(&ZEROS[..chunk])?;
+        count -= chunk as u64;
+    }
+    Ok(())
+}
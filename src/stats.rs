@@ -0,0 +1,41 @@
+use std::ops::AddAssign;
+
+/// Counters for the voice data processed by a [`SteamVoiceDecoder`](crate::SteamVoiceDecoder)
+///
+/// Statistics of multiple decoders can be combined with `+=`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DecodeStats {
+    /// Number of payloads decoded
+    pub payloads: u64,
+    /// Number of opus frames decoded
+    pub frames: u64,
+    /// Number of lost frames that were concealed
+    pub concealed: u64,
+    /// Number of samples of silence written to the output
+    pub silence_samples: u64,
+    /// Number of times the opus state was reset, either by the voice data or because of a sequence discontinuity
+    pub resets: u64,
+    /// Number of payloads passed to [`decode_payload`](crate::SteamVoiceDecoder::decode_payload) that had an invalid checksum
+    pub crc_failures: u64,
+    /// Number of packets of unknown types skipped in [lenient](crate::ParseMode::Lenient) parsing
+    pub skipped_packets: u64,
+    /// Number of bytes skipped to resynchronize after malformed packets in [lenient](crate::ParseMode::Lenient) parsing
+    pub skipped_bytes: u64,
+    /// Number of packets of unsupported codecs, like the legacy, silk or speex codecs, left out of the output
+    pub unsupported_packets: u64,
+}
+
+impl AddAssign for DecodeStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.payloads += rhs.payloads;
+        self.frames += rhs.frames;
+        self.concealed += rhs.concealed;
+        self.silence_samples += rhs.silence_samples;
+        self.resets += rhs.resets;
+        self.crc_failures += rhs.crc_failures;
+        self.skipped_packets += rhs.skipped_packets;
+        self.skipped_bytes += rhs.skipped_bytes;
+        self.unsupported_packets += rhs.unsupported_packets;
+    }
+}
@@ -0,0 +1,18 @@
+//! The steam id of voice data as a [`SteamID`]
+
+use crate::{OwnedSteamVoiceData, SteamVoiceData};
+use steamid_ng::SteamID;
+
+impl SteamVoiceData<'_> {
+    /// The steam id of the speaker, which can be rendered as SteamID2 or SteamID3
+    pub fn steamid(&self) -> SteamID {
+        SteamID::from(self.steam_id)
+    }
+}
+
+impl OwnedSteamVoiceData {
+    /// The steam id of the speaker, which can be rendered as SteamID2 or SteamID3
+    pub fn steamid(&self) -> SteamID {
+        SteamID::from(self.steam_id)
+    }
+}
@@ -0,0 +1,60 @@
+use crate::{SteamAudioError, SteamVoiceData, SteamVoiceDecoder};
+use futures_core::Stream;
+use std::fmt::Debug;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Decodes a stream of voice payloads, yielding the decoded PCM for each payload
+pub struct VoiceStream<S> {
+    decoder: SteamVoiceDecoder,
+    payloads: S,
+}
+
+impl<S> Debug for VoiceStream<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VoiceStream").finish_non_exhaustive()
+    }
+}
+
+impl<S, T> VoiceStream<S>
+where
+    S: Stream<Item = T> + Unpin,
+    T: AsRef<[u8]>,
+{
+    pub fn new(decoder: SteamVoiceDecoder, payloads: S) -> Self {
+        VoiceStream { decoder, payloads }
+    }
+
+    /// Get back the decoder
+    pub fn into_decoder(self) -> SteamVoiceDecoder {
+        self.decoder
+    }
+
+    fn decode(&mut self, payload: &[u8]) -> Result<Vec<i16>, SteamAudioError> {
+        let voice_data = SteamVoiceData::new(payload)?;
+        let mut samples = Vec::new();
+        self.decoder.decode_append(voice_data, &mut samples)?;
+        Ok(samples)
+    }
+}
+
+impl<S, T> Stream for VoiceStream<S>
+where
+    S: Stream<Item = T> + Unpin,
+    T: AsRef<[u8]>,
+{
+    type Item = Result<Vec<i16>, SteamAudioError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.payloads).poll_next(cx) {
+            Poll::Ready(Some(payload)) => Poll::Ready(Some(this.decode(payload.as_ref()))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.payloads.size_hint()
+    }
+}
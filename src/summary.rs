@@ -0,0 +1,277 @@
+//! Packet counts and duration of voice data, without decoding it
+
+use crate::toc::packet_samples;
+use crate::{
+    decode_rate, frames_lost, OpusFrame, OpusFrameIterator, Packet, SteamAudioError, SteamVoiceData,
+};
+use std::ops::AddAssign;
+use std::time::Duration;
+
+/// Overview of the packets in voice data, see [`SteamVoiceData::summary`]
+///
+/// Summaries of multiple payloads can be combined with `+=`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PayloadSummary {
+    /// Total number of packets
+    pub packets: usize,
+    pub silence_packets: usize,
+    pub opus_packets: usize,
+    pub sample_rate_packets: usize,
+    pub raw_packets: usize,
+    /// Number of packets encoded with a codec that isn't supported
+    pub unsupported_packets: usize,
+    pub marker_packets: usize,
+    /// Number of opus frames, not including encoder resets
+    pub frames: usize,
+    /// Number of encoder resets in the opus packets
+    pub resets: usize,
+    /// Number of samples of silence
+    pub silence_samples: usize,
+    /// Number of samples the voice data decodes to, at the sample rate of the voice data
+    pub samples: usize,
+    /// Duration of the decoded samples
+    pub duration: Duration,
+    /// The last sample rate in the voice data
+    pub sample_rate: Option<u16>,
+}
+
+impl PayloadSummary {
+    pub(crate) fn new(voice_data: &SteamVoiceData) -> Result<Self, SteamAudioError> {
+        let mut summary = PayloadSummary::default();
+        // samples before the first sample rate packet are timed at the first sample rate
+        let mut untimed = 0;
+        for packet in voice_data.packets() {
+            let packet = packet?;
+            summary.packets += 1;
+            let samples = match packet {
+                Packet::SampleRate(rate) => {
+                    summary.sample_rate_packets += 1;
+                    if summary.sample_rate.is_none() && rate > 0 {
+                        summary.duration += samples_duration(untimed, rate);
+                    }
+                    summary.sample_rate = Some(rate);
+                    0
+                }
+                Packet::Silence(silence) => {
+                    summary.silence_packets += 1;
+                    summary.silence_samples += silence as usize;
+                    silence as usize
+                }
+                Packet::RawPcm(pcm) => {
+                    summary.raw_packets += 1;
+                    pcm.len() / 2
+                }
+                Packet::OpusPlc(opus) => {
+                    summary.opus_packets += 1;
+                    let rate = summary.sample_rate.ok_or(SteamAudioError::NoSampleRate)?;
+                    let mut samples = 0;
+                    for frame in OpusFrameIterator::new(opus.data) {
+                        match frame? {
+                            OpusFrame::Reset => summary.resets += 1,
+                            OpusFrame::Frame { data, .. } => {
+                                summary.frames += 1;
+                                if !data.is_empty() {
+                                    samples += packet_samples(data, rate as u32)?;
+                                }
+                            }
+                        }
+                    }
+                    samples
+                }
+                Packet::Unsupported { .. } => {
+                    summary.unsupported_packets += 1;
+                    0
+                }
+                Packet::Marker(_) => {
+                    summary.marker_packets += 1;
+                    0
+                }
+                Packet::Unknown { .. } => 0,
+            };
+            summary.samples += samples;
+            match summary.sample_rate {
+                Some(rate) if rate > 0 => summary.duration += samples_duration(samples, rate),
+                Some(_) => {}
+                None => untimed += samples,
+            }
+        }
+        Ok(summary)
+    }
+}
+
+fn samples_duration(samples: usize, sample_rate: u16) -> Duration {
+    Duration::from_secs_f64(samples as f64 / sample_rate as f64)
+}
+
+impl AddAssign for PayloadSummary {
+    fn add_assign(&mut self, rhs: Self) {
+        self.packets += rhs.packets;
+        self.silence_packets += rhs.silence_packets;
+        self.opus_packets += rhs.opus_packets;
+        self.sample_rate_packets += rhs.sample_rate_packets;
+        self.raw_packets += rhs.raw_packets;
+        self.unsupported_packets += rhs.unsupported_packets;
+        self.marker_packets += rhs.marker_packets;
+        self.frames += rhs.frames;
+        self.resets += rhs.resets;
+        self.silence_samples += rhs.silence_samples;
+        self.samples += rhs.samples;
+        self.duration += rhs.duration;
+        self.sample_rate = rhs.sample_rate.or(self.sample_rate);
+    }
+}
+
+/// Timing of the audio that the voice data of a speaker decodes to, from the packet metadata only
+///
+/// This follows the decoder without decoding: opus frames are timed by their TOC, silence is converted to the
+/// output rate and lost frames are concealed with the duration of the last frame, so the audio of a speaker
+/// lines up the same with and without a decoder.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PacketClock {
+    sample_rate: u16,
+    seq: u16,
+    /// Number of samples of the last opus frame at the output rate
+    last_frame: usize,
+    /// Fraction of an output sample left over from converting silence to the output rate
+    remainder: u64,
+}
+
+/// Timing of a single payload, see [`PacketClock::advance`]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PacketTiming {
+    /// Sample rate of the audio, once the voice data set it
+    pub sample_rate: Option<u32>,
+    /// Number of samples per channel the payload decodes to
+    pub samples: usize,
+    /// Offsets of the concealed lost frames within the audio of the payload, with their number of samples and
+    /// whether the decoder recovers them from forward error correction data
+    pub concealed: Vec<(usize, usize, bool)>,
+}
+
+impl PacketClock {
+    /// Time the audio of the next payload of the speaker, at `output_rate` or the decode rate of the voice data
+    ///
+    /// Gaps in the frame sequence of up to `max_concealment` frames are concealed, larger gaps restart the
+    /// sequence like a reset of the decoder. With `use_fec` the last lost frame of a gap is recovered from the
+    /// frame following it.
+    pub(crate) fn advance(
+        &mut self,
+        voice_data: &SteamVoiceData,
+        output_rate: Option<u32>,
+        max_concealment: Option<u16>,
+        use_fec: bool,
+    ) -> Result<PacketTiming, SteamAudioError> {
+        let mut timing = PacketTiming {
+            sample_rate: self.output_rate(output_rate)?,
+            ..PacketTiming::default()
+        };
+        for packet in voice_data.packets() {
+            match packet? {
+                Packet::SampleRate(rate) => {
+                    if rate != self.sample_rate {
+                        self.sample_rate = rate;
+                        self.remainder = 0;
+                    }
+                    let previous = timing.sample_rate;
+                    timing.sample_rate = self.output_rate(output_rate)?;
+                    if timing.sample_rate != previous {
+                        // the decoder switches to an opus decoder for the new rate, without a last frame
+                        self.last_frame = 0;
+                    }
+                }
+                Packet::Silence(silence) => {
+                    timing.samples += self.convert(silence as usize, timing.sample_rate);
+                }
+                Packet::RawPcm(pcm) => {
+                    let rate = timing.sample_rate.ok_or(SteamAudioError::NoSampleRate)?;
+                    timing.samples += self.convert(pcm.len() / 2, Some(rate));
+                }
+                Packet::OpusPlc(opus) => {
+                    let rate = timing.sample_rate.ok_or(SteamAudioError::NoSampleRate)?;
+                    for frame in OpusFrameIterator::new(opus.data) {
+                        let (seq, data) = match frame? {
+                            OpusFrame::Reset => {
+                                self.seq = 0;
+                                self.last_frame = 0;
+                                continue;
+                            }
+                            OpusFrame::Frame { seq, data } => (seq, data),
+                        };
+                        let lost = frames_lost(self.seq, seq)
+                            .filter(|lost| max_concealment.map_or(true, |max| *lost <= max));
+                        if lost.is_none() {
+                            // the decoder is reset instead of concealing the gap
+                            self.last_frame = 0;
+                        }
+                        let frame_size = match self.last_frame {
+                            0 => rate as usize / 50,
+                            samples => samples,
+                        };
+                        let lost = lost.unwrap_or_default();
+                        for index in 0..lost {
+                            let recovered = use_fec && index + 1 == lost && !data.is_empty();
+                            timing
+                                .concealed
+                                .push((timing.samples, frame_size, recovered));
+                            timing.samples += frame_size;
+                        }
+                        self.seq = seq.wrapping_add(1);
+                        if data.is_empty() {
+                            // the decoder conceals an empty frame like a lost one
+                            timing.concealed.push((timing.samples, frame_size, false));
+                            timing.samples += frame_size;
+                        } else {
+                            self.last_frame = packet_samples(data, rate)?;
+                            timing.samples += self.last_frame;
+                        }
+                    }
+                }
+                Packet::Unsupported { .. } | Packet::Marker(_) | Packet::Unknown { .. } => {}
+            }
+        }
+        Ok(timing)
+    }
+
+    /// The rate the audio is decoded at, a fixed `output_rate` or the decode rate of the voice data
+    fn output_rate(&self, output_rate: Option<u32>) -> Result<Option<u32>, SteamAudioError> {
+        match (output_rate, self.sample_rate) {
+            (Some(output_rate), _) => Ok(Some(output_rate)),
+            (None, 0) => Ok(None),
+            (None, rate) => decode_rate(rate as u32).map(Some),
+        }
+    }
+
+    /// Convert a number of samples at the rate of the voice data to the output rate, carrying the fraction of a
+    /// sample over to the next conversion
+    fn convert(&mut self, samples: usize, output_rate: Option<u32>) -> usize {
+        match (output_rate, self.sample_rate) {
+            (Some(output_rate), rate) if rate > 0 => {
+                let total = samples as u64 * output_rate as u64 + self.remainder;
+                self.remainder = total % rate as u64;
+                (total / rate as u64) as usize
+            }
+            _ => samples,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data::{opus, payload, sample_rate, FRAME};
+
+    #[test]
+    fn empty_frame_is_concealed() {
+        let payload = payload(&[
+            sample_rate(24000),
+            opus(&[(0, FRAME), (1, &[]), (3, FRAME)]),
+        ]);
+        let voice_data = SteamVoiceData::new(&payload).unwrap();
+        let timing = PacketClock::default()
+            .advance(&voice_data, None, Some(10), true)
+            .unwrap();
+        assert_eq!(timing.samples, 4 * 480);
+        assert_eq!(timing.concealed, [(480, 480, false), (960, 480, true)]);
+    }
+}
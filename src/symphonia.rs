@@ -0,0 +1,371 @@
+//! [`symphonia_core`] integration, for reading streams of voice payloads with symphonia
+//!
+//! The payloads are read by registering [`SymphoniaVoiceReader`] with a [`Probe`](symphonia_core::probe::Probe)
+//! and decoded by registering [`SymphoniaVoiceDecoder`] with a [`CodecRegistry`](symphonia_core::codecs::CodecRegistry).
+
+use crate::{
+    payload_length, DecoderOptions, SteamAudioError, SteamVoiceData, SteamVoiceDecoder,
+    VoiceFormatReader, VoicePacket, PACKET_TIME_BASE,
+};
+use std::fmt::Debug;
+use std::io::{self, Read};
+use std::sync::{Mutex, PoisonError};
+use symphonia_core::audio::{
+    AsAudioBufferRef, AudioBuffer, AudioBufferRef, Channels, Signal, SignalSpec,
+};
+use symphonia_core::codecs::{
+    decl_codec_type, CodecDescriptor, CodecParameters, CodecType, Decoder, FinalizeResult,
+};
+use symphonia_core::errors::{end_of_stream_error, seek_error, unsupported_error};
+use symphonia_core::errors::{Error, Result, SeekErrorKind};
+use symphonia_core::formats::{Cue, FormatOptions, FormatReader, Packet, SeekMode, SeekTo};
+use symphonia_core::formats::{SeekedTo, Track};
+use symphonia_core::io::{MediaSourceStream, SeekBuffered};
+use symphonia_core::meta::{Metadata, MetadataLog};
+use symphonia_core::probe::{Descriptor, Instantiate, QueryDescriptor};
+use symphonia_core::support_codec;
+use symphonia_core::units::TimeBase;
+
+/// Codec of the tracks of a [`SymphoniaVoiceReader`]
+pub const CODEC_TYPE_STEAM_VOICE: CodecType = decl_codec_type(b"steam");
+
+/// The upper half of the steam id of an individual account, followed by the sample rate packet the payloads of
+/// the account start with
+///
+/// The payloads have no magic bytes, this is the closest thing to it, found 4 bytes into the first payload.
+const MARKER: &[u8] = &[0x01, 0x00, 0x10, 0x01, 0x0B];
+
+/// Errors of the voice data are decode errors, which are skipped by symphonia players, other errors end the decoding
+fn error(error: SteamAudioError) -> Error {
+    if error.caused_by_data() {
+        Error::DecodeError("steam voice: malformed voice data")
+    } else {
+        Error::IoError(io::Error::other(error))
+    }
+}
+
+/// Reads a byte stream of consecutive voice payloads as a symphonia container, with a track per speaker
+///
+/// The tracks and packets are those of a [`VoiceFormatReader`], the packets are timed in [`PACKET_TIME_BASE`]
+/// samples of the audio of the speaker and the steam id of the speaker is the extra data of its track, as 8 little
+/// endian bytes.
+///
+/// Since the speakers aren't known before all payloads are read, the whole stream is read when the reader is
+/// created.
+pub struct SymphoniaVoiceReader {
+    source: MediaSourceStream,
+    tracks: Vec<Track>,
+    packets: Vec<VoicePacket>,
+    /// Index of the next packet
+    position: usize,
+    metadata: MetadataLog,
+}
+
+impl Debug for SymphoniaVoiceReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SymphoniaVoiceReader")
+            .field("packets", &self.packets.len())
+            .field("position", &self.position)
+            .finish_non_exhaustive()
+    }
+}
+
+impl QueryDescriptor for SymphoniaVoiceReader {
+    fn query() -> &'static [Descriptor] {
+        &[Descriptor {
+            short_name: "steam",
+            long_name: "Steam Voice Payloads",
+            extensions: &[],
+            mime_types: &["audio/x-steam-voice"],
+            markers: &[MARKER],
+            score: Self::score,
+            // the probe stops at the marker, which is 4 bytes into the payload
+            inst: Instantiate::Format(|mut source, options| {
+                source.seek_buffered_rev(4);
+                Ok(Box::new(SymphoniaVoiceReader::try_new(source, options)?))
+            }),
+        }]
+    }
+
+    fn score(_context: &[u8]) -> u8 {
+        255
+    }
+}
+
+impl FormatReader for SymphoniaVoiceReader {
+    fn try_new(mut source: MediaSourceStream, _options: &FormatOptions) -> Result<Self> {
+        let mut data = Vec::new();
+        source.read_to_end(&mut data)?;
+
+        let mut payloads = Vec::new();
+        let mut rest = data.as_slice();
+        // a truncated payload at the end of the stream is ignored
+        while let Some(length) = payload_length(rest).map_err(error)? {
+            payloads.push(&rest[..length]);
+            rest = &rest[length..];
+        }
+
+        let mut reader = VoiceFormatReader::new(payloads);
+        let packets = reader
+            .by_ref()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(error)?;
+        let tracks = reader
+            .tracks()
+            .iter()
+            .map(|track| {
+                let n_frames = packets
+                    .iter()
+                    .rev()
+                    .find(|packet| packet.track_id == track.id)
+                    .map_or(0, |packet| packet.ts + packet.dur);
+                let mut params = CodecParameters::new();
+                params
+                    .for_codec(CODEC_TYPE_STEAM_VOICE)
+                    .with_sample_rate(PACKET_TIME_BASE)
+                    .with_time_base(TimeBase::new(1, PACKET_TIME_BASE))
+                    .with_n_frames(n_frames)
+                    .with_channels(Channels::FRONT_LEFT)
+                    .with_extra_data(Box::new(track.steam_id.to_le_bytes()));
+                Track::new(track.id, params)
+            })
+            .collect();
+
+        Ok(SymphoniaVoiceReader {
+            source,
+            tracks,
+            packets,
+            position: 0,
+            metadata: MetadataLog::default(),
+        })
+    }
+
+    fn cues(&self) -> &[Cue] {
+        &[]
+    }
+
+    fn metadata(&mut self) -> Metadata<'_> {
+        self.metadata.metadata()
+    }
+
+    /// Seek to the packet of the track that contains the timestamp, the packets of the other tracks continue
+    /// from there
+    fn seek(&mut self, _mode: SeekMode, to: SeekTo) -> Result<SeekedTo> {
+        let (track_id, required_ts) = match to {
+            SeekTo::TimeStamp { ts, track_id } => (Some(track_id), ts),
+            SeekTo::Time { time, track_id } => (
+                track_id.or(self.default_track().map(|track| track.id)),
+                TimeBase::new(1, PACKET_TIME_BASE).calc_timestamp(time),
+            ),
+        };
+        let Some(track_id) = track_id.filter(|&id| self.tracks.iter().any(|track| track.id == id))
+        else {
+            return seek_error(SeekErrorKind::InvalidTrack);
+        };
+        let Some(index) = self
+            .packets
+            .iter()
+            .position(|packet| packet.track_id == track_id && packet.ts + packet.dur > required_ts)
+        else {
+            return seek_error(SeekErrorKind::OutOfRange);
+        };
+        self.position = index;
+        Ok(SeekedTo {
+            track_id,
+            required_ts,
+            actual_ts: self.packets[index].ts,
+        })
+    }
+
+    fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    fn next_packet(&mut self) -> Result<Packet> {
+        let Some(packet) = self.packets.get(self.position) else {
+            return end_of_stream_error();
+        };
+        self.position += 1;
+        Ok(Packet::new_from_slice(
+            packet.track_id,
+            packet.ts,
+            packet.dur,
+            &packet.data,
+        ))
+    }
+
+    fn into_inner(self: Box<Self>) -> MediaSourceStream {
+        self.source
+    }
+}
+
+/// Decodes the packets of a track of a [`SymphoniaVoiceReader`] to mono audio at [`PACKET_TIME_BASE`]
+pub struct SymphoniaVoiceDecoder {
+    /// Symphonia requires decoders to be `Sync`, the decoder is only accessed mutably so the lock is never taken
+    decoder: Mutex<SteamVoiceDecoder>,
+    params: CodecParameters,
+    samples: Vec<i16>,
+    buffer: AudioBuffer<i16>,
+}
+
+impl Debug for SymphoniaVoiceDecoder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SymphoniaVoiceDecoder")
+            .field("params", &self.params)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Decoder for SymphoniaVoiceDecoder {
+    fn try_new(
+        params: &CodecParameters,
+        _options: &symphonia_core::codecs::DecoderOptions,
+    ) -> Result<Self> {
+        if params.codec != CODEC_TYPE_STEAM_VOICE {
+            return unsupported_error("steam voice: invalid codec type");
+        }
+        let options = DecoderOptions {
+            output_rate: Some(PACKET_TIME_BASE),
+            ..DecoderOptions::default()
+        };
+        Ok(SymphoniaVoiceDecoder {
+            decoder: Mutex::new(SteamVoiceDecoder::with_options(options).map_err(error)?),
+            params: params.clone(),
+            samples: Vec::new(),
+            buffer: AudioBuffer::new(0, SignalSpec::new(PACKET_TIME_BASE, Channels::FRONT_LEFT)),
+        })
+    }
+
+    fn supported_codecs() -> &'static [CodecDescriptor] {
+        &[support_codec!(
+            CODEC_TYPE_STEAM_VOICE,
+            "steam",
+            "Steam Voice"
+        )]
+    }
+
+    fn reset(&mut self) {
+        let decoder = self
+            .decoder
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        // the opus backends only fail to reset a decoder that fails to decode, which the next packet reports
+        let _ = decoder.reset();
+        self.buffer.clear();
+    }
+
+    fn codec_params(&self) -> &CodecParameters {
+        &self.params
+    }
+
+    fn decode(&mut self, packet: &Packet) -> Result<AudioBufferRef<'_>> {
+        let decoder = self
+            .decoder
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        self.samples.clear();
+        self.buffer.clear();
+        let voice_data = SteamVoiceData::new(packet.buf()).map_err(error)?;
+        decoder
+            .decode_append(voice_data, &mut self.samples)
+            .map_err(error)?;
+
+        if self.buffer.capacity() < self.samples.len() {
+            self.buffer = AudioBuffer::new(self.samples.len() as u64, *self.buffer.spec());
+        }
+        self.buffer.render_reserved(Some(self.samples.len()));
+        self.buffer.chan_mut(0).copy_from_slice(&self.samples);
+        Ok(self.buffer.as_audio_buffer_ref())
+    }
+
+    fn finalize(&mut self) -> FinalizeResult {
+        FinalizeResult::default()
+    }
+
+    fn last_decoded(&self) -> AudioBufferRef<'_> {
+        self.buffer.as_audio_buffer_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_crc;
+    use crate::test_data::{raw, sample_rate};
+    use std::io::Cursor;
+    use symphonia_core::meta::MetadataOptions;
+    use symphonia_core::probe::{Hint, Probe};
+
+    const STEAM_ID: u64 = 0x0110_0001_0000_0001;
+
+    /// A payload of an individual account, which the probe recognizes
+    fn payload(samples: &[i16]) -> Vec<u8> {
+        let mut data = STEAM_ID.to_le_bytes().to_vec();
+        data.extend(sample_rate(24000));
+        data.extend(raw(samples));
+        append_crc(&mut data);
+        data
+    }
+
+    fn probe(stream: Vec<u8>) -> Box<dyn FormatReader> {
+        let mut probe = Probe::default();
+        probe.register_all::<SymphoniaVoiceReader>();
+        let source = MediaSourceStream::new(Box::new(Cursor::new(stream)), Default::default());
+        probe
+            .format(
+                &Hint::new(),
+                source,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .unwrap()
+            .format
+    }
+
+    #[test]
+    fn probe_packets() {
+        let mut stream = payload(&[100; 480]);
+        stream.extend(payload(&[200; 240]));
+        let mut format = probe(stream);
+        assert_eq!(format.tracks().len(), 1);
+        let params = &format.tracks()[0].codec_params;
+        assert_eq!(params.n_frames, Some(1440));
+        assert_eq!(
+            params.extra_data.as_deref(),
+            Some(&STEAM_ID.to_le_bytes()[..])
+        );
+
+        let packet = format.next_packet().unwrap();
+        assert_eq!((packet.ts, packet.dur), (0, 960));
+        let packet = format.next_packet().unwrap();
+        assert_eq!((packet.ts, packet.dur), (960, 480));
+        assert!(format.next_packet().is_err());
+
+        let seeked = format
+            .seek(
+                SeekMode::Accurate,
+                SeekTo::TimeStamp {
+                    ts: 1000,
+                    track_id: 0,
+                },
+            )
+            .unwrap();
+        assert_eq!(seeked.actual_ts, 960);
+        assert_eq!(format.next_packet().unwrap().ts, 960);
+    }
+
+    #[test]
+    #[cfg(any(feature = "opus", feature = "audiopus", feature = "opus-decoder"))]
+    fn decode() {
+        let mut format = probe(payload(&[100; 480]));
+        let mut codecs = symphonia_core::codecs::CodecRegistry::new();
+        codecs.register_all::<SymphoniaVoiceDecoder>();
+        let mut decoder = codecs
+            .make(&format.tracks()[0].codec_params, &Default::default())
+            .unwrap();
+
+        let decoded = decoder.decode(&format.next_packet().unwrap()).unwrap();
+        assert_eq!(decoded.spec().rate, PACKET_TIME_BASE);
+        assert!(decoded.frames() > 0);
+    }
+}
@@ -0,0 +1,88 @@
+//! Statistics of how much and how loud every speaker talked
+
+use std::time::Duration;
+
+/// Talk time statistics of a speaker in a [`VoiceSession`](crate::VoiceSession)
+///
+/// Utterances are separated by the gaps that the [`TickTimeline`](crate::TickTimeline) of the session keeps
+/// as silence. Without a timeline the gaps between talk spurts aren't known and all audio of the speaker
+/// counts as a single utterance.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TalkStats {
+    /// Total duration of the decoded audio
+    pub speaking_time: Duration,
+    pub utterances: u32,
+    pub longest_utterance: Duration,
+    /// Number of decoded samples per channel, audio of a session without a decoder isn't counted
+    pub samples: u64,
+    /// Sum of the squares of the decoded samples, normalized to the range of -1.0 to 1.0
+    pub square_sum: f64,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    current_utterance: u64,
+}
+
+impl TalkStats {
+    /// Account for decoded audio at `sample_rate`, `new_utterance` marks the start of a new utterance
+    pub(crate) fn add(
+        &mut self,
+        samples: &[i16],
+        channels: usize,
+        sample_rate: u32,
+        new_utterance: bool,
+    ) {
+        let frames = (samples.len() / channels.max(1)) as u64;
+        if frames == 0 || sample_rate == 0 {
+            return;
+        }
+        self.add_time(frames, sample_rate, new_utterance);
+        self.samples += frames;
+        self.square_sum += samples
+            .iter()
+            .step_by(channels.max(1))
+            .map(|sample| {
+                let sample = *sample as f64 / 32768.0;
+                sample * sample
+            })
+            .sum::<f64>();
+    }
+
+    /// Account for `frames` samples per channel of audio at `sample_rate` that wasn't decoded, only timed
+    ///
+    /// The talk time is counted, but the audio doesn't contribute to the level.
+    pub(crate) fn add_time(&mut self, frames: u64, sample_rate: u32, new_utterance: bool) {
+        if frames == 0 || sample_rate == 0 {
+            return;
+        }
+        if new_utterance || self.utterances == 0 {
+            self.utterances += 1;
+            self.current_utterance = 0;
+        }
+        self.current_utterance += frames;
+
+        let duration = |samples: u64| Duration::from_secs_f64(samples as f64 / sample_rate as f64);
+        self.speaking_time += duration(frames);
+        self.longest_utterance = self.longest_utterance.max(duration(self.current_utterance));
+    }
+
+    /// Average duration of an utterance
+    pub fn average_utterance(&self) -> Duration {
+        match self.utterances {
+            0 => Duration::ZERO,
+            utterances => self.speaking_time / utterances,
+        }
+    }
+
+    /// Root mean square level of the decoded audio, from 0.0 to 1.0
+    pub fn rms(&self) -> f64 {
+        match self.samples {
+            0 => 0.0,
+            samples => (self.square_sum / samples as f64).sqrt(),
+        }
+    }
+
+    /// Average level of the decoded audio in dBFS, `-inf` for silence
+    pub fn level_db(&self) -> f64 {
+        20.0 * self.rms().log10()
+    }
+}
@@ -0,0 +1,111 @@
+//! Placing decoded audio at the demo time it was received at
+
+use std::time::Duration;
+
+/// Default for [`TickTimeline::max_jitter`]
+pub const DEFAULT_MAX_JITTER: Duration = Duration::from_millis(200);
+
+/// Maps demo ticks to sample positions on an absolute timeline, starting at tick 0
+///
+/// A voice payload arrives at a tick after the audio in it was recorded, so the audio of a payload
+/// is placed to end at the position of its tick. Payloads of a speaker don't arrive at a steady rate, so
+/// audio that arrives within [`max_jitter`](Self::max_jitter) of the end of the previous audio of the speaker
+/// continues directly after it, only larger gaps are kept as silence on the timeline.
+///
+/// Over a long demo the number of samples a speaker sent drifts from the time that passed between the ticks,
+/// so audio that is placed directly after the previous audio slowly falls behind or runs ahead of the demo.
+/// With [drift correction](Self::with_drift_correction) the placement of contiguous audio is nudged back towards
+/// the demo time once it drifts further than the tolerance, by inserting or dropping at most five percent of the
+/// samples of a payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TickTimeline {
+    sample_rate: u32,
+    tick_interval: Duration,
+    max_jitter: Duration,
+    drift_tolerance: Option<Duration>,
+}
+
+impl TickTimeline {
+    /// Create a timeline at `sample_rate` for a demo with `tick_interval` between ticks
+    ///
+    /// The tick interval is the `interval_per_tick` of the demo, 15ms for TF2 and CS:GO demos at 66 ticks per second.
+    pub fn new(sample_rate: u32, tick_interval: Duration) -> Self {
+        TickTimeline {
+            sample_rate,
+            tick_interval,
+            max_jitter: DEFAULT_MAX_JITTER,
+            drift_tolerance: None,
+        }
+    }
+
+    /// Set the largest gap between the audio of a speaker that is closed instead of kept as silence
+    pub fn with_max_jitter(mut self, max_jitter: Duration) -> Self {
+        self.max_jitter = max_jitter;
+        self
+    }
+
+    /// Keep contiguous audio within `tolerance` of the demo time
+    pub fn with_drift_correction(mut self, tolerance: Duration) -> Self {
+        self.drift_tolerance = Some(tolerance);
+        self
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn tick_interval(&self) -> Duration {
+        self.tick_interval
+    }
+
+    pub fn max_jitter(&self) -> Duration {
+        self.max_jitter
+    }
+
+    /// The drift from the demo time that is corrected, if drift correction is enabled
+    pub fn drift_tolerance(&self) -> Option<Duration> {
+        self.drift_tolerance
+    }
+
+    /// Sample position of a tick on the timeline
+    pub fn tick_position(&self, tick: u32) -> u64 {
+        self.samples(self.tick_interval * tick)
+    }
+
+    /// Time of a sample position on the timeline
+    pub fn time(&self, position: u64) -> Duration {
+        Duration::from_secs_f64(position as f64 / self.sample_rate as f64)
+    }
+
+    fn samples(&self, duration: Duration) -> u64 {
+        (duration.as_secs_f64() * self.sample_rate as f64).round() as u64
+    }
+
+    /// Position on the timeline for `count` samples received at `tick`
+    ///
+    /// `end` is the position directly after the previous audio of the speaker, if there is any.
+    /// With drift correction the position can be before `end`, in which case the overlapping samples
+    /// should be dropped.
+    pub fn place(&self, end: Option<u64>, tick: u32, count: usize) -> u64 {
+        let target = self.tick_position(tick).saturating_sub(count as u64);
+        match end {
+            Some(end) if self.is_contiguous(end, target) => {
+                let Some(tolerance) = self.drift_tolerance else {
+                    return end;
+                };
+                let drift = target as i64 - end as i64;
+                if drift.unsigned_abs() <= self.samples(tolerance) {
+                    return end;
+                }
+                let step = (count as i64 / 20).max(1);
+                end.saturating_add_signed(drift.clamp(-step, step))
+            }
+            _ => target,
+        }
+    }
+
+    /// Whether audio placed at `start` continues the audio ending at `end` instead of following a gap
+    pub fn is_contiguous(&self, end: u64, start: u64) -> bool {
+        start <= end + self.samples(self.max_jitter)
+    }
+}
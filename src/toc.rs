@@ -0,0 +1,50 @@
+//! Parsing of the opus TOC byte, so frame durations are known without an opus decoder
+
+use crate::SteamAudioError;
+
+/// Number of samples per frame of an opus packet at the given sample rate, based on the TOC byte
+pub(crate) fn samples_per_frame(toc: u8, sample_rate: u32) -> usize {
+    let sample_rate = sample_rate as usize;
+    if toc & 0x80 != 0 {
+        // CELT-only: 2.5, 5, 10 or 20ms
+        (sample_rate << ((toc >> 3) & 0x03)) / 400
+    } else if toc & 0x60 == 0x60 {
+        // hybrid: 10 or 20ms
+        if toc & 0x08 != 0 {
+            sample_rate / 50
+        } else {
+            sample_rate / 100
+        }
+    } else {
+        // SILK-only: 10, 20, 40 or 60ms
+        match (toc >> 3) & 0x03 {
+            3 => sample_rate * 60 / 1000,
+            size => (sample_rate << size) / 100,
+        }
+    }
+}
+
+/// Number of frames in an opus packet
+pub(crate) fn frame_count(packet: &[u8]) -> Result<usize, SteamAudioError> {
+    let toc = *packet.first().ok_or(SteamAudioError::InvalidOpusPacket)?;
+    match toc & 0x03 {
+        0 => Ok(1),
+        1 | 2 => Ok(2),
+        _ => packet
+            .get(1)
+            .map(|count| (count & 0x3F) as usize)
+            .ok_or(SteamAudioError::InvalidOpusPacket),
+    }
+}
+
+/// Number of samples an opus packet decodes to at the given sample rate
+pub(crate) fn packet_samples(packet: &[u8], sample_rate: u32) -> Result<usize, SteamAudioError> {
+    let toc = *packet.first().ok_or(SteamAudioError::InvalidOpusPacket)?;
+    let samples = frame_count(packet)? * samples_per_frame(toc, sample_rate);
+    // packets are at most 120ms long
+    if samples * 25 > sample_rate as usize * 3 {
+        Err(SteamAudioError::InvalidOpusPacket)
+    } else {
+        Ok(samples)
+    }
+}
@@ -0,0 +1,259 @@
+//! Collapsing long silences in decoded audio
+
+use std::time::Duration;
+
+/// Default for [`SilenceTrimmer::max_gap`]
+pub const DEFAULT_MAX_GAP: Duration = Duration::from_secs(1);
+
+/// The point where the output continues after a collapsed silence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TimingMapEntry {
+    /// Position in the trimmed output, counting samples per channel
+    pub output: u64,
+    /// Position in the original audio
+    pub original: u64,
+}
+
+/// Maps positions in trimmed audio back to the original audio
+///
+/// Every entry marks a position where a silence was collapsed, between entries the output and
+/// original audio advance at the same rate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TimingMap {
+    sample_rate: u32,
+    entries: Vec<TimingMapEntry>,
+}
+
+impl TimingMap {
+    fn new(sample_rate: u32, start: u64) -> Self {
+        TimingMap {
+            sample_rate,
+            entries: vec![TimingMapEntry {
+                output: 0,
+                original: start,
+            }],
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// The entries ordered by position, starting with the start of the output
+    pub fn entries(&self) -> &[TimingMapEntry] {
+        &self.entries
+    }
+
+    /// The position in the original audio of a position in the output
+    pub fn original(&self, output: u64) -> u64 {
+        let index = self
+            .entries
+            .partition_point(|entry| entry.output <= output)
+            .saturating_sub(1);
+        let entry = self.entries[index];
+        entry.original + output.saturating_sub(entry.output)
+    }
+
+    /// The position in the output of a position in the original audio
+    ///
+    /// Positions within a collapsed part of a silence map to the point where the output continues.
+    pub fn output(&self, original: u64) -> u64 {
+        let index = self
+            .entries
+            .partition_point(|entry| entry.original <= original)
+            .saturating_sub(1);
+        let entry = self.entries[index];
+        let offset = original.saturating_sub(entry.original);
+        match self.entries.get(index + 1) {
+            Some(next) => entry.output + offset.min(next.output - entry.output),
+            None => entry.output + offset,
+        }
+    }
+
+    /// The time in the original audio of a time in the output
+    pub fn original_time(&self, output: Duration) -> Duration {
+        let position = (output.as_secs_f64() * self.sample_rate as f64).round() as u64;
+        Duration::from_secs_f64(self.original(position) as f64 / self.sample_rate as f64)
+    }
+
+    /// The total duration of silence removed from the output
+    pub fn removed(&self) -> Duration {
+        let entry = self.entries[self.entries.len() - 1];
+        let start = self.entries[0].original;
+        let removed = entry.original - start - entry.output;
+        Duration::from_secs_f64(removed as f64 / self.sample_rate as f64)
+    }
+}
+
+/// Shortens silences longer than a maximum gap, for compact files containing only the voice comms
+///
+/// Audio is passed through unchanged except for silences longer than the [`max_gap`](Self::max_gap),
+/// of which only the first `max_gap` is kept. A sample frame counts as silent when none of its channels
+/// exceeds the [`level`](Self::level). The positions where silence was removed are recorded in a
+/// [`TimingMap`], so times in the output can be traced back to the original audio, e.g. the demo tick.
+#[derive(Debug, Clone)]
+pub struct SilenceTrimmer {
+    channels: usize,
+    max_gap: u64,
+    level: u16,
+    /// Kept samples of the current silence
+    silence: Vec<i16>,
+    /// Number of frames of the current silence
+    silent_frames: u64,
+    /// Position in the original audio directly after the processed samples
+    original: u64,
+    /// Position in the output directly after the returned samples
+    output: u64,
+    map: TimingMap,
+}
+
+impl SilenceTrimmer {
+    /// Create a trimmer for audio at `sample_rate` with `channels` interleaved channels
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        let channels = channels.max(1);
+        SilenceTrimmer {
+            channels,
+            max_gap: (DEFAULT_MAX_GAP.as_secs_f64() * sample_rate as f64).round() as u64,
+            level: 0,
+            silence: Vec::new(),
+            silent_frames: 0,
+            original: 0,
+            output: 0,
+            map: TimingMap::new(sample_rate, 0),
+        }
+    }
+
+    /// Set the longest silence that is kept in full
+    pub fn with_max_gap(mut self, max_gap: Duration) -> Self {
+        self.max_gap = (max_gap.as_secs_f64() * self.map.sample_rate as f64).round() as u64;
+        self
+    }
+
+    /// Set the highest sample amplitude that counts as silence
+    pub fn with_level(mut self, level: u16) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Set the position in the original audio of the first sample, e.g. the timeline position of a mix
+    pub fn with_start(mut self, start: u64) -> Self {
+        self.original = start;
+        self.map = TimingMap::new(self.map.sample_rate, start);
+        self
+    }
+
+    pub fn max_gap(&self) -> Duration {
+        Duration::from_secs_f64(self.max_gap as f64 / self.map.sample_rate as f64)
+    }
+
+    pub fn level(&self) -> u16 {
+        self.level
+    }
+
+    pub fn timing_map(&self) -> &TimingMap {
+        &self.map
+    }
+
+    /// Trim the next chunk of interleaved audio, returns the samples to output
+    ///
+    /// The start of a silence is held back until it is known whether it gets collapsed.
+    pub fn process(&mut self, samples: &[i16]) -> Vec<i16> {
+        let mut output = Vec::with_capacity(samples.len());
+        for frame in samples.chunks_exact(self.channels) {
+            self.original += 1;
+            let silent = frame
+                .iter()
+                .all(|sample| sample.unsigned_abs() <= self.level);
+            if silent {
+                if self.silent_frames < self.max_gap {
+                    self.silence.extend_from_slice(frame);
+                }
+                self.silent_frames += 1;
+                continue;
+            }
+
+            self.flush_silence(&mut output);
+            if self.original - 1 > self.map.original(self.output) {
+                self.map.entries.push(TimingMapEntry {
+                    output: self.output,
+                    original: self.original - 1,
+                });
+            }
+            output.extend_from_slice(frame);
+            self.output += 1;
+        }
+        output
+    }
+
+    /// End the audio, returns the held back silence up to the maximum gap
+    pub fn finish(&mut self) -> Vec<i16> {
+        let mut output = Vec::new();
+        self.flush_silence(&mut output);
+        output
+    }
+
+    fn flush_silence(&mut self, output: &mut Vec<i16>) {
+        output.append(&mut self.silence);
+        self.output += self.silent_frames.min(self.max_gap);
+        self.silent_frames = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collapses_long_silence() {
+        let speech: Vec<i16> = (0..100)
+            .map(|i| if i % 2 == 0 { 1000 } else { -1000 })
+            .collect();
+        let quiet = [5i16; 600];
+        let mut trimmer = SilenceTrimmer::new(1000, 2)
+            .with_max_gap(Duration::from_millis(100))
+            .with_level(10)
+            .with_start(1000);
+
+        // 50 frames of speech, 300 frames of silence, 50 frames of speech and a short silence at the end
+        let mut output = trimmer.process(&speech);
+        output.extend(trimmer.process(&quiet));
+        output.extend(trimmer.process(&speech));
+        output.extend(trimmer.process(&quiet[..40]));
+        output.extend(trimmer.finish());
+
+        let expected: Vec<i16> = speech
+            .iter()
+            .chain(&quiet[..200])
+            .chain(&speech)
+            .chain(&quiet[..40])
+            .copied()
+            .collect();
+        assert_eq!(output, expected);
+
+        let map = trimmer.timing_map();
+        assert_eq!(
+            map.entries(),
+            &[
+                TimingMapEntry {
+                    output: 0,
+                    original: 1000
+                },
+                TimingMapEntry {
+                    output: 150,
+                    original: 1350
+                },
+            ]
+        );
+        assert_eq!(map.removed(), Duration::from_millis(200));
+        assert_eq!(map.original(120), 1120);
+        assert_eq!(map.original(160), 1360);
+        assert_eq!(map.output(1200), 150);
+        assert_eq!(map.output(1360), 160);
+        assert_eq!(
+            map.original_time(Duration::from_millis(170)),
+            Duration::from_millis(1370)
+        );
+    }
+}
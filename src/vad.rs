@@ -0,0 +1,272 @@
+//! Detecting which parts of decoded audio contain speech
+
+use std::time::Duration;
+
+/// Default for [`EnergyVad::threshold_db`]
+pub const DEFAULT_VAD_THRESHOLD_DB: f32 = -45.0;
+/// Default for [`VadSegmenter::min_silence`]
+pub const DEFAULT_MIN_SILENCE: Duration = Duration::from_millis(300);
+/// Duration of the frames the audio is classified in
+const VAD_FRAME: Duration = Duration::from_millis(20);
+
+/// Classifies frames of mono audio as speech or non-speech
+///
+/// [`EnergyVad`] is a simple detector based on the level of the audio, detectors based on
+/// a model, like the WebRTC VAD, can be plugged in by implementing this trait.
+pub trait VoiceActivityDetector {
+    /// Whether a frame of 20ms of audio at `sample_rate` contains speech
+    fn is_speech(&mut self, frame: &[i16], sample_rate: u32) -> bool;
+
+    /// Forget any state of previous frames
+    fn reset(&mut self) {}
+}
+
+/// Detects speech by the level of the audio
+///
+/// Voice chat is only transmitted while a player is talking, and the decoded audio contains little noise
+/// besides the speech, so a fixed level threshold separates speech from the pauses within a talk spurt.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnergyVad {
+    threshold_db: f32,
+}
+
+impl Default for EnergyVad {
+    fn default() -> Self {
+        EnergyVad::new(DEFAULT_VAD_THRESHOLD_DB)
+    }
+}
+
+impl EnergyVad {
+    /// Create a detector classifying frames with a level above `threshold_db` dBFS as speech
+    pub fn new(threshold_db: f32) -> Self {
+        EnergyVad { threshold_db }
+    }
+
+    pub fn threshold_db(&self) -> f32 {
+        self.threshold_db
+    }
+}
+
+impl VoiceActivityDetector for EnergyVad {
+    fn is_speech(&mut self, frame: &[i16], _sample_rate: u32) -> bool {
+        if frame.is_empty() {
+            return false;
+        }
+        let square_sum: f64 = frame
+            .iter()
+            .map(|sample| {
+                let sample = *sample as f64 / 32768.0;
+                sample * sample
+            })
+            .sum();
+        let rms = (square_sum / frame.len() as f64).sqrt();
+        20.0 * rms.log10() > self.threshold_db as f64
+    }
+}
+
+/// A section of audio containing speech, in samples from the start of the audio
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SpeechSegment {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl SpeechSegment {
+    pub fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// Splits a stream of mono audio into segments of speech
+///
+/// The audio is classified in frames of 20ms by a [`VoiceActivityDetector`], speech frames separated by less
+/// than the [`min_silence`](Self::min_silence) are joined into a single segment so short pauses between words
+/// don't split the speech. Use a separate segmenter for every speaker.
+#[derive(Debug, Clone)]
+pub struct VadSegmenter<D = EnergyVad> {
+    detector: D,
+    sample_rate: u32,
+    min_silence: u64,
+    /// Samples of the current incomplete frame
+    frame: Vec<i16>,
+    /// Position of the first sample of `frame`
+    position: u64,
+    /// Start and end of the speech of the current segment
+    current: Option<SpeechSegment>,
+}
+
+impl VadSegmenter<EnergyVad> {
+    /// Create a segmenter for audio at `sample_rate` using the [`EnergyVad`] with the default threshold
+    pub fn energy(sample_rate: u32) -> Self {
+        VadSegmenter::new(EnergyVad::default(), sample_rate)
+    }
+}
+
+impl<D: VoiceActivityDetector> VadSegmenter<D> {
+    pub fn new(detector: D, sample_rate: u32) -> Self {
+        VadSegmenter {
+            detector,
+            sample_rate,
+            min_silence: duration_samples(DEFAULT_MIN_SILENCE, sample_rate),
+            frame: Vec::new(),
+            position: 0,
+            current: None,
+        }
+    }
+
+    /// Set the shortest pause that ends a segment
+    pub fn with_min_silence(mut self, min_silence: Duration) -> Self {
+        self.min_silence = duration_samples(min_silence, self.sample_rate);
+        self
+    }
+
+    pub fn min_silence(&self) -> Duration {
+        Duration::from_secs_f64(self.min_silence as f64 / self.sample_rate as f64)
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn detector(&self) -> &D {
+        &self.detector
+    }
+
+    /// Position directly after the last processed sample
+    pub fn position(&self) -> u64 {
+        self.position + self.frame.len() as u64
+    }
+
+    /// The speech segment that hasn't ended yet
+    pub fn current(&self) -> Option<SpeechSegment> {
+        self.current
+    }
+
+    /// Position of the first sample that hasn't been classified yet, the start of the incomplete frame
+    pub fn unclassified(&self) -> u64 {
+        self.position
+    }
+
+    /// Classify the next chunk of audio, returns the speech segments that ended within it
+    pub fn process(&mut self, samples: &[i16]) -> Vec<SpeechSegment> {
+        let frame_size = duration_samples(VAD_FRAME, self.sample_rate).max(1) as usize;
+        let mut segments = Vec::new();
+        let mut samples = samples;
+        while !samples.is_empty() {
+            let take = (frame_size - self.frame.len()).min(samples.len());
+            self.frame.extend_from_slice(&samples[..take]);
+            samples = &samples[take..];
+            if self.frame.len() < frame_size {
+                break;
+            }
+
+            let start = self.position;
+            let end = start + frame_size as u64;
+            if self.detector.is_speech(&self.frame, self.sample_rate) {
+                match self.current.as_mut() {
+                    Some(current) => current.end = end,
+                    None => self.current = Some(SpeechSegment { start, end }),
+                }
+            } else if let Some(current) = self.current {
+                if end - current.end >= self.min_silence {
+                    segments.push(current);
+                    self.current = None;
+                }
+            }
+            self.frame.clear();
+            self.position = end;
+        }
+        segments
+    }
+
+    /// Advance over a gap without audio, e.g. between the talk spurts of a speaker, returns the segment
+    /// that ended if the gap is long enough
+    ///
+    /// An incomplete frame before the gap is dropped.
+    pub fn skip(&mut self, count: u64) -> Option<SpeechSegment> {
+        self.position += self.frame.len() as u64 + count;
+        self.frame.clear();
+        match self.current {
+            Some(current) if self.position - current.end >= self.min_silence => self.current.take(),
+            _ => None,
+        }
+    }
+
+    /// End the audio, returns the segment that was still open
+    pub fn finish(&mut self) -> Option<SpeechSegment> {
+        self.position += self.frame.len() as u64;
+        self.frame.clear();
+        self.detector.reset();
+        self.current.take()
+    }
+}
+
+fn duration_samples(duration: Duration, sample_rate: u32) -> u64 {
+    (duration.as_secs_f64() * sample_rate as f64).round() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(count: usize) -> impl Iterator<Item = i16> {
+        (0..count).map(|i| ((i as f32 * 0.1).sin() * 8000.0) as i16)
+    }
+
+    #[test]
+    fn joins_short_pauses() {
+        // speech from 0.5s to 2.1s with a 100ms pause at 1.5s, followed by a second of silence
+        let samples: Vec<i16> = std::iter::repeat(0)
+            .take(12000)
+            .chain(tone(24000))
+            .chain(std::iter::repeat(0).take(2400))
+            .chain(tone(12000))
+            .chain(std::iter::repeat(0).take(24000))
+            .collect();
+
+        let mut segmenter = VadSegmenter::energy(24000);
+        let segments: Vec<_> = samples
+            .chunks(1000)
+            .flat_map(|chunk| segmenter.process(chunk))
+            .collect();
+        assert_eq!(
+            segments,
+            vec![SpeechSegment {
+                start: 12000,
+                end: 50400
+            }]
+        );
+        assert_eq!(segmenter.finish(), None);
+        assert_eq!(segmenter.position(), samples.len() as u64);
+    }
+
+    #[test]
+    fn splits_on_long_silence() {
+        let mut segmenter =
+            VadSegmenter::energy(24000).with_min_silence(Duration::from_millis(100));
+        let first: Vec<i16> = tone(4800).chain(std::iter::repeat(0).take(4800)).collect();
+        assert_eq!(
+            segmenter.process(&first),
+            vec![SpeechSegment {
+                start: 0,
+                end: 4800
+            }]
+        );
+
+        // a gap between talk spurts ends the segment without any audio
+        assert_eq!(segmenter.process(&tone(4800).collect::<Vec<_>>()), vec![]);
+        assert_eq!(
+            segmenter.skip(2400),
+            Some(SpeechSegment {
+                start: 9600,
+                end: 14400
+            })
+        );
+        assert_eq!(segmenter.finish(), None);
+    }
+}
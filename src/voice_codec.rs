@@ -0,0 +1,199 @@
+//! Selecting the decoder for the codec of a `VoiceInit` message
+
+use crate::speex::{speex_frame_size, SPEEX_FRAME_SAMPLES};
+use crate::SteamAudioError;
+
+/// Sample rate of the legacy engine codecs, for `VoiceInit` messages without a sample rate
+const LEGACY_SAMPLE_RATE: u32 = 11025;
+/// The quality of a `VoiceInit` message that carries a sample rate
+const QUALITY_WITH_SAMPLE_RATE: u8 = 255;
+/// The voice quality the engine uses when the `VoiceInit` message doesn't set one
+const DEFAULT_QUALITY: u8 = 3;
+
+/// The CELT mode of a `vaudio_celt` voice quality
+///
+/// Source uses CELT 0.11 with a fixed mode and encoded frame size for every quality level. Newer CELT versions and
+/// opus can't decode the frames, as the bitstream changed after 0.11.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CeltMode {
+    pub sample_rate: u32,
+    /// Number of samples of an encoded frame
+    pub frame_samples: usize,
+    /// Size of an encoded frame
+    pub frame_size: usize,
+}
+
+/// Modes of the quality levels 0 to 3
+const CELT_MODES: [CeltMode; 4] = [
+    CeltMode {
+        sample_rate: 44100,
+        frame_samples: 256,
+        frame_size: 120,
+    },
+    CeltMode {
+        sample_rate: 22050,
+        frame_samples: 120,
+        frame_size: 60,
+    },
+    CeltMode {
+        sample_rate: 22050,
+        frame_samples: 256,
+        frame_size: 60,
+    },
+    CeltMode {
+        sample_rate: 22050,
+        frame_samples: 512,
+        frame_size: 64,
+    },
+];
+
+impl CeltMode {
+    /// The mode for the voice quality of the `VoiceInit` message
+    pub fn for_quality(quality: u8) -> Result<Self, SteamAudioError> {
+        CELT_MODES
+            .get(quality as usize)
+            .copied()
+            .ok_or(SteamAudioError::UnsupportedCodec {
+                codec: format!("vaudio_celt quality {quality}"),
+            })
+    }
+}
+
+/// The voice codec of a demo or server, from the `VoiceInit` message
+///
+/// Only the `steam` codec produces steam voice payloads, the engine codecs send their encoded frames
+/// in the voice messages as is and need the codec parameters of the `VoiceInit` message to be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum VoiceCodec {
+    /// `steam`, voice data is a steam voice payload that can be parsed with [`SteamVoiceData::new`](crate::SteamVoiceData::new)
+    Steam,
+    /// `vaudio_opus`, voice data can be wrapped with [`EngineOpusData`](crate::EngineOpusData)
+    EngineOpus { sample_rate: u16 },
+    /// `vaudio_celt`, fixed size CELT frames in the mode of the voice quality
+    Celt { quality: u8, mode: CeltMode },
+    /// `vaudio_speex`, fixed size narrowband speex frames for the voice quality of 1 to 5
+    Speex { quality: u8, sample_rate: u32 },
+}
+
+impl VoiceCodec {
+    /// The codec for the codec name, quality and sample rate of a `VoiceInit` message
+    ///
+    /// Older `VoiceInit` messages don't contain a sample rate, a quality of 255 marks messages that do.
+    /// Without one, the engine uses 22050 Hz for `vaudio_celt` and 11025 Hz for all other codecs.
+    ///
+    /// Fails with [`SteamAudioError::UnsupportedCodec`] for unknown codecs and unsupported quality levels.
+    pub fn from_voice_init(
+        codec: &str,
+        quality: u8,
+        sample_rate: u16,
+    ) -> Result<Self, SteamAudioError> {
+        let has_quality = quality != QUALITY_WITH_SAMPLE_RATE;
+        let has_sample_rate = !has_quality && sample_rate > 0;
+        let quality = if has_quality {
+            quality
+        } else {
+            DEFAULT_QUALITY
+        };
+        let codec = match codec {
+            "steam" => VoiceCodec::Steam,
+            "vaudio_opus" => VoiceCodec::EngineOpus {
+                sample_rate: if has_sample_rate {
+                    sample_rate
+                } else {
+                    return Err(SteamAudioError::UnsupportedCodec {
+                        codec: "vaudio_opus without sample rate".into(),
+                    });
+                },
+            },
+            "vaudio_celt" if has_sample_rate => {
+                // prefer the default quality, otherwise the first mode with the sample rate
+                let quality = [DEFAULT_QUALITY, 0, 1, 2]
+                    .into_iter()
+                    .find(|quality| {
+                        CeltMode::for_quality(*quality)
+                            .is_ok_and(|mode| mode.sample_rate == sample_rate as u32)
+                    })
+                    .ok_or(SteamAudioError::UnsupportedCodec {
+                        codec: format!("vaudio_celt at {sample_rate} Hz"),
+                    })?;
+                VoiceCodec::Celt {
+                    quality,
+                    mode: CeltMode::for_quality(quality)?,
+                }
+            }
+            "vaudio_celt" => VoiceCodec::Celt {
+                quality,
+                mode: CeltMode::for_quality(quality)?,
+            },
+            "vaudio_speex" => VoiceCodec::Speex {
+                quality,
+                sample_rate: if has_sample_rate {
+                    sample_rate as u32
+                } else {
+                    LEGACY_SAMPLE_RATE
+                },
+            },
+            _ => {
+                return Err(SteamAudioError::UnsupportedCodec {
+                    codec: codec.into(),
+                })
+            }
+        };
+        codec.frame_size()?;
+        Ok(codec)
+    }
+
+    /// The codec name as used in the `VoiceInit` message
+    pub fn name(&self) -> &'static str {
+        match self {
+            VoiceCodec::Steam => "steam",
+            VoiceCodec::EngineOpus { .. } => "vaudio_opus",
+            VoiceCodec::Celt { .. } => "vaudio_celt",
+            VoiceCodec::Speex { .. } => "vaudio_speex",
+        }
+    }
+
+    /// Whether the voice data is a steam voice payload
+    pub fn is_steam(&self) -> bool {
+        matches!(self, VoiceCodec::Steam)
+    }
+
+    /// Sample rate of the decoded audio
+    ///
+    /// `None` for the steam codec, which sets the sample rate in the voice data.
+    pub fn sample_rate(&self) -> Option<u32> {
+        match self {
+            VoiceCodec::Steam => None,
+            VoiceCodec::EngineOpus { sample_rate } => Some(*sample_rate as u32),
+            VoiceCodec::Celt { mode, .. } => Some(mode.sample_rate),
+            VoiceCodec::Speex { sample_rate, .. } => Some(*sample_rate),
+        }
+    }
+
+    /// Size of an encoded frame, for the codecs with fixed size frames
+    ///
+    /// Fails for speex with a quality outside of 1 to 5.
+    pub fn frame_size(&self) -> Result<Option<usize>, SteamAudioError> {
+        match self {
+            VoiceCodec::Steam | VoiceCodec::EngineOpus { .. } => Ok(None),
+            VoiceCodec::Celt { mode, .. } => Ok(Some(mode.frame_size)),
+            VoiceCodec::Speex { quality, .. } => {
+                speex_frame_size(*quality)
+                    .map(Some)
+                    .ok_or(SteamAudioError::UnsupportedCodec {
+                        codec: format!("vaudio_speex quality {quality}"),
+                    })
+            }
+        }
+    }
+
+    /// Number of samples an encoded frame decodes to, for the codecs with fixed size frames
+    pub fn frame_samples(&self) -> Option<usize> {
+        match self {
+            VoiceCodec::Steam | VoiceCodec::EngineOpus { .. } => None,
+            VoiceCodec::Celt { mode, .. } => Some(mode.frame_samples),
+            VoiceCodec::Speex { .. } => Some(SPEEX_FRAME_SAMPLES),
+        }
+    }
+}
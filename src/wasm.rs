@@ -0,0 +1,91 @@
+//! Decoding voice in WebAssembly builds, with the pure Rust opus decoder
+
+use crate::{DecoderOptions, OutputChannels, RustOpus, SteamVoiceData, SteamVoiceDecoder};
+use js_sys::Float32Array;
+use wasm_bindgen::prelude::*;
+
+/// A decoder for WebAssembly builds, turning voice payloads into chunks of samples for the Web Audio API
+///
+/// The decoder always uses [`RustOpus`], so it builds for `wasm32-unknown-unknown` without libopus. Every payload
+/// decodes to a `Float32Array` of interleaved samples between -1 and 1, the chunks of a speaker are played back to
+/// back, e.g. by copying them into an `AudioBuffer` with the [`sampleRate`](Self::sample_rate) and
+/// [`channels`](Self::channels) of the decoder.
+///
+/// The decoder is exported to JavaScript with `wasm-bindgen`, errors are thrown as `Error`s.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct WasmVoiceDecoder {
+    decoder: SteamVoiceDecoder,
+    samples: Vec<i16>,
+    output: Vec<f32>,
+    steam_id: Option<u64>,
+}
+
+#[wasm_bindgen]
+impl WasmVoiceDecoder {
+    /// Create a decoder decoding at `output_rate`, or the sample rate of the voice data if it is 0, to mono or
+    /// interleaved stereo
+    ///
+    /// Browsers play back any sample rate, but decoding at the rate of the `AudioContext` saves them from
+    /// resampling every chunk.
+    #[wasm_bindgen(constructor)]
+    pub fn new(output_rate: u32, stereo: bool) -> Result<WasmVoiceDecoder, JsError> {
+        let options = DecoderOptions {
+            output_rate: (output_rate != 0).then_some(output_rate),
+            channels: if stereo {
+                OutputChannels::Stereo
+            } else {
+                OutputChannels::Mono
+            },
+            ..DecoderOptions::default()
+        };
+        Ok(WasmVoiceDecoder {
+            decoder: SteamVoiceDecoder::with_backend(options, RustOpus::boxed)?,
+            samples: Vec::new(),
+            output: Vec::new(),
+            steam_id: None,
+        })
+    }
+
+    /// Decode a voice payload into a chunk of interleaved samples between -1 and 1
+    pub fn decode(&mut self, payload: &[u8]) -> Result<Float32Array, JsError> {
+        let voice_data = SteamVoiceData::new(payload)?;
+        self.steam_id = Some(voice_data.steam_id);
+        self.samples.clear();
+        self.decoder.decode_append(voice_data, &mut self.samples)?;
+        self.output.clear();
+        self.output
+            .extend(self.samples.iter().map(|sample| *sample as f32 / 32768.0));
+        Ok(Float32Array::from(self.output.as_slice()))
+    }
+
+    /// Sample rate of the decoded audio, 0 until a payload set it
+    #[wasm_bindgen(getter = sampleRate)]
+    pub fn sample_rate(&self) -> u32 {
+        self.decoder.output_rate().unwrap_or_default()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn channels(&self) -> u32 {
+        self.decoder.options().channels.count() as u32
+    }
+
+    /// Steam id of the speaker of the last decoded payload, as a string since JavaScript numbers can't hold it
+    #[wasm_bindgen(getter = steamId)]
+    pub fn steam_id(&self) -> Option<String> {
+        self.steam_id.map(|steam_id| steam_id.to_string())
+    }
+
+    /// Reset the decoder to decode a new, unrelated, stream of voice data, e.g. after seeking
+    pub fn reset(&mut self) -> Result<(), JsError> {
+        self.steam_id = None;
+        Ok(self.decoder.reset()?)
+    }
+}
+
+impl WasmVoiceDecoder {
+    /// Get back the decoder
+    pub fn into_decoder(self) -> SteamVoiceDecoder {
+        self.decoder
+    }
+}
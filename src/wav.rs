@@ -0,0 +1,113 @@
+//! Writing decoded audio to WAV files
+
+use crate::{PcmSink, SteamAudioError};
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{BufWriter, Seek, Write};
+use std::path::Path;
+
+/// The spec of 16 bit WAV audio at `sample_rate` with `channels` interleaved channels
+fn wav_spec(sample_rate: u32, channels: u16) -> WavSpec {
+    WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    }
+}
+
+/// A [`PcmSink`] writing 16 bit audio to a WAV file
+///
+/// The header is updated when the sink is [finished](PcmSink::finish), [finalized](Self::finalize) or dropped.
+pub struct WavSink<W: Write + Seek> {
+    writer: WavWriter<W>,
+}
+
+impl<W: Write + Seek> Debug for WavSink<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WavSink")
+            .field("spec", &self.writer.spec())
+            .field("duration", &self.writer.duration())
+            .finish()
+    }
+}
+
+impl WavSink<BufWriter<File>> {
+    /// Create a WAV file at `path` for audio at `sample_rate` with `channels` interleaved channels
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        sample_rate: u32,
+        channels: u16,
+    ) -> Result<Self, SteamAudioError> {
+        Ok(WavSink {
+            writer: WavWriter::create(path, wav_spec(sample_rate, channels))?,
+        })
+    }
+
+    /// Create a WAV file at `path` for the audio produced by `decoder`
+    ///
+    /// The sample rate is only known once the decoder decoded a payload setting it, before that this fails with
+    /// [`SteamAudioError::NoSampleRate`] unless the decoder has a fixed output rate.
+    #[cfg(feature = "decoder")]
+    pub fn for_decoder<P: AsRef<Path>>(
+        path: P,
+        decoder: &crate::SteamVoiceDecoder,
+    ) -> Result<Self, SteamAudioError> {
+        let sample_rate = decoder.output_rate().ok_or(SteamAudioError::NoSampleRate)?;
+        WavSink::create(path, sample_rate, decoder.options().channels.count() as u16)
+    }
+}
+
+impl<W: Write + Seek> WavSink<W> {
+    /// Write audio at `sample_rate` with `channels` interleaved channels to `writer`
+    pub fn new(writer: W, sample_rate: u32, channels: u16) -> Result<Self, SteamAudioError> {
+        Ok(WavSink {
+            writer: WavWriter::new(writer, wav_spec(sample_rate, channels))?,
+        })
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.writer.spec().sample_rate
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.writer.spec().channels
+    }
+
+    /// Number of samples per channel written so far
+    pub fn duration(&self) -> u32 {
+        self.writer.duration()
+    }
+
+    /// Update the header and close the file
+    pub fn finalize(self) -> Result<(), SteamAudioError> {
+        Ok(self.writer.finalize()?)
+    }
+}
+
+impl<W: Write + Seek> PcmSink for WavSink<W> {
+    fn write_samples(&mut self, samples: &[i16]) -> Result<(), SteamAudioError> {
+        let mut writer = self.writer.get_i16_writer(samples.len() as u32);
+        for &sample in samples {
+            writer.write_sample(sample);
+        }
+        Ok(writer.flush()?)
+    }
+
+    fn finish(&mut self) -> Result<(), SteamAudioError> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+/// Write interleaved audio at `sample_rate` with `channels` channels to a WAV file at `path`
+pub fn write_wav<P: AsRef<Path>>(
+    path: P,
+    samples: &[i16],
+    sample_rate: u32,
+    channels: u16,
+) -> Result<(), SteamAudioError> {
+    let mut sink = WavSink::create(path, sample_rate, channels)?;
+    sink.write_samples(samples)?;
+    sink.finalize()
+}